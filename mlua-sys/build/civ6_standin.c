@@ -0,0 +1,573 @@
+/*
+ * Stand-in for the HavokScript runtime shipped with Civilization VI.
+ *
+ * Exports the MSVC-mangled symbol set that `lua51_civ6` binds to, implemented on
+ * top of stock Lua 5.1. This lets the high-level `mlua` test suite run against the
+ * civ6 bindings on platforms where `HavokScript_FinalRelease.dll` is not available.
+ *
+ * The ABI follows the mangled signatures rather than `lua.h`:
+ *   - `lua_Integer` is a 32-bit `int` (`H` in the mangled name)
+ *   - `lua_toboolean` returns `bool` (`_N`)
+ *   - `LuaPlus::LuaState` members take the state as `this` and return `LuaStackObject`
+ *     through a hidden pointer passed as the second argument
+ *
+ * Every function only calls into the stock Lua API. Calling another mangled
+ * symbol from C would not assemble when building position independent code.
+ */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdio.h>
+
+#include "lua.h"
+#include "lauxlib.h"
+#include "lualib.h"
+
+// Mach-O symbols carry a leading underscore that asm labels bypass
+#ifdef __APPLE__
+#define HKS_EXPORT(sym) __asm__("\"_" sym "\"")
+#else
+#define HKS_EXPORT(sym) __asm__("\"" sym "\"")
+#endif
+
+typedef struct LuaStackObject {
+    lua_State *L;
+    int m_stackIndex;
+} LuaStackObject;
+
+static LuaStackObject *hks_stack_object(lua_State *L, LuaStackObject *ret) {
+    ret->L = L;
+    ret->m_stackIndex = lua_gettop(L);
+    return ret;
+}
+
+/*
+ * 64-bit unsigned integers
+ *
+ * Stock Lua has no `ui64` type, so they are emulated with a userdata carrying a
+ * dedicated metatable, which `lua_type` reports as `LUA_TUI64`.
+ */
+
+#define HKS_TUI64 11
+#define HKS_TSTRUCT 12
+
+#define HKS_UI64_METATABLE "havokscript.ui64"
+
+static int hks_isui64(lua_State *L, int idx) {
+    int result = 0;
+    if (lua_checkstack(L, 2) && lua_getmetatable(L, idx)) {
+        lua_getfield(L, LUA_REGISTRYINDEX, HKS_UI64_METATABLE);
+        result = lua_rawequal(L, -1, -2);
+        lua_pop(L, 2);
+    }
+    return result;
+}
+
+static int hks_ui64_tostring(lua_State *L) {
+    char buf[24];
+    snprintf(buf, sizeof(buf), "%llu", *(unsigned long long *)lua_touserdata(L, 1));
+    lua_pushstring(L, buf);
+    return 1;
+}
+
+static int hks_ui64_eq(lua_State *L) {
+    unsigned long long *a = lua_touserdata(L, 1);
+    unsigned long long *b = lua_touserdata(L, 2);
+    lua_pushboolean(L, *a == *b);
+    return 1;
+}
+
+/*
+ * State manipulation
+ */
+
+lua_State *hks_newstate(lua_Alloc f, void *ud)
+    HKS_EXPORT("?lua_newstate@@YAPEAUlua_State@@P6APEAXPEAX0_K1@Z0@Z");
+lua_State *hks_newstate(lua_Alloc f, void *ud) { return lua_newstate(f, ud); }
+
+void hks_close(lua_State *L) HKS_EXPORT("?lua_close@@YAXPEAUlua_State@@@Z");
+void hks_close(lua_State *L) { lua_close(L); }
+
+lua_State *hks_newthread(lua_State *L) HKS_EXPORT("?lua_newthread@@YAPEAUlua_State@@PEAU1@@Z");
+lua_State *hks_newthread(lua_State *L) { return lua_newthread(L); }
+
+lua_CFunction hks_atpanic(lua_State *L, lua_CFunction panicf)
+    HKS_EXPORT("?lua_atpanic@@YAP6AHPEAUlua_State@@@Z0P6AH0@Z@Z");
+lua_CFunction hks_atpanic(lua_State *L, lua_CFunction panicf) { return lua_atpanic(L, panicf); }
+
+/*
+ * Basic stack manipulation
+ */
+
+int hks_gettop(lua_State *L) HKS_EXPORT("?lua_gettop@@YAHPEAUlua_State@@@Z");
+int hks_gettop(lua_State *L) { return lua_gettop(L); }
+
+void hks_settop(lua_State *L, int idx) HKS_EXPORT("?lua_settop@@YAXPEAUlua_State@@H@Z");
+void hks_settop(lua_State *L, int idx) { lua_settop(L, idx); }
+
+void hks_pushvalue(lua_State *L, int idx) HKS_EXPORT("?lua_pushvalue@@YAXPEAUlua_State@@H@Z");
+void hks_pushvalue(lua_State *L, int idx) { lua_pushvalue(L, idx); }
+
+void hks_remove(lua_State *L, int idx) HKS_EXPORT("?lua_remove@@YAXPEAUlua_State@@H@Z");
+void hks_remove(lua_State *L, int idx) { lua_remove(L, idx); }
+
+void hks_insert(lua_State *L, int idx) HKS_EXPORT("?lua_insert@@YAXPEAUlua_State@@H@Z");
+void hks_insert(lua_State *L, int idx) { lua_insert(L, idx); }
+
+void hks_replace(lua_State *L, int idx) HKS_EXPORT("?lua_replace@@YAXPEAUlua_State@@H@Z");
+void hks_replace(lua_State *L, int idx) { lua_replace(L, idx); }
+
+/*
+ * Access functions (stack -> C)
+ */
+
+int hks_isnumber(lua_State *L, int idx) HKS_EXPORT("?lua_isnumber@@YAHPEAUlua_State@@H@Z");
+int hks_isnumber(lua_State *L, int idx) { return lua_isnumber(L, idx); }
+
+int hks_isstring(lua_State *L, int idx) HKS_EXPORT("?lua_isstring@@YAHPEAUlua_State@@H@Z");
+int hks_isstring(lua_State *L, int idx) { return lua_isstring(L, idx); }
+
+int hks_iscfunction(lua_State *L, int idx) HKS_EXPORT("?lua_iscfunction@@YAHPEAUlua_State@@H@Z");
+int hks_iscfunction(lua_State *L, int idx) { return lua_iscfunction(L, idx); }
+
+int hks_isuserdata(lua_State *L, int idx) HKS_EXPORT("?lua_isuserdata@@YAHPEAUlua_State@@H@Z");
+int hks_isuserdata(lua_State *L, int idx) { return lua_isuserdata(L, idx); }
+
+int hks_type(lua_State *L, int idx) HKS_EXPORT("?lua_type@@YAHPEAUlua_State@@H@Z");
+int hks_type(lua_State *L, int idx) {
+    int t = lua_type(L, idx);
+    if (t == LUA_TUSERDATA && hks_isui64(L, idx))
+        return HKS_TUI64;
+    return t;
+}
+
+const char *hks_typename(lua_State *L, int tp) HKS_EXPORT("?lua_typename@@YAPEBDPEAUlua_State@@H@Z");
+const char *hks_typename(lua_State *L, int tp) {
+    switch (tp) {
+    case HKS_TUI64:
+        return "ui64";
+    case HKS_TSTRUCT:
+        return "struct";
+    default:
+        return lua_typename(L, tp);
+    }
+}
+
+int hks_equal(lua_State *L, int idx1, int idx2) HKS_EXPORT("?lua_equal@@YAHPEAUlua_State@@HH@Z");
+int hks_equal(lua_State *L, int idx1, int idx2) { return lua_equal(L, idx1, idx2); }
+
+int hks_rawequal(lua_State *L, int idx1, int idx2) HKS_EXPORT("?lua_rawequal@@YAHPEAUlua_State@@HH@Z");
+int hks_rawequal(lua_State *L, int idx1, int idx2) { return lua_rawequal(L, idx1, idx2); }
+
+int hks_lessthan(lua_State *L, int idx1, int idx2) HKS_EXPORT("?lua_lessthan@@YAHPEAUlua_State@@HH@Z");
+int hks_lessthan(lua_State *L, int idx1, int idx2) { return lua_lessthan(L, idx1, idx2); }
+
+lua_Number hks_tonumber(lua_State *L, int idx) HKS_EXPORT("?lua_tonumber@@YANPEAUlua_State@@H@Z");
+lua_Number hks_tonumber(lua_State *L, int idx) { return lua_tonumber(L, idx); }
+
+int hks_tointeger(lua_State *L, int idx) HKS_EXPORT("?lua_tointeger@@YAHPEAUlua_State@@H@Z");
+int hks_tointeger(lua_State *L, int idx) { return (int)lua_tointeger(L, idx); }
+
+bool hks_toboolean(lua_State *L, int idx) HKS_EXPORT("?lua_toboolean@@YA_NPEAUlua_State@@H@Z");
+bool hks_toboolean(lua_State *L, int idx) { return lua_toboolean(L, idx) != 0; }
+
+const char *hks_tolstring(lua_State *L, int idx, size_t *len)
+    HKS_EXPORT("?lua_tolstring@@YAPEBDPEAUlua_State@@HPEA_K@Z");
+const char *hks_tolstring(lua_State *L, int idx, size_t *len) { return lua_tolstring(L, idx, len); }
+
+size_t hks_objlen(lua_State *L, int idx) HKS_EXPORT("?lua_objlen@@YA_KPEAUlua_State@@H@Z");
+size_t hks_objlen(lua_State *L, int idx) { return lua_objlen(L, idx); }
+
+lua_CFunction hks_tocfunction(lua_State *L, int idx)
+    HKS_EXPORT("?lua_tocfunction@@YAP6AHPEAUlua_State@@@Z0H@Z");
+lua_CFunction hks_tocfunction(lua_State *L, int idx) { return lua_tocfunction(L, idx); }
+
+void *hks_touserdata(lua_State *L, int idx) HKS_EXPORT("?lua_touserdata@@YAPEAXPEAUlua_State@@H@Z");
+void *hks_touserdata(lua_State *L, int idx) { return lua_touserdata(L, idx); }
+
+lua_State *hks_tothread(lua_State *L, int idx) HKS_EXPORT("?lua_tothread@@YAPEAUlua_State@@PEAU1@H@Z");
+lua_State *hks_tothread(lua_State *L, int idx) { return lua_tothread(L, idx); }
+
+const void *hks_topointer(lua_State *L, int idx) HKS_EXPORT("?lua_topointer@@YAPEBXPEAUlua_State@@H@Z");
+const void *hks_topointer(lua_State *L, int idx) { return lua_topointer(L, idx); }
+
+/*
+ * Push functions (C -> stack)
+ */
+
+void hks_pushui64(lua_State *L, unsigned long long n) HKS_EXPORT("?lua_pushui64@@YAXPEAUlua_State@@_K@Z");
+void hks_pushui64(lua_State *L, unsigned long long n) {
+    unsigned long long *p = lua_newuserdata(L, sizeof(*p));
+    *p = n;
+    if (luaL_newmetatable(L, HKS_UI64_METATABLE)) {
+        lua_pushcfunction(L, hks_ui64_tostring);
+        lua_setfield(L, -2, "__tostring");
+        lua_pushcfunction(L, hks_ui64_eq);
+        lua_setfield(L, -2, "__eq");
+    }
+    lua_setmetatable(L, -2);
+}
+
+unsigned long long hks_toui64(lua_State *L, int idx) HKS_EXPORT("?lua_toui64@@YA_KPEAUlua_State@@H@Z");
+unsigned long long hks_toui64(lua_State *L, int idx) {
+    if (hks_isui64(L, idx))
+        return *(unsigned long long *)lua_touserdata(L, idx);
+    return (unsigned long long)lua_tonumber(L, idx);
+}
+
+void hks_pushnil(lua_State *L) HKS_EXPORT("?lua_pushnil@@YAXPEAUlua_State@@@Z");
+void hks_pushnil(lua_State *L) { lua_pushnil(L); }
+
+void hks_pushnumber(lua_State *L, lua_Number n) HKS_EXPORT("?lua_pushnumber@@YAXPEAUlua_State@@N@Z");
+void hks_pushnumber(lua_State *L, lua_Number n) { lua_pushnumber(L, n); }
+
+void hks_pushinteger(lua_State *L, int n) HKS_EXPORT("?lua_pushinteger@@YAXPEAUlua_State@@H@Z");
+void hks_pushinteger(lua_State *L, int n) { lua_pushinteger(L, n); }
+
+const char *hks_pushfstring(lua_State *L, const char *fmt, ...)
+    HKS_EXPORT("?lua_pushfstring@@YAPEBDPEAUlua_State@@PEBDZZ");
+const char *hks_pushfstring(lua_State *L, const char *fmt, ...) {
+    const char *ret;
+    va_list argp;
+    va_start(argp, fmt);
+    ret = lua_pushvfstring(L, fmt, argp);
+    va_end(argp);
+    return ret;
+}
+
+void hks_pushlightuserdata(lua_State *L, void *p)
+    HKS_EXPORT("?lua_pushlightuserdata@@YAXPEAUlua_State@@PEAX@Z");
+void hks_pushlightuserdata(lua_State *L, void *p) { lua_pushlightuserdata(L, p); }
+
+int hks_pushthread(lua_State *L) HKS_EXPORT("?lua_pushthread@@YAHPEAUlua_State@@@Z");
+int hks_pushthread(lua_State *L) { return lua_pushthread(L); }
+
+/*
+ * Get functions (Lua -> stack)
+ */
+
+void hks_gettable(lua_State *L, int idx) HKS_EXPORT("?lua_gettable@@YAXPEAUlua_State@@H@Z");
+void hks_gettable(lua_State *L, int idx) { lua_gettable(L, idx); }
+
+void hks_getfield(lua_State *L, int idx, const char *k) HKS_EXPORT("?lua_getfield@@YAXPEAUlua_State@@HPEBD@Z");
+void hks_getfield(lua_State *L, int idx, const char *k) { lua_getfield(L, idx, k); }
+
+void hks_rawget(lua_State *L, int idx) HKS_EXPORT("?lua_rawget@@YAXPEAUlua_State@@H@Z");
+void hks_rawget(lua_State *L, int idx) { lua_rawget(L, idx); }
+
+void hks_rawgeti(lua_State *L, int idx, int n) HKS_EXPORT("?lua_rawgeti@@YAXPEAUlua_State@@HH@Z");
+void hks_rawgeti(lua_State *L, int idx, int n) { lua_rawgeti(L, idx, n); }
+
+void hks_createtable(lua_State *L, int narr, int nrec) HKS_EXPORT("?lua_createtable@@YAXPEAUlua_State@@HH@Z");
+void hks_createtable(lua_State *L, int narr, int nrec) { lua_createtable(L, narr, nrec); }
+
+void *hks_newuserdata(lua_State *L, size_t sz) HKS_EXPORT("?lua_newuserdata@@YAPEAXPEAUlua_State@@_K@Z");
+void *hks_newuserdata(lua_State *L, size_t sz) { return lua_newuserdata(L, sz); }
+
+int hks_getmetatable(lua_State *L, int objindex) HKS_EXPORT("?lua_getmetatable@@YAHPEAUlua_State@@H@Z");
+int hks_getmetatable(lua_State *L, int objindex) { return lua_getmetatable(L, objindex); }
+
+void hks_getfenv(lua_State *L, int idx) HKS_EXPORT("?lua_getfenv@@YAXPEAUlua_State@@H@Z");
+void hks_getfenv(lua_State *L, int idx) { lua_getfenv(L, idx); }
+
+/*
+ * Set functions (stack -> Lua)
+ */
+
+void hks_setfield(lua_State *L, int idx, const char *k) HKS_EXPORT("?lua_setfield@@YAXPEAUlua_State@@HPEBD@Z");
+void hks_setfield(lua_State *L, int idx, const char *k) { lua_setfield(L, idx, k); }
+
+void hks_rawset(lua_State *L, int idx) HKS_EXPORT("?lua_rawset@@YAXPEAUlua_State@@H@Z");
+void hks_rawset(lua_State *L, int idx) { lua_rawset(L, idx); }
+
+void hks_rawseti(lua_State *L, int idx, int n) HKS_EXPORT("?lua_rawseti@@YAXPEAUlua_State@@HH@Z");
+void hks_rawseti(lua_State *L, int idx, int n) { lua_rawseti(L, idx, n); }
+
+int hks_setmetatable(lua_State *L, int objindex) HKS_EXPORT("?lua_setmetatable@@YAHPEAUlua_State@@H@Z");
+int hks_setmetatable(lua_State *L, int objindex) { return lua_setmetatable(L, objindex); }
+
+int hks_setfenv(lua_State *L, int idx) HKS_EXPORT("?lua_setfenv@@YAHPEAUlua_State@@H@Z");
+int hks_setfenv(lua_State *L, int idx) { return lua_setfenv(L, idx); }
+
+/*
+ * 'load' and 'call' functions
+ */
+
+void hks_call(lua_State *L, int nargs, int nresults) HKS_EXPORT("?lua_call@@YAXPEAUlua_State@@HH@Z");
+void hks_call(lua_State *L, int nargs, int nresults) { lua_call(L, nargs, nresults); }
+
+int hks_pcall(lua_State *L, int nargs, int nresults, int errfunc) HKS_EXPORT("?lua_pcall@@YAHPEAUlua_State@@HHH@Z");
+int hks_pcall(lua_State *L, int nargs, int nresults, int errfunc) {
+    return lua_pcall(L, nargs, nresults, errfunc);
+}
+
+int hks_cpcall(lua_State *L, lua_CFunction f, void *ud) HKS_EXPORT("?lua_cpcall@@YAHPEAUlua_State@@P6AH0@ZPEAX@Z");
+int hks_cpcall(lua_State *L, lua_CFunction f, void *ud) { return lua_cpcall(L, f, ud); }
+
+int hks_load(lua_State *L, lua_Reader reader, void *data, const char *chunkname)
+    HKS_EXPORT("?lua_load@@YAHPEAUlua_State@@P6APEBD0PEAXPEA_K@Z1PEBD@Z");
+int hks_load(lua_State *L, lua_Reader reader, void *data, const char *chunkname) {
+    return lua_load(L, reader, data, chunkname);
+}
+
+int hks_dump(lua_State *L, lua_Writer writer, void *data) HKS_EXPORT("?lua_dump@@YAHPEAUlua_State@@P6AH0PEBX_KPEAX@Z3@Z");
+int hks_dump(lua_State *L, lua_Writer writer, void *data) { return lua_dump(L, writer, data); }
+
+/*
+ * Coroutine functions
+ */
+
+int hks_yield(lua_State *L, int nresults) HKS_EXPORT("?lua_yield@@YAHPEAUlua_State@@H@Z");
+int hks_yield(lua_State *L, int nresults) { return lua_yield(L, nresults); }
+
+int hks_resume(lua_State *L, int narg) HKS_EXPORT("?lua_resume@@YAHPEAUlua_State@@H@Z");
+int hks_resume(lua_State *L, int narg) { return lua_resume(L, narg); }
+
+/*
+ * Garbage-collection and miscellaneous functions
+ */
+
+int hks_gc(lua_State *L, int what, int data) HKS_EXPORT("?lua_gc@@YAHPEAUlua_State@@HH@Z");
+int hks_gc(lua_State *L, int what, int data) { return lua_gc(L, what, data); }
+
+int hks_error(lua_State *L) HKS_EXPORT("?lua_error@@YAHPEAUlua_State@@@Z");
+int hks_error(lua_State *L) { return lua_error(L); }
+
+int hks_next(lua_State *L, int idx) HKS_EXPORT("?lua_next@@YAHPEAUlua_State@@H@Z");
+int hks_next(lua_State *L, int idx) { return lua_next(L, idx); }
+
+void hks_concat(lua_State *L, int n) HKS_EXPORT("?lua_concat@@YAXPEAUlua_State@@H@Z");
+void hks_concat(lua_State *L, int n) { lua_concat(L, n); }
+
+lua_Alloc hks_getallocf(lua_State *L, void **ud)
+    HKS_EXPORT("?lua_getallocf@@YAP6APEAXPEAX0_K1@ZPEAUlua_State@@PEAPEAX@Z");
+lua_Alloc hks_getallocf(lua_State *L, void **ud) { return lua_getallocf(L, ud); }
+
+void hks_setallocf(lua_State *L, lua_Alloc f, void *ud)
+    HKS_EXPORT("?lua_setallocf@@YAXPEAUlua_State@@P6APEAXPEAX1_K2@Z1@Z");
+void hks_setallocf(lua_State *L, lua_Alloc f, void *ud) { lua_setallocf(L, f, ud); }
+
+/*
+ * Debug API
+ */
+
+int hks_getstack(lua_State *L, int level, lua_Debug *ar)
+    HKS_EXPORT("?lua_getstack@@YAHPEAUlua_State@@HPEAUlua_Debug@@@Z");
+int hks_getstack(lua_State *L, int level, lua_Debug *ar) { return lua_getstack(L, level, ar); }
+
+int hks_getinfo(lua_State *L, const char *what, lua_Debug *ar)
+    HKS_EXPORT("?lua_getinfo@@YAHPEAUlua_State@@PEBDPEAUlua_Debug@@@Z");
+int hks_getinfo(lua_State *L, const char *what, lua_Debug *ar) { return lua_getinfo(L, what, ar); }
+
+const char *hks_getlocal(lua_State *L, const lua_Debug *ar, int n)
+    HKS_EXPORT("?lua_getlocal@@YAPEBDPEAUlua_State@@PEAUlua_Debug@@H@Z");
+const char *hks_getlocal(lua_State *L, const lua_Debug *ar, int n) { return lua_getlocal(L, ar, n); }
+
+const char *hks_setlocal(lua_State *L, const lua_Debug *ar, int n)
+    HKS_EXPORT("?lua_setlocal@@YAPEBDPEAUlua_State@@PEAUlua_Debug@@H@Z");
+const char *hks_setlocal(lua_State *L, const lua_Debug *ar, int n) { return lua_setlocal(L, ar, n); }
+
+const char *hks_getupvalue(lua_State *L, int funcindex, int n)
+    HKS_EXPORT("?lua_getupvalue@@YAPEBDPEAUlua_State@@HH@Z");
+const char *hks_getupvalue(lua_State *L, int funcindex, int n) { return lua_getupvalue(L, funcindex, n); }
+
+const char *hks_setupvalue(lua_State *L, int funcindex, int n)
+    HKS_EXPORT("?lua_setupvalue@@YAPEBDPEAUlua_State@@HH@Z");
+const char *hks_setupvalue(lua_State *L, int funcindex, int n) { return lua_setupvalue(L, funcindex, n); }
+
+int hks_sethook(lua_State *L, lua_Hook func, int mask, int count)
+    HKS_EXPORT("?lua_sethook@@YAHPEAUlua_State@@P6AX0PEAUlua_Debug@@@ZHH@Z");
+int hks_sethook(lua_State *L, lua_Hook func, int mask, int count) { return lua_sethook(L, func, mask, count); }
+
+lua_Hook hks_gethook(lua_State *L) HKS_EXPORT("?lua_gethook@@YAP6AXPEAUlua_State@@PEAUlua_Debug@@@Z0@Z");
+lua_Hook hks_gethook(lua_State *L) { return lua_gethook(L); }
+
+int hks_gethookmask(lua_State *L) HKS_EXPORT("?lua_gethookmask@@YAHPEAUlua_State@@@Z");
+int hks_gethookmask(lua_State *L) { return lua_gethookmask(L); }
+
+int hks_gethookcount(lua_State *L) HKS_EXPORT("?lua_gethookcount@@YAHPEAUlua_State@@@Z");
+int hks_gethookcount(lua_State *L) { return lua_gethookcount(L); }
+
+/*
+ * LuaPlus::LuaState members
+ */
+
+int hks_CheckStack(lua_State *L, int sz) HKS_EXPORT("?CheckStack@LuaState@LuaPlus@@QEAAHH@Z");
+int hks_CheckStack(lua_State *L, int sz) { return lua_checkstack(L, sz); }
+
+void hks_XMove(lua_State *L, lua_State *to, int n) HKS_EXPORT("?XMove@LuaState@LuaPlus@@QEAAXPEAV12@H@Z");
+void hks_XMove(lua_State *L, lua_State *to, int n) { lua_xmove(L, to, n); }
+
+void hks_SetTable(lua_State *L, int idx) HKS_EXPORT("?SetTable@LuaState@LuaPlus@@QEAAXH@Z");
+void hks_SetTable(lua_State *L, int idx) { lua_settable(L, idx); }
+
+int hks_CoStatus(lua_State *L) HKS_EXPORT("?CoStatus@LuaState@LuaPlus@@QEAAHXZ");
+int hks_CoStatus(lua_State *L) { return lua_status(L); }
+
+LuaStackObject *hks_PushLString(lua_State *L, LuaStackObject *ret, const char *s, size_t l)
+    HKS_EXPORT("?PushLString@LuaState@LuaPlus@@QEAA?AVLuaStackObject@2@PEBD_K@Z");
+LuaStackObject *hks_PushLString(lua_State *L, LuaStackObject *ret, const char *s, size_t l) {
+    lua_pushlstring(L, s, l);
+    return hks_stack_object(L, ret);
+}
+
+LuaStackObject *hks_PushString(lua_State *L, LuaStackObject *ret, const char *s)
+    HKS_EXPORT("?PushString@LuaState@LuaPlus@@QEAA?AVLuaStackObject@2@PEBD@Z");
+LuaStackObject *hks_PushString(lua_State *L, LuaStackObject *ret, const char *s) {
+    lua_pushstring(L, s);
+    return hks_stack_object(L, ret);
+}
+
+LuaStackObject *hks_PushCClosure(lua_State *L, LuaStackObject *ret, lua_CFunction f, int n)
+    HKS_EXPORT("?PushCClosure@LuaState@LuaPlus@@QEAA?AVLuaStackObject@2@P6AHPEAUlua_State@@@ZH@Z");
+LuaStackObject *hks_PushCClosure(lua_State *L, LuaStackObject *ret, lua_CFunction f, int n) {
+    lua_pushcclosure(L, f, n);
+    return hks_stack_object(L, ret);
+}
+
+LuaStackObject *hks_PushBoolean(lua_State *L, LuaStackObject *ret, bool b)
+    HKS_EXPORT("?PushBoolean@LuaState@LuaPlus@@QEAA?AVLuaStackObject@2@_N@Z");
+LuaStackObject *hks_PushBoolean(lua_State *L, LuaStackObject *ret, bool b) {
+    lua_pushboolean(L, b);
+    return hks_stack_object(L, ret);
+}
+
+/*
+ * Auxiliary library
+ */
+
+void hksL_register(lua_State *L, const char *libname, const luaL_Reg *l)
+    HKS_EXPORT("?luaL_register@@YAXPEAUlua_State@@PEBDPEBUluaL_Reg@@@Z");
+void hksL_register(lua_State *L, const char *libname, const luaL_Reg *l) { luaL_register(L, libname, l); }
+
+int hksL_getmetafield(lua_State *L, int obj, const char *e) HKS_EXPORT("?luaL_getmetafield@@YAHPEAUlua_State@@HPEBD@Z");
+int hksL_getmetafield(lua_State *L, int obj, const char *e) { return luaL_getmetafield(L, obj, e); }
+
+int hksL_callmeta(lua_State *L, int obj, const char *e) HKS_EXPORT("?luaL_callmeta@@YAHPEAUlua_State@@HPEBD@Z");
+int hksL_callmeta(lua_State *L, int obj, const char *e) { return luaL_callmeta(L, obj, e); }
+
+int hksL_typerror(lua_State *L, int narg, const char *tname) HKS_EXPORT("?luaL_typerror@@YAHPEAUlua_State@@HPEBD@Z");
+int hksL_typerror(lua_State *L, int narg, const char *tname) { return luaL_typerror(L, narg, tname); }
+
+int hksL_argerror(lua_State *L, int narg, const char *extramsg) HKS_EXPORT("?luaL_argerror@@YAHPEAUlua_State@@HPEBD@Z");
+int hksL_argerror(lua_State *L, int narg, const char *extramsg) { return luaL_argerror(L, narg, extramsg); }
+
+const char *hksL_checklstring(lua_State *L, int narg, size_t *l)
+    HKS_EXPORT("?luaL_checklstring@@YAPEBDPEAUlua_State@@HPEA_K@Z");
+const char *hksL_checklstring(lua_State *L, int narg, size_t *l) { return luaL_checklstring(L, narg, l); }
+
+const char *hksL_optlstring(lua_State *L, int narg, const char *def, size_t *l)
+    HKS_EXPORT("?luaL_optlstring@@YAPEBDPEAUlua_State@@HPEBDPEA_K@Z");
+const char *hksL_optlstring(lua_State *L, int narg, const char *def, size_t *l) {
+    return luaL_optlstring(L, narg, def, l);
+}
+
+lua_Number hksL_checknumber(lua_State *L, int narg) HKS_EXPORT("?luaL_checknumber@@YANPEAUlua_State@@H@Z");
+lua_Number hksL_checknumber(lua_State *L, int narg) { return luaL_checknumber(L, narg); }
+
+lua_Number hksL_optnumber(lua_State *L, int narg, lua_Number def) HKS_EXPORT("?luaL_optnumber@@YANPEAUlua_State@@HN@Z");
+lua_Number hksL_optnumber(lua_State *L, int narg, lua_Number def) { return luaL_optnumber(L, narg, def); }
+
+int hksL_checkinteger(lua_State *L, int narg) HKS_EXPORT("?luaL_checkinteger@@YAHPEAUlua_State@@H@Z");
+int hksL_checkinteger(lua_State *L, int narg) { return (int)luaL_checkinteger(L, narg); }
+
+int hksL_optinteger(lua_State *L, int narg, int def) HKS_EXPORT("?luaL_optinteger@@YAHPEAUlua_State@@HH@Z");
+int hksL_optinteger(lua_State *L, int narg, int def) { return (int)luaL_optinteger(L, narg, def); }
+
+void hksL_checkstack(lua_State *L, int sz, const char *msg) HKS_EXPORT("?luaL_checkstack@@YAXPEAUlua_State@@HPEBD@Z");
+void hksL_checkstack(lua_State *L, int sz, const char *msg) { luaL_checkstack(L, sz, msg); }
+
+void hksL_checktype(lua_State *L, int narg, int t) HKS_EXPORT("?luaL_checktype@@YAXPEAUlua_State@@HH@Z");
+void hksL_checktype(lua_State *L, int narg, int t) { luaL_checktype(L, narg, t); }
+
+void hksL_checkany(lua_State *L, int narg) HKS_EXPORT("?luaL_checkany@@YAXPEAUlua_State@@H@Z");
+void hksL_checkany(lua_State *L, int narg) { luaL_checkany(L, narg); }
+
+int hksL_newmetatable(lua_State *L, const char *tname) HKS_EXPORT("?luaL_newmetatable@@YAHPEAUlua_State@@PEBD@Z");
+int hksL_newmetatable(lua_State *L, const char *tname) { return luaL_newmetatable(L, tname); }
+
+void *hksL_checkudata(lua_State *L, int ud, const char *tname) HKS_EXPORT("?luaL_checkudata@@YAPEAXPEAUlua_State@@HPEBD@Z");
+void *hksL_checkudata(lua_State *L, int ud, const char *tname) { return luaL_checkudata(L, ud, tname); }
+
+void hksL_where(lua_State *L, int lvl) HKS_EXPORT("?luaL_where@@YAXPEAUlua_State@@H@Z");
+void hksL_where(lua_State *L, int lvl) { luaL_where(L, lvl); }
+
+int hksL_error(lua_State *L, const char *fmt, ...) HKS_EXPORT("?luaL_error@@YAHPEAUlua_State@@PEBDZZ");
+int hksL_error(lua_State *L, const char *fmt, ...) {
+    va_list argp;
+    va_start(argp, fmt);
+    luaL_where(L, 1);
+    lua_pushvfstring(L, fmt, argp);
+    va_end(argp);
+    lua_concat(L, 2);
+    return lua_error(L);
+}
+
+int hksL_checkoption(lua_State *L, int narg, const char *def, const char *const lst[])
+    HKS_EXPORT("?luaL_checkoption@@YAHPEAUlua_State@@HPEBDQEBQEBD@Z");
+int hksL_checkoption(lua_State *L, int narg, const char *def, const char *const lst[]) {
+    return luaL_checkoption(L, narg, def, lst);
+}
+
+int hksL_ref(lua_State *L, int t) HKS_EXPORT("?luaL_ref@@YAHPEAUlua_State@@H@Z");
+int hksL_ref(lua_State *L, int t) { return luaL_ref(L, t); }
+
+void hksL_unref(lua_State *L, int t, int ref) HKS_EXPORT("?luaL_unref@@YAXPEAUlua_State@@HH@Z");
+void hksL_unref(lua_State *L, int t, int ref) { luaL_unref(L, t, ref); }
+
+int hksL_loadfile(lua_State *L, const char *filename) HKS_EXPORT("?luaL_loadfile@@YAHPEAUlua_State@@PEBD@Z");
+int hksL_loadfile(lua_State *L, const char *filename) { return luaL_loadfile(L, filename); }
+
+int hksL_loadbuffer(lua_State *L, const char *buff, size_t sz, const char *name)
+    HKS_EXPORT("?luaL_loadbuffer@@YAHPEAUlua_State@@PEBD_K1@Z");
+int hksL_loadbuffer(lua_State *L, const char *buff, size_t sz, const char *name) {
+    return luaL_loadbuffer(L, buff, sz, name);
+}
+
+int hksL_loadstring(lua_State *L, const char *s) HKS_EXPORT("?luaL_loadstring@@YAHPEAUlua_State@@PEBD@Z");
+int hksL_loadstring(lua_State *L, const char *s) { return luaL_loadstring(L, s); }
+
+lua_State *hksL_newstate(void) HKS_EXPORT("?luaL_newstate@@YAPEAUlua_State@@XZ");
+lua_State *hksL_newstate(void) { return luaL_newstate(); }
+
+const char *hksL_gsub(lua_State *L, const char *s, const char *p, const char *r)
+    HKS_EXPORT("?luaL_gsub@@YAPEBDPEAUlua_State@@PEBD11@Z");
+const char *hksL_gsub(lua_State *L, const char *s, const char *p, const char *r) { return luaL_gsub(L, s, p, r); }
+
+const char *hksL_findtable(lua_State *L, int idx, const char *fname, int szhint)
+    HKS_EXPORT("?luaL_findtable@@YAPEBDPEAUlua_State@@HPEBDH@Z");
+const char *hksL_findtable(lua_State *L, int idx, const char *fname, int szhint) {
+    return luaL_findtable(L, idx, fname, szhint);
+}
+
+/*
+ * Standard libraries
+ */
+
+int hksopen_base(lua_State *L) HKS_EXPORT("?luaopen_base@@YAHPEAUlua_State@@@Z");
+int hksopen_base(lua_State *L) { return luaopen_base(L); }
+
+int hksopen_table(lua_State *L) HKS_EXPORT("?luaopen_table@@YAHPEAUlua_State@@@Z");
+int hksopen_table(lua_State *L) { return luaopen_table(L); }
+
+int hksopen_io(lua_State *L) HKS_EXPORT("?luaopen_io@@YAHPEAUlua_State@@@Z");
+int hksopen_io(lua_State *L) { return luaopen_io(L); }
+
+int hksopen_os(lua_State *L) HKS_EXPORT("?luaopen_os@@YAHPEAUlua_State@@@Z");
+int hksopen_os(lua_State *L) { return luaopen_os(L); }
+
+int hksopen_string(lua_State *L) HKS_EXPORT("?luaopen_string@@YAHPEAUlua_State@@@Z");
+int hksopen_string(lua_State *L) { return luaopen_string(L); }
+
+int hksopen_math(lua_State *L) HKS_EXPORT("?luaopen_math@@YAHPEAUlua_State@@@Z");
+int hksopen_math(lua_State *L) { return luaopen_math(L); }
+
+int hksopen_debug(lua_State *L) HKS_EXPORT("?luaopen_debug@@YAHPEAUlua_State@@@Z");
+int hksopen_debug(lua_State *L) { return luaopen_debug(L); }
+
+int hksopen_package(lua_State *L) HKS_EXPORT("?luaopen_package@@YAHPEAUlua_State@@@Z");
+int hksopen_package(lua_State *L) { return luaopen_package(L); }
+
+void hksL_openlibs(lua_State *L) HKS_EXPORT("?luaL_openlibs@@YAXPEAUlua_State@@@Z");
+void hksL_openlibs(lua_State *L) { luaL_openlibs(L); }
@@ -0,0 +1,78 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "civ6-dynamic")]
+use std::process::Command;
+
+/// Builds the HavokScript stand-in library on top of the vendored Lua 5.1 sources.
+pub fn build_standin() {
+    let target_env = env::var("CARGO_CFG_TARGET_ENV").unwrap();
+    if target_env == "msvc" {
+        panic!("`civ6-standin` requires a GCC compatible C compiler and cannot be built with MSVC");
+    }
+
+    let artifacts = lua_src::Build::new().build(lua_src::Lua51);
+
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    let mut build = cc::Build::new();
+    build
+        .include(artifacts.include_dir())
+        .file(standin_source(&out_dir))
+        .warnings(false)
+        .out_dir(out_dir.join("standin-build"));
+
+    #[cfg(not(feature = "civ6-dynamic"))]
+    {
+        build.compile("havokscript_standin");
+
+        // The stand-in references stock Lua symbols, so it must come first on the link line
+        artifacts.print_cargo_metadata();
+    }
+
+    // With runtime symbol resolution the stand-in is not linked at all, instead it's built
+    // as a shared library that is opened the same way as the game runtime.
+    #[cfg(feature = "civ6-dynamic")]
+    {
+        let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap();
+        let (lib_name, whole_archive, no_whole_archive) = match target_os.as_str() {
+            "macos" | "ios" => ("libhavokscript_standin.dylib", "-Wl,-all_load", None),
+            "windows" => ("havokscript_standin.dll", "-Wl,--whole-archive", Some("-Wl,--no-whole-archive")),
+            _ => ("libhavokscript_standin.so", "-Wl,--whole-archive", Some("-Wl,--no-whole-archive")),
+        };
+        let lib_path = out_dir.join(lib_name);
+
+        let objects = build.compile_intermediates();
+        let mut cmd: Command = build.get_compiler().to_command();
+        cmd.arg("-shared").arg("-o").arg(&lib_path).args(&objects);
+        cmd.arg(whole_archive);
+        for lib in artifacts.libs() {
+            cmd.arg(artifacts.lib_dir().join(format!("lib{lib}.a")));
+        }
+        cmd.args(no_whole_archive);
+        if target_os != "windows" {
+            cmd.arg("-lm");
+        }
+
+        let status = cmd.status().expect("failed to run the C compiler");
+        if !status.success() {
+            panic!("failed to link the HavokScript stand-in shared library");
+        }
+
+        println!("cargo:rustc-env=HAVOKSCRIPT_STANDIN_PATH={}", lib_path.display());
+    }
+}
+
+// ELF reserves `@` in dynamic symbol names for symbol versioning, so a shared stand-in
+// exports the mangled names with `$` instead (the runtime resolver knows about this)
+fn standin_source(out_dir: &Path) -> PathBuf {
+    let source = PathBuf::from("build/civ6_standin.c");
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap();
+    if cfg!(not(feature = "civ6-dynamic")) || matches!(target_os.as_str(), "macos" | "ios" | "windows") {
+        return source;
+    }
+
+    let patched = out_dir.join("civ6_standin.c");
+    let code = fs::read_to_string(&source).unwrap().replace('@', "$");
+    fs::write(&patched, code).unwrap();
+    patched
+}
@@ -8,6 +8,9 @@ cfg_if::cfg_if! {
     }
 }
 
+#[cfg(feature = "civ6-standin")]
+mod civ6_standin;
+
 fn main() {
     #[cfg(all(feature = "luau", feature = "module", windows))]
     compile_error!("Luau does not support `module` mode on Windows");
@@ -17,7 +20,12 @@ fn main() {
 
     println!("cargo:rerun-if-changed=build");
 
-    #[cfg(windows)]
+    // The stand-in replaces the game runtime and is linked even in `module` mode (or built as a
+    // shared library when symbols are resolved at runtime)
+    #[cfg(feature = "civ6-standin")]
+    civ6_standin::build_standin();
+
+    #[cfg(all(windows, not(any(feature = "civ6-standin", feature = "civ6-dynamic"))))]
     if cfg!(feature = "module") {
         if !std::env::var("LUA_LIB_NAME").unwrap_or_default().is_empty() {
             // Don't use raw-dylib linking
@@ -28,6 +36,6 @@ fn main() {
         println!("cargo:rustc-cfg=raw_dylib");
     }
 
-    #[cfg(not(feature = "module"))]
+    #[cfg(not(any(feature = "module", feature = "civ6-standin", feature = "civ6-dynamic")))]
     find::probe_lua();
 }
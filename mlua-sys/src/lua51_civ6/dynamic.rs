@@ -0,0 +1,401 @@
+//! Runtime resolution of the HavokScript API.
+//!
+//! With the `civ6-dynamic` feature the entry points are not linked against the game runtime.
+//! Instead they are looked up by name in a shared library, either explicitly with one of the
+//! `load*` functions, or on the first call into the API from the default library
+//! (`HavokScript_FinalRelease.dll` already loaded by the game on Windows).
+//!
+//! Loading fails with [`LoadError::MissingSymbols`] listing every essential entry point
+//! that could not be found. Non-essential entry points (hooks, dumping, some of the standard
+//! libraries, ...) are replaced with fallbacks, which are listed by [`fallbacks`].
+//!
+//! ELF does not allow `@` in exported names, so on ELF platforms a symbol is also looked up
+//! with every `@` replaced by `$` (this is how the `civ6-standin` library is built there).
+
+use std::error::Error as StdError;
+use std::ffi::OsStr;
+use std::fmt;
+use std::ops::Deref;
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use std::sync::Mutex;
+
+use libloading::Library;
+
+use super::lauxlib::{self, LUA_ERRFILE};
+use super::lua::{
+    self, lua_Alloc, lua_CFunction, lua_Debug, lua_Hook, lua_State, lua_UI64, lua_Writer,
+};
+use super::lualib;
+
+/// An error that can occur while loading the HavokScript runtime.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum LoadError {
+    /// The library could not be opened.
+    Library(libloading::Error),
+    /// The library does not export the listed (essential) entry points.
+    MissingSymbols(Vec<&'static str>),
+    /// A runtime has already been loaded.
+    AlreadyLoaded,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Library(err) => write!(fmt, "cannot open HavokScript library: {err}"),
+            LoadError::MissingSymbols(symbols) => {
+                write!(
+                    fmt,
+                    "HavokScript library is missing symbols: {}",
+                    symbols.join(", ")
+                )
+            }
+            LoadError::AlreadyLoaded => write!(fmt, "HavokScript runtime is already loaded"),
+        }
+    }
+}
+
+impl StdError for LoadError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            LoadError::Library(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Path to the stand-in library built by the `civ6-standin` feature.
+#[cfg(feature = "civ6-standin")]
+pub const STANDIN_LIBRARY: &str = env!("HAVOKSCRIPT_STANDIN_PATH");
+
+struct Loaded {
+    _library: Library,
+    fallbacks: Vec<&'static str>,
+}
+
+static LOADED: Mutex<Option<Loaded>> = Mutex::new(None);
+static UI64_SUPPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Loads the HavokScript runtime from the shared library at `path`.
+///
+/// # Safety
+/// The library must export the HavokScript API with the signatures used by this crate.
+pub unsafe fn load(path: impl AsRef<OsStr>) -> Result<(), LoadError> {
+    let library = Library::new(path).map_err(LoadError::Library)?;
+    load_from_library(library)
+}
+
+/// Loads the HavokScript runtime from a raw library handle (as returned by `dlopen` or
+/// `LoadLibrary`/`GetModuleHandle`).
+///
+/// The handle is owned by the runtime afterwards and never closed.
+///
+/// # Safety
+/// The handle must be valid and the library must export the HavokScript API with the
+/// signatures used by this crate.
+pub unsafe fn load_from_handle(handle: *mut c_void) -> Result<(), LoadError> {
+    #[cfg(unix)]
+    let library = libloading::os::unix::Library::from_raw(handle);
+    #[cfg(windows)]
+    let library = libloading::os::windows::Library::from_raw(handle as isize);
+    load_from_library(library.into())
+}
+
+/// Loads the HavokScript runtime from an already opened library.
+///
+/// # Safety
+/// The library must export the HavokScript API with the signatures used by this crate.
+pub unsafe fn load_from_library(library: Library) -> Result<(), LoadError> {
+    let mut resolver = Resolver {
+        library: &library,
+        missing: Vec::new(),
+        fallbacks: Vec::new(),
+    };
+
+    let lua_api = lua::LuaApi::resolve(&mut resolver);
+    let lua_gc_api = lua::LuaGcApi::resolve(&mut resolver);
+    let lua_misc_api = lua::LuaMiscApi::resolve(&mut resolver);
+    let lua_debug_api = lua::LuaDebugApi::resolve(&mut resolver);
+    let lauxlib_api = lauxlib::LauxlibApi::resolve(&mut resolver);
+    let lauxlib_ref_api = lauxlib::LauxlibRefApi::resolve(&mut resolver);
+    let lualib_api = lualib::LualibApi::resolve(&mut resolver);
+    let lua_ui64_api = lua::LuaUi64Api::resolve(&mut resolver);
+    let vararg_api = VarargApi::resolve(&mut resolver);
+
+    if !resolver.missing.is_empty() {
+        return Err(LoadError::MissingSymbols(resolver.missing));
+    }
+    let fallbacks = resolver.fallbacks;
+
+    let mut loaded = LOADED.lock().unwrap_or_else(|err| err.into_inner());
+    if loaded.is_some() {
+        return Err(LoadError::AlreadyLoaded);
+    }
+
+    install(&VARARG_API, vararg_api);
+    install(&lua::LUA_API, lua_api);
+    install(&lua::LUA_GC_API, lua_gc_api);
+    install(&lua::LUA_MISC_API, lua_misc_api);
+    install(&lua::LUA_DEBUG_API, lua_debug_api);
+    install(&lauxlib::LAUXLIB_API, lauxlib_api);
+    install(&lauxlib::LAUXLIB_REF_API, lauxlib_ref_api);
+    install(&lualib::LUALIB_API, lualib_api);
+    install(&lua::LUA_UI64_API, lua_ui64_api);
+    UI64_SUPPORTED.store(
+        !fallbacks
+            .iter()
+            .any(|sym| sym.starts_with("?lua_pushui64@") || sym.starts_with("?lua_toui64@")),
+        Ordering::Release,
+    );
+
+    *loaded = Some(Loaded {
+        _library: library,
+        fallbacks,
+    });
+    Ok(())
+}
+
+/// Returns `true` if the HavokScript runtime is loaded.
+pub fn is_loaded() -> bool {
+    let loaded = LOADED.lock().unwrap_or_else(|err| err.into_inner());
+    loaded.is_some()
+}
+
+/// Returns the entry points missing from the loaded runtime that were replaced with fallbacks.
+pub fn fallbacks() -> Vec<&'static str> {
+    let loaded = LOADED.lock().unwrap_or_else(|err| err.into_inner());
+    match &*loaded {
+        Some(loaded) => loaded.fallbacks.clone(),
+        None => Vec::new(),
+    }
+}
+
+/// Returns `true` if the loaded runtime supports 64-bit unsigned integers (`ui64`).
+///
+/// Without it, [`lua_pushui64`] pushes a (lossy) number and [`lua_toui64`] converts a number.
+///
+/// [`lua_pushui64`]: super::lua::lua_pushui64
+/// [`lua_toui64`]: super::lua::lua_toui64
+pub fn lua_ui64_supported() -> bool {
+    UI64_SUPPORTED.load(Ordering::Acquire)
+}
+
+// Loads the runtime from the default location
+unsafe fn load_default() -> Result<(), LoadError> {
+    #[cfg(feature = "civ6-standin")]
+    return load(STANDIN_LIBRARY);
+
+    #[cfg(all(windows, not(feature = "civ6-standin")))]
+    return load_from_library(
+        libloading::os::windows::Library::open_already_loaded("HavokScript_FinalRelease.dll")
+            .map_err(LoadError::Library)?
+            .into(),
+    );
+
+    #[cfg(not(any(windows, feature = "civ6-standin")))]
+    panic!("HavokScript runtime is not loaded");
+}
+
+#[doc(hidden)]
+pub(crate) trait FunctionTable: Sized {
+    unsafe fn resolve(resolver: &mut Resolver) -> Option<Self>;
+}
+
+#[doc(hidden)]
+pub(crate) struct Resolver<'a> {
+    library: &'a Library,
+    missing: Vec<&'static str>,
+    fallbacks: Vec<&'static str>,
+}
+
+impl<'a> Resolver<'a> {
+    pub(crate) unsafe fn resolve<F: Copy>(
+        &mut self,
+        symbol: &'static str,
+        fallback: Option<F>,
+    ) -> Option<F> {
+        match self.lookup(symbol) {
+            Some(f) => Some(f),
+            None if fallback.is_some() => {
+                self.fallbacks.push(symbol);
+                fallback
+            }
+            None => {
+                self.missing.push(symbol);
+                None
+            }
+        }
+    }
+
+    unsafe fn lookup<F: Copy>(&self, symbol: &str) -> Option<F> {
+        if let Ok(f) = self.library.get::<F>(symbol.as_bytes()) {
+            return Some(*f);
+        }
+        // ELF reserves `@` in dynamic symbol names for symbol versioning, so libraries built
+        // for ELF platforms export the mangled names with `$` instead
+        #[cfg(all(unix, not(target_vendor = "apple")))]
+        if let Ok(f) = self.library.get::<F>(symbol.replace('@', "$").as_bytes()) {
+            return Some(*f);
+        }
+        None
+    }
+}
+
+fn install<T>(slot: &AtomicPtr<T>, table: Option<T>) {
+    let table = Box::new(table.expect("function table is resolved"));
+    slot.store(Box::into_raw(table), Ordering::Release);
+}
+
+#[doc(hidden)]
+#[inline(always)]
+pub(crate) fn table<T>(slot: &AtomicPtr<T>) -> &'static T {
+    let mut table = slot.load(Ordering::Acquire);
+    if table.is_null() {
+        table = load_table(slot);
+    }
+    // Tables are never freed once installed
+    unsafe { &*table }
+}
+
+#[cold]
+fn load_table<T>(slot: &AtomicPtr<T>) -> *mut T {
+    match unsafe { load_default() } {
+        Ok(()) | Err(LoadError::AlreadyLoaded) => {}
+        Err(err) => panic!("{err}"),
+    }
+    slot.load(Ordering::Acquire)
+}
+
+// Variadic entry points, which cannot be wrapped by a Rust function
+pub(crate) struct VarargApi {
+    pub(crate) lua_pushfstring: lua::lua_pushfstring_t,
+    pub(crate) luaL_error: lauxlib::luaL_error_t,
+}
+
+static VARARG_API: AtomicPtr<VarargApi> = AtomicPtr::new(ptr::null_mut());
+
+impl FunctionTable for VarargApi {
+    unsafe fn resolve(resolver: &mut Resolver) -> Option<Self> {
+        let lua_pushfstring = resolver.resolve(
+            "?lua_pushfstring@@YAPEBDPEAUlua_State@@PEBDZZ",
+            None::<lua::lua_pushfstring_t>,
+        );
+        let luaL_error = resolver.resolve(
+            "?luaL_error@@YAHPEAUlua_State@@PEBDZZ",
+            None::<lauxlib::luaL_error_t>,
+        );
+        Some(VarargApi {
+            lua_pushfstring: lua_pushfstring?,
+            luaL_error: luaL_error?,
+        })
+    }
+}
+
+#[inline(always)]
+pub(crate) fn vararg_api() -> &'static VarargApi {
+    table(&VARARG_API)
+}
+
+/// A variadic entry point of the runtime.
+///
+/// Dereferences to the function pointer (loading the runtime on first use like the other
+/// entry points), so it is called as a regular function.
+pub struct VarargFn<F: 'static> {
+    get: fn() -> &'static F,
+}
+
+impl<F> VarargFn<F> {
+    pub(crate) const fn new(get: fn() -> &'static F) -> Self {
+        VarargFn { get }
+    }
+}
+
+impl<F> Deref for VarargFn<F> {
+    type Target = F;
+
+    #[inline(always)]
+    fn deref(&self) -> &F {
+        (self.get)()
+    }
+}
+
+// Fallbacks for non-essential entry points
+
+pub(crate) unsafe extern "C-unwind" fn lua_newstate(
+    _f: lua_Alloc,
+    _ud: *mut c_void,
+) -> *mut lua_State {
+    ptr::null_mut()
+}
+
+pub(crate) unsafe extern "C-unwind" fn lua_close(_L: *mut lua_State) {}
+
+pub(crate) unsafe extern "C-unwind" fn lua_dump(
+    _L: *mut lua_State,
+    _writer: lua_Writer,
+    _data: *mut c_void,
+) -> c_int {
+    1
+}
+
+pub(crate) unsafe extern "C-unwind" fn lua_gethook(_L: *mut lua_State) -> Option<lua_Hook> {
+    None
+}
+
+pub(crate) unsafe extern "C-unwind" fn lua_gethookmask(_L: *mut lua_State) -> c_int {
+    0
+}
+
+pub(crate) unsafe extern "C-unwind" fn lua_gethookcount(_L: *mut lua_State) -> c_int {
+    0
+}
+
+pub(crate) unsafe extern "C-unwind" fn lua_nolocal(
+    _L: *mut lua_State,
+    _ar: *const lua_Debug,
+    _n: c_int,
+) -> *const c_char {
+    ptr::null()
+}
+
+pub(crate) unsafe extern "C-unwind" fn lua_pushui64(L: *mut lua_State, n: lua_UI64) {
+    lua::lua_pushnumber(L, n as lua::lua_Number);
+}
+
+pub(crate) unsafe extern "C-unwind" fn lua_toui64(L: *mut lua_State, idx: c_int) -> lua_UI64 {
+    lua::lua_tonumber(L, idx) as lua_UI64
+}
+
+pub(crate) unsafe extern "C-unwind" fn luaL_loadfile(
+    L: *mut lua_State,
+    _filename: *const c_char,
+) -> c_int {
+    lua::lua_pushliteral(L, "loading files is not supported");
+    LUA_ERRFILE
+}
+
+pub(crate) unsafe extern "C-unwind" fn luaL_newstate() -> *mut lua_State {
+    ptr::null_mut()
+}
+
+// Missing standard libraries are opened as empty tables
+pub(crate) unsafe extern "C-unwind" fn luaopen_empty(L: *mut lua_State) -> c_int {
+    lua::lua_newtable(L);
+    1
+}
+
+pub(crate) unsafe extern "C-unwind" fn luaL_openlibs(L: *mut lua_State) {
+    let libs: [(&[u8], lua_CFunction); 4] = [
+        (b"\0", lualib::luaopen_base),
+        (b"table\0", lualib::luaopen_table),
+        (b"string\0", lualib::luaopen_string),
+        (b"math\0", lualib::luaopen_math),
+    ];
+    for (name, func) in libs {
+        lua::lua_pushcfunction(L, func);
+        lua::lua_pushstring_(L, name.as_ptr() as *const c_char);
+        lua::lua_call(L, 1, 0);
+    }
+}
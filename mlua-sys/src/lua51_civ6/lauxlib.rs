@@ -1,5 +1,6 @@
 //! Contains definitions from `lauxlib.h`.
 
+use std::ffi::CStr;
 use std::os::raw::{c_char, c_int, c_void};
 use std::ptr;
 
@@ -14,11 +15,8 @@ pub struct luaL_Reg {
     pub func: lua_CFunction,
 }
 
-#[cfg_attr(
-    all(windows, raw_dylib),
-    link(name = "HavokScript_FinalRelease", kind = "raw-dylib")
-)]
-extern "C-unwind" {
+hks_extern! {
+    static LAUXLIB_API: LauxlibApi;
     #[link_name = "?luaL_register@@YAXPEAUlua_State@@PEBDPEBUluaL_Reg@@@Z"]
     pub fn luaL_register(L: *mut lua_State, libname: *const c_char, l: *const luaL_Reg);
     #[link_name = "?luaL_getmetafield@@YAHPEAUlua_State@@HPEBD@Z"]
@@ -60,8 +58,7 @@ extern "C-unwind" {
 
     #[link_name = "?luaL_where@@YAXPEAUlua_State@@H@Z"]
     pub fn luaL_where(L: *mut lua_State, lvl: c_int);
-    #[link_name = "?luaL_error@@YAHPEAUlua_State@@PEBDZZ"]
-    pub fn luaL_error(L: *mut lua_State, fmt: *const c_char, ...) -> c_int;
+    // luaL_error (variadic, declared below)
 
     #[link_name = "?luaL_checkoption@@YAHPEAUlua_State@@HPEBDQEBQEBD@Z"]
     pub fn luaL_checkoption(
@@ -72,21 +69,40 @@ extern "C-unwind" {
     ) -> c_int;
 }
 
-// Pre-defined references
-pub const LUA_NOREF: c_int = -2;
-pub const LUA_REFNIL: c_int = -1;
-
+// Variadic functions cannot be wrapped, so with `civ6-dynamic` they are exposed as statics
+// dereferencing to function pointers
+#[cfg(not(feature = "civ6-dynamic"))]
 #[cfg_attr(
     all(windows, raw_dylib),
     link(name = "HavokScript_FinalRelease", kind = "raw-dylib")
 )]
 extern "C-unwind" {
+    #[link_name = "?luaL_error@@YAHPEAUlua_State@@PEBDZZ"]
+    pub fn luaL_error(L: *mut lua_State, fmt: *const c_char, ...) -> c_int;
+}
+
+#[cfg(feature = "civ6-dynamic")]
+pub type luaL_error_t =
+    unsafe extern "C-unwind" fn(L: *mut lua_State, fmt: *const c_char, ...) -> c_int;
+
+#[cfg(feature = "civ6-dynamic")]
+#[allow(non_upper_case_globals)]
+pub static luaL_error: super::dynamic::VarargFn<luaL_error_t> =
+    super::dynamic::VarargFn::new(|| &super::dynamic::vararg_api().luaL_error);
+
+// Pre-defined references
+pub const LUA_NOREF: c_int = -2;
+pub const LUA_REFNIL: c_int = -1;
+
+hks_extern! {
+    static LAUXLIB_REF_API: LauxlibRefApi;
     #[link_name = "?luaL_ref@@YAHPEAUlua_State@@H@Z"]
     pub fn luaL_ref(L: *mut lua_State, t: c_int) -> c_int;
     #[link_name = "?luaL_unref@@YAXPEAUlua_State@@HH@Z"]
     pub fn luaL_unref(L: *mut lua_State, t: c_int, r#ref: c_int);
 
     #[link_name = "?luaL_loadfile@@YAHPEAUlua_State@@PEBD@Z"]
+    #[fallback = super::dynamic::luaL_loadfile]
     pub fn luaL_loadfile(L: *mut lua_State, filename: *const c_char) -> c_int;
     #[link_name = "?luaL_loadbuffer@@YAHPEAUlua_State@@PEBD_K1@Z"]
     pub fn luaL_loadbuffer(
@@ -99,6 +115,7 @@ extern "C-unwind" {
     pub fn luaL_loadstring(L: *mut lua_State, s: *const c_char) -> c_int;
 
     #[link_name = "?luaL_newstate@@YAPEAUlua_State@@XZ"]
+    #[fallback = super::dynamic::luaL_newstate]
     pub fn luaL_newstate() -> *mut lua_State;
 
     #[link_name = "?luaL_gsub@@YAPEBDPEAUlua_State@@PEBD11@Z"]
@@ -173,5 +190,141 @@ pub unsafe fn luaL_getmetatable(L: *mut lua_State, n: *const c_char) {
 // TODO: luaL_opt
 
 //
-// TODO: Generic Buffer Manipulation
+// Generic Buffer Manipulation
+//
+// HavokScript does not export the buffer functions, so they are reimplemented here on top of
+// the Lua API (following `lauxlib.c` of Lua 5.1).
 //
+
+/// Buffer size used for on-stack string operations (`BUFSIZ` of the game runtime).
+pub const LUAL_BUFFERSIZE: usize = 512;
+
+// Limit of strings kept on the stack before they are concatenated
+const BUFFER_STACK_LIMIT: c_int = lua::LUA_MINSTACK / 2;
+
+/// String buffer, see the Lua 5.1 reference manual.
+///
+/// The buffer points into itself, so it must not be moved after `luaL_buffinit`.
+#[repr(C)]
+pub struct luaL_Buffer {
+    pub p: *mut c_char, // current position in buffer
+    pub lvl: c_int,     // number of strings in the stack (level)
+    pub L: *mut lua_State,
+    pub buffer: [c_char; LUAL_BUFFERSIZE],
+}
+
+#[inline(always)]
+unsafe fn bufflen(B: *mut luaL_Buffer) -> usize {
+    (*B).p.offset_from((*B).buffer.as_ptr()) as usize
+}
+
+#[inline(always)]
+unsafe fn bufffree(B: *mut luaL_Buffer) -> usize {
+    LUAL_BUFFERSIZE - bufflen(B)
+}
+
+unsafe fn emptybuffer(B: *mut luaL_Buffer) -> bool {
+    let l = bufflen(B);
+    if l == 0 {
+        // Put nothing on stack
+        return false;
+    }
+    lua::lua_pushlstring_((*B).L, (*B).buffer.as_ptr(), l);
+    (*B).p = (*B).buffer.as_mut_ptr();
+    (*B).lvl += 1;
+    true
+}
+
+unsafe fn adjuststack(B: *mut luaL_Buffer) {
+    if (*B).lvl > 1 {
+        let L = (*B).L;
+        // Number of levels to concat
+        let mut toget = 1;
+        let mut toplen = lua::lua_objlen(L, -1);
+        loop {
+            let l = lua::lua_objlen(L, -(toget + 1));
+            if (*B).lvl - toget + 1 >= BUFFER_STACK_LIMIT || toplen > l {
+                toplen += l;
+                toget += 1;
+            } else {
+                break;
+            }
+            if toget >= (*B).lvl {
+                break;
+            }
+        }
+        lua::lua_concat(L, toget);
+        (*B).lvl = (*B).lvl - toget + 1;
+    }
+}
+
+pub unsafe fn luaL_buffinit(L: *mut lua_State, B: *mut luaL_Buffer) {
+    (*B).L = L;
+    (*B).p = (*B).buffer.as_mut_ptr();
+    (*B).lvl = 0;
+}
+
+pub unsafe fn luaL_prepbuffer(B: *mut luaL_Buffer) -> *mut c_char {
+    if emptybuffer(B) {
+        adjuststack(B);
+    }
+    (*B).buffer.as_mut_ptr()
+}
+
+#[inline(always)]
+pub unsafe fn luaL_addsize(B: *mut luaL_Buffer, n: usize) {
+    (*B).p = (*B).p.add(n);
+}
+
+#[inline(always)]
+pub unsafe fn luaL_addchar(B: *mut luaL_Buffer, c: c_char) {
+    if bufffree(B) == 0 {
+        luaL_prepbuffer(B);
+    }
+    *(*B).p = c;
+    (*B).p = (*B).p.add(1);
+}
+
+pub unsafe fn luaL_addlstring(B: *mut luaL_Buffer, mut s: *const c_char, mut l: usize) {
+    while l > 0 {
+        if bufffree(B) == 0 {
+            luaL_prepbuffer(B);
+        }
+        let n = l.min(bufffree(B));
+        ptr::copy_nonoverlapping(s, (*B).p, n);
+        luaL_addsize(B, n);
+        s = s.add(n);
+        l -= n;
+    }
+}
+
+pub unsafe fn luaL_addstring(B: *mut luaL_Buffer, s: *const c_char) {
+    luaL_addlstring(B, s, CStr::from_ptr(s).to_bytes().len());
+}
+
+pub unsafe fn luaL_addvalue(B: *mut luaL_Buffer) {
+    let L = (*B).L;
+    let mut vl = 0;
+    let s = lua::lua_tolstring(L, -1, &mut vl);
+    if vl <= bufffree(B) {
+        // Fits into buffer
+        if vl > 0 {
+            ptr::copy_nonoverlapping(s, (*B).p, vl);
+            luaL_addsize(B, vl);
+        }
+        lua::lua_pop(L, 1);
+    } else {
+        if emptybuffer(B) {
+            // Put buffer before new value
+            lua::lua_insert(L, -2);
+        }
+        (*B).lvl += 1;
+        adjuststack(B);
+    }
+}
+
+pub unsafe fn luaL_pushresult(B: *mut luaL_Buffer) {
+    emptybuffer(B);
+    lua::lua_concat((*B).L, (*B).lvl);
+    (*B).lvl = 1;
+}
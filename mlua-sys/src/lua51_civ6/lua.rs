@@ -54,17 +54,23 @@ pub const LUA_TFUNCTION: c_int = 6;
 pub const LUA_TUSERDATA: c_int = 7;
 pub const LUA_TTHREAD: c_int = 8;
 
+// HavokScript extensions
+pub const LUA_TIFUNCTION: c_int = 9;
+pub const LUA_TCFUNCTION: c_int = 10;
+pub const LUA_TUI64: c_int = 11;
+pub const LUA_TSTRUCT: c_int = 12;
+
 /// Minimum Lua stack available to a C function
 pub const LUA_MINSTACK: c_int = 20;
 
 /// A Lua number, usually equivalent to `f64`
 pub type lua_Number = c_double;
 
-/// A Lua integer, usually equivalent to `i64`
-#[cfg(target_pointer_width = "32")]
-pub type lua_Integer = i32;
-#[cfg(target_pointer_width = "64")]
-pub type lua_Integer = i64;
+/// A Lua integer, always 32-bit in HavokScript (`int`, `H` in the mangled names)
+pub type lua_Integer = c_int;
+
+/// A HavokScript unsigned 64-bit integer (`ui64`)
+pub type lua_UI64 = u64;
 
 /// Type for native C functions that can be passed to Lua.
 pub type lua_CFunction = unsafe extern "C-unwind" fn(L: *mut lua_State) -> c_int;
@@ -82,17 +88,16 @@ pub type lua_Writer =
 pub type lua_Alloc =
     unsafe extern "C-unwind" fn(ud: *mut c_void, ptr: *mut c_void, osize: usize, nsize: usize) -> *mut c_void;
 
-#[cfg_attr(
-    all(windows, raw_dylib),
-    link(name = "HavokScript_FinalRelease", kind = "raw-dylib")
-)]
-extern "C-unwind" {
+hks_extern! {
+    static LUA_API: LuaApi;
     //
     // State manipulation
     //
     #[link_name = "?lua_newstate@@YAPEAUlua_State@@P6APEAXPEAX0_K1@Z0@Z"]
+    #[fallback = super::dynamic::lua_newstate]
     pub fn lua_newstate(f: lua_Alloc, ud: *mut c_void) -> *mut lua_State;
     #[link_name = "?lua_close@@YAXPEAUlua_State@@@Z"]
+    #[fallback = super::dynamic::lua_close]
     pub fn lua_close(L: *mut lua_State);
     #[link_name = "?lua_newthread@@YAPEAUlua_State@@PEAU1@@Z"]
     pub fn lua_newthread(L: *mut lua_State) -> *mut lua_State;
@@ -148,7 +153,7 @@ extern "C-unwind" {
     #[link_name = "?lua_tointeger@@YAHPEAUlua_State@@H@Z"]
     pub fn lua_tointeger_(L: *mut lua_State, idx: c_int) -> lua_Integer;
     #[link_name = "?lua_toboolean@@YA_NPEAUlua_State@@H@Z"]
-    pub fn lua_toboolean(L: *mut lua_State, idx: c_int) -> c_int;
+    pub fn lua_toboolean_(L: *mut lua_State, idx: c_int) -> bool;
     #[link_name = "?lua_tolstring@@YAPEBDPEAUlua_State@@HPEA_K@Z"]
     pub fn lua_tolstring(L: *mut lua_State, idx: c_int, len: *mut usize) -> *const c_char;
     #[link_name = "?lua_objlen@@YA_KPEAUlua_State@@H@Z"]
@@ -177,8 +182,7 @@ extern "C-unwind" {
     // #[link_name = "lua_pushstring"]
     // pub fn lua_pushstring_(L: *mut lua_State, s: *const c_char);
     // lua_pushvfstring
-    #[link_name = "?lua_pushfstring@@YAPEBDPEAUlua_State@@PEBDZZ"]
-    pub fn lua_pushfstring(L: *mut lua_State, fmt: *const c_char, ...) -> *const c_char;
+    // lua_pushfstring (variadic, declared below)
     // pub fn lua_pushcclosure(L: *mut lua_State, f: lua_CFunction, n: c_int);
 
     // pub fn lua_pushboolean(L: *mut lua_State, b: c_int);
@@ -240,6 +244,7 @@ extern "C-unwind" {
     ) -> c_int;
 
     #[link_name = "?lua_dump@@YAHPEAUlua_State@@P6AH0PEBX_KPEAX@Z3@Z"]
+    #[fallback = super::dynamic::lua_dump]
     pub fn lua_dump_(L: *mut lua_State, writer: lua_Writer, data: *mut c_void) -> c_int;
 
     //
@@ -291,6 +296,41 @@ extern "C-unwind" {
     ) -> *mut LuaStackObject;
 }
 
+// 64-bit unsigned integers are not exported by every runtime, so static builds import them only
+// with the opt-in `civ6-ui64` feature. When resolving symbols at runtime, missing ones are replaced
+// with fallbacks converting numbers, see `lua_ui64_supported`.
+#[cfg(any(feature = "civ6-ui64", feature = "civ6-dynamic"))]
+hks_extern! {
+    static LUA_UI64_API: LuaUi64Api;
+    #[link_name = "?lua_pushui64@@YAXPEAUlua_State@@_K@Z"]
+    #[fallback = super::dynamic::lua_pushui64]
+    pub fn lua_pushui64(L: *mut lua_State, n: lua_UI64);
+    #[link_name = "?lua_toui64@@YA_KPEAUlua_State@@H@Z"]
+    #[fallback = super::dynamic::lua_toui64]
+    pub fn lua_toui64(L: *mut lua_State, idx: c_int) -> lua_UI64;
+}
+
+// Variadic functions cannot be wrapped, so with `civ6-dynamic` they are exposed as statics
+// dereferencing to function pointers
+#[cfg(not(feature = "civ6-dynamic"))]
+#[cfg_attr(
+    all(windows, raw_dylib),
+    link(name = "HavokScript_FinalRelease", kind = "raw-dylib")
+)]
+extern "C-unwind" {
+    #[link_name = "?lua_pushfstring@@YAPEBDPEAUlua_State@@PEBDZZ"]
+    pub fn lua_pushfstring(L: *mut lua_State, fmt: *const c_char, ...) -> *const c_char;
+}
+
+#[cfg(feature = "civ6-dynamic")]
+pub type lua_pushfstring_t =
+    unsafe extern "C-unwind" fn(L: *mut lua_State, fmt: *const c_char, ...) -> *const c_char;
+
+#[cfg(feature = "civ6-dynamic")]
+#[allow(non_upper_case_globals)]
+pub static lua_pushfstring: super::dynamic::VarargFn<lua_pushfstring_t> =
+    super::dynamic::VarargFn::new(|| &super::dynamic::vararg_api().lua_pushfstring);
+
 // Structs for workaround function signatures
 #[repr(C)]
 pub struct LuaStackObject {
@@ -310,11 +350,8 @@ pub const LUA_GCSTEP: c_int = 5;
 pub const LUA_GCSETPAUSE: c_int = 6;
 pub const LUA_GCSETSTEPMUL: c_int = 7;
 
-#[cfg_attr(
-    all(windows, raw_dylib),
-    link(name = "HavokScript_FinalRelease", kind = "raw-dylib")
-)]
-extern "C-unwind" {
+hks_extern! {
+    static LUA_GC_API: LuaGcApi;
     #[link_name = "?lua_gc@@YAHPEAUlua_State@@HH@Z"]
     pub fn lua_gc(L: *mut lua_State, what: c_int, data: c_int) -> c_int;
 }
@@ -322,11 +359,8 @@ extern "C-unwind" {
 //
 // Miscellaneous functions
 //
-#[cfg_attr(
-    all(windows, raw_dylib),
-    link(name = "HavokScript_FinalRelease", kind = "raw-dylib")
-)]
-extern "C-unwind" {
+hks_extern! {
+    static LUA_MISC_API: LuaMiscApi;
     #[link_name = "?lua_error@@YAHPEAUlua_State@@@Z"]
     fn lua_error_(L: *mut lua_State) -> c_int;
     #[link_name = "?lua_next@@YAHPEAUlua_State@@H@Z"]
@@ -397,6 +431,11 @@ pub unsafe fn lua_pushboolean(L: *mut lua_State, b: c_int) {
     ret.assume_init();
 }
 
+#[inline(always)]
+pub unsafe fn lua_toboolean(L: *mut lua_State, idx: c_int) -> c_int {
+    lua_toboolean_(L, idx) as c_int
+}
+
 #[inline(always)]
 pub unsafe fn lua_settable(L: *mut lua_State, idx: c_int) {
     SetTable(L, idx);
@@ -532,18 +571,17 @@ pub const LUA_MASKCOUNT: c_int = 1 << (LUA_HOOKCOUNT as usize);
 /// Type for functions to be called on debug events.
 pub type lua_Hook = unsafe extern "C-unwind" fn(L: *mut lua_State, ar: *mut lua_Debug);
 
-#[cfg_attr(
-    all(windows, raw_dylib),
-    link(name = "HavokScript_FinalRelease", kind = "raw-dylib")
-)]
-extern "C-unwind" {
+hks_extern! {
+    static LUA_DEBUG_API: LuaDebugApi;
     #[link_name = "?lua_getstack@@YAHPEAUlua_State@@HPEAUlua_Debug@@@Z"]
     pub fn lua_getstack(L: *mut lua_State, level: c_int, ar: *mut lua_Debug) -> c_int;
     #[link_name = "?lua_getinfo@@YAHPEAUlua_State@@PEBDPEAUlua_Debug@@@Z"]
     pub fn lua_getinfo(L: *mut lua_State, what: *const c_char, ar: *mut lua_Debug) -> c_int;
     #[link_name = "?lua_getlocal@@YAPEBDPEAUlua_State@@PEAUlua_Debug@@H@Z"]
+    #[fallback = super::dynamic::lua_nolocal]
     pub fn lua_getlocal(L: *mut lua_State, ar: *const lua_Debug, n: c_int) -> *const c_char;
     #[link_name = "?lua_setlocal@@YAPEBDPEAUlua_State@@PEAUlua_Debug@@H@Z"]
+    #[fallback = super::dynamic::lua_nolocal]
     pub fn lua_setlocal(L: *mut lua_State, ar: *const lua_Debug, n: c_int) -> *const c_char;
     #[link_name = "?lua_getupvalue@@YAPEBDPEAUlua_State@@HH@Z"]
     pub fn lua_getupvalue(L: *mut lua_State, funcindex: c_int, n: c_int) -> *const c_char;
@@ -558,10 +596,13 @@ extern "C-unwind" {
         count: c_int,
     ) -> c_int;
     #[link_name = "?lua_gethook@@YAP6AXPEAUlua_State@@PEAUlua_Debug@@@Z0@Z"]
+    #[fallback = super::dynamic::lua_gethook]
     pub fn lua_gethook(L: *mut lua_State) -> Option<lua_Hook>;
     #[link_name = "?lua_gethookmask@@YAHPEAUlua_State@@@Z"]
+    #[fallback = super::dynamic::lua_gethookmask]
     pub fn lua_gethookmask(L: *mut lua_State) -> c_int;
     #[link_name = "?lua_gethookcount@@YAHPEAUlua_State@@@Z"]
+    #[fallback = super::dynamic::lua_gethookcount]
     pub fn lua_gethookcount(L: *mut lua_State) -> c_int;
 }
 
@@ -5,7 +5,73 @@ pub use lauxlib::*;
 pub use lua::*;
 pub use lualib::*;
 
+// Declares HavokScript entry points.
+//
+// By default this is a plain `extern` block that is linked against the game runtime.
+// With the `civ6-dynamic` feature every block becomes a table of function pointers resolved
+// at runtime (see the `dynamic` module), and each function a thin wrapper calling through it.
+// Entry points marked with `#[fallback = path]` are not essential and use the given
+// function if the runtime does not export them.
+macro_rules! hks_extern {
+    (
+        static $table:ident: $table_ty:ident;
+        $(
+            #[link_name = $sym:literal]
+            $(#[fallback = $fallback:path])?
+            $vis:vis fn $name:ident($($arg:ident: $arg_ty:ty),* $(,)?) $(-> $ret:ty)?;
+        )*
+    ) => {
+        #[cfg(not(feature = "civ6-dynamic"))]
+        #[cfg_attr(
+            all(windows, raw_dylib),
+            link(name = "HavokScript_FinalRelease", kind = "raw-dylib")
+        )]
+        extern "C-unwind" {
+            $(
+                #[link_name = $sym]
+                $vis fn $name($($arg: $arg_ty),*) $(-> $ret)?;
+            )*
+        }
+
+        #[cfg(feature = "civ6-dynamic")]
+        pub(crate) struct $table_ty {
+            $($name: unsafe extern "C-unwind" fn($($arg_ty),*) $(-> $ret)?,)*
+        }
+
+        #[cfg(feature = "civ6-dynamic")]
+        pub(crate) static $table: std::sync::atomic::AtomicPtr<$table_ty> =
+            std::sync::atomic::AtomicPtr::new(std::ptr::null_mut());
+
+        #[cfg(feature = "civ6-dynamic")]
+        impl super::dynamic::FunctionTable for $table_ty {
+            unsafe fn resolve(resolver: &mut super::dynamic::Resolver) -> Option<Self> {
+                $(
+                    let $name = resolver.resolve::<unsafe extern "C-unwind" fn($($arg_ty),*) $(-> $ret)?>(
+                        $sym,
+                        hks_extern!(@fallback $($fallback)?),
+                    );
+                )*
+                Some($table_ty { $($name: $name?,)* })
+            }
+        }
+
+        $(
+            #[cfg(feature = "civ6-dynamic")]
+            #[inline(always)]
+            $vis unsafe extern "C-unwind" fn $name($($arg: $arg_ty),*) $(-> $ret)? {
+                (super::dynamic::table(&$table).$name)($($arg),*)
+            }
+        )*
+    };
+
+    (@fallback) => { None };
+    (@fallback $fallback:path) => { Some($fallback) };
+}
+
 pub mod compat;
 pub mod lauxlib;
 pub mod lua;
 pub mod lualib;
+
+#[cfg(feature = "civ6-dynamic")]
+pub mod dynamic;
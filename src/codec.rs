@@ -0,0 +1,311 @@
+use std::os::raw::c_void;
+
+use rustc_hash::FxHashMap;
+
+use crate::error::{Error, Result};
+use crate::lua::Lua;
+use crate::string::String;
+use crate::table::Table;
+use crate::types::{Integer, Number};
+use crate::util::binary::{write_varint, Format, Reader, MAX_DEPTH};
+use crate::value::Value;
+
+static FORMAT: Format = Format {
+    magic: b"\x1bMLV",
+    version: 1,
+    name: "encoded value",
+};
+
+const FLAG_COMPRESSED: u8 = 1;
+
+// LZ4 cannot compress data better than 255:1
+#[cfg(feature = "compress")]
+const MAX_LZ4_RATIO: usize = 255;
+
+const TAG_NIL: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_INTEGER: u8 = 3;
+const TAG_NUMBER: u8 = 4;
+const TAG_STRING: u8 = 5;
+// Repeated string, by the order of the first occurrence
+const TAG_STRING_REF: u8 = 6;
+const TAG_TABLE: u8 = 7;
+// Table written before, by the order of the first occurrence
+const TAG_TABLE_REF: u8 = 8;
+
+/// Options of [`Lua::encode_value`].
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct EncodeOptions {
+    /// Compress the encoded data with LZ4.
+    ///
+    /// Requires `feature = "compress"`
+    ///
+    /// Default: **false**
+    #[cfg(feature = "compress")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compress")))]
+    pub compress: bool,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        EncodeOptions::new()
+    }
+}
+
+impl EncodeOptions {
+    /// Returns a new instance of `EncodeOptions` with default parameters.
+    pub const fn new() -> Self {
+        EncodeOptions {
+            #[cfg(feature = "compress")]
+            compress: false,
+        }
+    }
+
+    /// Sets [`compress`] option.
+    ///
+    /// [`compress`]: #structfield.compress
+    #[cfg(feature = "compress")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compress")))]
+    #[must_use]
+    pub const fn compress(mut self, enabled: bool) -> Self {
+        self.compress = enabled;
+        self
+    }
+}
+
+pub(crate) fn encode(value: Value, options: EncodeOptions) -> Result<Vec<u8>> {
+    let mut encoder = Encoder {
+        tables: FxHashMap::default(),
+        strings: FxHashMap::default(),
+        buf: Vec::new(),
+        depth: 0,
+    };
+    encoder.write_value(value)?;
+
+    let mut data = Vec::new();
+    #[cfg(feature = "compress")]
+    if options.compress {
+        FORMAT.write_header(&mut data, FLAG_COMPRESSED);
+        data.extend_from_slice(&lz4_flex::compress_prepend_size(&encoder.buf));
+        return Ok(data);
+    }
+    let _ = options;
+    FORMAT.write_header(&mut data, 0);
+    data.extend_from_slice(&encoder.buf);
+    Ok(data)
+}
+
+pub(crate) fn decode<'lua>(lua: &'lua Lua, data: &[u8]) -> Result<Value<'lua>> {
+    let (flags, body) = FORMAT.read_header(data)?;
+
+    #[cfg(feature = "compress")]
+    let decompressed;
+    let body = match flags {
+        0 => body,
+        #[cfg(feature = "compress")]
+        FLAG_COMPRESSED => {
+            // The output is preallocated, so check the size against the best ratio of LZ4
+            let valid_size = match body {
+                [b0, b1, b2, b3, rest @ ..] => {
+                    let size = u32::from_le_bytes([*b0, *b1, *b2, *b3]) as usize;
+                    size <= rest.len().saturating_mul(MAX_LZ4_RATIO)
+                }
+                _ => false,
+            };
+            if !valid_size {
+                return Err(FORMAT.corrupted("bad decompressed size"));
+            }
+            decompressed = lz4_flex::decompress_size_prepended(body)
+                .map_err(|err| FORMAT.corrupted(&err.to_string()))?;
+            &decompressed
+        }
+        #[cfg(not(feature = "compress"))]
+        FLAG_COMPRESSED => {
+            return Err(Error::runtime(
+                "decoding compressed values requires `feature = \"compress\"`",
+            ))
+        }
+        _ => return Err(FORMAT.corrupted("unknown flags")),
+    };
+
+    let mut decoder = Decoder {
+        lua,
+        tables: Vec::new(),
+        strings: Vec::new(),
+        reader: Reader::new(&FORMAT, body),
+        depth: 0,
+    };
+    let value = decoder.read_value()?;
+    decoder.reader.finish()?;
+    Ok(value)
+}
+
+struct Encoder {
+    // Pointers of the written tables to their ids
+    tables: FxHashMap<*const c_void, usize>,
+    strings: FxHashMap<Vec<u8>, usize>,
+    buf: Vec<u8>,
+    depth: usize,
+}
+
+impl Encoder {
+    fn write_value(&mut self, value: Value) -> Result<()> {
+        match value {
+            Value::Nil => self.buf.push(TAG_NIL),
+            Value::Boolean(false) => self.buf.push(TAG_FALSE),
+            Value::Boolean(true) => self.buf.push(TAG_TRUE),
+            Value::Integer(i) => {
+                self.buf.push(TAG_INTEGER);
+                // Zigzag encoding keeps small negative numbers short
+                let i = i as i64;
+                self.write_varint(((i << 1) ^ (i >> 63)) as u64);
+            }
+            Value::Number(n) => {
+                self.buf.push(TAG_NUMBER);
+                self.buf.extend_from_slice(&n.to_le_bytes());
+            }
+            Value::String(s) => self.write_string(s)?,
+            Value::Table(t) => self.write_table(t)?,
+            value => {
+                return Err(Error::runtime(format!(
+                    "cannot encode {}",
+                    value.type_name()
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    fn write_string(&mut self, s: String) -> Result<()> {
+        let bytes = s.as_bytes();
+        if let Some(&id) = self.strings.get(bytes) {
+            self.buf.push(TAG_STRING_REF);
+            self.write_varint(id as u64);
+            return Ok(());
+        }
+        self.strings.insert(bytes.to_vec(), self.strings.len());
+        self.buf.push(TAG_STRING);
+        self.write_varint(bytes.len() as u64);
+        self.buf.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn write_table(&mut self, table: Table) -> Result<()> {
+        let ptr = table.to_pointer();
+        if let Some(&id) = self.tables.get(&ptr) {
+            self.buf.push(TAG_TABLE_REF);
+            self.write_varint(id as u64);
+            return Ok(());
+        }
+        if self.depth == MAX_DEPTH {
+            return Err(Error::runtime("cannot encode value: nesting is too deep"));
+        }
+        self.tables.insert(ptr, self.tables.len());
+        self.depth += 1;
+
+        // The sequence part is written without keys
+        let len = table.raw_len();
+        let mut pairs = Vec::new();
+        for pair in table.clone().pairs::<Value, Value>() {
+            let (key, value) = pair?;
+            match key {
+                Value::Integer(i) if i >= 1 && (i as usize) <= len => {}
+                key => pairs.push((key, value)),
+            }
+        }
+        self.buf.push(TAG_TABLE);
+        self.write_varint(len as u64);
+        for i in 1..=len {
+            self.write_value(table.raw_get(i)?)?;
+        }
+        self.write_varint(pairs.len() as u64);
+        for (key, value) in pairs {
+            self.write_value(key)?;
+            self.write_value(value)?;
+        }
+
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn write_varint(&mut self, n: u64) {
+        write_varint(&mut self.buf, n);
+    }
+}
+
+struct Decoder<'lua, 'a> {
+    lua: &'lua Lua,
+    tables: Vec<Table<'lua>>,
+    strings: Vec<String<'lua>>,
+    reader: Reader<'a>,
+    depth: usize,
+}
+
+impl<'lua, 'a> Decoder<'lua, 'a> {
+    fn read_value(&mut self) -> Result<Value<'lua>> {
+        let value = match self.reader.read_u8()? {
+            TAG_NIL => Value::Nil,
+            TAG_FALSE => Value::Boolean(false),
+            TAG_TRUE => Value::Boolean(true),
+            TAG_INTEGER => {
+                let n = self.reader.read_varint()?;
+                let i = (n >> 1) as i64 ^ -((n & 1) as i64);
+                // Integers may be narrower than 64 bits
+                match Integer::try_from(i) {
+                    Ok(i) => Value::Integer(i),
+                    Err(_) => Value::Number(i as Number),
+                }
+            }
+            TAG_NUMBER => {
+                let bytes = self.reader.read_slice(8)?.try_into().unwrap();
+                Value::Number(f64::from_le_bytes(bytes))
+            }
+            TAG_STRING => {
+                let s = self.lua.create_string(self.reader.read_bytes()?)?;
+                self.strings.push(s.clone());
+                Value::String(s)
+            }
+            TAG_STRING_REF => {
+                let id = self.reader.read_len()?;
+                let s = self.strings.get(id).cloned();
+                Value::String(s.ok_or_else(|| FORMAT.corrupted("bad string reference"))?)
+            }
+            TAG_TABLE => Value::Table(self.read_table()?),
+            TAG_TABLE_REF => {
+                let id = self.reader.read_len()?;
+                let t = self.tables.get(id).cloned();
+                Value::Table(t.ok_or_else(|| FORMAT.corrupted("bad table reference"))?)
+            }
+            _ => return Err(FORMAT.corrupted("unknown tag")),
+        };
+        Ok(value)
+    }
+
+    fn read_table(&mut self) -> Result<Table<'lua>> {
+        if self.depth == MAX_DEPTH {
+            return Err(FORMAT.corrupted("nesting is too deep"));
+        }
+        self.depth += 1;
+
+        // Every element takes at least one byte, this limits preallocation for bad data
+        let len = self.reader.read_len()?;
+        let left = self.reader.remaining();
+        let table = (self.lua).create_table_with_capacity(len.min(left), 0)?;
+        // Registered before the contents to resolve cycles
+        self.tables.push(table.clone());
+        for i in 1..=len {
+            table.raw_set(i, self.read_value()?)?;
+        }
+        let count = self.reader.read_len()?;
+        for _ in 0..count {
+            let key = self.read_value()?;
+            let value = self.read_value()?;
+            table.raw_set(key, value)?;
+        }
+
+        self.depth -= 1;
+        Ok(table)
+    }
+}
@@ -7,7 +7,7 @@ use std::string::String as StdString;
 use std::{slice, str};
 
 use bstr::{BStr, BString};
-use num_traits::cast;
+use num_traits::{cast, NumCast};
 
 use crate::error::{Error, Result};
 use crate::function::Function;
@@ -15,7 +15,7 @@ use crate::lua::Lua;
 use crate::string::String;
 use crate::table::Table;
 use crate::thread::Thread;
-use crate::types::{LightUserData, MaybeSend, RegistryKey};
+use crate::types::{LightUserData, MaybeSend, Number, RegistryKey};
 use crate::userdata::{AnyUserData, UserData, UserDataRef, UserDataRefMut};
 use crate::value::{FromLua, IntoLua, Nil, Value};
 
@@ -752,6 +752,12 @@ where
     lua.push_value(T::into_lua(this, lua)?)
 }
 
+// Converts an integer that does not fit `Integer` into `Number` if it is represented exactly
+fn exact_number<T: Copy + PartialEq + NumCast>(i: T) -> Option<Number> {
+    let n: Number = cast(i)?;
+    (cast::<_, T>(n) == Some(i)).then_some(n)
+}
+
 macro_rules! lua_convert_int {
     ($x:ty) => {
         impl<'lua> IntoLua<'lua> for $x {
@@ -759,8 +765,7 @@ macro_rules! lua_convert_int {
             fn into_lua(self, _: &'lua Lua) -> Result<Value<'lua>> {
                 cast(self)
                     .map(Value::Integer)
-                    .or_else(|| cast(self).map(Value::Number))
-                    // This is impossible error because conversion to Number never fails
+                    .or_else(|| exact_number(self).map(Value::Number))
                     .ok_or_else(|| Error::ToLuaConversionError {
                         from: stringify!($x),
                         to: "number",
@@ -772,7 +777,16 @@ macro_rules! lua_convert_int {
             unsafe fn push_into_stack(self, lua: &'lua Lua) -> Result<()> {
                 match cast(self) {
                     Some(i) => ffi::lua_pushinteger(lua.state(), i),
-                    None => ffi::lua_pushnumber(lua.state(), self as ffi::lua_Number),
+                    None => match exact_number(self) {
+                        Some(n) => ffi::lua_pushnumber(lua.state(), n),
+                        None => {
+                            return Err(Error::ToLuaConversionError {
+                                from: stringify!($x),
+                                to: "number",
+                                message: Some("out of range".to_owned()),
+                            })
+                        }
+                    },
                 }
                 Ok(())
             }
@@ -785,6 +799,8 @@ macro_rules! lua_convert_int {
                 (match value {
                     Value::Integer(i) => cast(i),
                     Value::Number(n) => cast(n),
+                    #[cfg(feature = "lua51_civ6")]
+                    Value::Other(ref v) if v.is_ui64() => v.as_u64()?.and_then(cast),
                     _ => {
                         if let Some(i) = lua.coerce_integer(value.clone())? {
                             cast(i)
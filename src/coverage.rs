@@ -0,0 +1,169 @@
+use std::collections::BTreeMap;
+use std::io;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::error::Result;
+use crate::hook::{Debug, DebugEvent, DebugSource, HookId};
+use crate::lua::Lua;
+
+pub(crate) struct CoverageState {
+    pub(crate) hook: HookId,
+    // `None` for the chunks rejected by the filter
+    chunks: FxHashMap<String, Option<BTreeMap<usize, u64>>>,
+    // Functions (chunk, line defined) whose active lines were recorded
+    functions: FxHashSet<(String, usize)>,
+}
+
+impl CoverageState {
+    pub(crate) fn new(hook: HookId) -> Self {
+        CoverageState {
+            hook,
+            chunks: FxHashMap::default(),
+            functions: FxHashSet::default(),
+        }
+    }
+
+    pub(crate) fn finish(self) -> LineCoverage {
+        let chunks = (self.chunks.into_iter())
+            .filter_map(|(name, lines)| Some((name, lines?)))
+            .collect();
+        LineCoverage { chunks }
+    }
+}
+
+// Name of the chunk as it appears in the coverage report
+fn chunk_name<'a>(source: &'a DebugSource) -> Option<&'a str> {
+    if source.what == "C" {
+        return None;
+    }
+    match source.source.as_deref()? {
+        s if s.starts_with('@') || s.starts_with('=') => Some(&s[1..]),
+        _ => source.short_src.as_deref(),
+    }
+}
+
+pub(crate) fn hook<F>(lua: &Lua, debug: Debug, filter: &F) -> Result<()>
+where
+    F: Fn(&str) -> bool,
+{
+    let source = debug.source();
+    let chunk = match chunk_name(&source) {
+        Some(chunk) => chunk,
+        None => return Ok(()),
+    };
+
+    let event = debug.event();
+    let is_call = match event {
+        DebugEvent::Call => true,
+        #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+        DebugEvent::TailCall => true,
+        DebugEvent::Line => false,
+        _ => return Ok(()),
+    };
+
+    let first_call = lua.with_coverage(|coverage| {
+        if !coverage.chunks.contains_key(chunk) {
+            let lines = filter(chunk).then(BTreeMap::new);
+            coverage.chunks.insert(chunk.to_string(), lines);
+        }
+        let line_defined = source.line_defined.unwrap_or(0);
+        (coverage.chunks[chunk].is_some())
+            .then(|| is_call && coverage.functions.insert((chunk.to_string(), line_defined)))
+    });
+
+    match first_call.flatten() {
+        // Lines that are not executed yet are reported with zero hits
+        Some(true) => {
+            let lines = debug.active_lines()?;
+            lua.with_coverage(|coverage| {
+                if let Some(Some(hits)) = coverage.chunks.get_mut(chunk) {
+                    for line in lines {
+                        hits.entry(line).or_insert(0);
+                    }
+                }
+            });
+        }
+        Some(false) if !is_call => {
+            let line = debug.curr_line();
+            lua.with_coverage(|coverage| {
+                if let (Some(Some(hits)), Ok(line)) =
+                    (coverage.chunks.get_mut(chunk), line.try_into())
+                {
+                    *hits.entry(line).or_insert(0) += 1;
+                }
+            });
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Line hit counts collected by [`Lua::start_coverage`].
+///
+/// Lines of the functions that were called at least once are included with zero hits if they
+/// were not executed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LineCoverage {
+    chunks: BTreeMap<String, BTreeMap<usize, u64>>,
+}
+
+impl LineCoverage {
+    /// Returns the names of the covered chunks in sorted order.
+    ///
+    /// The name of a chunk loaded from a file (`@path`) or with an explicit name (`=name`) is
+    /// the name without the prefix, otherwise it is the "printable" version of the source.
+    pub fn chunks(&self) -> impl Iterator<Item = &str> {
+        self.chunks.keys().map(|name| name.as_str())
+    }
+
+    /// Returns `(line, hits)` pairs of the chunk in ascending line order.
+    pub fn lines(&self, chunk: &str) -> Option<Vec<(usize, u64)>> {
+        let lines = self.chunks.get(chunk)?;
+        Some(lines.iter().map(|(&line, &hits)| (line, hits)).collect())
+    }
+
+    /// Returns the number of times the line of the chunk was executed.
+    ///
+    /// Returns `None` if the line contains no code or the chunk is not covered.
+    pub fn hits(&self, chunk: &str, line: usize) -> Option<u64> {
+        self.chunks.get(chunk)?.get(&line).copied()
+    }
+
+    /// Adds the hit counts of `other` to this coverage.
+    ///
+    /// Useful to combine the results of several [`Lua`] instances.
+    pub fn merge(&mut self, other: &LineCoverage) {
+        for (chunk, lines) in &other.chunks {
+            let hits = self.chunks.entry(chunk.clone()).or_default();
+            for (&line, &count) in lines {
+                *hits.entry(line).or_insert(0) += count;
+            }
+        }
+    }
+
+    /// Writes the coverage in the LCOV tracefile format.
+    ///
+    /// Chunk names are used as source file paths.
+    pub fn write_lcov<W: io::Write>(&self, mut w: W) -> io::Result<()> {
+        writeln!(w, "TN:")?;
+        for (chunk, lines) in &self.chunks {
+            writeln!(w, "SF:{chunk}")?;
+            for (line, hits) in lines {
+                writeln!(w, "DA:{line},{hits}")?;
+            }
+            writeln!(w, "LF:{}", lines.len())?;
+            writeln!(w, "LH:{}", lines.values().filter(|&&hits| hits > 0).count())?;
+            writeln!(w, "end_of_record")?;
+        }
+        Ok(())
+    }
+
+    /// Returns the coverage in the LCOV tracefile format.
+    pub fn to_lcov(&self) -> String {
+        let mut buf = Vec::new();
+        self.write_lcov(&mut buf)
+            .expect("writing to a Vec cannot fail");
+        String::from_utf8(buf).expect("LCOV must be valid UTF-8")
+    }
+}
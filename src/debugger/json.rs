@@ -0,0 +1,198 @@
+// Helpers to build and read the JSON values of the DAP messages
+
+use std::ops::{Index, IndexMut};
+
+use crate::util::json::JsonValue;
+
+static NULL: JsonValue = JsonValue::Null;
+
+// Builds a `JsonValue` from a JSON-like literal, other values are converted using `ToJson`
+macro_rules! json {
+    ({ $($tt:tt)* }) => {
+        json!(@object [] $($tt)*)
+    };
+    ([ $($tt:tt)* ]) => {
+        json!(@array [] $($tt)*)
+    };
+    (@object [$($done:expr,)*]) => {
+        $crate::util::json::JsonValue::Object(vec![$($done,)*])
+    };
+    (@object [$($done:expr,)*] $key:literal : { $($value:tt)* } $(, $($rest:tt)*)?) => {
+        json!(@object [$($done,)* ($key.to_string(), json!({ $($value)* })),] $($($rest)*)?)
+    };
+    (@object [$($done:expr,)*] $key:literal : [ $($value:tt)* ] $(, $($rest:tt)*)?) => {
+        json!(@object [$($done,)* ($key.to_string(), json!([ $($value)* ])),] $($($rest)*)?)
+    };
+    (@object [$($done:expr,)*] $key:literal : $value:expr $(, $($rest:tt)*)?) => {
+        json!(@object [$($done,)* ($key.to_string(), json!($value)),] $($($rest)*)?)
+    };
+    (@array [$($done:expr,)*]) => {
+        $crate::util::json::JsonValue::Array(vec![$($done,)*])
+    };
+    (@array [$($done:expr,)*] { $($value:tt)* } $(, $($rest:tt)*)?) => {
+        json!(@array [$($done,)* json!({ $($value)* }),] $($($rest)*)?)
+    };
+    (@array [$($done:expr,)*] [ $($value:tt)* ] $(, $($rest:tt)*)?) => {
+        json!(@array [$($done,)* json!([ $($value)* ]),] $($($rest)*)?)
+    };
+    (@array [$($done:expr,)*] $value:expr $(, $($rest:tt)*)?) => {
+        json!(@array [$($done,)* json!($value),] $($($rest)*)?)
+    };
+    ($value:expr) => {
+        $crate::debugger::json::ToJson::to_json(&$value)
+    };
+}
+
+pub(super) trait ToJson {
+    fn to_json(&self) -> JsonValue;
+}
+
+impl<T: ToJson + ?Sized> ToJson for &T {
+    fn to_json(&self) -> JsonValue {
+        (**self).to_json()
+    }
+}
+
+impl ToJson for JsonValue {
+    fn to_json(&self) -> JsonValue {
+        self.clone()
+    }
+}
+
+impl ToJson for bool {
+    fn to_json(&self) -> JsonValue {
+        JsonValue::Bool(*self)
+    }
+}
+
+impl ToJson for i32 {
+    fn to_json(&self) -> JsonValue {
+        JsonValue::Integer(*self as i64)
+    }
+}
+
+impl ToJson for i64 {
+    fn to_json(&self) -> JsonValue {
+        JsonValue::Integer(*self)
+    }
+}
+
+macro_rules! unsigned_to_json {
+    ($($ty:ty),*) => {
+        $(
+            impl ToJson for $ty {
+                fn to_json(&self) -> JsonValue {
+                    match i64::try_from(*self) {
+                        Ok(i) => JsonValue::Integer(i),
+                        Err(_) => JsonValue::Number(*self as f64),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+unsigned_to_json!(u64, usize);
+
+impl ToJson for str {
+    fn to_json(&self) -> JsonValue {
+        JsonValue::String(self.to_string())
+    }
+}
+
+impl ToJson for String {
+    fn to_json(&self) -> JsonValue {
+        JsonValue::String(self.clone())
+    }
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn to_json(&self) -> JsonValue {
+        JsonValue::Array(self.iter().map(ToJson::to_json).collect())
+    }
+}
+
+impl JsonValue {
+    pub(super) fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_i64(&self) -> Option<i64> {
+        match *self {
+            JsonValue::Integer(i) => Some(i),
+            JsonValue::Number(n) if n.fract() == 0.0 && n.abs() < 2f64.powi(63) => Some(n as i64),
+            _ => None,
+        }
+    }
+
+    pub(super) fn as_u64(&self) -> Option<u64> {
+        self.as_i64().and_then(|n| u64::try_from(n).ok())
+    }
+
+    pub(super) fn as_array(&self) -> Option<&Vec<JsonValue>> {
+        match self {
+            JsonValue::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+}
+
+impl Index<&str> for JsonValue {
+    type Output = JsonValue;
+
+    fn index(&self, key: &str) -> &JsonValue {
+        match self {
+            JsonValue::Object(object) => (object.iter())
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value)
+                .unwrap_or(&NULL),
+            _ => &NULL,
+        }
+    }
+}
+
+impl IndexMut<&str> for JsonValue {
+    // Turns the value into an object if needed
+    fn index_mut(&mut self, key: &str) -> &mut JsonValue {
+        if !matches!(self, JsonValue::Object(_)) {
+            *self = JsonValue::Object(Vec::new());
+        }
+        let JsonValue::Object(object) = self else {
+            unreachable!()
+        };
+        let i = match object.iter().position(|(k, _)| k == key) {
+            Some(i) => i,
+            None => {
+                object.push((key.to_string(), JsonValue::Null));
+                object.len() - 1
+            }
+        };
+        &mut object[i].1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::json::JsonValue;
+
+    #[test]
+    fn tests() {
+        let text = r#" {"a": [1, -2.5, true, null], "b": {"c": "x"}} "#;
+        let value = JsonValue::parse(text.as_bytes()).unwrap();
+        assert_eq!(value["a"].as_array().map(Vec::len), Some(4));
+        assert_eq!(value["b"]["c"].as_str(), Some("x"));
+        assert_eq!(value["missing"]["c"].as_str(), None);
+
+        let mut value = json!({"seq": 1, "list": [{"n": 2 + 3}, []], "s": "t"});
+        value["seq"] = json!(u64::MAX);
+        assert_eq!(value["seq"].as_u64(), None);
+        value["seq"] = json!(7);
+        assert_eq!(
+            value.to_string(),
+            r#"{"seq":7,"list":[{"n":5},[]],"s":"t"}"#
+        );
+    }
+}
@@ -0,0 +1,751 @@
+//! A [Debug Adapter Protocol] server for debugging Lua scripts.
+//!
+//! The [`Debugger`] listens on a TCP port and serves a single DAP client (an IDE) at a time.
+//! It is built on the debug hooks ([`Lua::add_hook`] and [`Thread::add_hook`]), so it can be
+//! attached together with the profiler, the coverage collector or other hooks.
+//!
+//! Supported features are line breakpoints, pause, stepping (in, over and out), stack traces,
+//! local variables, upvalues and expression evaluation in a paused frame. All Lua code is inspected
+//! on the thread that runs it: a paused hook blocks that thread until the client resumes it.
+//!
+//! Breakpoints are matched against the chunk name ([`DebugSource::source`]) without the leading
+//! `@` or `=`, using a case-insensitive comparison of the path suffix. For example a breakpoint
+//! set in `C:\Mods\MyMod\Scripts\main.lua` matches the chunk named `@Scripts/main.lua`.
+//!
+//! [Debug Adapter Protocol]: https://microsoft.github.io/debug-adapter-protocol/
+//! [`DebugSource::source`]: crate::DebugSource::source
+
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::result::Result as StdResult;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rustc_hash::FxHashMap;
+
+use crate::error::Result;
+use crate::hook::{Debug, DebugEvent, HookId, HookTriggers};
+use crate::lua::Lua;
+use crate::table::Table;
+use crate::thread::Thread;
+use crate::util::json::JsonValue;
+use crate::value::{MultiValue, Value};
+
+#[macro_use]
+mod json;
+
+// The only thread reported to the client
+const THREAD_ID: i64 = 1;
+
+/// A Debug Adapter Protocol server.
+///
+/// See the [module documentation](crate::debugger) for details.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use mlua::{Lua, Result};
+/// # use mlua::debugger::Debugger;
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let debugger = Debugger::listen("127.0.0.1:4711")?;
+/// debugger.attach(&lua);
+///
+/// // Give the IDE a chance to set breakpoints
+/// debugger.wait_for_client(Some(Duration::from_secs(10)));
+/// lua.load(std::path::Path::new("main.lua")).exec()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Debugger {
+    shared: Arc<Shared>,
+    local_addr: SocketAddr,
+}
+
+impl std::fmt::Debug for Debugger {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.debug_struct("Debugger")
+            .field("local_addr", &self.local_addr)
+            .field("connected", &self.is_connected())
+            .finish()
+    }
+}
+
+impl Debugger {
+    /// Starts listening for a DAP client on the given address.
+    ///
+    /// Clients are served on a background thread. Use port `0` to pick a free port and
+    /// [`Debugger::local_addr`] to find it out.
+    pub fn listen(addr: impl ToSocketAddrs) -> Result<Debugger> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+
+        let (tx, rx) = mpsc::channel();
+        let shared = Arc::new(Shared {
+            breakpoints: Mutex::new(FxHashMap::default()),
+            has_breakpoints: AtomicBool::new(false),
+            pause_requested: AtomicBool::new(false),
+            configured: Mutex::new(false),
+            configured_cond: Condvar::new(),
+            writer: Mutex::new(None),
+            seq: AtomicI64::new(0),
+            requests_tx: Mutex::new(tx),
+            requests_rx: Mutex::new(rx),
+            step: Mutex::new(None),
+            closed: AtomicBool::new(false),
+        });
+
+        let shared2 = shared.clone();
+        thread::Builder::new()
+            .name("mlua-debugger".to_string())
+            .spawn(move || serve(&shared2, listener))?;
+
+        Ok(Debugger { shared, local_addr })
+    }
+
+    /// Returns the address the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Returns `true` if a client is connected.
+    pub fn is_connected(&self) -> bool {
+        lock(&self.shared.writer).is_some()
+    }
+
+    /// Attaches the debugger to every thread of the Lua instance.
+    ///
+    /// Like the profiler, the debugger hook covers coroutines created after attaching it and
+    /// threads resumed from Rust (e.g. the tasks of a [`Scheduler`]). It runs along with the other
+    /// hooks. Pass the returned handle to [`Lua::remove_hook_by_id`] to detach the debugger.
+    ///
+    /// [`Scheduler`]: crate::Scheduler
+    pub fn attach(&self, lua: &Lua) -> HookId {
+        lua.add_global_hook(Self::triggers(), self.hook())
+    }
+
+    /// Attaches the debugger to a single Lua thread (coroutine).
+    ///
+    /// Not needed for the threads covered by [`Debugger::attach`].
+    pub fn attach_thread(&self, thread: &Thread) -> HookId {
+        thread.add_hook(Self::triggers(), self.hook())
+    }
+
+    /// Blocks until a client connects and finishes its configuration (sets the initial
+    /// breakpoints).
+    ///
+    /// Returns `false` if the timeout has elapsed.
+    pub fn wait_for_client(&self, timeout: Option<Duration>) -> bool {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut configured = lock(&self.shared.configured);
+        while !*configured {
+            configured = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return false;
+                    }
+                    let cond = &self.shared.configured_cond;
+                    match cond.wait_timeout(configured, deadline - now) {
+                        Ok((guard, _)) => guard,
+                        Err(err) => err.into_inner().0,
+                    }
+                }
+                None => match self.shared.configured_cond.wait(configured) {
+                    Ok(guard) => guard,
+                    Err(err) => err.into_inner(),
+                },
+            };
+        }
+        true
+    }
+
+    fn triggers() -> HookTriggers {
+        HookTriggers::new().on_calls().on_returns().every_line()
+    }
+
+    // The hook can be called for several Lua threads, so it keeps the call depth of each one
+    fn hook(&self) -> impl Fn(&Lua, Debug) -> Result<()> + Send + 'static {
+        let shared = self.shared.clone();
+        let depths = Mutex::new(FxHashMap::default());
+        move |lua, debug| shared.on_hook(lua, debug, &depths)
+    }
+}
+
+impl Drop for Debugger {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Relaxed);
+        if let Some(stream) = lock(&self.shared.writer).as_ref() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+struct Shared {
+    // Normalized source path -> lines
+    breakpoints: Mutex<FxHashMap<String, Vec<i64>>>,
+    has_breakpoints: AtomicBool,
+    pause_requested: AtomicBool,
+    configured: Mutex<bool>,
+    configured_cond: Condvar,
+    writer: Mutex<Option<TcpStream>>,
+    seq: AtomicI64,
+    // Requests that must be handled on the Lua thread
+    requests_tx: Mutex<Sender<Request>>,
+    requests_rx: Mutex<Receiver<Request>>,
+    step: Mutex<Option<Step>>,
+    closed: AtomicBool,
+}
+
+// Stepping over and out is limited to the Lua thread (identified by its state) where the execution
+// was paused, with the call depth relative to the moment the hook was set
+#[derive(Clone, Copy)]
+enum Step {
+    In,
+    Over { thread: usize, depth: i64 },
+    Out { thread: usize, depth: i64 },
+}
+
+struct Request {
+    seq: i64,
+    command: String,
+    arguments: JsonValue,
+}
+
+// A variable of a paused frame, visible to the evaluated expressions
+enum Binding {
+    Local(usize),
+    Upvalue(usize),
+}
+
+// Values that can be expanded by the client while the execution is paused
+enum VarRef<'lua> {
+    Locals(usize),
+    Upvalues(usize),
+    Table(Table<'lua>),
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+fn serve(shared: &Shared, listener: TcpListener) {
+    while !shared.closed.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                if stream.set_nonblocking(false).is_ok() {
+                    shared.serve_client(stream);
+                }
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(50));
+            }
+            Err(_) => thread::sleep(Duration::from_millis(50)),
+        }
+    }
+}
+
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<JsonValue>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let length = length.ok_or_else(|| io::Error::from(ErrorKind::InvalidData))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    JsonValue::parse(&body)
+        .map(Some)
+        .map_err(|_| io::Error::from(ErrorKind::InvalidData))
+}
+
+fn normalize_path(path: &str) -> String {
+    let path = (path.strip_prefix('@'))
+        .or_else(|| path.strip_prefix('='))
+        .unwrap_or(path);
+    path.replace('\\', "/").to_lowercase()
+}
+
+fn path_matches(a: &str, b: &str) -> bool {
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    long.ends_with(short)
+        && (long.len() == short.len() || long[..long.len() - short.len()].ends_with('/'))
+}
+
+impl Shared {
+    fn send(&self, mut message: JsonValue) {
+        message["seq"] = json!(self.seq.fetch_add(1, Ordering::Relaxed) + 1);
+        let body = message.to_string();
+        if let Some(writer) = lock(&self.writer).as_mut() {
+            let _ = write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len())
+                .and_then(|_| writer.flush());
+        }
+    }
+
+    fn respond(&self, request: &Request, body: JsonValue) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request.seq,
+            "command": request.command,
+            "success": true,
+            "body": body,
+        }));
+    }
+
+    fn respond_error(&self, request: &Request, message: &str) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request.seq,
+            "command": request.command,
+            "success": false,
+            "message": message,
+        }));
+    }
+
+    fn event(&self, event: &str, body: JsonValue) {
+        self.send(json!({"type": "event", "event": event, "body": body}));
+    }
+
+    fn serve_client(&self, stream: TcpStream) {
+        match stream.try_clone() {
+            Ok(writer) => *lock(&self.writer) = Some(writer),
+            Err(_) => return,
+        }
+
+        let mut reader = BufReader::new(stream);
+        while let Ok(Some(message)) = read_message(&mut reader) {
+            if message["type"].as_str() != Some("request") {
+                continue;
+            }
+            let request = Request {
+                seq: message["seq"].as_i64().unwrap_or_default(),
+                command: message["command"].as_str().unwrap_or_default().to_string(),
+                arguments: message["arguments"].clone(),
+            };
+            if !self.handle_request(request) {
+                break;
+            }
+        }
+
+        // The client is gone, resume the execution
+        self.reset();
+        *lock(&self.writer) = None;
+    }
+
+    // Handles requests that do not need the Lua state. Returns `false` on disconnect.
+    fn handle_request(&self, request: Request) -> bool {
+        match request.command.as_str() {
+            "initialize" => {
+                self.respond(
+                    &request,
+                    json!({
+                        "supportsConfigurationDoneRequest": true,
+                        "supportsEvaluateForHovers": true,
+                    }),
+                );
+                self.event("initialized", json!({}));
+            }
+            "launch" | "attach" => self.respond(&request, json!({})),
+            "setBreakpoints" => {
+                let source = &request.arguments["source"];
+                let path = (source["path"].as_str())
+                    .or_else(|| source["name"].as_str())
+                    .unwrap_or_default();
+                let lines: Vec<i64> = (request.arguments["breakpoints"].as_array())
+                    .map(|bps| bps.iter().filter_map(|bp| bp["line"].as_i64()).collect())
+                    .unwrap_or_default();
+                let body = lines
+                    .iter()
+                    .map(|line| json!({"verified": true, "line": line}))
+                    .collect::<Vec<_>>();
+
+                let mut breakpoints = lock(&self.breakpoints);
+                if lines.is_empty() {
+                    breakpoints.remove(&normalize_path(path));
+                } else {
+                    breakpoints.insert(normalize_path(path), lines);
+                }
+                self.has_breakpoints
+                    .store(!breakpoints.is_empty(), Ordering::Relaxed);
+                drop(breakpoints);
+                self.respond(&request, json!({ "breakpoints": body }));
+            }
+            "setExceptionBreakpoints" => self.respond(&request, json!({ "breakpoints": [] })),
+            "configurationDone" => {
+                self.respond(&request, json!({}));
+                *lock(&self.configured) = true;
+                self.configured_cond.notify_all();
+            }
+            "threads" => self.respond(
+                &request,
+                json!({ "threads": [{"id": THREAD_ID, "name": "main"}] }),
+            ),
+            "pause" => {
+                self.pause_requested.store(true, Ordering::Relaxed);
+                self.respond(&request, json!({}));
+            }
+            "disconnect" => {
+                self.respond(&request, json!({}));
+                return false;
+            }
+            _ => {
+                let _ = lock(&self.requests_tx).send(request);
+            }
+        }
+        true
+    }
+
+    fn reset(&self) {
+        lock(&self.breakpoints).clear();
+        self.has_breakpoints.store(false, Ordering::Relaxed);
+        self.pause_requested.store(false, Ordering::Relaxed);
+        *lock(&self.step) = None;
+        *lock(&self.configured) = false;
+        // Wake up the paused hook
+        let request = Request {
+            seq: 0,
+            command: "disconnect".to_string(),
+            arguments: JsonValue::Null,
+        };
+        let _ = lock(&self.requests_tx).send(request);
+    }
+
+    fn on_hook(
+        &self,
+        lua: &Lua,
+        debug: Debug,
+        depths: &Mutex<FxHashMap<usize, i64>>,
+    ) -> Result<()> {
+        let thread = lua.state() as usize;
+        let change = match debug.event() {
+            DebugEvent::Call => 1,
+            DebugEvent::Ret => -1,
+            // In Lua 5.1 the event is a return from a function that did a tail call
+            #[cfg(any(feature = "lua51", feature = "luajit"))]
+            DebugEvent::TailCall => -1,
+            DebugEvent::Line => 0,
+            _ => return Ok(()),
+        };
+        let depth = {
+            let mut depths = lock(depths);
+            let depth = depths.entry(thread).or_insert(0);
+            *depth += change;
+            let depth = *depth;
+            // Finished coroutines are back to zero, so forget them
+            if depth == 0 {
+                depths.remove(&thread);
+            }
+            depth
+        };
+
+        if change != 0 {
+            return Ok(());
+        }
+
+        let mut step = lock(&self.step);
+        let reason = if self.pause_requested.swap(false, Ordering::Relaxed) {
+            Some("pause")
+        } else if self.has_breakpoints.load(Ordering::Relaxed) && self.is_breakpoint(&debug) {
+            Some("breakpoint")
+        } else {
+            match *step {
+                Some(Step::In) => Some("step"),
+                Some(Step::Over {
+                    thread: t,
+                    depth: d,
+                }) if t == thread && depth <= d => Some("step"),
+                Some(Step::Out {
+                    thread: t,
+                    depth: d,
+                }) if t == thread && depth < d => Some("step"),
+                _ => None,
+            }
+        };
+        if let Some(reason) = reason {
+            if lock(&self.writer).is_some() {
+                *step = None;
+                drop(step);
+                let next = self.pause(lua, reason, thread, depth);
+                *lock(&self.step) = next;
+            }
+        }
+        Ok(())
+    }
+
+    fn is_breakpoint(&self, debug: &Debug) -> bool {
+        let line = debug.curr_line() as i64;
+        let source = match debug.source().source {
+            Some(source) => normalize_path(&source),
+            None => return false,
+        };
+        let breakpoints = lock(&self.breakpoints);
+        breakpoints
+            .iter()
+            .any(|(path, lines)| lines.contains(&line) && path_matches(path, &source))
+    }
+
+    // Serves the client requests until it resumes the execution
+    fn pause(&self, lua: &Lua, reason: &str, thread: usize, depth: i64) -> Option<Step> {
+        let rx = lock(&self.requests_rx);
+
+        // Requests received while running are out of date
+        while let Ok(request) = rx.try_recv() {
+            if request.command != "disconnect" {
+                self.respond_error(&request, "not paused");
+            }
+        }
+
+        self.event(
+            "stopped",
+            json!({"reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true}),
+        );
+
+        let mut refs = Vec::new();
+        while let Ok(request) = rx.recv() {
+            let args = &request.arguments;
+            let result = match request.command.as_str() {
+                "continue" => {
+                    self.respond(&request, json!({"allThreadsContinued": true}));
+                    return None;
+                }
+                "next" => {
+                    self.respond(&request, json!({}));
+                    return Some(Step::Over { thread, depth });
+                }
+                "stepIn" => {
+                    self.respond(&request, json!({}));
+                    return Some(Step::In);
+                }
+                "stepOut" => {
+                    self.respond(&request, json!({}));
+                    return Some(Step::Out { thread, depth });
+                }
+                "disconnect" => return None,
+                "stackTrace" => stack_trace(lua, args),
+                "scopes" => {
+                    let level = frame_level(args);
+                    refs.push(VarRef::Locals(level));
+                    refs.push(VarRef::Upvalues(level));
+                    Ok(json!({"scopes": [
+                        {"name": "Locals", "variablesReference": refs.len() - 1, "expensive": false},
+                        {"name": "Upvalues", "variablesReference": refs.len(), "expensive": false},
+                    ]}))
+                }
+                "variables" => variables(lua, args, &mut refs),
+                "evaluate" => evaluate(lua, args, &mut refs),
+                command => Err(format!("unsupported request '{command}'")),
+            };
+            match result {
+                Ok(body) => self.respond(&request, body),
+                Err(message) => self.respond_error(&request, &message),
+            }
+        }
+        None
+    }
+}
+
+fn frame_level(args: &JsonValue) -> usize {
+    (args["frameId"].as_u64().unwrap_or(1) as usize).saturating_sub(1)
+}
+
+fn stack_trace(lua: &Lua, args: &JsonValue) -> StdResult<JsonValue, String> {
+    let start = args["startFrame"].as_u64().unwrap_or(0) as usize;
+    let levels = match args["levels"].as_u64() {
+        Some(0) | None => usize::MAX,
+        Some(levels) => levels as usize,
+    };
+
+    let mut frames = Vec::new();
+    let mut level = 0;
+    while let Some(debug) = lua.inspect_stack(level) {
+        if level >= start && frames.len() < levels {
+            frames.push(stack_frame(level, &debug));
+        }
+        level += 1;
+    }
+    Ok(json!({"stackFrames": frames, "totalFrames": level}))
+}
+
+fn stack_frame(level: usize, debug: &Debug) -> JsonValue {
+    let source = debug.source();
+    let name = match debug.names().name {
+        Some(name) => name.into_owned(),
+        None if source.what == "main" => "main chunk".to_string(),
+        None => "?".to_string(),
+    };
+    let mut frame = json!({
+        "id": level + 1,
+        "name": name,
+        "line": debug.curr_line().max(0),
+        "column": 0,
+    });
+    if source.what == "C" {
+        frame["presentationHint"] = json!("subtle");
+        return frame;
+    }
+    let short_src = source.short_src.as_deref().unwrap_or("?");
+    frame["source"] = match source.source.as_deref() {
+        Some(path) if path.starts_with('@') => {
+            let name = path.rsplit(['/', '\\']).next().unwrap_or(short_src);
+            json!({"name": name, "path": &path[1..]})
+        }
+        _ => json!({ "name": short_src }),
+    };
+    frame
+}
+
+fn variables<'lua>(
+    lua: &'lua Lua,
+    args: &JsonValue,
+    refs: &mut Vec<VarRef<'lua>>,
+) -> StdResult<JsonValue, String> {
+    let index = args["variablesReference"].as_u64().unwrap_or(0) as usize;
+    let vars: Vec<(String, Value)> = match refs.get(index.wrapping_sub(1)) {
+        Some(VarRef::Locals(level)) => {
+            let debug = lua.inspect_stack(*level).ok_or("invalid frame")?;
+            let locals = debug.locals().map_err(|err| err.to_string())?;
+            locals
+                .into_iter()
+                .filter(|(name, _)| !name.starts_with('('))
+                .collect()
+        }
+        Some(VarRef::Upvalues(level)) => {
+            let debug = lua.inspect_stack(*level).ok_or("invalid frame")?;
+            (debug.function())
+                .and_then(|func| func.upvalues())
+                .map_err(|err| err.to_string())?
+        }
+        Some(VarRef::Table(table)) => {
+            let mut pairs = (table.clone().pairs::<Value, Value>())
+                .collect::<Result<Vec<_>>>()
+                .map_err(|err| err.to_string())?;
+            pairs.sort_by(|(a, _), (b, _)| a.cmp(b));
+            pairs
+                .into_iter()
+                .map(|(key, value)| {
+                    let name = match key {
+                        Value::String(s) => s.to_string_lossy().into_owned(),
+                        key => format!("[{}]", display_value(&key)),
+                    };
+                    (name, value)
+                })
+                .collect()
+        }
+        None => return Err("invalid variables reference".to_string()),
+    };
+
+    let vars = vars
+        .into_iter()
+        .map(|(name, value)| {
+            json!({
+                "name": name,
+                "value": display_value(&value),
+                "type": value.type_name(),
+                "variablesReference": value_ref(value, refs),
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(json!({ "variables": vars }))
+}
+
+fn evaluate<'lua>(
+    lua: &'lua Lua,
+    args: &JsonValue,
+    refs: &mut Vec<VarRef<'lua>>,
+) -> StdResult<JsonValue, String> {
+    let expression = args["expression"].as_str().unwrap_or_default();
+    let level = frame_level(args);
+
+    // Upvalues and locals of the frame are visible on top of the globals.
+    // Assignments to them are written back to the frame after the evaluation.
+    let eval = || -> Result<MultiValue> {
+        let env = lua.create_table()?;
+        let mut bindings = Vec::new();
+        let debug = lua.inspect_stack(level);
+        let func = debug.as_ref().map(|debug| debug.function()).transpose()?;
+        if let (Some(debug), Some(func)) = (&debug, &func) {
+            for (i, (name, value)) in func.upvalues()?.into_iter().enumerate() {
+                if !name.is_empty() {
+                    env.raw_set(name.as_str(), value.clone())?;
+                    bindings.push((name, Binding::Upvalue(i + 1), value));
+                }
+            }
+            for (i, (name, value)) in debug.locals()?.into_iter().enumerate() {
+                if !name.starts_with('(') {
+                    env.raw_set(name.as_str(), value.clone())?;
+                    bindings.push((name, Binding::Local(i + 1), value));
+                }
+            }
+        }
+        let meta = lua.create_table()?;
+        meta.raw_set("__index", lua.globals())?;
+        meta.raw_set("__newindex", lua.globals())?;
+        env.set_metatable(Some(meta));
+
+        let values = lua
+            .load(expression)
+            .set_name("=(eval)")
+            .set_environment(env.clone())
+            .eval::<MultiValue>()?;
+
+        // Only the innermost binding of a name is visible
+        let mut seen = Vec::new();
+        for (name, binding, old) in bindings.into_iter().rev() {
+            if seen.contains(&name) {
+                continue;
+            }
+            let new = env.raw_get::<_, Value>(name.as_str())?;
+            if new != old {
+                match (binding, &debug, &func) {
+                    (Binding::Local(n), Some(debug), _) => debug.set_local(n, new)?,
+                    (Binding::Upvalue(n), _, Some(func)) => func.set_upvalue(n, new)?,
+                    _ => {}
+                }
+            }
+            seen.push(name);
+        }
+        Ok(values)
+    };
+    let values = eval().map_err(|err| err.to_string())?;
+
+    let result = values.iter().map(display_value).collect::<Vec<_>>();
+    let reference = match values.len() {
+        1 => value_ref(values.into_iter().next().unwrap(), refs),
+        _ => 0,
+    };
+    Ok(json!({"result": result.join(", "), "variablesReference": reference}))
+}
+
+fn value_ref<'lua>(value: Value<'lua>, refs: &mut Vec<VarRef<'lua>>) -> usize {
+    match value {
+        Value::Table(table) => {
+            refs.push(VarRef::Table(table));
+            refs.len()
+        }
+        _ => 0,
+    }
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("{:?}", s.to_string_lossy()),
+        Value::Table(_) | Value::Function(_) | Value::Thread(_) | Value::UserData(_) => {
+            format!("{}: {:?}", value.type_name(), value.to_pointer())
+        }
+        _ => (value.to_string()).unwrap_or_else(|_| value.type_name().to_string()),
+    }
+}
@@ -0,0 +1,391 @@
+use std::os::raw::c_void;
+
+use rustc_hash::FxHashMap;
+
+use crate::error::{Error, Result};
+use crate::lua::Lua;
+use crate::table::Table;
+use crate::types::{Integer, Number};
+use crate::value::Value;
+
+/// Options of the deterministic execution mode.
+///
+/// See [`Lua::enable_deterministic_mode`] for details.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct DeterministicOptions {
+    /// Initial seed of `math.random`.
+    ///
+    /// Default: **0**
+    pub seed: u64,
+
+    /// Value returned by `os.time()` when called without arguments.
+    ///
+    /// Default: **0**
+    pub time: i64,
+
+    /// Value returned by `os.clock()`.
+    ///
+    /// Default: **0.0**
+    pub clock: f64,
+
+    /// Replace the global `pairs` function with `opairs` (iteration in sorted key order).
+    ///
+    /// Default: **false**
+    pub ordered_pairs: bool,
+}
+
+impl Default for DeterministicOptions {
+    fn default() -> Self {
+        DeterministicOptions::new()
+    }
+}
+
+impl DeterministicOptions {
+    /// Returns a new instance of `DeterministicOptions` with default parameters.
+    pub const fn new() -> Self {
+        DeterministicOptions {
+            seed: 0,
+            time: 0,
+            clock: 0.0,
+            ordered_pairs: false,
+        }
+    }
+
+    /// Sets [`seed`] option.
+    ///
+    /// [`seed`]: #structfield.seed
+    #[must_use]
+    pub const fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Sets [`time`] option.
+    ///
+    /// [`time`]: #structfield.time
+    #[must_use]
+    pub const fn time(mut self, time: i64) -> Self {
+        self.time = time;
+        self
+    }
+
+    /// Sets [`clock`] option.
+    ///
+    /// [`clock`]: #structfield.clock
+    #[must_use]
+    pub const fn clock(mut self, clock: f64) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Sets [`ordered_pairs`] option.
+    ///
+    /// [`ordered_pairs`]: #structfield.ordered_pairs
+    #[must_use]
+    pub const fn ordered_pairs(mut self, enabled: bool) -> Self {
+        self.ordered_pairs = enabled;
+        self
+    }
+}
+
+// Host controlled state behind the replaced functions
+pub(crate) struct DeterministicState {
+    rng: u64,
+    pub(crate) time: i64,
+    pub(crate) clock: f64,
+    pub(crate) ordered_pairs: bool,
+}
+
+impl DeterministicState {
+    pub(crate) fn new(options: DeterministicOptions) -> Self {
+        DeterministicState {
+            rng: options.seed,
+            time: options.time,
+            clock: options.clock,
+            ordered_pairs: options.ordered_pairs,
+        }
+    }
+
+    pub(crate) fn set_seed(&mut self, seed: u64) {
+        self.rng = seed;
+    }
+
+    // SplitMix64, the output does not depend on the platform or Lua version
+    fn next_u64(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    // Implements `math.random([m [, n]])`
+    fn random(&mut self, m: Option<Number>, n: Option<Number>) -> Result<Value<'static>> {
+        let (low, high) = match (m, n) {
+            (None, _) => {
+                let r = (self.next_u64() >> 11) as Number / (1u64 << 53) as Number;
+                return Ok(Value::Number(r));
+            }
+            (Some(m), None) => (1, random_bound(m)?),
+            (Some(m), Some(n)) => (random_bound(m)?, random_bound(n)?),
+        };
+        if low > high {
+            return Err(Error::runtime(
+                "bad argument to 'random' (interval is empty)",
+            ));
+        }
+        let range = (high as i128 - low as i128 + 1) as u128;
+        let r = low as i128 + (self.next_u64() as u128 % range) as i128;
+        // Always fits as both bounds are `Integer`
+        Ok(Value::Integer(r as Integer))
+    }
+}
+
+// Floors a bound of `math.random` like Lua 5.1 does
+fn random_bound(n: Number) -> Result<i64> {
+    let n = n.floor();
+    if !(Integer::MIN as Number..=Integer::MAX as Number).contains(&n) {
+        return Err(Error::runtime(
+            "bad argument to 'random' (number is out of range)",
+        ));
+    }
+    Ok(n as i64)
+}
+
+const DETERMINISTIC_LIB: &str = r#"
+local sorted_keys, rawget = ...
+
+-- Iterates over table in the sorted key order
+local function opairs(t)
+    local keys = sorted_keys(t)
+    local i = 0
+    return function()
+        i = i + 1
+        local k = keys[i]
+        if k ~= nil then
+            return k, rawget(t, k)
+        end
+    end, t, nil
+end
+
+return opairs
+"#;
+
+// Replaces non-deterministic functions of the loaded standard libraries
+pub(crate) fn install(lua: &Lua) -> Result<()> {
+    let globals = lua.globals();
+
+    if let Some(math) = globals.get::<_, Option<Table>>("math")? {
+        let random = lua.create_function(|lua, (m, n): (Option<Number>, Option<Number>)| {
+            lua.with_deterministic_state(|state| state.random(m, n))
+        })?;
+        let randomseed = lua.create_function(|lua, seed: Number| {
+            lua.with_deterministic_state(|state| {
+                state.set_seed(seed as i64 as u64);
+                Ok(())
+            })
+        })?;
+        math.raw_set("random", random)?;
+        math.raw_set("randomseed", randomseed)?;
+    }
+
+    if let Some(os) = globals.get::<_, Option<Table>>("os")? {
+        let time = lua.create_function(|lua, t: Option<Table>| match t {
+            Some(t) => utc_time(&t).map(Value::Number),
+            None => lua.with_deterministic_state(|state| Ok(Value::Number(state.time as Number))),
+        })?;
+        let clock =
+            lua.create_function(|lua, ()| lua.with_deterministic_state(|state| Ok(state.clock)))?;
+        os.raw_set("time", time)?;
+        os.raw_set("clock", clock)?;
+    }
+
+    let sorted_keys = lua.create_function(|lua, t: Table| {
+        let mut keys = t
+            .pairs::<Value, Value>()
+            .map(|kv| kv.map(|(k, _)| k))
+            .collect::<Result<Vec<_>>>()?;
+        for key in &keys {
+            check_ordered_key(key, "iterate in order over")?;
+        }
+        keys.sort_by(|a, b| a.cmp(b));
+        lua.create_sequence_from(keys)
+    })?;
+    let opairs = lua
+        .load(DETERMINISTIC_LIB)
+        .set_name("=__mlua_deterministic")
+        .call::<_, Value>((sorted_keys, globals.raw_get::<_, Value>("rawget")?))?;
+    globals.raw_set("opairs", opairs.clone())?;
+    if lua.with_deterministic_state(|state| Ok(state.ordered_pairs))? {
+        globals.raw_set("pairs", opairs)?;
+    }
+
+    Ok(())
+}
+
+// Only primitive keys can be ordered the same way in every state, other keys are ordered by address
+fn check_ordered_key(key: &Value, action: &str) -> Result<()> {
+    match key {
+        Value::Boolean(_) | Value::Integer(_) | Value::Number(_) | Value::String(_) => Ok(()),
+        _ => Err(Error::runtime(format!(
+            "cannot {action} a table with a key of type {}",
+            key.type_name()
+        ))),
+    }
+}
+
+// Implements `os.time(t)` in UTC, so the result does not depend on the local timezone
+fn utc_time(t: &Table) -> Result<Number> {
+    let field = |name: &str, default: Option<i64>| -> Result<i64> {
+        match (t.get::<_, Option<Number>>(name)?, default) {
+            (Some(v), _) => Ok(v as i64),
+            (None, Some(d)) => Ok(d),
+            (None, None) => Err(Error::runtime(format!(
+                "field '{name}' missing in date table"
+            ))),
+        }
+    };
+    let (year, month, day) = (
+        field("year", None)?,
+        field("month", None)?,
+        field("day", None)?,
+    );
+    let (hour, min, sec) = (
+        field("hour", Some(12))?,
+        field("min", Some(0))?,
+        field("sec", Some(0))?,
+    );
+
+    // Days from civil algorithm (normalizes out of range months)
+    let (year, month) = (
+        year + (month - 1).div_euclid(12),
+        (month - 1).rem_euclid(12) + 1,
+    );
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    Ok((days * 86400 + hour * 3600 + min * 60 + sec) as Number)
+}
+
+// FNV-1a
+struct Hasher(u64);
+
+impl Hasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn write_tag(&mut self, tag: u8) {
+        self.write(&[tag]);
+    }
+}
+
+// Computes a checksum of tables contents that is the same for equal tables in every Lua state
+pub(crate) fn checksum(tables: &[Table]) -> Result<u64> {
+    let mut hasher = Hasher(0xcbf29ce484222325);
+    let mut visited = FxHashMap::default();
+    for t in tables {
+        hash_table(&mut hasher, t, &mut visited)?;
+    }
+    Ok(hasher.0)
+}
+
+fn hash_table(
+    hasher: &mut Hasher,
+    t: &Table,
+    visited: &mut FxHashMap<*const c_void, u64>,
+) -> Result<()> {
+    // Shared and recursive tables are referred by the visit order
+    let index = visited.len() as u64;
+    if let Some(index) = visited.get(&t.to_pointer()) {
+        hasher.write_tag(7);
+        hasher.write(&index.to_le_bytes());
+        return Ok(());
+    }
+    visited.insert(t.to_pointer(), index);
+
+    let mut pairs = t
+        .clone()
+        .pairs::<Value, Value>()
+        .collect::<Result<Vec<_>>>()?;
+    for (key, _) in &pairs {
+        check_ordered_key(key, "checksum")?;
+    }
+    pairs.sort_by(|(a, _), (b, _)| a.cmp(b));
+    hasher.write_tag(6);
+    hasher.write(&(pairs.len() as u64).to_le_bytes());
+    for (k, v) in &pairs {
+        hash_value(hasher, k, visited)?;
+        hash_value(hasher, v, visited)?;
+    }
+    Ok(())
+}
+
+fn hash_value(
+    hasher: &mut Hasher,
+    value: &Value,
+    visited: &mut FxHashMap<*const c_void, u64>,
+) -> Result<()> {
+    match value {
+        Value::Nil => hasher.write_tag(0),
+        Value::Boolean(false) => hasher.write_tag(1),
+        Value::Boolean(true) => hasher.write_tag(2),
+        Value::Integer(i) => hash_integer(hasher, *i as i64),
+        // Integral floats are hashed as integers to not depend on number representation
+        Value::Number(n)
+            if n.fract() == 0.0 && *n >= i64::MIN as Number && *n < i64::MAX as Number =>
+        {
+            hash_integer(hasher, *n as i64)
+        }
+        Value::Number(n) => {
+            hasher.write_tag(4);
+            let bits = if n.is_nan() {
+                Number::NAN.to_bits()
+            } else {
+                n.to_bits()
+            };
+            hasher.write(&bits.to_le_bytes());
+        }
+        Value::String(s) => {
+            hasher.write_tag(5);
+            hasher.write(&(s.as_bytes().len() as u64).to_le_bytes());
+            hasher.write(s.as_bytes());
+        }
+        Value::Table(t) => hash_table(hasher, t, visited)?,
+        #[cfg(feature = "luau")]
+        Value::Vector(v) => {
+            hasher.write_tag(14);
+            for c in v.0 {
+                hasher.write(&c.to_le_bytes());
+            }
+        }
+        #[cfg(feature = "lua51_civ6")]
+        Value::Other(v) => {
+            hasher.write_tag(12);
+            if let Some(n) = v.as_u64()? {
+                hasher.write(&n.to_le_bytes());
+            }
+        }
+        // Values of other types have only identity, which is different between states
+        Value::LightUserData(_) => hasher.write_tag(11),
+        Value::Function(_) => hasher.write_tag(8),
+        Value::UserData(_) => hasher.write_tag(9),
+        Value::Thread(_) => hasher.write_tag(10),
+        Value::Error(_) => hasher.write_tag(13),
+    }
+    Ok(())
+}
+
+fn hash_integer(hasher: &mut Hasher, i: i64) {
+    hasher.write_tag(3);
+    hasher.write(&i.to_le_bytes());
+}
@@ -8,12 +8,16 @@ use std::string::String as StdString;
 use std::sync::Arc;
 
 use crate::private::Sealed;
+use crate::types::RegistryKey;
 
 /// Error type returned by `mlua` methods.
 #[derive(Debug, Clone)]
 #[non_exhaustive]
 pub enum Error {
     /// Syntax error while parsing Lua source code.
+    ///
+    /// More fields may be added in the future, so patterns must use `..`.
+    #[non_exhaustive]
     SyntaxError {
         /// The error message as returned by Lua.
         message: StdString,
@@ -22,6 +26,19 @@ pub enum Error {
         /// This is useful for implementing REPLs as they can query the user for more input if this
         /// is set.
         incomplete_input: bool,
+        /// Name of the chunk as set by [`Chunk::set_name`] (e.g. `@mods/ui.lua`).
+        ///
+        /// [`Chunk::set_name`]: crate::Chunk::set_name
+        chunk_name: Option<StdString>,
+        /// Line where the error was detected.
+        line: Option<usize>,
+        /// Column (1-based, in bytes) of `token` within `line`.
+        ///
+        /// Lua does not report columns, so it is present only if the token can be found
+        /// unambiguously in the source line.
+        column: Option<usize>,
+        /// The token near which the error was detected (`<eof>` for the end of the input).
+        token: Option<StdString>,
     },
     /// Lua runtime error, aka `LUA_ERRRUN`.
     ///
@@ -47,6 +64,20 @@ pub enum Error {
     /// This error can only happen when Lua state was not created by us and does not have the
     /// custom allocator attached.
     MemoryLimitNotAvailable,
+    /// The HavokScript runtime does not export some essential entry points.
+    ///
+    /// Contains the (mangled) names of all missing symbols.
+    #[cfg(any(feature = "civ6-dynamic", doc))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "civ6-dynamic")))]
+    MissingSymbols(Vec<StdString>),
+    /// The HavokScript runtime does not support 64-bit unsigned integers (`ui64`).
+    #[cfg(any(feature = "lua51_civ6", doc))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "lua51_civ6")))]
+    Ui64NotAvailable,
+    /// The execution budget of [`Function::call_with_budget`] was exceeded.
+    ///
+    /// [`Function::call_with_budget`]: crate::Function::call_with_budget
+    BudgetExceeded,
     /// A mutable callback has triggered Lua code that has called the same mutable callback again.
     ///
     /// This is an error because a mutable callback can only be borrowed mutably once.
@@ -197,6 +228,22 @@ pub enum Error {
         /// Underlying error.
         cause: Arc<Error>,
     },
+    /// A Lua error with the original error value and the structured Lua call stack.
+    ///
+    /// Runtime and callback errors are wrapped into it if enabled with [`Lua::set_error_details`].
+    /// The details are available via [`Error::value`] and [`Error::traceback`].
+    ///
+    /// [`Lua::set_error_details`]: crate::Lua::set_error_details
+    WithDetails {
+        /// Lua call stack at the point where the error was raised, innermost frame first.
+        traceback: Vec<StackFrame>,
+        /// The original error value if it was not a string.
+        ///
+        /// Returning this error from a Rust callback raises the original value in Lua again.
+        value: Option<Arc<RegistryKey>>,
+        /// Underlying error.
+        cause: Arc<Error>,
+    },
 }
 
 /// A specialized `Result` type used by `mlua`'s API.
@@ -221,6 +268,15 @@ impl fmt::Display for Error {
             Error::MemoryLimitNotAvailable => {
                 write!(fmt, "setting memory limit is not available")
             }
+            #[cfg(feature = "civ6-dynamic")]
+            Error::MissingSymbols(ref symbols) => {
+                write!(fmt, "HavokScript runtime is missing symbols: {}", symbols.join(", "))
+            }
+            #[cfg(feature = "lua51_civ6")]
+            Error::Ui64NotAvailable => {
+                write!(fmt, "ui64 values are not supported by the runtime")
+            }
+            Error::BudgetExceeded => write!(fmt, "execution budget exceeded"),
             Error::RecursiveMutCallback => write!(fmt, "mutable callback called recursively"),
             Error::CallbackDestructed => write!(
                 fmt,
@@ -278,7 +334,7 @@ impl fmt::Display for Error {
             Error::CallbackError { ref cause, ref traceback } => {
                 // Trace errors down to the root
                 let (mut cause, mut full_traceback) = (cause, None);
-                while let Error::CallbackError { cause: ref cause2, traceback: ref traceback2 } = **cause {
+                while let Error::CallbackError { cause: ref cause2, traceback: ref traceback2 } = *cause.without_details() {
                     cause = cause2;
                     full_traceback = Some(traceback2);
                 }
@@ -314,6 +370,7 @@ impl fmt::Display for Error {
                 writeln!(fmt, "{context}")?;
                 write!(fmt, "{cause}")
             }
+            Error::WithDetails { ref cause, .. } => write!(fmt, "{cause}"),
         }
     }
 }
@@ -331,6 +388,7 @@ impl StdError for Error {
                 Error::ExternalError(err) => err.source(),
                 _ => None,
             },
+            Error::WithDetails { ref cause, .. } => cause.source(),
             _ => None,
         }
     }
@@ -343,6 +401,50 @@ impl Error {
         Error::RuntimeError(message.to_string())
     }
 
+    /// Returns the registry key of the original Lua error value, if it was not a string.
+    ///
+    /// Values are kept only if enabled with [`Lua::set_error_details`]. Looks through
+    /// [`Error::WithContext`] and [`Error::CallbackError`] causes. The value can be retrieved with
+    /// [`Lua::registry_value`].
+    ///
+    /// [`Lua::set_error_details`]: crate::Lua::set_error_details
+    /// [`Lua::registry_value`]: crate::Lua::registry_value
+    pub fn value(&self) -> Option<&RegistryKey> {
+        match self {
+            Error::WithDetails {
+                value: Some(value), ..
+            } => Some(value),
+            Error::WithDetails { cause, .. }
+            | Error::WithContext { cause, .. }
+            | Error::CallbackError { cause, .. } => cause.value(),
+            _ => None,
+        }
+    }
+
+    /// Returns the Lua call stack captured with the error, innermost frame first.
+    ///
+    /// Stack frames are captured only if enabled with [`Lua::set_error_details`]. Looks through
+    /// [`Error::WithContext`] and [`Error::CallbackError`] causes.
+    ///
+    /// [`Lua::set_error_details`]: crate::Lua::set_error_details
+    pub fn traceback(&self) -> Option<&[StackFrame]> {
+        match self {
+            Error::WithDetails { traceback, .. } => Some(traceback),
+            Error::WithContext { cause, .. } | Error::CallbackError { cause, .. } => {
+                cause.traceback()
+            }
+            _ => None,
+        }
+    }
+
+    // Returns the underlying error if this error only adds details to it
+    fn without_details(&self) -> &Error {
+        match self {
+            Error::WithDetails { cause, .. } => cause,
+            err => err,
+        }
+    }
+
     /// Wraps an external error object.
     #[inline]
     pub fn external<T: Into<Box<dyn StdError + Send + Sync>>>(err: T) -> Self {
@@ -364,6 +466,57 @@ impl Error {
         }
     }
 
+    // Builds `SyntaxError` from the message returned by Lua
+    pub(crate) fn syntax(message: StdString) -> Self {
+        // Messages look like `chunk:line: text near 'token'` (or `got 'token'` for Luau)
+        let line = syntax_error_line(&message, None);
+        let token = [" near ", ", got "].iter().find_map(|prefix| {
+            let pos = message.rfind(prefix)?;
+            let mut token = &message[pos + prefix.len()..];
+            if *prefix == ", got " {
+                // Luau can append a hint after the token
+                token = token.split_once("; ").map_or(token, |(token, _)| token);
+            }
+            let token = match token.strip_prefix('\'') {
+                Some(quoted) => quoted.strip_suffix('\'')?,
+                None => token,
+            };
+            (!token.is_empty()).then(|| token.to_string())
+        });
+        Error::SyntaxError {
+            // This seems terrible, but as far as I can tell, this is exactly what the
+            // stock Lua REPL does.
+            incomplete_input: message.ends_with("<eof>") || message.ends_with("'<eof>'"),
+            message,
+            chunk_name: None,
+            line,
+            column: None,
+            token,
+        }
+    }
+
+    // Attaches the chunk name and locates the token within the (text) source
+    pub(crate) fn with_chunk_source(mut self, name: Option<&str>, source: Option<&[u8]>) -> Self {
+        if let Error::SyntaxError {
+            message,
+            chunk_name,
+            line,
+            column,
+            token,
+            ..
+        } = &mut self
+        {
+            if name.is_some() {
+                *line = syntax_error_line(message, name);
+            }
+            *chunk_name = name.map(|name| name.to_string());
+            if let (Some(line), Some(token), Some(source)) = (*line, token.as_deref(), source) {
+                *column = token_column(source, line, token);
+            }
+        }
+        self
+    }
+
     pub(crate) fn bad_self_argument(to: &str, cause: Error) -> Self {
         Error::BadArgument {
             to: Some(to.to_string()),
@@ -386,6 +539,123 @@ impl Error {
     }
 }
 
+// Finds the line number in the `chunk:line: text` syntax error message.
+// The chunk part is checked against the chunk name (if known), as it can contain `:line:` itself.
+fn syntax_error_line(message: &str, chunk_name: Option<&str>) -> Option<usize> {
+    let mut found = None;
+    let mut start = 0;
+    while let Some(pos) = message[start..].find(':') {
+        let (chunk, rest) = (&message[..start + pos], &message[start + pos + 1..]);
+        start += pos + 1;
+        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 || !rest[digits..].starts_with(':') {
+            continue;
+        }
+        let line = rest[..digits].parse().ok();
+        let Some(name) = chunk_name else {
+            return line;
+        };
+        // Prefer the exact chunk name, then the longest shortened one, then the first match
+        match short_src_match(chunk, name) {
+            Some(true) => return line,
+            Some(false) => found = Some(line),
+            None => found = found.or(Some(line)),
+        }
+    }
+    found.flatten()
+}
+
+// Checks if `short_src` is the chunk name as formatted by Lua in the messages (`luaO_chunkid`):
+// `=name` and `@file` without the prefix, `[string "source"]` otherwise.
+// Returns `Some(false)` if it matches the shortened name.
+fn short_src_match(short_src: &str, chunk_name: &str) -> Option<bool> {
+    let (src, name, shortened) = match chunk_name.strip_prefix(['=', '@']) {
+        Some(name) => (short_src, name, short_src.strip_prefix("...")),
+        None => {
+            let src =
+                (short_src.strip_prefix("[string \"")).and_then(|src| src.strip_suffix("\"]"))?;
+            (src, chunk_name, None)
+        }
+    };
+    if src == name {
+        return Some(true);
+    }
+    let shortened = match shortened {
+        // `@file` keeps the end of the name
+        Some(tail) => name.ends_with(tail),
+        // `=name` and sources keep the beginning
+        None => {
+            let head = src.strip_suffix("...").unwrap_or(src);
+            !head.is_empty() && name.starts_with(head)
+        }
+    };
+    shortened.then_some(false)
+}
+
+// Finds the column of the token if it occurs exactly once in the source line
+fn token_column(source: &[u8], line: usize, token: &str) -> Option<usize> {
+    if token == "<eof>" {
+        return None;
+    }
+    let source_line = source.split(|&b| b == b'\n').nth(line.checked_sub(1)?)?;
+    let token = token.as_bytes();
+    let mut found = (0..source_line.len()).filter(|&i| source_line[i..].starts_with(token));
+    match (found.next(), found.next()) {
+        (Some(pos), None) => Some(pos + 1),
+        _ => None,
+    }
+}
+
+/// A frame of the Lua call stack captured with an error.
+///
+/// `Display` renders the frame the same way as a line of the `debug.traceback` output.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct StackFrame {
+    /// Stack level of the frame (the innermost frame has the lowest level).
+    ///
+    /// Levels are not contiguous if the middle of a deep stack was omitted.
+    pub level: usize,
+    /// Source of the chunk that created the function (e.g. `@mods/ui.lua` or `=name`).
+    pub source: Option<StdString>,
+    /// A "printable" version of `source`, used in error messages.
+    pub short_src: Option<StdString>,
+    /// The line being executed, if known.
+    pub line: Option<usize>,
+    /// The line where the function definition starts.
+    pub line_defined: Option<usize>,
+    /// A reasonable name for the function, if known.
+    pub name: Option<StdString>,
+    /// Explains the `name` field: "global", "local", "method", "field" or "upvalue".
+    ///
+    /// Always `None` for Luau.
+    pub name_what: Option<StdString>,
+    /// Type of the function: "Lua", "C", "main" or "tail".
+    pub what: &'static str,
+    /// `true` if the function is a Rust callback.
+    pub is_callback: bool,
+}
+
+impl fmt::Display for StackFrame {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}:", self.short_src.as_deref().unwrap_or("?"))?;
+        if let Some(line) = self.line {
+            write!(fmt, "{line}:")?;
+        }
+        match (&self.name, self.what) {
+            (Some(name), _) => write!(fmt, " in function '{name}'"),
+            (None, "main") => write!(fmt, " in main chunk"),
+            (None, "C") => write!(fmt, " in ?"),
+            (None, _) => write!(
+                fmt,
+                " in function <{}:{}>",
+                self.short_src.as_deref().unwrap_or("?"),
+                self.line_defined.unwrap_or(0)
+            ),
+        }
+    }
+}
+
 /// Trait for converting [`std::error::Error`] into Lua [`Error`].
 pub trait ExternalError {
     fn into_lua_err(self) -> Error;
@@ -473,6 +743,18 @@ impl From<Utf8Error> for Error {
     }
 }
 
+#[cfg(feature = "civ6-dynamic")]
+impl From<ffi::dynamic::LoadError> for Error {
+    fn from(err: ffi::dynamic::LoadError) -> Self {
+        match err {
+            ffi::dynamic::LoadError::MissingSymbols(symbols) => {
+                Error::MissingSymbols(symbols.into_iter().map(StdString::from).collect())
+            }
+            err => Error::external(err),
+        }
+    }
+}
+
 #[cfg(feature = "serialize")]
 impl serde::ser::Error for Error {
     fn custom<T: fmt::Display>(msg: T) -> Self {
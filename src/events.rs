@@ -0,0 +1,319 @@
+use std::fmt;
+use std::mem;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::lua::Lua;
+use crate::table::{Table, TableExt};
+use crate::types::MaybeSend;
+use crate::userdata_ext::AnyUserDataExt;
+use crate::value::{FromLuaMulti, IntoLua, IntoLuaMulti, MultiValue, Value};
+
+/// The maximum number of listener errors kept until taken by the host.
+pub const MAX_EVENT_ERRORS: usize = 256;
+
+// Listener errors not yet taken by the host
+#[derive(Default)]
+struct EventErrors {
+    errors: Vec<Error>,
+    // Errors reported while the buffer was full
+    dropped: usize,
+}
+
+const EVENTS_LIB: &str = r#"
+local setmetatable, rawset, pcall, type, error, report = ...
+
+local function new_event(name)
+    local listeners = {}
+    local event = {}
+
+    function event.Add(f)
+        if type(f) ~= "function" then
+            error("bad argument #1 to 'Add' (function expected)", 2)
+        end
+        for i = 1, #listeners do
+            if listeners[i] == f then return end
+        end
+        listeners[#listeners + 1] = f
+    end
+
+    function event.Remove(f)
+        for i = 1, #listeners do
+            if listeners[i] == f then
+                for j = i, #listeners do
+                    listeners[j] = listeners[j + 1]
+                end
+                return
+            end
+        end
+    end
+
+    function event.Count()
+        return #listeners
+    end
+
+    return setmetatable(event, {
+        __call = function(_, ...)
+            -- Listeners added or removed during the dispatch take effect on the next one
+            local snapshot = {}
+            for i = 1, #listeners do
+                snapshot[i] = listeners[i]
+            end
+            for i = 1, #snapshot do
+                local ok, err = pcall(snapshot[i], ...)
+                if not ok then
+                    report(name, err)
+                end
+            end
+        end,
+    })
+end
+
+return function(group)
+    return setmetatable({}, {
+        __index = function(t, name)
+            if type(name) ~= "string" then return nil end
+            local event = new_event(group .. "." .. name)
+            rawset(t, name, event)
+            return event
+        end,
+    })
+end
+"#;
+
+/// A bridge to the Civ6-style event tables, `Events` and `LuaEvents`.
+///
+/// Scripts subscribe to events using `Events.Foo.Add(f)` and `Events.Foo.Remove(f)`, and fire
+/// Lua events by calling them: `LuaEvents.Bar(...)`. The bridge accesses the event tables only
+/// through this interface, so it works the same with the tables provided by the game (when the
+/// [`Lua`] instance is attached to the game state) and with the local emulation that is installed
+/// when the tables do not exist (for example, in tests).
+///
+/// Errors of the listeners do not abort the dispatch. Errors of Rust listeners (and, in the
+/// emulation, of Lua listeners) are collected and returned by [`Event::fire`] or
+/// [`EventBus::take_errors`]. At most [`MAX_EVENT_ERRORS`] errors are kept, the rest are only
+/// counted (see [`EventBus::dropped_errors`]).
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{EventBus, Lua, Result};
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let bus = EventBus::new(&lua)?;
+///
+/// bus.lua_events("PlayerTurnStarted")?.add(|_, player: i32| {
+///     println!("turn of player {player}");
+///     Ok(())
+/// })?;
+/// lua.load("LuaEvents.PlayerTurnStarted(1)").exec()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct EventBus<'lua> {
+    lua: &'lua Lua,
+    events: Table<'lua>,
+    lua_events: Table<'lua>,
+    emulated: bool,
+}
+
+impl<'lua> fmt::Debug for EventBus<'lua> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("EventBus")
+            .field("emulated", &self.emulated)
+            .finish()
+    }
+}
+
+impl<'lua> EventBus<'lua> {
+    /// Creates a bridge to the global `Events` and `LuaEvents` tables.
+    ///
+    /// If the tables do not exist, installs the local emulation of them.
+    pub fn new(lua: &'lua Lua) -> Result<EventBus<'lua>> {
+        if lua.app_data_ref::<EventErrors>().is_none() {
+            lua.set_app_data(EventErrors::default());
+        }
+
+        let globals = lua.globals();
+        let events = globals.get::<_, Option<Table>>("Events")?;
+        let lua_events = globals.get::<_, Option<Table>>("LuaEvents")?;
+        let emulated = events.is_none() || lua_events.is_none();
+
+        let mut new_group = None;
+        let mut group = |table: Option<Table<'lua>>, name: &str| -> Result<Table<'lua>> {
+            if let Some(table) = table {
+                return Ok(table);
+            }
+            if new_group.is_none() {
+                let report = lua.create_function(|lua, (name, err): (String, Value)| {
+                    let err = match err {
+                        Value::Error(err) => err,
+                        value => Error::runtime(value.to_string()?),
+                    };
+                    report_error(lua, &name, err);
+                    Ok(())
+                })?;
+                let new: Function = lua.load(EVENTS_LIB).set_name("=__mlua_events").call((
+                    globals.get::<_, Value>("setmetatable")?,
+                    globals.get::<_, Value>("rawset")?,
+                    globals.get::<_, Value>("pcall")?,
+                    globals.get::<_, Value>("type")?,
+                    globals.get::<_, Value>("error")?,
+                    report,
+                ))?;
+                new_group = Some(new);
+            }
+            let table: Table = new_group.as_ref().unwrap().call(name)?;
+            globals.set(name, table.clone())?;
+            Ok(table)
+        };
+
+        Ok(EventBus {
+            lua,
+            events: group(events, "Events")?,
+            lua_events: group(lua_events, "LuaEvents")?,
+            emulated,
+        })
+    }
+
+    /// Returns `true` if any of the event tables is the local emulation.
+    pub fn is_emulated(&self) -> bool {
+        self.emulated
+    }
+
+    /// Returns the event `Events.<name>`.
+    pub fn events(&self, name: &str) -> Result<Event<'lua>> {
+        self.event(&self.events, "Events", name)
+    }
+
+    /// Returns the event `LuaEvents.<name>`.
+    pub fn lua_events(&self, name: &str) -> Result<Event<'lua>> {
+        self.event(&self.lua_events, "LuaEvents", name)
+    }
+
+    /// Takes errors of the listeners that were reported since the previous call.
+    ///
+    /// These are errors of listeners invoked by Lua code or by the game, errors of listeners
+    /// invoked by [`Event::fire`] are returned from it.
+    pub fn take_errors(&self) -> Vec<Error> {
+        (self.lua.app_data_mut::<EventErrors>())
+            .map(|mut errors| mem::take(&mut errors.errors))
+            .unwrap_or_default()
+    }
+
+    /// Returns the number of listener errors that were dropped since the previous call, because
+    /// [`MAX_EVENT_ERRORS`] errors were already waiting to be taken.
+    pub fn dropped_errors(&self) -> usize {
+        (self.lua.app_data_mut::<EventErrors>())
+            .map(|mut errors| mem::take(&mut errors.dropped))
+            .unwrap_or_default()
+    }
+
+    fn event(&self, group: &Table<'lua>, group_name: &str, name: &str) -> Result<Event<'lua>> {
+        let object = group.get::<_, Value>(name)?;
+        let name = format!("{group_name}.{name}");
+        match object {
+            Value::Table(_) | Value::UserData(_) => Ok(Event {
+                lua: self.lua,
+                name,
+                object,
+            }),
+            _ => Err(Error::runtime(format!("event '{name}' does not exist"))),
+        }
+    }
+}
+
+/// A single event of an [`EventBus`].
+#[derive(Clone)]
+pub struct Event<'lua> {
+    lua: &'lua Lua,
+    name: String,
+    object: Value<'lua>,
+}
+
+impl<'lua> fmt::Debug for Event<'lua> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("Event").field(&self.name).finish()
+    }
+}
+
+impl<'lua> Event<'lua> {
+    /// Returns the full name of the event, for example `LuaEvents.Foo`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Subscribes a Rust closure to the event.
+    ///
+    /// The event arguments are converted to `A`. An error returned by the closure (or a
+    /// conversion error) does not reach the dispatcher and is reported as a listener error.
+    ///
+    /// Returns the Lua function that was subscribed, which can be passed to [`Event::remove`].
+    pub fn add<A, F>(&self, func: F) -> Result<Function<'lua>>
+    where
+        A: FromLuaMulti<'lua>,
+        F: Fn(&'lua Lua, A) -> Result<()> + MaybeSend + 'static,
+    {
+        let name = self.name.clone();
+        let listener = self.lua.create_function(move |lua, args: MultiValue| {
+            if let Err(err) = A::from_lua_multi(args, lua).and_then(|args| func(lua, args)) {
+                report_error(lua, &name, err);
+            }
+            Ok(())
+        })?;
+        self.add_function(&listener)?;
+        Ok(listener)
+    }
+
+    /// Subscribes a Lua function to the event.
+    pub fn add_function(&self, func: &Function<'lua>) -> Result<()> {
+        self.method("Add")?.call(func)
+    }
+
+    /// Unsubscribes a function from the event.
+    pub fn remove(&self, func: &Function<'lua>) -> Result<()> {
+        self.method("Remove")?.call(func)
+    }
+
+    /// Fires the event with the given arguments.
+    ///
+    /// Returns errors of the listeners that failed during this dispatch.
+    pub fn fire(&self, args: impl IntoLuaMulti<'lua>) -> Result<Vec<Error>> {
+        // Collect errors of this dispatch separately from the ones waiting to be taken
+        let pending = (self.lua.app_data_mut::<EventErrors>())
+            .map(|mut errors| mem::take(&mut errors.errors))
+            .unwrap_or_default();
+        let res = match &self.object {
+            Value::Table(t) => t.call::<_, ()>(args),
+            Value::UserData(ud) => ud.call::<_, ()>(args),
+            _ => unreachable!(),
+        };
+        let errors = (self.lua.app_data_mut::<EventErrors>())
+            .map(|mut errors| mem::replace(&mut errors.errors, pending))
+            .unwrap_or_default();
+        res.map(|_| errors)
+    }
+
+    fn method(&self, name: &str) -> Result<Function<'lua>> {
+        let key = name.into_lua(self.lua)?;
+        match &self.object {
+            Value::Table(t) => t.get(key),
+            Value::UserData(ud) => ud.get(key),
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn report_error(lua: &Lua, name: &str, err: Error) {
+    if let Some(mut errors) = lua.app_data_mut::<EventErrors>() {
+        if errors.errors.len() >= MAX_EVENT_ERRORS {
+            errors.dropped += 1;
+            return;
+        }
+        errors.errors.push(Error::WithContext {
+            context: format!("error in a listener of event '{name}'"),
+            cause: Arc::new(err),
+        });
+    }
+}
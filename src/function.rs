@@ -3,6 +3,7 @@ use std::mem;
 use std::os::raw::{c_int, c_void};
 use std::ptr;
 use std::slice;
+use std::time::{Duration, Instant};
 
 use crate::error::{Error, Result};
 use crate::lua::Lua;
@@ -81,6 +82,119 @@ pub struct CoverageInfo {
     pub hits: Vec<i32>,
 }
 
+/// Execution limits of [`Function::call_with_budget`].
+#[derive(Clone, Copy, Debug, Default)]
+#[non_exhaustive]
+pub struct Budget {
+    /// Maximum number of VM instructions the call can execute.
+    ///
+    /// Instructions are counted in steps of up to 1000, so the call can execute slightly more
+    /// instructions before it is stopped. On Luau the number of interrupt checks (function calls
+    /// and loop iterations) is counted instead.
+    ///
+    /// Default: **none**
+    pub instructions: Option<u64>,
+
+    /// Maximum wall time of the call.
+    ///
+    /// The time is checked together with the instructions count, so time spent inside a Rust
+    /// callback is not interrupted.
+    ///
+    /// Default: **none**
+    pub time: Option<Duration>,
+}
+
+impl Budget {
+    /// Returns a new instance of `Budget` without limits.
+    pub const fn new() -> Self {
+        Budget {
+            instructions: None,
+            time: None,
+        }
+    }
+
+    /// Sets [`instructions`] option.
+    ///
+    /// [`instructions`]: #structfield.instructions
+    #[must_use]
+    pub const fn instructions(mut self, instructions: u64) -> Self {
+        self.instructions = Some(instructions);
+        self
+    }
+
+    /// Sets [`time`] option.
+    ///
+    /// [`time`]: #structfield.time
+    #[must_use]
+    pub const fn time(mut self, time: Duration) -> Self {
+        self.time = Some(time);
+        self
+    }
+}
+
+// Remaining budget of the innermost `call_with_budget`
+pub(crate) struct BudgetState {
+    instructions: Option<u64>,
+    deadline: Option<Instant>,
+    used: u64,
+    exceeded: bool,
+}
+
+impl BudgetState {
+    // Nested budgets cannot exceed the enclosing one
+    pub(crate) fn new(budget: Budget, outer: Option<&BudgetState>) -> Self {
+        let deadline = budget
+            .time
+            .and_then(|time| Instant::now().checked_add(time));
+        let outer_instructions = outer.and_then(|outer| outer.instructions);
+        let outer_deadline = outer.and_then(|outer| outer.deadline);
+        BudgetState {
+            instructions: budget
+                .instructions
+                .into_iter()
+                .chain(outer_instructions)
+                .min(),
+            deadline: deadline.into_iter().chain(outer_deadline).min(),
+            used: 0,
+            exceeded: false,
+        }
+    }
+
+    // Interval of the count hook that checks the budget
+    #[cfg(not(feature = "luau"))]
+    pub(crate) fn interval(&self, max: u32) -> u32 {
+        match self.instructions {
+            Some(n) if n < max as u64 => n.max(1) as u32,
+            _ => max,
+        }
+    }
+
+    // Accounts the executed instructions and checks the limits
+    pub(crate) fn consume(&mut self, instructions: u64) -> Result<()> {
+        self.used += instructions;
+        if let Some(left) = self.instructions.as_mut() {
+            *left = left.saturating_sub(instructions);
+            self.exceeded |= *left == 0;
+        }
+        if matches!(self.deadline, Some(deadline) if Instant::now() >= deadline) {
+            self.exceeded = true;
+        }
+        match self.exceeded {
+            true => Err(Error::BudgetExceeded),
+            false => Ok(()),
+        }
+    }
+
+    // Accounts the instructions used by a finished nested call
+    pub(crate) fn finish_nested(&mut self, inner: &BudgetState) {
+        let _ = self.consume(inner.used);
+    }
+
+    pub(crate) fn is_exceeded(&self) -> bool {
+        self.exceeded
+    }
+}
+
 impl<'lua> Function<'lua> {
     /// Calls the function, passing `args` as function arguments.
     ///
@@ -146,6 +260,44 @@ impl<'lua> Function<'lua> {
         }
     }
 
+    /// Calls the function like [`Function::call`], stopping it when the budget is exceeded.
+    ///
+    /// Returns [`Error::BudgetExceeded`] if the function executed more instructions or ran longer
+    /// than allowed by `budget`, even if the error raised to stop it was caught by the Lua code.
+    /// Nested calls with budgets are limited by the enclosing budget as well.
+    ///
+    /// On backends other than Luau the budget is checked by a count hook, which is combined with
+    /// the hook set by [`Lua::set_hook`] (if any). Coroutines that were created before the call
+    /// are not limited.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use mlua::{Budget, Error, Function, Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// # let lua = Lua::new();
+    /// let runaway: Function = lua.load("function() while true do end end").eval()?;
+    /// let budget = Budget::new().instructions(100_000).time(Duration::from_secs(1));
+    /// let result = runaway.call_with_budget::<_, ()>((), budget);
+    /// assert!(matches!(result, Err(Error::BudgetExceeded)));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_with_budget<A, R>(&self, args: A, budget: Budget) -> Result<R>
+    where
+        A: IntoLuaMulti<'lua>,
+        R: FromLuaMulti<'lua>,
+    {
+        let lua = self.0.lua;
+        let mut guard = lua.enter_budget(budget);
+        let result = self.call(args);
+        match guard.finish() {
+            true => Err(Error::BudgetExceeded),
+            false => result,
+        }
+    }
+
     /// Returns a future that, when polled, calls `self`, passing `args` as function arguments,
     /// and drives the execution.
     ///
@@ -280,7 +432,7 @@ impl<'lua> Function<'lua> {
     /// By default Lua functions shares a global environment.
     ///
     /// This function always returns `None` for Rust/C functions.
-    pub fn environment(&self) -> Option<Table> {
+    pub fn environment(&self) -> Option<Table<'lua>> {
         let lua = self.0.lua;
         let state = lua.state();
         unsafe {
@@ -402,6 +554,56 @@ impl<'lua> Function<'lua> {
         }
     }
 
+    /// Returns the upvalues of the function with their current values.
+    ///
+    /// The upvalue at index `i` of the returned vector can be changed using
+    /// [`Function::set_upvalue`] with `n = i + 1`. Upvalues of C (and Rust) functions have empty
+    /// names.
+    pub fn upvalues(&self) -> Result<Vec<(String, Value<'lua>)>> {
+        let lua = self.0.lua;
+        let state = lua.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 3)?;
+
+            lua.push_ref(&self.0);
+            let mut upvalues = Vec::new();
+            loop {
+                let name = ffi::lua_getupvalue(state, -1, upvalues.len() as c_int + 1);
+                if name.is_null() {
+                    break;
+                }
+                let name = ptr_to_lossy_str(name).unwrap_or_default().into_owned();
+                upvalues.push((name, lua.pop_value()));
+            }
+            Ok(upvalues)
+        }
+    }
+
+    /// Sets the value of the upvalue `n` (starting from 1) of the function.
+    ///
+    /// Returns an error if there is no such upvalue.
+    pub fn set_upvalue(&self, n: usize, value: impl IntoLua<'lua>) -> Result<()> {
+        let lua = self.0.lua;
+        let value = value.into_lua(lua)?;
+        let state = lua.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 2)?;
+
+            lua.push_ref(&self.0);
+            lua.push_value(value)?;
+            let name = match n {
+                1..=0x7fff_ffff => ffi::lua_setupvalue(state, -2, n as c_int),
+                _ => ptr::null(),
+            };
+            if name.is_null() {
+                return Err(Error::runtime(format!("upvalue #{n} does not exist")));
+            }
+            Ok(())
+        }
+    }
+
     /// Dumps the function as a binary chunk.
     ///
     /// If `strip` is true, the binary representation may not include all debug information
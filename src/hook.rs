@@ -2,12 +2,17 @@ use std::borrow::Cow;
 use std::cell::UnsafeCell;
 #[cfg(not(feature = "luau"))]
 use std::ops::{BitOr, BitOrAssign};
-use std::os::raw::c_int;
+use std::os::raw::{c_char, c_int};
 
 use ffi::lua_Debug;
 
+use crate::error::{Error, Result};
+use crate::function::Function;
 use crate::lua::Lua;
-use crate::util::{linenumber_to_usize, ptr_to_lossy_str, ptr_to_str};
+#[cfg(not(feature = "luau"))]
+use crate::types::HookCallback;
+use crate::util::{check_stack, linenumber_to_usize, ptr_to_lossy_str, ptr_to_str, StackGuard};
+use crate::value::{IntoLua, Value};
 
 /// Contains information about currently executing Lua code.
 ///
@@ -184,6 +189,121 @@ impl<'lua> Debug<'lua> {
             stack
         }
     }
+
+    /// Corresponds to the `f` what mask. Returns the function running at this level.
+    pub fn function(&self) -> Result<Function<'lua>> {
+        let state = self.lua.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 1)?;
+
+            #[cfg(not(feature = "luau"))]
+            mlua_assert!(
+                ffi::lua_getinfo(state, cstr!("f"), self.ar.get()) != 0,
+                "lua_getinfo failed with `f`"
+            );
+            #[cfg(feature = "luau")]
+            mlua_assert!(
+                ffi::lua_getinfo(state, self.level, cstr!("f"), self.ar.get()) != 0,
+                "lua_getinfo failed with `f`"
+            );
+            Ok(Function(self.lua.pop_ref()))
+        }
+    }
+
+    /// Corresponds to the `L` what mask. Returns the lines of the function that contain code,
+    /// in ascending order.
+    ///
+    /// Returns an empty vector for C functions.
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub fn active_lines(&self) -> Result<Vec<usize>> {
+        let state = self.lua.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 3)?;
+
+            mlua_assert!(
+                ffi::lua_getinfo(state, cstr!("L"), self.ar.get()) != 0,
+                "lua_getinfo failed with `L`"
+            );
+            let mut lines = Vec::new();
+            if ffi::lua_type(state, -1) == ffi::LUA_TTABLE {
+                ffi::lua_pushnil(state);
+                while ffi::lua_next(state, -2) != 0 {
+                    if let Some(line) = linenumber_to_usize(ffi::lua_tointeger(state, -2) as c_int)
+                    {
+                        lines.push(line);
+                    }
+                    ffi::lua_pop(state, 1);
+                }
+            }
+            lines.sort_unstable();
+            Ok(lines)
+        }
+    }
+
+    /// Returns the local variables of the function with their current values.
+    ///
+    /// The local variable at index `i` of the returned vector can be changed using
+    /// [`Debug::set_local`] with `n = i + 1`. Names starting with `(` denote internal variables
+    /// (temporaries, loop control variables, etc).
+    pub fn locals(&self) -> Result<Vec<(String, Value<'lua>)>> {
+        let state = self.lua.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 3)?;
+
+            let mut locals = Vec::new();
+            loop {
+                let name = self.get_local_raw(state, locals.len() as c_int + 1);
+                if name.is_null() {
+                    break;
+                }
+                let name = ptr_to_lossy_str(name).unwrap_or_default().into_owned();
+                locals.push((name, self.lua.pop_value()));
+            }
+            Ok(locals)
+        }
+    }
+
+    /// Sets the value of the local variable `n` (starting from 1) of the function.
+    ///
+    /// Returns an error if there is no such local variable.
+    pub fn set_local(&self, n: usize, value: impl IntoLua<'lua>) -> Result<()> {
+        let value = value.into_lua(self.lua)?;
+        let state = self.lua.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 1)?;
+
+            self.lua.push_value(value)?;
+            let name = match n {
+                1..=0x7fff_ffff => self.set_local_raw(state, n as c_int),
+                _ => std::ptr::null(),
+            };
+            if name.is_null() {
+                return Err(Error::runtime(format!(
+                    "local variable #{n} does not exist"
+                )));
+            }
+            Ok(())
+        }
+    }
+
+    unsafe fn get_local_raw(&self, state: *mut ffi::lua_State, n: c_int) -> *const c_char {
+        #[cfg(not(feature = "luau"))]
+        return ffi::lua_getlocal(state, self.ar.get(), n);
+        #[cfg(feature = "luau")]
+        return ffi::lua_getlocal(state, self.level, n);
+    }
+
+    unsafe fn set_local_raw(&self, state: *mut ffi::lua_State, n: c_int) -> *const c_char {
+        #[cfg(not(feature = "luau"))]
+        return ffi::lua_setlocal(state, self.ar.get(), n);
+        #[cfg(feature = "luau")]
+        return ffi::lua_setlocal(state, self.level, n);
+    }
 }
 
 enum ActivationRecord {
@@ -381,3 +501,27 @@ impl BitOrAssign for HookTriggers {
         *self = *self | rhs;
     }
 }
+
+/// Handle of a hook registered by [`Lua::add_hook`] or [`Thread::add_hook`].
+///
+/// Pass it to [`Lua::remove_hook_by_id`] to unregister the hook.
+///
+/// [`Thread::add_hook`]: crate::Thread::add_hook
+#[cfg(not(feature = "luau"))]
+#[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HookId(pub(crate) u64);
+
+// A hook function registered for a thread
+#[cfg(not(feature = "luau"))]
+pub(crate) struct HookEntry {
+    pub(crate) id: HookId,
+    // Null for the hooks of every thread (see `Lua::add_global_hook`)
+    pub(crate) thread: *mut ffi::lua_State,
+    // The thread is tracked in the weak table of hook threads (see `Lua::register_hook`)
+    pub(crate) tracked: bool,
+    pub(crate) triggers: HookTriggers,
+    // Instructions counted since the last call of the count hook
+    pub(crate) count: u32,
+    pub(crate) callback: HookCallback,
+}
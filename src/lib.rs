@@ -80,8 +80,13 @@
 mod macros;
 
 mod chunk;
+mod codec;
 mod conversion;
+#[cfg(not(feature = "luau"))]
+mod coverage;
+mod deterministic;
 mod error;
+mod events;
 mod function;
 mod hook;
 mod lua;
@@ -89,9 +94,17 @@ mod lua;
 mod luau;
 mod memory;
 mod multi;
+#[cfg(not(feature = "luau"))]
+mod persist;
+#[cfg(not(feature = "luau"))]
+mod profiler;
+#[cfg(not(feature = "luau"))]
+mod sandbox;
+mod scheduler;
 mod scope;
 mod stdlib;
 mod string;
+mod string_builder;
 mod table;
 mod thread;
 mod types;
@@ -106,17 +119,24 @@ pub mod prelude;
 pub use ffi::{self, lua_CFunction, lua_State};
 
 pub use crate::chunk::{AsChunk, Chunk, ChunkMode};
-pub use crate::error::{Error, ErrorContext, ExternalError, ExternalResult, Result};
-pub use crate::function::{Function, FunctionInfo};
+pub use crate::codec::EncodeOptions;
+pub use crate::deterministic::DeterministicOptions;
+pub use crate::error::{Error, ErrorContext, ExternalError, ExternalResult, Result, StackFrame};
+pub use crate::events::{Event, EventBus, MAX_EVENT_ERRORS};
+pub use crate::function::{Budget, Function, FunctionInfo};
 pub use crate::hook::{Debug, DebugEvent, DebugNames, DebugSource, DebugStack};
 pub use crate::lua::{GCMode, Lua, LuaOptions};
 pub use crate::multi::Variadic;
+pub use crate::scheduler::{Scheduler, TaskId, TaskInfo, TaskState};
 pub use crate::scope::Scope;
 pub use crate::stdlib::StdLib;
 pub use crate::string::String;
+pub use crate::string_builder::StringBuilder;
 pub use crate::table::{Table, TableExt, TablePairs, TableSequence};
 pub use crate::thread::{Thread, ThreadStatus};
-pub use crate::types::{AppDataRef, AppDataRefMut, Integer, LightUserData, Number, RegistryKey};
+pub use crate::types::{
+    AppDataRef, AppDataRefMut, Integer, LightUserData, Number, RegistryKey, VmState,
+};
 pub use crate::userdata::{
     AnyUserData, MetaMethod, UserData, UserDataFields, UserDataMetatable, UserDataMethods,
     UserDataRef, UserDataRefMut,
@@ -126,15 +146,20 @@ pub use crate::userdata_impl::UserDataRegistry;
 pub use crate::value::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti, MultiValue, Nil, Value};
 
 #[cfg(not(feature = "luau"))]
-pub use crate::hook::HookTriggers;
+pub use crate::hook::{HookId, HookTriggers};
+#[cfg(not(feature = "luau"))]
+pub use crate::{
+    coverage::LineCoverage,
+    profiler::{Profile, ProfileFunction},
+};
 
 #[cfg(any(feature = "luau", doc))]
 #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
-pub use crate::{
-    chunk::Compiler,
-    function::CoverageInfo,
-    types::{Vector, VmState},
-};
+pub use crate::{chunk::Compiler, function::CoverageInfo, types::Vector};
+
+#[cfg(any(feature = "lua51_civ6", doc))]
+#[cfg_attr(docsrs, doc(cfg(feature = "lua51_civ6")))]
+pub use crate::value::OtherValue;
 
 #[cfg(feature = "async")]
 pub use crate::thread::AsyncThread;
@@ -142,13 +167,18 @@ pub use crate::thread::AsyncThread;
 #[cfg(feature = "serialize")]
 #[doc(inline)]
 pub use crate::serde::{
-    de::Options as DeserializeOptions, ser::Options as SerializeOptions, LuaSerdeExt,
+    de::Options as DeserializeOptions, de::StringArena, ser::Options as SerializeOptions,
+    LuaSerdeExt,
 };
 
 #[cfg(feature = "serialize")]
 #[cfg_attr(docsrs, doc(cfg(feature = "serialize")))]
 pub mod serde;
 
+#[cfg(all(feature = "debugger", not(feature = "luau")))]
+#[cfg_attr(docsrs, doc(cfg(feature = "debugger")))]
+pub mod debugger;
+
 #[cfg(feature = "mlua_derive")]
 #[allow(unused_imports)]
 #[macro_use]
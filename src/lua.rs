@@ -15,18 +15,22 @@ use std::sync::{Arc, Mutex};
 use rustc_hash::FxHashMap;
 
 use crate::chunk::{AsChunk, Chunk, ChunkMode};
+use crate::codec::{self, EncodeOptions};
+use crate::deterministic::{self, DeterministicOptions, DeterministicState};
 use crate::error::{Error, Result};
-use crate::function::Function;
+use crate::function::{Budget, BudgetState, Function};
 use crate::hook::Debug;
+#[cfg(not(feature = "luau"))]
 use crate::memory::{MemoryState, ALLOCATOR};
 use crate::scope::Scope;
 use crate::stdlib::StdLib;
 use crate::string::String;
+use crate::string_builder::StringBuilder;
 use crate::table::Table;
 use crate::thread::Thread;
 use crate::types::{
     AppData, AppDataRef, AppDataRefMut, Callback, CallbackUpvalue, DestructedUserdata, Integer,
-    LightUserData, LuaRef, MaybeSend, Number, RegistryKey, SubtypeId,
+    InterruptCallback, LightUserData, LuaRef, MaybeSend, Number, RegistryKey, SubtypeId, VmState,
 };
 use crate::userdata::{AnyUserData, MetaMethod, UserData, UserDataCell};
 use crate::userdata_impl::{UserDataProxy, UserDataRegistry};
@@ -35,6 +39,7 @@ use crate::util::{
     get_gc_metatable, get_gc_userdata, get_main_state, get_userdata, init_error_registry,
     init_gc_metatable, init_userdata_metatable, pop_error, push_gc_userdata, push_string,
     push_table, rawset_field, safe_pcall, safe_xpcall, short_type_name, StackGuard, WrappedFailure,
+    REGISTRY_KEYS,
 };
 use crate::value::{FromLua, FromLuaMulti, IntoLua, IntoLuaMulti, MultiValue, Nil, Value};
 
@@ -44,16 +49,21 @@ use crate::util::push_userdata;
 use crate::{types::WarnCallback, userdata::USER_VALUE_MAXSLOT, util::push_userdata_uv};
 
 #[cfg(not(feature = "luau"))]
-use crate::{hook::HookTriggers, types::HookCallback};
-
-#[cfg(feature = "luau")]
-use crate::types::InterruptCallback;
-#[cfg(any(feature = "luau", doc))]
 use crate::{
-    chunk::Compiler,
-    types::{Vector, VmState},
+    coverage::{self, CoverageState, LineCoverage},
+    hook::{HookEntry, HookId, HookTriggers},
+    persist,
+    profiler::{self, Profile, ProfilerState},
+    sandbox,
+    types::HookCallback,
 };
 
+#[cfg(any(feature = "luau", doc))]
+use crate::{chunk::Compiler, types::Vector};
+
+#[cfg(feature = "lua51_civ6")]
+use crate::value::OtherValue;
+
 #[cfg(feature = "async")]
 use {
     crate::types::{AsyncCallback, AsyncCallbackUpvalue, AsyncPollUpvalue},
@@ -96,6 +106,19 @@ pub(crate) struct ExtraData {
     libs: StdLib,
     #[cfg(feature = "module")]
     skip_memory_check: bool,
+    // Attach the original values and stack frames to Lua errors
+    error_details: bool,
+    // Allocator installed by `Lua::wrap_allocator` (stays allocated after restoring)
+    #[cfg(not(feature = "luau"))]
+    chained_mem_state: *mut MemoryState,
+    // State of the deterministic mode (if enabled)
+    deterministic: Option<DeterministicState>,
+    // State of the sampling profiler (if running)
+    #[cfg(not(feature = "luau"))]
+    profiler: Option<Box<ProfilerState>>,
+    // Line coverage being collected (if started)
+    #[cfg(not(feature = "luau"))]
+    coverage: Option<Box<CoverageState>>,
 
     // Auxiliary thread to store references
     ref_thread: *mut ffi::lua_State,
@@ -118,17 +141,27 @@ pub(crate) struct ExtraData {
     #[cfg(feature = "async")]
     waker: NonNull<Waker>,
 
+    // Hooks in the order of registration
     #[cfg(not(feature = "luau"))]
-    hook_callback: Option<HookCallback>,
+    hooks: Vec<HookEntry>,
     #[cfg(not(feature = "luau"))]
-    hook_thread: *mut ffi::lua_State,
+    next_hook_id: u64,
     #[cfg(feature = "lua54")]
     warn_callback: Option<WarnCallback>,
-    #[cfg(feature = "luau")]
     interrupt_callback: Option<InterruptCallback>,
+    // Instructions counted since the last call of the interrupt
+    #[cfg(not(feature = "luau"))]
+    interrupt_count: u32,
+    // Budget of the innermost `Function::call_with_budget`
+    budget: Option<BudgetState>,
 
-    #[cfg(feature = "luau")]
     sandboxed: bool,
+    // Environment of the chunks loaded in the sandbox
+    #[cfg(not(feature = "luau"))]
+    sandbox_env: Option<RegistryKey>,
+    // Globals of the threads that use the sandbox environment (the others are sandboxed threads)
+    #[cfg(any(feature = "lua51", feature = "luajit"))]
+    sandbox_globals: Option<RegistryKey>,
     #[cfg(feature = "luau")]
     compiler: Option<Compiler>,
     #[cfg(feature = "luau-jit")]
@@ -222,7 +255,6 @@ impl LuaOptions {
 
 #[cfg(feature = "async")]
 pub(crate) static ASYNC_POLL_PENDING: u8 = 0;
-pub(crate) static EXTRA_REGISTRY_KEY: u8 = 0;
 
 const WRAPPED_FAILURE_POOL_SIZE: usize = 64;
 const MULTIVALUE_POOL_SIZE: usize = 64;
@@ -244,6 +276,8 @@ impl Drop for Lua {
 impl Drop for LuaInner {
     fn drop(&mut self) {
         unsafe {
+            #[cfg(not(feature = "luau"))]
+            (*self.extra.get()).release_chained_allocator(self.main_state);
             let mem_state = MemoryState::get(self.main_state);
 
             ffi::lua_close(self.main_state);
@@ -260,6 +294,8 @@ impl Drop for ExtraData {
     fn drop(&mut self) {
         #[cfg(feature = "module")]
         unsafe {
+            #[cfg(not(feature = "luau"))]
+            self.release_chained_allocator(self.inner.assume_init_ref().main_state);
             self.inner.assume_init_drop();
         }
 
@@ -298,6 +334,13 @@ impl Lua {
     ///
     /// See [`StdLib`] documentation for a list of unsafe modules that cannot be loaded.
     ///
+    /// # Panics
+    ///
+    /// Panics if the Lua VM cannot be created. With `civ6-dynamic` this happens when the loaded
+    /// runtime exports neither `lua_newstate` nor `luaL_newstate`; use [`Lua::init_from_ptr`]
+    /// with the state created by the game instead.
+    /// The same applies to the other constructors.
+    ///
     /// [`StdLib`]: crate::StdLib
     pub fn new() -> Lua {
         mlua_expect!(
@@ -388,6 +431,13 @@ impl Lua {
             drop(Box::from_raw(mem_state));
             state = ffi::luaL_newstate();
         }
+        #[cfg(feature = "civ6-dynamic")]
+        assert!(
+            !state.is_null(),
+            "Failed to instantiate Lua VM: the HavokScript runtime does not export \
+             `lua_newstate`, use `Lua::init_from_ptr` with the state created by the game"
+        );
+        #[cfg(not(feature = "civ6-dynamic"))]
         assert!(!state.is_null(), "Failed to instantiate Lua VM");
 
         ffi::luaL_requiref(state, cstr!("_G"), ffi::luaopen_base, 1);
@@ -441,10 +491,40 @@ impl Lua {
         lua
     }
 
+    /// Loads the HavokScript runtime from the shared library at `path`.
+    ///
+    /// All entry points are resolved at once. If any essential entry point is not exported,
+    /// [`Error::MissingSymbols`] listing all of them is returned.
+    /// Without an explicit call, the runtime is loaded from the game on first use.
+    ///
+    /// # Safety
+    /// The library must export the HavokScript API with the signatures expected by `mlua`.
+    #[cfg(any(feature = "civ6-dynamic", doc))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "civ6-dynamic")))]
+    pub unsafe fn load_runtime<P: AsRef<std::ffi::OsStr>>(path: P) -> Result<()> {
+        ffi::dynamic::load(path).map_err(Error::from)
+    }
+
+    /// Loads the HavokScript runtime from a raw library handle (`dlopen` or `LoadLibrary` result).
+    ///
+    /// See [`Lua::load_runtime`] for details.
+    ///
+    /// # Safety
+    /// The handle must be valid and the library must export the HavokScript API with the
+    /// signatures expected by `mlua`.
+    #[cfg(any(feature = "civ6-dynamic", doc))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "civ6-dynamic")))]
+    pub unsafe fn load_runtime_from_handle(handle: *mut c_void) -> Result<()> {
+        ffi::dynamic::load_from_handle(handle).map_err(Error::from)
+    }
+
     /// Constructs a new Lua instance from an existing raw state.
     ///
     /// Once called, a returned Lua state is cached in the registry and can be retrieved
     /// by calling this function again.
+    ///
+    /// The cache is private to this copy of mlua: separately built modules attached to the same
+    /// state get their own instances and can exchange only plain Lua values.
     #[allow(clippy::missing_safety_doc, clippy::arc_with_non_send_sync)]
     pub unsafe fn init_from_ptr(state: *mut ffi::lua_State) -> Lua {
         assert!(!state.is_null(), "Lua state is NULL");
@@ -519,6 +599,14 @@ impl Lua {
             libs: StdLib::NONE,
             #[cfg(feature = "module")]
             skip_memory_check: false,
+            error_details: false,
+            #[cfg(not(feature = "luau"))]
+            chained_mem_state: ptr::null_mut(),
+            deterministic: None,
+            #[cfg(not(feature = "luau"))]
+            profiler: None,
+            #[cfg(not(feature = "luau"))]
+            coverage: None,
             ref_thread,
             // We need some reserved stack space to move values in and out of the ref stack.
             ref_stack_size: ffi::LUA_MINSTACK - REF_STACK_RESERVE,
@@ -532,15 +620,20 @@ impl Lua {
             #[cfg(feature = "async")]
             waker: NonNull::from(noop_waker_ref()),
             #[cfg(not(feature = "luau"))]
-            hook_callback: None,
+            hooks: Vec::new(),
             #[cfg(not(feature = "luau"))]
-            hook_thread: ptr::null_mut(),
+            next_hook_id: 1,
             #[cfg(feature = "lua54")]
             warn_callback: None,
-            #[cfg(feature = "luau")]
             interrupt_callback: None,
-            #[cfg(feature = "luau")]
+            #[cfg(not(feature = "luau"))]
+            interrupt_count: 0,
+            budget: None,
             sandboxed: false,
+            #[cfg(not(feature = "luau"))]
+            sandbox_env: None,
+            #[cfg(any(feature = "lua51", feature = "luajit"))]
+            sandbox_globals: None,
             #[cfg(feature = "luau")]
             compiler: None,
             #[cfg(feature = "luau-jit")]
@@ -611,9 +704,238 @@ impl Lua {
         }
         unsafe { (*self.extra.get()).libs |= libs };
 
+        // Newly loaded libraries must not bring back non-deterministic functions
+        if res.is_ok() && unsafe { (*self.extra.get()).deterministic.is_some() } {
+            deterministic::install(self)?;
+        }
+
         res
     }
 
+    /// Installs the `json` module with `json.encode`, `json.decode` and `json.null`.
+    ///
+    /// The module is set as the `json` global and, if the `package` library is loaded, is also
+    /// available via `require("json")`.
+    ///
+    /// `json.encode(value, opts)` converts the value with [`LuaSerdeExt::from_value_with`] and
+    /// returns a JSON string. Tables with a sequence part or the [`array_metatable`] are encoded as
+    /// arrays, other tables as objects with string or number keys. `json.null` is encoded as
+    /// `null`, and so are NaN and infinities. The `opts` table accepts the fields of
+    /// [`DeserializeOptions`] (`deny_unsupported_types`, `deny_recursive_tables`, `sort_keys`)
+    /// and `pretty`, which is `true`, the number of spaces or a string to indent the output with.
+    /// Object keys are written in sorted order unless the `preserve_order` feature of
+    /// `serde_json` is enabled, then `sort_keys` applies.
+    ///
+    /// `json.decode(str, opts)` returns the decoded value. Arrays get the [`array_metatable`]
+    /// unless the `set_array_metatable` option is `false`, and `null` is decoded as `json.null`
+    /// unless the `null` option is `false` (then it is decoded as `nil`).
+    ///
+    /// Errors include the path to the value that cannot be encoded (for example
+    /// ``units[3].name: unsupported value type `function` ``) or the line and column of invalid
+    /// JSON.
+    ///
+    /// Requires `feature = "json"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.load_json_module()?;
+    ///
+    /// let s: String = lua.load(r#"return json.encode({ a = { 1, 2 } })"#).eval()?;
+    /// assert_eq!(s, r#"{"a":[1,2]}"#);
+    /// lua.load(r#"assert(json.decode('{"b":null}').b == json.null)"#).exec()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`array_metatable`]: crate::LuaSerdeExt::array_metatable
+    /// [`DeserializeOptions`]: crate::DeserializeOptions
+    /// [`LuaSerdeExt::from_value_with`]: crate::LuaSerdeExt::from_value_with
+    #[cfg(feature = "json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    pub fn load_json_module(&self) -> Result<()> {
+        crate::serde::json::install(self)
+    }
+
+    /// Enables deterministic execution mode, for example for lockstep multiplayer.
+    ///
+    /// Replaces the standard library functions that give different results on different
+    /// machines with versions controlled by the host:
+    /// - `math.random` and `math.randomseed` use a portable seeded generator
+    /// - `os.time()` and `os.clock()` return the values set by [`Lua::set_deterministic_time`],
+    ///   and `os.time(t)` interprets the date table in UTC
+    ///
+    /// Also adds a global `opairs` function, which iterates over a table in the sorted key order
+    /// (the same as [`DeserializeOptions::sort_keys`]). Keys of non-primitive types (tables,
+    /// functions, etc.) would be ordered by address, which differs between states, so iterating
+    /// over a table having such keys raises an error.
+    /// With [`DeterministicOptions::ordered_pairs`] enabled, `pairs` is replaced as well.
+    ///
+    /// Libraries loaded later using [`Lua::load_from_std_lib`] are patched too.
+    /// Calling this function again resets the state.
+    ///
+    /// [`DeserializeOptions::sort_keys`]: crate::DeserializeOptions::sort_keys
+    pub fn enable_deterministic_mode(&self, options: DeterministicOptions) -> Result<()> {
+        unsafe { (*self.extra.get()).deterministic = Some(DeterministicState::new(options)) };
+        deterministic::install(self)
+    }
+
+    /// Sets the values returned by `os.time()` and `os.clock()` in deterministic mode.
+    ///
+    /// Returns an error if the deterministic mode is not enabled.
+    pub fn set_deterministic_time(&self, time: i64, clock: f64) -> Result<()> {
+        self.with_deterministic_state(|state| {
+            state.time = time;
+            state.clock = clock;
+            Ok(())
+        })
+    }
+
+    /// Reseeds the `math.random` generator in deterministic mode.
+    ///
+    /// Returns an error if the deterministic mode is not enabled.
+    pub fn set_deterministic_seed(&self, seed: u64) -> Result<()> {
+        self.with_deterministic_state(|state| {
+            state.set_seed(seed);
+            Ok(())
+        })
+    }
+
+    /// Computes a checksum of the contents of the given tables.
+    ///
+    /// Tables are traversed recursively in the sorted key order, so the checksum is equal for
+    /// tables with equal contents in different Lua states (and different processes), which allows
+    /// to detect state divergence between peers. Integral floats are hashed as integers.
+    /// Values that have only identity (functions, userdata, threads) contribute only their type.
+    /// Metatables are ignored.
+    ///
+    /// Keys must be booleans, numbers or strings: keys of other types cannot be ordered the same
+    /// way in every state (see `opairs` in [`Lua::enable_deterministic_mode`]), so tables having
+    /// them return an error.
+    pub fn checksum(&self, tables: &[Table]) -> Result<u64> {
+        deterministic::checksum(tables)
+    }
+
+    /// Encodes a value into a compact binary format.
+    ///
+    /// Supports nil, booleans, integers, numbers, strings and tables. Tables referenced several
+    /// times (including cycles) are written once and restored as the same table by
+    /// [`Lua::decode_value`]. Integers and floats stay distinct and strings keep their raw bytes.
+    /// Metatables are not encoded, and functions, userdata and threads return an error.
+    ///
+    /// The format is independent of the Lua version.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{EncodeOptions, Lua, Result, Table};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let t: Table = lua.load("local t = { 1, 2.5, x = 'y' } t.self = t return t").eval()?;
+    /// let data = lua.encode_value(t, EncodeOptions::new())?;
+    ///
+    /// let t: Table = lua.decode_value(&data)?;
+    /// assert_eq!(t.get::<_, Table>("self")?, t);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn encode_value<'lua>(
+        &'lua self,
+        value: impl IntoLua<'lua>,
+        options: EncodeOptions,
+    ) -> Result<Vec<u8>> {
+        codec::encode(value.into_lua(self)?, options)
+    }
+
+    /// Decodes a value encoded by [`Lua::encode_value`].
+    ///
+    /// Decoding compressed data requires `feature = "compress"`. The decompressed size declared in
+    /// the data must be reachable by LZ4 (at most 255 times the compressed size), so corrupted data
+    /// cannot make it allocate an arbitrary amount of memory.
+    pub fn decode_value<'lua, R: FromLua<'lua>>(&'lua self, data: &[u8]) -> Result<R> {
+        R::from_lua(codec::decode(self, data)?, self)
+    }
+
+    /// Serializes a value with everything reachable from it into a binary blob.
+    ///
+    /// Tables (including their metatables), Lua functions with their upvalues and environments,
+    /// and threads are written once, so shared references and cycles are restored as they were.
+    /// Values that cannot be serialized (userdata, C functions) must be listed in the
+    /// `permanents` table, which maps such values to keys (booleans, numbers or strings). Only
+    /// the key is written and [`Lua::unpersist`] looks the value up by this key. Any other value
+    /// can be made permanent as well, for example the globals table.
+    ///
+    /// Functions are stored as bytecode, so the blob can only be loaded by the same Lua version.
+    /// Upvalues shared between closures stay shared on Lua 5.2+. Lua 5.1 (and LuaJIT) cannot
+    /// join upvalues, so persisting closures that share an upvalue returns an error there.
+    ///
+    /// Threads can be persisted only if they are not started yet or finished: the state of a
+    /// suspended coroutine cannot be saved, it has to be kept in tables instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Function, Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let permanents = lua.create_table()?;
+    /// permanents.set(lua.globals(), "_G")?;
+    ///
+    /// let counter: Function = lua
+    ///     .load("local n = 0 return function() n = n + 1 return n end")
+    ///     .eval()?;
+    /// counter.call::<_, ()>(())?;
+    /// let data = lua.persist(counter, &permanents)?;
+    ///
+    /// let lua2 = Lua::new();
+    /// let permanents = lua2.create_table()?;
+    /// permanents.set(lua2.globals(), "_G")?;
+    /// let counter: Function = lua2.unpersist(&data, &permanents)?;
+    /// assert_eq!(counter.call::<_, i32>(())?, 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub fn persist<'lua>(
+        &'lua self,
+        value: impl IntoLua<'lua>,
+        permanents: &Table<'lua>,
+    ) -> Result<Vec<u8>> {
+        persist::persist(self, value.into_lua(self)?, permanents)
+    }
+
+    /// Restores a value serialized by [`Lua::persist`].
+    ///
+    /// The `permanents` table must map the values to the same keys as the one used for
+    /// persisting.
+    ///
+    /// The functions are restored by loading the bytecode stored in the blob, which Lua does not
+    /// check for consistency. Loading untrusted bytecode is unsafe and can crash the interpreter,
+    /// so the blobs must come from a trusted source.
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub fn unpersist<'lua, R: FromLua<'lua>>(
+        &'lua self,
+        data: &[u8],
+        permanents: &Table<'lua>,
+    ) -> Result<R> {
+        R::from_lua(persist::unpersist(self, data, permanents)?, self)
+    }
+
+    pub(crate) fn with_deterministic_state<R>(
+        &self,
+        f: impl FnOnce(&mut DeterministicState) -> Result<R>,
+    ) -> Result<R> {
+        match unsafe { (*self.extra.get()).deterministic.as_mut() } {
+            Some(state) => f(state),
+            None => Err(Error::runtime("deterministic mode is not enabled")),
+        }
+    }
+
     /// Loads module `modname` into an existing Lua state using the specified entrypoint
     /// function.
     ///
@@ -749,13 +1071,25 @@ impl Lua {
 
     /// Enables (or disables) sandbox mode on this Lua instance.
     ///
-    /// This method, in particular:
+    /// On Luau this method, in particular:
     /// - Set all libraries to read-only
     /// - Set all builtin metatables to read-only
     /// - Set globals to read-only (and activates safeenv)
     /// - Setup local environment table that performs writes locally and proxies reads
     ///   to the global environment.
     ///
+    /// On other backends the globals are left untouched, so the code loaded by the host (for
+    /// example the game scripts of a state attached with [`Lua::init_from_ptr`]) is not affected
+    /// and [`Lua::globals`] keeps returning the real globals. Instead, chunks loaded by this `Lua`
+    /// while the sandbox is enabled get a proxy table as their environment (unless
+    /// [`Chunk::set_environment`] is used), which performs writes locally and reads the original
+    /// globals. Standard libraries are seen through read-only proxies, `loadstring`, `load`,
+    /// `dofile`, `require` and the `debug`, `io` and `package` libraries are hidden, `os` only
+    /// has `clock`, `date`, `difftime` and `time`, and `setfenv` cannot change the environment of
+    /// functions defined outside the sandbox. Disabling the sandbox drops the proxy: chunks loaded
+    /// later use the globals again, while the ones loaded in the sandbox keep their environment.
+    /// Use [`Lua::reset_sandbox`] to discard the changes made in the sandbox.
+    ///
     /// # Examples
     ///
     /// ```
@@ -765,19 +1099,47 @@ impl Lua {
     ///
     /// lua.sandbox(true)?;
     /// lua.load("var = 123").exec()?;
-    /// assert_eq!(lua.globals().get::<_, u32>("var")?, 123);
+    /// assert_eq!(lua.load("return var").eval::<u32>()?, 123);
+    /// # #[cfg(not(feature = "luau"))]
+    /// assert_eq!(lua.globals().get::<_, Option<u32>>("var")?, None);
     ///
     /// // Restore the global environment (clear changes made in sandbox)
     /// lua.sandbox(false)?;
-    /// assert_eq!(lua.globals().get::<_, Option<u32>>("var")?, None);
+    /// assert_eq!(lua.load("return var").eval::<Option<u32>>()?, None);
     /// # Ok(())
     /// # }
     /// ```
     ///
-    /// Requires `feature = "luau"`
-    #[cfg(any(feature = "luau", docsrs))]
-    #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+    /// [`Chunk::set_environment`]: crate::Chunk::set_environment
     pub fn sandbox(&self, enabled: bool) -> Result<()> {
+        #[cfg(not(feature = "luau"))]
+        unsafe {
+            let extra = self.extra.get();
+            if (*extra).sandboxed == enabled {
+                return Ok(());
+            }
+            if enabled {
+                let globals = self.globals();
+                let env = sandbox::create_env(self, globals.clone())?;
+                (*extra).sandbox_env = Some(self.create_registry_value(env)?);
+                #[cfg(any(feature = "lua51", feature = "luajit"))]
+                {
+                    (*extra).sandbox_globals = Some(self.create_registry_value(globals)?);
+                }
+            } else {
+                if let Some(key) = (*extra).sandbox_env.take() {
+                    self.remove_registry_value(key)?;
+                }
+                #[cfg(any(feature = "lua51", feature = "luajit"))]
+                if let Some(key) = (*extra).sandbox_globals.take() {
+                    self.remove_registry_value(key)?;
+                }
+            }
+            (*extra).sandboxed = enabled;
+            Ok(())
+        }
+
+        #[cfg(feature = "luau")]
         unsafe {
             if (*self.extra.get()).sandboxed != enabled {
                 let state = self.main_state;
@@ -799,6 +1161,60 @@ impl Lua {
         }
     }
 
+    /// Discards the changes made in the sandbox.
+    ///
+    /// Removes the globals set by the code running in the sandbox, including the chunks loaded
+    /// before the reset. Does nothing if the sandbox is not enabled.
+    ///
+    /// On Luau the main thread gets a new environment instead, so only the code loaded after the
+    /// reset is affected.
+    pub fn reset_sandbox(&self) -> Result<()> {
+        #[cfg(not(feature = "luau"))]
+        {
+            match self.sandbox_env()? {
+                Some(env) => env.clear(),
+                None => Ok(()),
+            }
+        }
+
+        #[cfg(feature = "luau")]
+        unsafe {
+            if (*self.extra.get()).sandboxed {
+                let state = self.main_state;
+                check_stack(state, 3)?;
+                protect_lua!(state, 0, 0, |state| {
+                    // Start from the original `LUA_GLOBALSINDEX`
+                    ffi::lua_xpush(self.ref_thread(), state, ffi::LUA_GLOBALSINDEX);
+                    ffi::lua_replace(state, ffi::LUA_GLOBALSINDEX);
+                    ffi::luaL_sandboxthread(state);
+                })?;
+            }
+            Ok(())
+        }
+    }
+
+    // Returns the environment of the chunks loaded in the sandbox
+    #[cfg(not(feature = "luau"))]
+    pub(crate) fn sandbox_env(&self) -> Result<Option<Table>> {
+        match unsafe { &(*self.extra.get()).sandbox_env } {
+            Some(key) => self.registry_value(key).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    // Returns the sandbox environment for a chunk loaded into the current thread.
+    // Sandboxed threads keep their own globals.
+    #[cfg(not(feature = "luau"))]
+    fn chunk_sandbox_env(&self) -> Result<Option<Table>> {
+        #[cfg(any(feature = "lua51", feature = "luajit"))]
+        if let Some(key) = unsafe { &(*self.extra.get()).sandbox_globals } {
+            if self.globals() != self.registry_value::<Table>(key)? {
+                return Ok(None);
+            }
+        }
+        self.sandbox_env()
+    }
+
     /// Sets a 'hook' function that will periodically be called as Lua code executes.
     ///
     /// When exactly the hook function is called depends on the contents of the `triggers`
@@ -812,7 +1228,8 @@ impl Lua {
     /// This method sets a hook function for the current thread of this Lua instance.
     /// If you want to set a hook function for another thread (coroutine), use [`Thread::set_hook()`] instead.
     ///
-    /// Please note you cannot have more than one hook function set at a time for this Lua instance.
+    /// Setting a hook replaces the one previously set by this method or [`Thread::set_hook()`].
+    /// Use [`Lua::add_hook()`] to run several hook functions at the same time.
     ///
     /// # Example
     ///
@@ -839,157 +1256,400 @@ impl Lua {
     /// [`HookTriggers.every_nth_instruction`]: crate::HookTriggers::every_nth_instruction
     #[cfg(not(feature = "luau"))]
     #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
-    pub fn set_hook<F>(&self, triggers: HookTriggers, callback: F)
+    pub fn set_hook<F>(&self, triggers: HookTriggers, callback: F)
+    where
+        F: Fn(&Lua, Debug) -> Result<()> + MaybeSend + 'static,
+    {
+        unsafe { self.set_thread_hook(self.state(), triggers, callback) };
+    }
+
+    /// Sets a 'hook' function for a thread (coroutine).
+    #[cfg(not(feature = "luau"))]
+    pub(crate) unsafe fn set_thread_hook<F>(
+        &self,
+        state: *mut ffi::lua_State,
+        triggers: HookTriggers,
+        callback: F,
+    ) where
+        F: Fn(&Lua, Debug) -> Result<()> + MaybeSend + 'static,
+    {
+        self.register_hook(state, SET_HOOK_ID, triggers, Arc::new(callback));
+    }
+
+    /// Adds a 'hook' function that runs along with the other hooks of this Lua instance.
+    ///
+    /// Unlike [`Lua::set_hook`], every call adds a new hook to the current thread instead of
+    /// replacing the previous one. The `triggers` of all hooks of the thread are combined, and
+    /// each event is dispatched only to the hooks that requested it, in the order of
+    /// registration. A hook with [`HookTriggers::every_nth_instruction`] is called every `n`
+    /// instructions regardless of the intervals of the other hooks.
+    ///
+    /// Returns a handle to pass to [`Lua::remove_hook_by_id`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use std::sync::atomic::{AtomicU32, Ordering};
+    /// # use std::sync::Arc;
+    /// # use mlua::{Lua, HookTriggers, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// let lines = Arc::new(AtomicU32::new(0));
+    /// let lines2 = lines.clone();
+    /// let tracer = lua.add_hook(HookTriggers::EVERY_LINE, move |_, _| {
+    ///     lines2.fetch_add(1, Ordering::Relaxed);
+    ///     Ok(())
+    /// });
+    /// lua.add_hook(HookTriggers::ON_CALLS, |_, debug| {
+    ///     println!("call {:?}", debug.names().name);
+    ///     Ok(())
+    /// });
+    ///
+    /// lua.load("local x = 1\nlocal y = tostring(x)").exec()?;
+    /// assert_eq!(lines.load(Ordering::Relaxed), 2);
+    /// lua.remove_hook_by_id(tracer);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub fn add_hook<F>(&self, triggers: HookTriggers, callback: F) -> HookId
+    where
+        F: Fn(&Lua, Debug) -> Result<()> + MaybeSend + 'static,
+    {
+        unsafe { self.add_thread_hook(self.state(), triggers, callback) }
+    }
+
+    /// Adds a 'hook' function for a thread (coroutine).
+    #[cfg(not(feature = "luau"))]
+    pub(crate) unsafe fn add_thread_hook<F>(
+        &self,
+        state: *mut ffi::lua_State,
+        triggers: HookTriggers,
+        callback: F,
+    ) -> HookId
+    where
+        F: Fn(&Lua, Debug) -> Result<()> + MaybeSend + 'static,
+    {
+        let extra = self.extra.get();
+        let id = HookId((*extra).next_hook_id);
+        (*extra).next_hook_id += 1;
+        self.register_hook(state, id, triggers, Arc::new(callback));
+        id
+    }
+
+    /// Adds a 'hook' function for every thread, including coroutines.
+    ///
+    /// The hook is installed into the current and the main thread, inherited by coroutines created
+    /// from them and installed into threads resumed by [`Thread::resume`].
+    #[cfg(not(feature = "luau"))]
+    pub(crate) fn add_global_hook<F>(&self, triggers: HookTriggers, callback: F) -> HookId
+    where
+        F: Fn(&Lua, Debug) -> Result<()> + MaybeSend + 'static,
+    {
+        unsafe { self.add_thread_hook(ptr::null_mut(), triggers, callback) }
+    }
+
+    // Installs the hooks set for every thread into the thread (unless there are none)
+    #[cfg(not(feature = "luau"))]
+    pub(crate) unsafe fn install_global_hooks(&self, state: *mut ffi::lua_State) {
+        let extra = self.extra.get();
+        if (*extra).hooks.iter().any(|hook| hook.thread.is_null()) {
+            install_hook(extra, state);
+        }
+    }
+
+    #[cfg(not(feature = "luau"))]
+    unsafe fn register_hook(
+        &self,
+        state: *mut ffi::lua_State,
+        id: HookId,
+        triggers: HookTriggers,
+        callback: HookCallback,
+    ) {
+        let extra = self.extra.get();
+        prune_hooks(extra, self.state(), None);
+        let entry = HookEntry {
+            id,
+            thread: state, // Mark for what thread the hook is set
+            tracked: !state.is_null() && track_hook_thread(self.state(), state, id),
+            triggers,
+            count: 0,
+            callback,
+        };
+        let hooks = &mut (*extra).hooks;
+        let _old_hook = match hooks.iter_mut().find(|hook| hook.id == id) {
+            Some(hook) => Some(mem::replace(hook, entry)),
+            None => {
+                hooks.push(entry);
+                None
+            }
+        };
+        if !state.is_null() {
+            install_hook(extra, state);
+            return;
+        }
+        install_hook(extra, self.state());
+        match get_main_state(self.main_state) {
+            Some(main_state) if !ptr::eq(self.state(), main_state) => {
+                install_hook(extra, main_state);
+            }
+            _ => {}
+        };
+    }
+
+    /// Removes the hook previously set by [`Lua::set_hook()`] or [`Thread::set_hook()`].
+    ///
+    /// This function has no effect if a hook was not previously set. Hooks added by
+    /// [`Lua::add_hook`], the interrupt set by [`Lua::set_interrupt`] and budgets of
+    /// [`Function::call_with_budget`] keep working.
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub fn remove_hook(&self) {
+        unsafe { self.unregister_hook(SET_HOOK_ID) };
+    }
+
+    /// Removes the hook added by [`Lua::add_hook()`] or [`Thread::add_hook()`].
+    ///
+    /// Returns `false` if the hook was already removed.
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub fn remove_hook_by_id(&self, id: HookId) -> bool {
+        unsafe { self.unregister_hook(id) }
+    }
+
+    #[cfg(not(feature = "luau"))]
+    unsafe fn unregister_hook(&self, id: HookId) -> bool {
+        let extra = self.extra.get();
+        let hooks = &mut (*extra).hooks;
+        let hook = match hooks.iter().position(|hook| hook.id == id) {
+            Some(i) => hooks.remove(i),
+            None => return false,
+        };
+        let state = self.state();
+        if hook.tracked && ffi::lua_checkstack(state, 3) != 0 {
+            // Removing a key never allocates
+            push_hook_threads(state);
+            ffi::lua_pushnumber(state, id.0 as ffi::lua_Number);
+            ffi::lua_pushnil(state);
+            ffi::lua_rawset(state, -3);
+            ffi::lua_pop(state, 1);
+        }
+        // Other threads drop the hook on the next event
+        install_hook(extra, state);
+        match get_main_state(self.main_state) {
+            Some(main_state) if !ptr::eq(state, main_state) => {
+                // If main_state is different from state, remove hook from it too
+                install_hook(extra, main_state);
+            }
+            _ => {}
+        };
+        true
+    }
+
+    /// Starts the sampling profiler.
+    ///
+    /// The call stack is sampled every `interval_instructions` VM instructions using a hook set
+    /// with [`HookTriggers::every_nth_instruction`], and the wall time between samples is
+    /// attributed to the sampled stack. Time spent in Rust callbacks is measured separately on
+    /// entering and leaving every callback, so it is accounted to the callback frame.
+    ///
+    /// The profiler hook runs along with the other hooks (see [`Lua::add_hook`]) and samples
+    /// every thread: coroutines created after starting it and threads resumed from Rust are
+    /// sampled as well. Starting the profiler again discards the collected data.
+    ///
+    /// Call [`Lua::stop_profiler`] to get the results.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.start_profiler(1000);
+    /// lua.load("local n = 0 for i = 1, 100000 do n = n + i end").exec()?;
+    /// let profile = lua.stop_profiler().unwrap();
+    /// println!("{}", profile.to_folded());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub fn start_profiler(&self, interval_instructions: u32) {
+        self.stop_profiler();
+        let interval = interval_instructions.max(1);
+        let triggers = HookTriggers::new().every_nth_instruction(interval);
+        let hook = self.add_global_hook(triggers, |lua, _| {
+            profiler::mark(lua, 0, true);
+            Ok(())
+        });
+        let profiler = ProfilerState::new(interval, hook);
+        unsafe { (*self.extra.get()).profiler = Some(Box::new(profiler)) };
+    }
+
+    /// Stops the profiler started by [`Lua::start_profiler`] and returns the collected profile.
+    ///
+    /// Removes the profiler hook. Returns `None` if the profiler is not running.
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub fn stop_profiler(&self) -> Option<Profile> {
+        let profiler = unsafe { (*self.extra.get()).profiler.take()? };
+        self.remove_hook_by_id(profiler.hook);
+        Some(profiler.finish())
+    }
+
+    #[cfg(not(feature = "luau"))]
+    pub(crate) fn with_profiler<R>(&self, f: impl FnOnce(&mut ProfilerState) -> R) -> Option<R> {
+        unsafe { (*self.extra.get()).profiler.as_deref_mut().map(f) }
+    }
+
+    /// Starts collecting line coverage of the chunks whose names are accepted by `filter`.
+    ///
+    /// Hits are counted using a hook set with [`HookTriggers::on_calls`] and
+    /// [`HookTriggers::every_line`]. The filter receives the chunk name without the `@` or `=`
+    /// prefix (usually the script path) and is called once per chunk.
+    ///
+    /// Like the profiler, the coverage hook runs along with the other hooks and covers every
+    /// thread: coroutines created after starting it and threads resumed from Rust are covered as
+    /// well. Starting the coverage again discards the collected data.
+    ///
+    /// Call [`Lua::stop_coverage`] to get the results.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result};
+    /// # fn main() -> Result<()> {
+    /// let lua = Lua::new();
+    /// lua.start_coverage(|chunk| chunk.starts_with("scripts/"));
+    /// lua.load("local x = 1\nreturn x").set_name("@scripts/test.lua").exec()?;
+    /// let coverage = lua.stop_coverage().unwrap();
+    /// assert_eq!(coverage.hits("scripts/test.lua", 2), Some(1));
+    /// println!("{}", coverage.to_lcov());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub fn start_coverage<F>(&self, filter: F)
     where
-        F: Fn(&Lua, Debug) -> Result<()> + MaybeSend + 'static,
+        F: Fn(&str) -> bool + MaybeSend + 'static,
     {
-        unsafe { self.set_thread_hook(self.state(), triggers, callback) };
+        self.stop_coverage();
+        let triggers = HookTriggers::new().on_calls().every_line();
+        let hook = self.add_global_hook(triggers, move |lua, debug| {
+            coverage::hook(lua, debug, &filter)
+        });
+        unsafe { (*self.extra.get()).coverage = Some(Box::new(CoverageState::new(hook))) };
     }
 
-    /// Sets a 'hook' function for a thread (coroutine).
+    /// Stops collecting coverage started by [`Lua::start_coverage`] and returns the results.
+    ///
+    /// Removes the coverage hook. Returns `None` if the coverage is not being collected.
     #[cfg(not(feature = "luau"))]
-    pub(crate) unsafe fn set_thread_hook<F>(
-        &self,
-        state: *mut ffi::lua_State,
-        triggers: HookTriggers,
-        callback: F,
-    ) where
-        F: Fn(&Lua, Debug) -> Result<()> + MaybeSend + 'static,
-    {
-        unsafe extern "C-unwind" fn hook_proc(state: *mut ffi::lua_State, ar: *mut ffi::lua_Debug) {
-            let extra = extra_data(state);
-            if (*extra).hook_thread != state {
-                // Hook was destined for a different thread, ignore
-                ffi::lua_sethook(state, None, 0, 0);
-                return;
-            }
-            callback_error_ext(state, extra, move |_| {
-                let hook_cb = (*extra).hook_callback.clone();
-                let hook_cb = mlua_expect!(hook_cb, "no hook callback set in hook_proc");
-                if Arc::strong_count(&hook_cb) > 2 {
-                    return Ok(()); // Don't allow recursion
-                }
-                let lua: &Lua = mem::transmute((*extra).inner.assume_init_ref());
-                let _guard = StateGuard::new(&lua.0, state);
-                let debug = Debug::new(lua, ar);
-                hook_cb(lua, debug)
-            })
-        }
-
-        (*self.extra.get()).hook_callback = Some(Arc::new(callback));
-        (*self.extra.get()).hook_thread = state; // Mark for what thread the hook is set
-        ffi::lua_sethook(state, Some(hook_proc), triggers.mask(), triggers.count());
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub fn stop_coverage(&self) -> Option<LineCoverage> {
+        let coverage = unsafe { (*self.extra.get()).coverage.take()? };
+        self.remove_hook_by_id(coverage.hook);
+        Some(coverage.finish())
     }
 
-    /// Removes any hook previously set by [`Lua::set_hook()`] or [`Thread::set_hook()`].
-    ///
-    /// This function has no effect if a hook was not previously set.
     #[cfg(not(feature = "luau"))]
-    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
-    pub fn remove_hook(&self) {
-        unsafe {
-            let state = self.state();
-            ffi::lua_sethook(state, None, 0, 0);
-            match get_main_state(self.main_state) {
-                Some(main_state) if !ptr::eq(state, main_state) => {
-                    // If main_state is different from state, remove hook from it too
-                    ffi::lua_sethook(main_state, None, 0, 0);
-                }
-                _ => {}
-            };
-            (*self.extra.get()).hook_callback = None;
-            (*self.extra.get()).hook_thread = ptr::null_mut();
-        }
+    pub(crate) fn with_coverage<R>(&self, f: impl FnOnce(&mut CoverageState) -> R) -> Option<R> {
+        unsafe { (*self.extra.get()).coverage.as_deref_mut().map(f) }
     }
 
-    /// Sets an 'interrupt' function that will periodically be called by Luau VM.
+    /// Sets an 'interrupt' function that will periodically be called as Lua code executes.
     ///
-    /// Any Luau code is guaranteed to call this handler "eventually"
-    /// (in practice this can happen at any function call or at any loop iteration).
+    /// On Luau the native VM interrupt is used, and any Luau code is guaranteed to call this
+    /// handler "eventually" (in practice this can happen at any function call or at any loop
+    /// iteration). On other backends the handler is called every 1000 VM instructions by a count
+    /// hook in the current and the main thread (and in coroutines created from them). The hook
+    /// is combined with the one set by [`Lua::set_hook`], so both can be used at the same time.
     ///
     /// The provided interrupt function can error, and this error will be propagated through
-    /// the Luau code that was executing at the time the interrupt was triggered.
-    /// Also this can be used to implement continuous execution limits by instructing Luau VM to yield
-    /// by returning [`VmState::Yield`].
+    /// the Lua code that was executing at the time the interrupt was triggered.
+    /// Also this can be used to implement continuous execution limits by instructing Lua VM to yield
+    /// by returning [`VmState::Yield`]. Yielding from an interrupt requires Luau or Lua 5.2+,
+    /// otherwise it raises an error.
     ///
     /// This is similar to [`Lua::set_hook`] but in more simplified form.
     ///
     /// # Example
     ///
-    /// Periodically yield Luau VM to suspend execution.
+    /// Stop a runaway script.
     ///
     /// ```
     /// # use std::sync::{Arc, atomic::{AtomicU64, Ordering}};
-    /// # use mlua::{Lua, Result, ThreadStatus, VmState};
+    /// # use mlua::{Error, Lua, Result, VmState};
     /// # fn main() -> Result<()> {
     /// let lua = Lua::new();
     /// let count = Arc::new(AtomicU64::new(0));
     /// lua.set_interrupt(move |_| {
-    ///     if count.fetch_add(1, Ordering::Relaxed) % 2 == 0 {
-    ///         return Ok(VmState::Yield);
+    ///     if count.fetch_add(1, Ordering::Relaxed) >= 100 {
+    ///         return Err(Error::runtime("script is running for too long"));
     ///     }
     ///     Ok(VmState::Continue)
     /// });
     ///
-    /// let co = lua.create_thread(
-    ///     lua.load(r#"
-    ///         local b = 0
-    ///         for _, x in ipairs({1, 2, 3}) do b += x end
-    ///     "#)
-    ///     .into_function()?,
-    /// )?;
-    /// while co.status() == ThreadStatus::Resumable {
-    ///     co.resume(())?;
-    /// }
+    /// assert!(lua.load("while true do end").exec().is_err());
     /// # Ok(())
     /// # }
     /// ```
-    #[cfg(any(feature = "luau", docsrs))]
-    #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
     pub fn set_interrupt<F>(&self, callback: F)
     where
         F: Fn(&Lua) -> Result<VmState> + MaybeSend + 'static,
     {
-        unsafe extern "C-unwind" fn interrupt_proc(state: *mut ffi::lua_State, gc: c_int) {
-            if gc >= 0 {
-                // We don't support GC interrupts since they cannot survive Lua exceptions
-                return;
-            }
-            let extra = extra_data(state);
-            let result = callback_error_ext(state, extra, move |_| {
-                let interrupt_cb = (*extra).interrupt_callback.clone();
-                let interrupt_cb =
-                    mlua_expect!(interrupt_cb, "no interrupt callback set in interrupt_proc");
-                if Arc::strong_count(&interrupt_cb) > 2 {
-                    return Ok(VmState::Continue); // Don't allow recursion
-                }
-                let lua: &Lua = mem::transmute((*extra).inner.assume_init_ref());
-                let _guard = StateGuard::new(&lua.0, state);
-                interrupt_cb(lua)
-            });
-            match result {
-                VmState::Continue => {}
-                VmState::Yield => {
-                    ffi::lua_yield(state, 0);
-                }
-            }
-        }
-
         unsafe {
             (*self.extra.get()).interrupt_callback = Some(Arc::new(callback));
-            (*ffi::lua_callbacks(self.main_state)).interrupt = Some(interrupt_proc);
+            self.install_interrupt();
         }
     }
 
     /// Removes any 'interrupt' previously set by `set_interrupt`.
     ///
     /// This function has no effect if an 'interrupt' was not previously set.
-    #[cfg(any(feature = "luau", docsrs))]
-    #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
     pub fn remove_interrupt(&self) {
         unsafe {
             (*self.extra.get()).interrupt_callback = None;
-            (*ffi::lua_callbacks(self.main_state)).interrupt = None;
+            self.install_interrupt();
+        }
+    }
+
+    // Installs (or removes) the interrupt according to the interrupt callback and the budget
+    unsafe fn install_interrupt(&self) {
+        let extra = self.extra.get();
+        #[cfg(feature = "luau")]
+        {
+            let active = (*extra).interrupt_callback.is_some() || (*extra).budget.is_some();
+            (*ffi::lua_callbacks(self.main_state)).interrupt = match active {
+                true => Some(interrupt_proc),
+                false => None,
+            };
+        }
+        #[cfg(not(feature = "luau"))]
+        {
+            (*extra).interrupt_count = 0;
+            let state = self.state();
+            install_hook(extra, state);
+            match get_main_state(self.main_state) {
+                Some(main_state) if !ptr::eq(state, main_state) => install_hook(extra, main_state),
+                _ => {}
+            };
+        }
+    }
+
+    // Starts a budget of `Function::call_with_budget`, limited by the enclosing budget (if any)
+    pub(crate) fn enter_budget(&self, budget: Budget) -> BudgetGuard {
+        unsafe {
+            let extra = self.extra.get();
+            let outer = (*extra).budget.take();
+            (*extra).budget = Some(BudgetState::new(budget, outer.as_ref()));
+            self.install_interrupt();
+            BudgetGuard {
+                lua: self,
+                outer: Some(outer),
+            }
         }
     }
 
@@ -1082,10 +1742,24 @@ impl Lua {
         }
     }
 
+    /// Enables or disables attaching details to runtime and callback errors.
+    ///
+    /// When enabled, such errors are wrapped into [`Error::WithDetails`] carrying the Lua call stack
+    /// and the original error value if it is not a string, available via [`Error::traceback`] and
+    /// [`Error::value`]. The text traceback is kept in the error message as usual.
+    ///
+    /// Default: **false**
+    pub fn set_error_details(&self, enabled: bool) {
+        unsafe { (*self.extra.get()).error_details = enabled };
+    }
+
     /// Returns the amount of memory (in bytes) currently used inside this Lua state.
+    ///
+    /// If the allocator was wrapped using [`Lua::wrap_allocator`], returns the amount of memory
+    /// allocated since wrapping it instead.
     pub fn used_memory(&self) -> usize {
         unsafe {
-            match MemoryState::get(self.main_state) {
+            match self.memory_state() {
                 mem_state if !mem_state.is_null() => (*mem_state).used_memory(),
                 _ => {
                     // Get data from the Lua GC
@@ -1103,16 +1777,86 @@ impl Lua {
     /// a `Error::MemoryError` is generated instead.
     /// Returns previous limit (zero means no limit).
     ///
-    /// Does not work in module mode where Lua state is managed externally.
+    /// Does not work in module mode where Lua state is managed externally, unless the allocator
+    /// was wrapped using [`Lua::wrap_allocator`].
     pub fn set_memory_limit(&self, limit: usize) -> Result<usize> {
         unsafe {
-            match MemoryState::get(self.main_state) {
+            match self.memory_state() {
                 mem_state if !mem_state.is_null() => Ok((*mem_state).set_memory_limit(limit)),
                 _ => Err(Error::MemoryLimitNotAvailable),
             }
         }
     }
 
+    /// Wraps the allocator of a Lua state initialized by [`Lua::init_from_ptr`] to track the
+    /// memory used by this module.
+    ///
+    /// The new allocator forwards all requests to the original one and accounts every allocation
+    /// made by the state while it is installed, including the ones made by Lua functions that the
+    /// host calls directly (such as event handlers). [`Lua::used_memory`] returns their total and
+    /// [`Lua::set_memory_limit`] can be used to stop runaway scripts with `Error::MemoryError`.
+    /// The limit is soft: freeing memory allocated before wrapping is subtracted too, but the
+    /// total never goes below zero.
+    ///
+    /// Several modules can wrap the allocator of the same state, each with its own total and limit.
+    /// Calling this function again is a no-op.
+    ///
+    /// The original allocator is put back when the module data is finalized while the Lua state
+    /// is closed, which happens before the C library of the module is unloaded.
+    /// [`Lua::restore_allocator`] can be used to put it back earlier.
+    ///
+    /// # Safety
+    /// The allocator is replaced without the knowledge of the host, which must not change or
+    /// rely on it while the module is loaded.
+    ///
+    /// Requires `feature = "lua54/lua53/lua52/lua51/luajit"`
+    #[cfg(any(not(feature = "luau"), doc))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub unsafe fn wrap_allocator(&self) -> Result<()> {
+        let extra = &mut *self.extra.get();
+        if !extra.chained_mem_state.is_null() && (*extra.chained_mem_state).is_chained() {
+            return Ok(());
+        }
+        if !MemoryState::get(self.main_state).is_null() {
+            return Err(Error::runtime(
+                "memory of this Lua state is already tracked",
+            ));
+        }
+        // Reuse the state left after restoring the allocator (it can still be used by active calls)
+        if extra.chained_mem_state.is_null() {
+            extra.chained_mem_state = Box::into_raw(Box::default());
+        }
+        MemoryState::install_chained(self.main_state, extra.chained_mem_state);
+        Ok(())
+    }
+
+    /// Restores the original allocator replaced by [`Lua::wrap_allocator`] before the Lua state
+    /// is closed.
+    ///
+    /// Fails if another allocator was installed on top of ours later (for example by another
+    /// module), in which case the module must stay loaded. Modules have to restore allocators
+    /// in reverse order of wrapping.
+    ///
+    /// # Safety
+    /// Must not be called while the host relies on the current allocator.
+    ///
+    /// Requires `feature = "lua54/lua53/lua52/lua51/luajit"`
+    #[cfg(any(not(feature = "luau"), doc))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub unsafe fn restore_allocator(&self) -> Result<()> {
+        let mem_state = (*self.extra.get()).chained_mem_state;
+        if mem_state.is_null() || !(*mem_state).is_chained() {
+            return Ok(());
+        }
+        // The state is kept until the Lua state is closed as it may be used by active calls
+        if !MemoryState::restore_chained(self.main_state, mem_state) {
+            return Err(Error::runtime(
+                "cannot restore allocator: another allocator was installed on top of it",
+            ));
+        }
+        Ok(())
+    }
+
     /// Returns true if the garbage collector is currently running automatically.
     ///
     /// Requires `feature = "lua54/lua53/lua52/luau"`
@@ -1335,6 +2079,11 @@ impl Lua {
                 mode_str,
             ) {
                 ffi::LUA_OK => {
+                    #[cfg(not(feature = "luau"))]
+                    let env = match env {
+                        Some(env) => Some(env),
+                        None => self.chunk_sandbox_env()?,
+                    };
                     if let Some(env) = env {
                         self.push_ref(&env.0);
                         #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
@@ -1350,7 +2099,15 @@ impl Lua {
 
                     Ok(Function(self.pop_ref()))
                 }
-                err => Err(pop_error(state, err)),
+                err => {
+                    #[cfg(not(feature = "luau"))]
+                    let is_binary = source.starts_with(ffi::LUA_SIGNATURE);
+                    #[cfg(feature = "luau")]
+                    let is_binary = *source.first().unwrap_or(&u8::MAX) < b'\n';
+                    let name = name.map(|name| name.to_string_lossy());
+                    let source = (!is_binary).then_some(source);
+                    Err(pop_error(state, err).with_chunk_source(name.as_deref(), source))
+                }
             }
         }
     }
@@ -1373,6 +2130,17 @@ impl Lua {
         }
     }
 
+    /// Creates a [`StringBuilder`] to efficiently build a large string from many chunks,
+    /// appended from both Rust and Lua.
+    pub fn create_string_builder(&self) -> Result<StringBuilder> {
+        StringBuilder::new(self, 0)
+    }
+
+    /// Creates a [`StringBuilder`] with at least the specified capacity (in bytes).
+    pub fn create_string_builder_with_capacity(&self, capacity: usize) -> Result<StringBuilder> {
+        StringBuilder::new(self, capacity)
+    }
+
     /// Create and return a Luau [buffer] object from a byte slice of data.
     ///
     /// Requires `feature = "luau"`
@@ -1399,6 +2167,42 @@ impl Lua {
         self.create_table_with_capacity(0, 0)
     }
 
+    /// Creates a HavokScript `ui64` (64-bit unsigned integer) value.
+    ///
+    /// Returns [`Error::Ui64NotAvailable`] if the runtime does not support `ui64` values, or if
+    /// neither the `civ6-ui64` nor the `civ6-dynamic` feature is enabled.
+    #[cfg(any(feature = "lua51_civ6", doc))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "lua51_civ6")))]
+    pub fn create_ui64(&self, n: u64) -> Result<Value> {
+        #[cfg(not(any(feature = "civ6-ui64", feature = "civ6-dynamic")))]
+        {
+            let _ = n;
+            Err(Error::Ui64NotAvailable)
+        }
+        #[cfg(any(feature = "civ6-ui64", feature = "civ6-dynamic"))]
+        self.push_ui64(n)
+    }
+
+    #[cfg(any(feature = "civ6-ui64", feature = "civ6-dynamic"))]
+    fn push_ui64(&self, n: u64) -> Result<Value> {
+        #[cfg(feature = "civ6-dynamic")]
+        if !ffi::dynamic::lua_ui64_supported() {
+            return Err(Error::Ui64NotAvailable);
+        }
+
+        let state = self.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 3)?;
+            if self.unlikely_memory_error() {
+                ffi::lua_pushui64(state, n);
+            } else {
+                protect_lua!(state, 0, 1, |state| ffi::lua_pushui64(state, n))?;
+            }
+            Ok(self.pop_value())
+        }
+    }
+
     /// Creates and returns a new empty table, with the specified capacity.
     /// `narr` is a hint for how many elements the table will have as a sequence;
     /// `nrec` is a hint for how many other elements the table will have.
@@ -2349,6 +3153,8 @@ impl Lua {
             Value::Function(f) => self.push_ref(&f.0),
             Value::Thread(t) => self.push_ref(&t.0),
             Value::UserData(ud) => self.push_ref(&ud.0),
+            #[cfg(feature = "lua51_civ6")]
+            Value::Other(v) => self.push_ref(&v.0),
             Value::Error(err) => {
                 let protect = !self.unlikely_memory_error();
                 push_gc_userdata(state, WrappedFailure::Error(err.clone()), protect)?;
@@ -2463,7 +3269,17 @@ impl Lua {
                 Value::UserData(AnyUserData(self.pop_ref(), SubtypeId::CData))
             }
 
-            _ => mlua_panic!("LUA_TNONE in pop_value"),
+            #[cfg(feature = "lua51_civ6")]
+            ffi::LUA_TIFUNCTION | ffi::LUA_TCFUNCTION => Value::Function(Function(self.pop_ref())),
+
+            ffi::LUA_TNONE => mlua_panic!("LUA_TNONE in pop_value"),
+
+            // Runtime specific types (eg. HavokScript ui64) are kept as opaque references
+            #[cfg(feature = "lua51_civ6")]
+            tag => Value::Other(OtherValue(self.pop_ref(), tag)),
+
+            #[cfg(not(feature = "lua51_civ6"))]
+            _ => mlua_panic!("unknown value type in pop_value"),
         }
     }
 
@@ -2569,7 +3385,23 @@ impl Lua {
                 Value::UserData(AnyUserData(self.pop_ref_thread(), SubtypeId::CData))
             }
 
-            _ => mlua_panic!("LUA_TNONE in pop_value"),
+            #[cfg(feature = "lua51_civ6")]
+            ffi::LUA_TIFUNCTION | ffi::LUA_TCFUNCTION => {
+                ffi::lua_xpush(state, self.ref_thread(), idx);
+                Value::Function(Function(self.pop_ref_thread()))
+            }
+
+            ffi::LUA_TNONE => mlua_panic!("LUA_TNONE in stack_value"),
+
+            // Runtime specific types (eg. HavokScript ui64) are kept as opaque references
+            #[cfg(feature = "lua51_civ6")]
+            tag => {
+                ffi::lua_xpush(state, self.ref_thread(), idx);
+                Value::Other(OtherValue(self.pop_ref_thread(), tag))
+            }
+
+            #[cfg(not(feature = "lua51_civ6"))]
+            _ => mlua_panic!("unknown value type in stack_value"),
         }
     }
 
@@ -2912,6 +3744,8 @@ impl Lua {
 
                 let lua: &Lua = mem::transmute((*extra).inner.assume_init_ref());
                 let _guard = StateGuard::new(&lua.0, state);
+                #[cfg(not(feature = "luau"))]
+                let _profile = profiler::CallbackGuard::new(lua);
                 let func = &*(*upvalue).data;
 
                 func(lua, nargs)
@@ -3217,10 +4051,23 @@ impl Lua {
         Some(Lua(Arc::clone((*extra).inner.assume_init_ref())))
     }
 
+    // Returns the memory state of our chained (if installed) or own allocator
+    #[inline]
+    pub(crate) unsafe fn memory_state(&self) -> *mut MemoryState {
+        #[cfg(not(feature = "luau"))]
+        {
+            let chained = (*self.extra.get()).chained_mem_state;
+            if !chained.is_null() && (*chained).is_chained() {
+                return chained;
+            }
+        }
+        MemoryState::get(self.main_state)
+    }
+
     #[inline]
     pub(crate) unsafe fn unlikely_memory_error(&self) -> bool {
         // MemoryInfo is empty in module mode so we cannot predict memory limits
-        match MemoryState::get(self.main_state) {
+        match self.memory_state() {
             mem_state if !mem_state.is_null() => (*mem_state).memory_limit() == 0,
             #[cfg(feature = "module")]
             _ => (*self.extra.get()).skip_memory_check, // Check the special flag (only for module mode)
@@ -3275,6 +4122,21 @@ impl ExtraData {
     // Index of `error_traceback` function in auxiliary thread stack
     #[cfg(any(feature = "lua51", feature = "luajit", feature = "luau"))]
     const ERROR_TRACEBACK_IDX: c_int = 1;
+
+    // Restores the host allocator (if possible) and frees the chained allocator state.
+    // If another allocator was installed on top of ours, the state is leaked as it may still
+    // be called through.
+    #[cfg(not(feature = "luau"))]
+    unsafe fn release_chained_allocator(&mut self, state: *mut ffi::lua_State) {
+        let mem_state = self.chained_mem_state;
+        if mem_state.is_null() {
+            return;
+        }
+        if !(*mem_state).is_chained() || MemoryState::restore_chained(state, mem_state) {
+            drop(Box::from_raw(mem_state));
+        }
+        self.chained_mem_state = ptr::null_mut();
+    }
 }
 
 struct StateGuard<'a>(&'a LuaInner, *mut ffi::lua_State);
@@ -3299,7 +4161,7 @@ unsafe fn extra_data(state: *mut ffi::lua_State) -> *mut ExtraData {
         return (*ffi::lua_callbacks(state)).userdata as *mut _;
     }
 
-    let extra_key = &EXTRA_REGISTRY_KEY as *const u8 as *const c_void;
+    let extra_key = &REGISTRY_KEYS.extra as *const u8 as *const c_void;
     if ffi::lua_rawgetp(state, ffi::LUA_REGISTRYINDEX, extra_key) != ffi::LUA_TUSERDATA {
         // `ExtraData` can be null only when Lua state is foreign.
         // This case in used in `Lua::try_from_ptr()`.
@@ -3311,6 +4173,38 @@ unsafe fn extra_data(state: *mut ffi::lua_State) -> *mut ExtraData {
     (*extra_ptr).get()
 }
 
+// Checks if details should be attached to the Lua errors.
+// Uses 1 stack space, does not call checkstack.
+pub(crate) unsafe fn error_details(state: *mut ffi::lua_State) -> bool {
+    let extra = extra_data(state);
+    !extra.is_null() && (*extra).error_details
+}
+
+// Moves the value at the top of the stack to the registry.
+// Returns `None` if the Lua state is foreign. Can raise a memory error.
+// Uses 1 extra stack space, does not call checkstack.
+pub(crate) unsafe fn ref_error_value(state: *mut ffi::lua_State) -> Option<RegistryKey> {
+    let extra = extra_data(state);
+    if extra.is_null() {
+        ffi::lua_pop(state, 1);
+        return None;
+    }
+
+    // Try to reuse previously allocated slot
+    let free_registry_id = mlua_expect!((*extra).registry_unref_list.lock(), "unref list poisoned")
+        .as_mut()
+        .and_then(|x| x.pop());
+    let registry_id = match free_registry_id {
+        Some(registry_id) => {
+            ffi::lua_rawseti(state, ffi::LUA_REGISTRYINDEX, registry_id as Integer);
+            registry_id
+        }
+        None => ffi::luaL_ref(state, ffi::LUA_REGISTRYINDEX),
+    };
+    let unref_list = (*extra).registry_unref_list.clone();
+    Some(RegistryKey::new(registry_id, unref_list))
+}
+
 unsafe fn set_extra_data(
     state: *mut ffi::lua_State,
     extra: &Arc<UnsafeCell<ExtraData>>,
@@ -3323,7 +4217,7 @@ unsafe fn set_extra_data(
 
     push_gc_userdata(state, Arc::clone(extra), true)?;
     protect_lua!(state, 1, 0, fn(state) {
-        let extra_key = &EXTRA_REGISTRY_KEY as *const u8 as *const c_void;
+        let extra_key = &REGISTRY_KEYS.extra as *const u8 as *const c_void;
         ffi::lua_rawsetp(state, ffi::LUA_REGISTRYINDEX, extra_key);
     })
 }
@@ -3343,6 +4237,307 @@ pub(crate) fn init_metatable_cache(cache: &mut FxHashMap<TypeId, u8>) {
     }
 }
 
+/// Restores the enclosing budget when a call with a budget finishes.
+pub(crate) struct BudgetGuard<'a> {
+    lua: &'a Lua,
+    outer: Option<Option<BudgetState>>,
+}
+
+impl<'a> BudgetGuard<'a> {
+    // Returns `true` if the budget was exceeded
+    pub(crate) fn finish(&mut self) -> bool {
+        let mut outer = match self.outer.take() {
+            Some(outer) => outer,
+            None => return false,
+        };
+        unsafe {
+            let extra = self.lua.extra.get();
+            let inner = (*extra).budget.take();
+            if let (Some(outer), Some(inner)) = (outer.as_mut(), inner.as_ref()) {
+                outer.finish_nested(inner);
+            }
+            (*extra).budget = outer;
+            self.lua.install_interrupt();
+            inner.map(|inner| inner.is_exceeded()).unwrap_or(false)
+        }
+    }
+}
+
+impl<'a> Drop for BudgetGuard<'a> {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+// Id of the hook set by `Lua::set_hook` or `Thread::set_hook`
+#[cfg(not(feature = "luau"))]
+const SET_HOOK_ID: HookId = HookId(0);
+
+// Number of VM instructions between calls of the interrupt emulated by the count hook
+#[cfg(not(feature = "luau"))]
+const INTERRUPT_INTERVAL: u32 = 1000;
+
+#[cfg(not(feature = "luau"))]
+fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+// Records a coroutine with hooks in a weak table (keyed by the hook id), so the hooks can be
+// dropped once the coroutine is collected and the address of its `lua_State` is reused.
+// The main thread is never collected and is not tracked.
+// Uses 3 stack spaces, returns `false` if the thread is not tracked.
+#[cfg(not(feature = "luau"))]
+unsafe fn track_hook_thread(
+    state: *mut ffi::lua_State,
+    thread: *mut ffi::lua_State,
+    id: HookId,
+) -> bool {
+    if matches!(get_main_state(state), Some(main_state) if ptr::eq(thread, main_state)) {
+        return false;
+    }
+    if ffi::lua_checkstack(state, 3) == 0 || ffi::lua_checkstack(thread, 1) == 0 {
+        return false;
+    }
+    ffi::lua_pushthread(thread);
+    ffi::lua_xmove(thread, state, 1);
+    protect_lua!(state, 1, 0, |state| {
+        let key = &REGISTRY_KEYS.hook_threads as *const u8 as *const c_void;
+        if ffi::lua_rawgetp(state, ffi::LUA_REGISTRYINDEX, key) != ffi::LUA_TTABLE {
+            ffi::lua_pop(state, 1);
+            ffi::lua_createtable(state, 0, 0);
+            ffi::lua_createtable(state, 0, 1);
+            ffi::lua_pushstring(state, cstr!("v"));
+            ffi::lua_setfield(state, -2, cstr!("__mode"));
+            ffi::lua_setmetatable(state, -2);
+            ffi::lua_pushvalue(state, -1);
+            ffi::lua_rawsetp(state, ffi::LUA_REGISTRYINDEX, key);
+        }
+        ffi::lua_pushnumber(state, id.0 as ffi::lua_Number);
+        ffi::lua_pushvalue(state, -3);
+        ffi::lua_rawset(state, -3);
+    })
+    .is_ok()
+}
+
+// Pushes the weak table of hook threads (or `nil`) onto the stack.
+#[cfg(not(feature = "luau"))]
+unsafe fn push_hook_threads(state: *mut ffi::lua_State) {
+    let key = &REGISTRY_KEYS.hook_threads as *const u8 as *const c_void;
+    ffi::lua_rawgetp(state, ffi::LUA_REGISTRYINDEX, key);
+}
+
+// Removes the hooks of collected coroutines.
+// If `thread` is given, checks only the hooks of that thread.
+// Uses 2 stack spaces.
+#[cfg(not(feature = "luau"))]
+unsafe fn prune_hooks(
+    extra: *mut ExtraData,
+    state: *mut ffi::lua_State,
+    thread: Option<*mut ffi::lua_State>,
+) {
+    let hooks = &mut (*extra).hooks;
+    let is_checked =
+        |hook: &HookEntry| hook.tracked && thread.map_or(true, |t| ptr::eq(hook.thread, t));
+    if !hooks.iter().any(is_checked) || ffi::lua_checkstack(state, 2) == 0 {
+        return;
+    }
+    push_hook_threads(state);
+    hooks.retain(|hook| {
+        if !is_checked(hook) {
+            return true;
+        }
+        ffi::lua_pushnumber(state, hook.id.0 as ffi::lua_Number);
+        ffi::lua_rawget(state, -2);
+        let alive = ptr::eq(ffi::lua_tothread(state, -1), hook.thread);
+        ffi::lua_pop(state, 1);
+        alive
+    });
+    ffi::lua_pop(state, 1);
+}
+
+// Combined triggers of the hooks, the interrupt and the budget for the thread
+#[cfg(not(feature = "luau"))]
+unsafe fn hook_triggers(extra: *const ExtraData, state: *mut ffi::lua_State) -> HookTriggers {
+    let mut triggers = HookTriggers::new();
+    for hook in (*extra)
+        .hooks
+        .iter()
+        .filter(|hook| hook.thread.is_null() || ptr::eq(hook.thread, state))
+    {
+        let every_nth_instruction = triggers.every_nth_instruction;
+        triggers |= hook.triggers;
+        // The count hook is shared, each hook keeps its own counter
+        if let (Some(a), Some(b)) = (every_nth_instruction, hook.triggers.every_nth_instruction) {
+            triggers.every_nth_instruction = Some(gcd(a, b));
+        }
+    }
+    if matches!(&(*extra).budget, Some(budget) if budget.is_exceeded()) {
+        // Stop the code as soon as possible, including the calls of `pcall`
+        triggers.on_calls = true;
+        triggers.every_nth_instruction = Some(1);
+        return triggers;
+    }
+    let mut interval = None;
+    if (*extra).interrupt_callback.is_some() {
+        interval = Some(INTERRUPT_INTERVAL);
+    }
+    if let Some(budget) = &(*extra).budget {
+        interval = Some(budget.interval(interval.unwrap_or(INTERRUPT_INTERVAL)));
+    }
+    if let Some(interval) = interval {
+        let n = triggers.every_nth_instruction.unwrap_or(0);
+        triggers = triggers.every_nth_instruction(gcd(interval, n));
+    }
+    triggers
+}
+
+// Mask that enables the hook event
+#[cfg(not(feature = "luau"))]
+fn event_mask(event: c_int) -> c_int {
+    match event {
+        #[cfg(any(feature = "lua51", feature = "luajit"))]
+        ffi::LUA_HOOKTAILCALL => ffi::LUA_MASKRET,
+        #[cfg(not(any(feature = "lua51", feature = "luajit")))]
+        ffi::LUA_HOOKTAILCALL => ffi::LUA_MASKCALL,
+        event => 1 << event,
+    }
+}
+
+#[cfg(not(feature = "luau"))]
+unsafe fn install_hook(extra: *const ExtraData, state: *mut ffi::lua_State) {
+    let triggers = hook_triggers(extra, state);
+    match triggers.mask() {
+        0 => {
+            ffi::lua_sethook(state, None, 0, 0);
+        }
+        mask => {
+            ffi::lua_sethook(state, Some(hook_proc), mask, triggers.count());
+        }
+    }
+}
+
+#[cfg(not(feature = "luau"))]
+unsafe extern "C-unwind" fn hook_proc(state: *mut ffi::lua_State, ar: *mut ffi::lua_Debug) {
+    let extra = extra_data(state);
+    // The hooks of a collected coroutine must not be inherited by a new one at the same address
+    prune_hooks(extra, state, Some(state));
+    let triggers = hook_triggers(extra, state);
+    let count = ffi::lua_gethookcount(state).max(0) as u32;
+    if triggers.mask() != ffi::lua_gethookmask(state) || triggers.count() != count as c_int {
+        // The hook was set for a different configuration (or inherited by a coroutine)
+        install_hook(extra, state);
+        if triggers.mask() == 0 {
+            return;
+        }
+    }
+
+    let result = callback_error_ext(state, extra, move |_| {
+        let lua: &Lua = mem::transmute((*extra).inner.assume_init_ref());
+        let _guard = StateGuard::new(&lua.0, state);
+        let event = (*ar).event;
+        let mut vm_state = VmState::Continue;
+
+        if let Some(budget) = (*extra).budget.as_mut() {
+            let exceeded = budget.is_exceeded();
+            if exceeded || (event == ffi::LUA_HOOKCOUNT && budget.consume(count as u64).is_err()) {
+                if !exceeded {
+                    install_hook(extra, state);
+                }
+                return Err(Error::BudgetExceeded);
+            }
+        }
+
+        if event == ffi::LUA_HOOKCOUNT {
+            if let Some(interrupt_cb) = (*extra).interrupt_callback.clone() {
+                (*extra).interrupt_count += count;
+                // Don't allow recursion
+                if (*extra).interrupt_count >= INTERRUPT_INTERVAL
+                    && Arc::strong_count(&interrupt_cb) <= 2
+                {
+                    (*extra).interrupt_count = 0;
+                    vm_state = interrupt_cb(lua)?;
+                }
+            }
+        }
+
+        // Dispatch the event to the hooks of the thread that requested it
+        let mask = event_mask(event);
+        let mut callbacks = Vec::new();
+        for hook in (*extra).hooks.iter_mut() {
+            let for_thread = hook.thread.is_null() || ptr::eq(hook.thread, state);
+            if !for_thread || hook.triggers.mask() & mask == 0 {
+                continue;
+            }
+            if event == ffi::LUA_HOOKCOUNT {
+                hook.count += count;
+                if hook.count < hook.triggers.every_nth_instruction.unwrap_or(0) {
+                    continue;
+                }
+                hook.count = 0;
+            }
+            callbacks.push((hook.id, hook.callback.clone()));
+        }
+        for (id, hook_cb) in callbacks {
+            // Skip the hooks removed or replaced by the previous ones
+            let registered = ((*extra).hooks.iter())
+                .any(|hook| hook.id == id && Arc::ptr_eq(&hook.callback, &hook_cb));
+            // Don't allow recursion
+            if registered && Arc::strong_count(&hook_cb) <= 2 {
+                hook_cb(lua, Debug::new(lua, ar))?;
+            }
+        }
+
+        #[cfg(not(any(feature = "lua54", feature = "lua53", feature = "lua52")))]
+        if vm_state == VmState::Yield {
+            return Err(Error::runtime(
+                "yielding from an interrupt requires Lua 5.2 or later",
+            ));
+        }
+        Ok(vm_state)
+    });
+
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+    if result == VmState::Yield {
+        ffi::lua_yield(state, 0);
+    }
+    #[cfg(not(any(feature = "lua54", feature = "lua53", feature = "lua52")))]
+    let _ = result;
+}
+
+#[cfg(feature = "luau")]
+unsafe extern "C-unwind" fn interrupt_proc(state: *mut ffi::lua_State, gc: c_int) {
+    if gc >= 0 {
+        // We don't support GC interrupts since they cannot survive Lua exceptions
+        return;
+    }
+    let extra = extra_data(state);
+    let result = callback_error_ext(state, extra, move |_| {
+        // Each interrupt check counts as an instruction
+        if let Some(budget) = (*extra).budget.as_mut() {
+            budget.consume(1)?;
+        }
+        let interrupt_cb = match (*extra).interrupt_callback.clone() {
+            Some(interrupt_cb) => interrupt_cb,
+            None => return Ok(VmState::Continue),
+        };
+        if Arc::strong_count(&interrupt_cb) > 2 {
+            return Ok(VmState::Continue); // Don't allow recursion
+        }
+        let lua: &Lua = mem::transmute((*extra).inner.assume_init_ref());
+        let _guard = StateGuard::new(&lua.0, state);
+        interrupt_cb(lua)
+    });
+    match result {
+        VmState::Continue => {}
+        VmState::Yield => {
+            ffi::lua_yield(state, 0);
+        }
+    }
+}
+
 // An optimized version of `callback_error` that does not allocate `WrappedFailure` userdata
 // and instead reuses unsed values from previous calls (or allocates new).
 unsafe fn callback_error_ext<F, R>(state: *mut ffi::lua_State, mut extra: *mut ExtraData, f: F) -> R
@@ -3439,6 +4634,18 @@ where
             r
         }
         Ok(Err(err)) => {
+            // Raise the original Lua error value again
+            let value_id = (err.value())
+                .filter(|key| Arc::ptr_eq(&key.unref_list, &(*extra).registry_unref_list))
+                .map(|key| key.registry_id);
+            if let Some(registry_id) = value_id {
+                prealloc_failure.release(state, extra);
+                ffi::lua_settop(state, 0);
+                ffi::lua_rawgeti(state, ffi::LUA_REGISTRYINDEX, registry_id as Integer);
+                drop(err);
+                ffi::lua_error(state)
+            }
+
             let wrapped_error = prealloc_failure.r#use(state, extra);
 
             // Build `CallbackError` with traceback
@@ -3451,10 +4658,15 @@ where
                 "<not enough stack space for traceback>".to_string()
             };
             let cause = Arc::new(err);
-            ptr::write(
-                wrapped_error,
-                WrappedFailure::Error(Error::CallbackError { traceback, cause }),
-            );
+            let mut error = Error::CallbackError { traceback, cause };
+            if (*extra).error_details {
+                error = Error::WithDetails {
+                    traceback: util::stack_frames(state, 0),
+                    value: None,
+                    cause: Arc::new(error),
+                };
+            }
+            ptr::write(wrapped_error, WrappedFailure::Error(error));
             get_gc_metatable::<WrappedFailure>(state);
             ffi::lua_setmetatable(state, -2);
 
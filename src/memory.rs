@@ -1,10 +1,20 @@
 use std::alloc::{self, Layout};
+#[cfg(not(feature = "luau"))]
+use std::cell::Cell;
 use std::os::raw::c_void;
 use std::ptr;
 
 pub(crate) static ALLOCATOR: ffi::lua_Alloc = allocator;
+#[cfg(not(feature = "luau"))]
+pub(crate) static CHAINED_ALLOCATOR: ffi::lua_Alloc = chained_allocator;
+
+#[cfg(not(feature = "luau"))]
+thread_local! {
+    // Used instead of `MemoryState::ignore_limit` when the chained allocator is not on top
+    // of the allocator chain and we cannot find its state from the Lua state.
+    static IGNORE_CHAINED_LIMIT: Cell<bool> = const { Cell::new(false) };
+}
 
-#[repr(C)]
 #[derive(Default)]
 pub(crate) struct MemoryState {
     used_memory: isize,
@@ -15,6 +25,10 @@ pub(crate) struct MemoryState {
     // Indicates that the memory limit was reached on the last allocation.
     #[cfg(feature = "luau")]
     limit_reached: bool,
+    // Allocator (and its userdata) of a foreign Lua state that we forward to.
+    // Set only for the chained allocator installed by `Lua::wrap_allocator`.
+    #[cfg(not(feature = "luau"))]
+    inner_alloc: Option<(ffi::lua_Alloc, *mut c_void)>,
 }
 
 impl MemoryState {
@@ -27,12 +41,49 @@ impl MemoryState {
             mlua_assert!(!mem_state.is_null(), "Luau state has no allocator userdata");
         }
         #[cfg(not(feature = "luau"))]
-        if ffi::lua_getallocf(state, &mut mem_state) != ALLOCATOR {
-            mem_state = ptr::null_mut();
+        {
+            // The userdata of an allocator installed by someone else (including other copies of
+            // mlua loaded as modules) is opaque, so we recognize our allocator by its address
+            let alloc = ffi::lua_getallocf(state, &mut mem_state);
+            if alloc as usize != ALLOCATOR as usize {
+                mem_state = ptr::null_mut();
+            }
         }
         mem_state as *mut MemoryState
     }
 
+    // Installs the chained allocator forwarding to the current allocator of `state`
+    #[cfg(not(feature = "luau"))]
+    pub(crate) unsafe fn install_chained(state: *mut ffi::lua_State, mem_state: *mut Self) {
+        let mut inner_ud = ptr::null_mut();
+        let inner_alloc = ffi::lua_getallocf(state, &mut inner_ud);
+        (*mem_state).inner_alloc = Some((inner_alloc, inner_ud));
+        ffi::lua_setallocf(state, CHAINED_ALLOCATOR, mem_state as *mut c_void);
+    }
+
+    // Puts back the allocator that the chained allocator forwards to.
+    // Returns `false` if another allocator was installed on top of ours.
+    #[cfg(not(feature = "luau"))]
+    pub(crate) unsafe fn restore_chained(state: *mut ffi::lua_State, mem_state: *mut Self) -> bool {
+        // Chained memory states are used only by the chained allocator
+        let mut current_ud = ptr::null_mut();
+        ffi::lua_getallocf(state, &mut current_ud);
+        if current_ud != mem_state as *mut c_void {
+            return false;
+        }
+        if let Some((inner_alloc, inner_ud)) = (*mem_state).inner_alloc.take() {
+            ffi::lua_setallocf(state, inner_alloc, inner_ud);
+        }
+        true
+    }
+
+    // Returns `true` if this is a chained allocator that is still in use
+    #[cfg(not(feature = "luau"))]
+    #[inline]
+    pub(crate) fn is_chained(&self) -> bool {
+        self.inner_alloc.is_some()
+    }
+
     #[inline]
     pub(crate) fn used_memory(&self) -> usize {
         self.used_memory as usize
@@ -61,7 +112,11 @@ impl MemoryState {
             f();
             (*mem_state).ignore_limit = false;
         } else {
+            #[cfg(not(feature = "luau"))]
+            IGNORE_CHAINED_LIMIT.with(|ignore| ignore.set(true));
             f();
+            #[cfg(not(feature = "luau"))]
+            IGNORE_CHAINED_LIMIT.with(|ignore| ignore.set(false));
         }
     }
 
@@ -145,3 +200,36 @@ unsafe extern "C-unwind" fn allocator(
     }
     new_ptr
 }
+
+// Forwards to the allocator of a foreign Lua state, accounting all memory allocated through it
+// and enforcing the memory limit.
+#[cfg(not(feature = "luau"))]
+unsafe extern "C-unwind" fn chained_allocator(
+    extra: *mut c_void,
+    ptr: *mut c_void,
+    osize: usize,
+    nsize: usize,
+) -> *mut c_void {
+    let mem_state = &mut *(extra as *mut MemoryState);
+    let (inner_alloc, inner_ud) = mlua_expect!(mem_state.inner_alloc, "no inner allocator");
+    let old_size = if ptr.is_null() { 0 } else { osize as isize };
+    let mem_diff = nsize as isize - old_size;
+
+    // Only growing allocations can fail, Lua assumes that shrinking always succeeds
+    let mem_limit = mem_state.memory_limit;
+    if mem_limit > 0
+        && mem_diff > 0
+        && mem_state.used_memory + mem_diff > mem_limit
+        && !mem_state.ignore_limit
+        && !IGNORE_CHAINED_LIMIT.with(|ignore| ignore.get())
+    {
+        return ptr::null_mut();
+    }
+
+    let new_ptr = inner_alloc(inner_ud, ptr, osize, nsize);
+    if !new_ptr.is_null() || nsize == 0 {
+        // Memory allocated before wrapping can be freed too
+        mem_state.used_memory = (mem_state.used_memory + mem_diff).max(0);
+    }
+    new_ptr
+}
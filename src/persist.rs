@@ -0,0 +1,540 @@
+#[cfg(any(feature = "lua51", feature = "luajit"))]
+use std::ffi::CStr;
+use std::mem;
+#[cfg(any(feature = "lua51", feature = "luajit"))]
+use std::os::raw::c_int;
+use std::os::raw::c_void;
+
+use rustc_hash::FxHashMap;
+
+use crate::chunk::ChunkMode;
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::lua::Lua;
+use crate::table::Table;
+use crate::thread::Thread;
+use crate::types::Integer;
+#[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+use crate::util::assert_stack;
+use crate::util::binary::{write_varint, Format, Reader, MAX_DEPTH};
+use crate::util::{check_stack, StackGuard};
+use crate::value::Value;
+
+static FORMAT: Format = Format {
+    magic: b"\x1bMLP",
+    version: 1,
+    name: "persisted data",
+};
+
+// Functions are stored as bytecode, which is specific to the Lua version
+#[cfg(feature = "lua54")]
+const BACKEND: u8 = 0x54;
+#[cfg(feature = "lua53")]
+const BACKEND: u8 = 0x53;
+#[cfg(feature = "lua52")]
+const BACKEND: u8 = 0x52;
+#[cfg(feature = "luajit")]
+const BACKEND: u8 = 0x4a;
+#[cfg(all(feature = "lua51", not(feature = "lua51_civ6")))]
+const BACKEND: u8 = 0x51;
+#[cfg(feature = "lua51_civ6")]
+const BACKEND: u8 = 0xc6;
+
+const TAG_NIL: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_INTEGER: u8 = 3;
+const TAG_NUMBER: u8 = 4;
+const TAG_STRING: u8 = 5;
+// Reference to a table, function or thread written before
+const TAG_REF: u8 = 6;
+// Key of the value in the permanents table
+const TAG_PERMANENT: u8 = 7;
+const TAG_TABLE: u8 = 8;
+const TAG_FUNCTION: u8 = 9;
+const TAG_THREAD: u8 = 10;
+
+const UPVALUE_VALUE: u8 = 0;
+// Upvalue shared with a function written before
+#[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+const UPVALUE_JOINED: u8 = 1;
+
+const THREAD_NEW: u8 = 0;
+const THREAD_DEAD: u8 = 1;
+
+pub(crate) fn persist<'lua>(
+    lua: &'lua Lua,
+    value: Value<'lua>,
+    permanents: &Table<'lua>,
+) -> Result<Vec<u8>> {
+    let mut persister = Persister {
+        lua,
+        permanents,
+        refs: FxHashMap::default(),
+        #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+        upvalues: FxHashMap::default(),
+        #[cfg(any(feature = "lua51", feature = "luajit"))]
+        closures: Vec::new(),
+        buf: Vec::new(),
+        depth: 0,
+    };
+    FORMAT.write_header(&mut persister.buf, BACKEND);
+    persister.write_value(value)?;
+    Ok(persister.buf)
+}
+
+pub(crate) fn unpersist<'lua>(
+    lua: &'lua Lua,
+    data: &[u8],
+    permanents: &Table<'lua>,
+) -> Result<Value<'lua>> {
+    let (backend, body) = FORMAT.read_header(data)?;
+    if backend != BACKEND {
+        return Err(Error::runtime(
+            "persisted data was created by a different Lua version",
+        ));
+    }
+
+    // Permanents map objects to keys, we need the reverse mapping
+    let keys = lua.create_table()?;
+    for pair in permanents.clone().pairs::<Value, Value>() {
+        let (value, key) = pair?;
+        keys.raw_set(key, value)?;
+    }
+
+    let mut unpersister = Unpersister {
+        lua,
+        permanents: keys,
+        refs: Vec::new(),
+        reader: Reader::new(&FORMAT, body),
+        depth: 0,
+    };
+    let value = unpersister.read_value()?;
+    unpersister.reader.finish()?;
+    Ok(value)
+}
+
+struct Persister<'lua, 'a> {
+    lua: &'lua Lua,
+    permanents: &'a Table<'lua>,
+    // Pointers of the written tables, functions and threads to their ids
+    refs: FxHashMap<*const c_void, usize>,
+    // Upvalue ids to the (function id, upvalue index) that wrote them
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+    upvalues: FxHashMap<*mut c_void, (usize, usize)>,
+    // Written closures with their number of upvalues, to detect shared upvalues
+    #[cfg(any(feature = "lua51", feature = "luajit"))]
+    closures: Vec<(Function<'lua>, usize)>,
+    buf: Vec<u8>,
+    depth: usize,
+}
+
+impl<'lua, 'a> Persister<'lua, 'a> {
+    fn write_value(&mut self, value: Value<'lua>) -> Result<()> {
+        match value {
+            Value::Nil => self.buf.push(TAG_NIL),
+            Value::Boolean(false) => self.buf.push(TAG_FALSE),
+            Value::Boolean(true) => self.buf.push(TAG_TRUE),
+            Value::Integer(i) => {
+                self.buf.push(TAG_INTEGER);
+                self.buf.extend_from_slice(&(i as i64).to_le_bytes());
+            }
+            Value::Number(n) => {
+                self.buf.push(TAG_NUMBER);
+                self.buf.extend_from_slice(&n.to_le_bytes());
+            }
+            Value::String(s) => {
+                self.buf.push(TAG_STRING);
+                self.write_bytes(s.as_bytes());
+            }
+            value => self.write_object(value)?,
+        }
+        Ok(())
+    }
+
+    fn write_object(&mut self, value: Value<'lua>) -> Result<()> {
+        let is_lua_object = match value {
+            Value::Table(_) | Value::Thread(_) => true,
+            Value::Function(ref func) => func.info().what != "C",
+            _ => false,
+        };
+        if is_lua_object {
+            if let Some(&id) = self.refs.get(&value.to_pointer()) {
+                self.buf.push(TAG_REF);
+                self.write_varint(id);
+                return Ok(());
+            }
+        }
+
+        let key = self.permanents.raw_get::<_, Value>(value.clone())?;
+        if !key.is_nil() {
+            if !matches!(
+                key,
+                Value::Boolean(_) | Value::Integer(_) | Value::Number(_) | Value::String(_)
+            ) {
+                return Err(Error::runtime(format!(
+                    "permanent key must be a boolean, number or string, got {}",
+                    key.type_name()
+                )));
+            }
+            self.buf.push(TAG_PERMANENT);
+            return self.write_value(key);
+        }
+
+        if !is_lua_object {
+            let type_name = match value {
+                Value::Function(_) => "C function",
+                ref value => value.type_name(),
+            };
+            return Err(Error::runtime(format!(
+                "cannot persist {type_name} (not in the permanents table)"
+            )));
+        }
+        if self.depth == MAX_DEPTH {
+            return Err(Error::runtime("cannot persist value: nesting is too deep"));
+        }
+
+        let id = self.refs.len();
+        self.refs.insert(value.to_pointer(), id);
+        self.depth += 1;
+        match value {
+            Value::Table(table) => self.write_table(table)?,
+            Value::Function(func) => self.write_function(id, func)?,
+            Value::Thread(thread) => self.write_thread(thread)?,
+            _ => unreachable!(),
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn write_table(&mut self, table: Table<'lua>) -> Result<()> {
+        self.buf.push(TAG_TABLE);
+        for pair in table.clone().pairs::<Value, Value>() {
+            let (key, value) = pair?;
+            self.write_value(key)?;
+            self.write_value(value)?;
+        }
+        self.buf.push(TAG_NIL);
+        match table.get_metatable() {
+            Some(mt) => self.write_value(Value::Table(mt)),
+            None => self.write_value(Value::Nil),
+        }
+    }
+
+    #[cfg_attr(
+        not(any(feature = "lua54", feature = "lua53", feature = "lua52")),
+        allow(unused_variables)
+    )]
+    fn write_function(&mut self, id: usize, func: Function<'lua>) -> Result<()> {
+        self.buf.push(TAG_FUNCTION);
+        self.write_bytes(&func.dump(false));
+
+        let upvalues = func.upvalues()?;
+        #[cfg(any(feature = "lua51", feature = "luajit"))]
+        if !upvalues.is_empty() {
+            self.check_shared_upvalues(&func, upvalues.len())?;
+            self.closures.push((func.clone(), upvalues.len()));
+        }
+        self.write_varint(upvalues.len());
+        for (i, (_, value)) in upvalues.into_iter().enumerate() {
+            #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+            {
+                let upvalue_id = upvalue_id(self.lua, &func, i + 1);
+                if let Some(&(func_id, n)) = self.upvalues.get(&upvalue_id) {
+                    self.buf.push(UPVALUE_JOINED);
+                    self.write_varint(func_id);
+                    self.write_varint(n);
+                    continue;
+                }
+                self.upvalues.insert(upvalue_id, (id, i + 1));
+            }
+            self.buf.push(UPVALUE_VALUE);
+            self.write_value(value)?;
+        }
+
+        // Lua 5.2+ keeps the environment in the `_ENV` upvalue
+        #[cfg(any(feature = "lua51", feature = "luajit"))]
+        match func.environment() {
+            Some(env) => self.write_value(Value::Table(env))?,
+            None => self.write_value(Value::Nil)?,
+        }
+        Ok(())
+    }
+
+    // Lua 5.1 cannot join upvalues when reading, so closures sharing an upvalue are rejected.
+    // Every upvalue of `func` is replaced by a sentinel in turn and looked up in the closures
+    // written before.
+    #[cfg(any(feature = "lua51", feature = "luajit"))]
+    fn check_shared_upvalues(&self, func: &Function, count: usize) -> Result<()> {
+        if self.closures.is_empty() {
+            return Ok(());
+        }
+        let lua = self.lua;
+        let state = lua.state();
+        let sentinel = &count as *const usize as *mut c_void;
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 4)?;
+
+            lua.push_ref(&func.0);
+            let index = ffi::lua_gettop(state);
+            for n in 1..=count as c_int {
+                // Keep the original value on the stack
+                let name = ffi::lua_getupvalue(state, index, n);
+                ffi::lua_pushlightuserdata(state, sentinel);
+                ffi::lua_setupvalue(state, index, n);
+                let shared = self.closures.iter().any(|(other, other_count)| {
+                    lua.push_ref(&other.0);
+                    let shared = (1..=*other_count as c_int).any(|n2| {
+                        ffi::lua_getupvalue(state, -1, n2);
+                        let found = ffi::lua_type(state, -1) == ffi::LUA_TLIGHTUSERDATA
+                            && ffi::lua_touserdata(state, -1) == sentinel;
+                        ffi::lua_pop(state, 1);
+                        found
+                    });
+                    ffi::lua_pop(state, 1);
+                    shared
+                });
+                ffi::lua_setupvalue(state, index, n);
+                if shared {
+                    let name = CStr::from_ptr(name).to_string_lossy();
+                    return Err(Error::runtime(format!(
+                        "cannot persist closures sharing upvalue '{name}' \
+                        (upvalues cannot be joined on this Lua version)"
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_thread(&mut self, thread: Thread<'lua>) -> Result<()> {
+        let lua = self.lua;
+        let thread_state = thread.1;
+        let func = unsafe {
+            let mut ar: ffi::lua_Debug = mem::zeroed();
+            let status = ffi::lua_status(thread_state);
+            let started = ffi::lua_getstack(thread_state, 0, &mut ar) != 0;
+            let top = ffi::lua_gettop(thread_state);
+            if status != ffi::LUA_OK && status != ffi::LUA_YIELD || !started && top == 0 {
+                None
+            } else if status == ffi::LUA_OK
+                && !started
+                && top == 1
+                && ffi::lua_type(thread_state, 1) == ffi::LUA_TFUNCTION
+            {
+                let state = lua.state();
+                let _sg = StackGuard::new(state);
+                check_stack(state, 1)?;
+                ffi::lua_xpush(thread_state, state, 1);
+                Some(Function(lua.pop_ref()))
+            } else {
+                return Err(Error::runtime(
+                    "cannot persist a running or suspended thread",
+                ));
+            }
+        };
+
+        self.buf.push(TAG_THREAD);
+        match func {
+            Some(func) => {
+                self.buf.push(THREAD_NEW);
+                self.write_value(Value::Function(func))
+            }
+            None => {
+                self.buf.push(THREAD_DEAD);
+                Ok(())
+            }
+        }
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_varint(bytes.len());
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn write_varint(&mut self, n: usize) {
+        write_varint(&mut self.buf, n as u64);
+    }
+}
+
+#[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+fn upvalue_id(lua: &Lua, func: &Function, n: usize) -> *mut c_void {
+    let state = lua.state();
+    unsafe {
+        let _sg = StackGuard::new(state);
+        assert_stack(state, 1);
+
+        lua.push_ref(&func.0);
+        ffi::lua_upvalueid(state, -1, n as _)
+    }
+}
+
+struct Unpersister<'lua, 'a> {
+    lua: &'lua Lua,
+    // Keys to the permanent values
+    permanents: Table<'lua>,
+    // Tables, functions and threads in the order they were read
+    refs: Vec<Value<'lua>>,
+    reader: Reader<'a>,
+    depth: usize,
+}
+
+impl<'lua, 'a> Unpersister<'lua, 'a> {
+    fn read_value(&mut self) -> Result<Value<'lua>> {
+        let lua = self.lua;
+        let value = match self.reader.read_u8()? {
+            TAG_NIL => Value::Nil,
+            TAG_FALSE => Value::Boolean(false),
+            TAG_TRUE => Value::Boolean(true),
+            TAG_INTEGER => {
+                let bytes = self.reader.read_slice(8)?.try_into().unwrap();
+                #[allow(clippy::useless_conversion, clippy::unnecessary_fallible_conversions)]
+                let i = Integer::try_from(i64::from_le_bytes(bytes));
+                Value::Integer(i.map_err(|_| FORMAT.corrupted("integer out of range"))?)
+            }
+            TAG_NUMBER => {
+                let bytes = self.reader.read_slice(8)?.try_into().unwrap();
+                Value::Number(f64::from_le_bytes(bytes))
+            }
+            TAG_STRING => {
+                let bytes = self.reader.read_bytes()?;
+                Value::String(lua.create_string(bytes)?)
+            }
+            TAG_REF => {
+                let id = self.reader.read_len()?;
+                let value = self.refs.get(id).cloned();
+                value.ok_or_else(|| FORMAT.corrupted("bad reference"))?
+            }
+            TAG_PERMANENT => {
+                let key = self.read_value()?;
+                let value = self.permanents.raw_get::<_, Value>(key.clone())?;
+                if value.is_nil() {
+                    let key = key.to_string()?;
+                    return Err(Error::runtime(format!(
+                        "permanent '{key}' is not in the permanents table"
+                    )));
+                }
+                value
+            }
+            tag @ (TAG_TABLE | TAG_FUNCTION | TAG_THREAD) => {
+                if self.depth == MAX_DEPTH {
+                    return Err(FORMAT.corrupted("nesting is too deep"));
+                }
+                self.depth += 1;
+                let value = match tag {
+                    TAG_TABLE => self.read_table()?,
+                    TAG_FUNCTION => self.read_function()?,
+                    _ => self.read_thread()?,
+                };
+                self.depth -= 1;
+                value
+            }
+            _ => return Err(FORMAT.corrupted("unknown tag")),
+        };
+        Ok(value)
+    }
+
+    fn read_table(&mut self) -> Result<Value<'lua>> {
+        let table = self.lua.create_table()?;
+        self.refs.push(Value::Table(table.clone()));
+        loop {
+            let key = self.read_value()?;
+            if key.is_nil() {
+                break;
+            }
+            let value = self.read_value()?;
+            table.raw_set(key, value)?;
+        }
+        // Metatable is set after the contents to not trigger metamethods
+        match self.read_value()? {
+            Value::Nil => {}
+            Value::Table(mt) => table.set_metatable(Some(mt)),
+            _ => return Err(FORMAT.corrupted("metatable is not a table")),
+        }
+        Ok(Value::Table(table))
+    }
+
+    fn read_function(&mut self) -> Result<Value<'lua>> {
+        let bytecode = self.reader.read_bytes()?;
+        let func = (self.lua.load(bytecode))
+            .set_mode(ChunkMode::Binary)
+            .into_function()?;
+        self.refs.push(Value::Function(func.clone()));
+
+        let count = self.reader.read_len()?;
+        for n in 1..=count {
+            match self.reader.read_u8()? {
+                UPVALUE_VALUE => {
+                    let value = self.read_value()?;
+                    func.set_upvalue(n, value)?;
+                }
+                #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+                UPVALUE_JOINED => {
+                    let (func_id, n2) = (self.reader.read_len()?, self.reader.read_len()?);
+                    let other = match self.refs.get(func_id) {
+                        Some(Value::Function(other)) => other.clone(),
+                        _ => return Err(FORMAT.corrupted("bad upvalue reference")),
+                    };
+                    self.join_upvalue(&func, n, &other, n2)?;
+                }
+                _ => return Err(FORMAT.corrupted("unknown upvalue kind")),
+            }
+        }
+
+        #[cfg(any(feature = "lua51", feature = "luajit"))]
+        match self.read_value()? {
+            Value::Nil => {}
+            Value::Table(env) => {
+                func.set_environment(env)?;
+            }
+            _ => return Err(FORMAT.corrupted("environment is not a table")),
+        }
+        Ok(Value::Function(func))
+    }
+
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+    fn join_upvalue(&self, func: &Function, n: usize, other: &Function, n2: usize) -> Result<()> {
+        let lua = self.lua;
+        let state = lua.state();
+        unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 2)?;
+
+            lua.push_ref(&func.0);
+            lua.push_ref(&other.0);
+            if ffi::lua_upvalueid(state, -2, n as _).is_null()
+                || ffi::lua_upvalueid(state, -1, n2 as _).is_null()
+            {
+                return Err(FORMAT.corrupted("bad upvalue reference"));
+            }
+            ffi::lua_upvaluejoin(state, -2, n as _, -1, n2 as _);
+        }
+        Ok(())
+    }
+
+    fn read_thread(&mut self) -> Result<Value<'lua>> {
+        let lua = self.lua;
+        let state = lua.state();
+        // The thread must be registered before its function is read
+        let thread = unsafe {
+            let _sg = StackGuard::new(state);
+            check_stack(state, 1)?;
+            protect_lua!(state, 0, 1, |state| ffi::lua_newthread(state))?;
+            Thread::new(lua.pop_ref())
+        };
+        self.refs.push(Value::Thread(thread.clone()));
+
+        match self.reader.read_u8()? {
+            THREAD_NEW => match self.read_value()? {
+                Value::Function(func) => unsafe {
+                    ffi::lua_xpush(lua.ref_thread(), thread.1, func.0.index);
+                },
+                _ => return Err(FORMAT.corrupted("thread function is not a function")),
+            },
+            THREAD_DEAD => {}
+            _ => return Err(FORMAT.corrupted("unknown thread state")),
+        }
+        Ok(Value::Thread(thread))
+    }
+}
@@ -2,28 +2,34 @@
 
 #[doc(no_inline)]
 pub use crate::{
-    AnyUserData as LuaAnyUserData, AnyUserDataExt as LuaAnyUserDataExt, Chunk as LuaChunk,
-    Error as LuaError, ErrorContext as LuaErrorContext, ExternalError as LuaExternalError,
+    AnyUserData as LuaAnyUserData, AnyUserDataExt as LuaAnyUserDataExt, Budget as LuaBudget,
+    Chunk as LuaChunk, DeterministicOptions as LuaDeterministicOptions,
+    EncodeOptions as LuaEncodeOptions, Error as LuaError, ErrorContext as LuaErrorContext,
+    Event as LuaEvent, EventBus as LuaEventBus, ExternalError as LuaExternalError,
     ExternalResult as LuaExternalResult, FromLua, FromLuaMulti, Function as LuaFunction,
     FunctionInfo as LuaFunctionInfo, GCMode as LuaGCMode, Integer as LuaInteger, IntoLua,
     IntoLuaMulti, LightUserData as LuaLightUserData, Lua, LuaOptions, MetaMethod as LuaMetaMethod,
     MultiValue as LuaMultiValue, Nil as LuaNil, Number as LuaNumber, RegistryKey as LuaRegistryKey,
-    Result as LuaResult, StdLib as LuaStdLib, String as LuaString, Table as LuaTable,
+    Result as LuaResult, Scheduler as LuaScheduler, StackFrame as LuaStackFrame,
+    StdLib as LuaStdLib, String as LuaString, StringBuilder as LuaStringBuilder, Table as LuaTable,
     TableExt as LuaTableExt, TablePairs as LuaTablePairs, TableSequence as LuaTableSequence,
-    Thread as LuaThread, ThreadStatus as LuaThreadStatus, UserData as LuaUserData,
-    UserDataFields as LuaUserDataFields, UserDataMetatable as LuaUserDataMetatable,
-    UserDataMethods as LuaUserDataMethods, UserDataRef as LuaUserDataRef,
-    UserDataRefMut as LuaUserDataRefMut, UserDataRegistry as LuaUserDataRegistry,
-    Value as LuaValue,
+    TaskId as LuaTaskId, TaskInfo as LuaTaskInfo, TaskState as LuaTaskState, Thread as LuaThread,
+    ThreadStatus as LuaThreadStatus, UserData as LuaUserData, UserDataFields as LuaUserDataFields,
+    UserDataMetatable as LuaUserDataMetatable, UserDataMethods as LuaUserDataMethods,
+    UserDataRef as LuaUserDataRef, UserDataRefMut as LuaUserDataRefMut,
+    UserDataRegistry as LuaUserDataRegistry, Value as LuaValue, VmState as LuaVmState,
 };
 
 #[cfg(not(feature = "luau"))]
 #[doc(no_inline)]
-pub use crate::HookTriggers as LuaHookTriggers;
+pub use crate::{
+    HookId as LuaHookId, HookTriggers as LuaHookTriggers, LineCoverage as LuaLineCoverage,
+    Profile as LuaProfile, ProfileFunction as LuaProfileFunction,
+};
 
 #[cfg(feature = "luau")]
 #[doc(no_inline)]
-pub use crate::{CoverageInfo as LuaCoverageInfo, Vector as LuaVector, VmState as LuaVmState};
+pub use crate::{CoverageInfo as LuaCoverageInfo, Vector as LuaVector};
 
 #[cfg(feature = "async")]
 #[doc(no_inline)]
@@ -33,7 +39,7 @@ pub use crate::AsyncThread as LuaAsyncThread;
 #[doc(no_inline)]
 pub use crate::{
     DeserializeOptions as LuaDeserializeOptions, LuaSerdeExt,
-    SerializeOptions as LuaSerializeOptions,
+    SerializeOptions as LuaSerializeOptions, StringArena as LuaStringArena,
 };
 
 #[cfg(feature = "unstable")]
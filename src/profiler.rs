@@ -0,0 +1,339 @@
+use std::fmt::Write as _;
+use std::io;
+use std::mem;
+use std::time::{Duration, Instant};
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::hook::HookId;
+use crate::lua::Lua;
+use crate::util::json;
+
+// Stacks deeper than this are truncated (the innermost frames are kept)
+const MAX_DEPTH: usize = 256;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Frame {
+    name: Option<String>,
+    source: Option<String>,
+    line_defined: Option<usize>,
+    what: &'static str,
+}
+
+impl Frame {
+    // Label of the frame in the folded stacks
+    fn label(&self) -> String {
+        let label = match (self.what, &self.name) {
+            ("main", _) => format!("main chunk ({})", self.source.as_deref().unwrap_or("?")),
+            ("C", name) => format!("{} [C]", name.as_deref().unwrap_or("?")),
+            ("tail", _) => "(tail call)".to_string(),
+            (_, name) => format!(
+                "{} ({}:{})",
+                name.as_deref().unwrap_or("?"),
+                self.source.as_deref().unwrap_or("?"),
+                self.line_defined.unwrap_or(0)
+            ),
+        };
+        // `;` separates frames in the folded format
+        label.replace(';', ",")
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct StackStats {
+    samples: u64,
+    time: Duration,
+}
+
+pub(crate) struct ProfilerState {
+    pub(crate) hook: HookId,
+    interval: u32,
+    started: Instant,
+    last: Instant,
+    frames: Vec<Frame>,
+    frame_ids: FxHashMap<Frame, usize>,
+    // Frame ids from the outermost to the innermost one
+    stacks: FxHashMap<Vec<usize>, StackStats>,
+}
+
+impl ProfilerState {
+    pub(crate) fn new(interval: u32, hook: HookId) -> Self {
+        let now = Instant::now();
+        ProfilerState {
+            hook,
+            interval,
+            started: now,
+            last: now,
+            frames: Vec::new(),
+            frame_ids: FxHashMap::default(),
+            stacks: FxHashMap::default(),
+        }
+    }
+
+    // Attributes the time since the previous mark to the stack
+    fn record(&mut self, frames: Vec<Frame>, sample: bool) {
+        let now = Instant::now();
+        let elapsed = now - mem::replace(&mut self.last, now);
+        let mut stack = Vec::with_capacity(frames.len());
+        for frame in frames.into_iter().rev() {
+            let id = match self.frame_ids.get(&frame) {
+                Some(&id) => id,
+                None => {
+                    let id = self.frames.len();
+                    self.frames.push(frame.clone());
+                    self.frame_ids.insert(frame, id);
+                    id
+                }
+            };
+            stack.push(id);
+        }
+        let stats = self.stacks.entry(stack).or_default();
+        stats.samples += sample as u64;
+        stats.time += elapsed;
+    }
+
+    pub(crate) fn finish(self) -> Profile {
+        Profile {
+            interval: self.interval,
+            duration: self.started.elapsed(),
+            frames: self.frames,
+            stacks: self.stacks.into_iter().collect(),
+        }
+    }
+}
+
+// Walks the call stack of the current thread starting from `level`
+fn collect_frames(lua: &Lua, level: usize) -> Vec<Frame> {
+    let mut frames = Vec::new();
+    for level in level..level + MAX_DEPTH {
+        let debug = match lua.inspect_stack(level) {
+            Some(debug) => debug,
+            None => break,
+        };
+        let source = debug.source();
+        frames.push(Frame {
+            name: debug.names().name.map(|s| s.into_owned()),
+            source: source.short_src.map(|s| s.into_owned()),
+            line_defined: source.line_defined,
+            what: source.what,
+        });
+    }
+    frames
+}
+
+// Records the stack starting from `level` if the profiler is running
+pub(crate) fn mark(lua: &Lua, level: usize, sample: bool) {
+    if lua.with_profiler(|_| ()).is_none() {
+        return;
+    }
+    let frames = collect_frames(lua, level);
+    lua.with_profiler(|profiler| profiler.record(frames, sample));
+}
+
+// Attributes the time spent in a Rust callback to its frame
+pub(crate) struct CallbackGuard<'a>(Option<&'a Lua>);
+
+impl<'a> CallbackGuard<'a> {
+    pub(crate) fn new(lua: &'a Lua) -> Self {
+        if lua.with_profiler(|_| ()).is_none() {
+            return CallbackGuard(None);
+        }
+        // Time before the call belongs to the caller
+        mark(lua, 1, false);
+        CallbackGuard(Some(lua))
+    }
+}
+
+impl<'a> Drop for CallbackGuard<'a> {
+    fn drop(&mut self) {
+        if let Some(lua) = self.0 {
+            mark(lua, 0, false);
+        }
+    }
+}
+
+/// Summary of a single function in a [`Profile`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ProfileFunction {
+    /// Name of the function (if known).
+    pub name: Option<String>,
+    /// A "printable" version of the source of the chunk that created the function.
+    pub source: Option<String>,
+    /// The line number where the definition of the function starts.
+    pub line_defined: Option<usize>,
+    /// `Lua`, `C` (including Rust callbacks), `main` or `tail` (Lua 5.1 tail calls).
+    pub what: &'static str,
+    /// Number of samples taken while the function was running.
+    pub self_samples: u64,
+    /// Number of samples taken while the function was on the stack.
+    pub total_samples: u64,
+    /// Time spent in the function itself.
+    pub self_time: Duration,
+    /// Time spent in the function and in the functions called by it.
+    pub total_time: Duration,
+}
+
+/// Result of a profiling session started with [`Lua::start_profiler`].
+#[derive(Clone, Debug)]
+pub struct Profile {
+    interval: u32,
+    duration: Duration,
+    frames: Vec<Frame>,
+    stacks: Vec<(Vec<usize>, StackStats)>,
+}
+
+impl Profile {
+    /// Returns the number of instructions between samples.
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    /// Returns the wall time between starting and stopping the profiler.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Returns the total number of samples.
+    pub fn samples(&self) -> u64 {
+        self.stacks.iter().map(|(_, stats)| stats.samples).sum()
+    }
+
+    /// Returns the time spent in Rust callbacks (functions created by [`Lua::create_function`]
+    /// and similar).
+    pub fn callback_time(&self) -> Duration {
+        (self.stacks.iter())
+            .filter(|(stack, _)| matches!(stack.last(), Some(&id) if self.frames[id].what == "C"))
+            .map(|(_, stats)| stats.time)
+            .sum()
+    }
+
+    /// Returns the summary of every function seen by the profiler, ordered by self time.
+    pub fn functions(&self) -> Vec<ProfileFunction> {
+        let mut functions = (self.frames.iter())
+            .map(|frame| ProfileFunction {
+                name: frame.name.clone(),
+                source: frame.source.clone(),
+                line_defined: frame.line_defined,
+                what: frame.what,
+                self_samples: 0,
+                total_samples: 0,
+                self_time: Duration::ZERO,
+                total_time: Duration::ZERO,
+            })
+            .collect::<Vec<_>>();
+
+        let mut seen = FxHashSet::default();
+        for (stack, stats) in &self.stacks {
+            if let Some(&id) = stack.last() {
+                functions[id].self_samples += stats.samples;
+                functions[id].self_time += stats.time;
+            }
+            // Recursive functions are counted once per stack
+            seen.clear();
+            for &id in stack {
+                if seen.insert(id) {
+                    functions[id].total_samples += stats.samples;
+                    functions[id].total_time += stats.time;
+                }
+            }
+        }
+
+        functions.sort_by(|a, b| {
+            (b.self_time, b.self_samples, b.total_time).cmp(&(
+                a.self_time,
+                a.self_samples,
+                a.total_time,
+            ))
+        });
+        functions
+    }
+
+    /// Writes the profile in the folded stacks format, one stack per line.
+    ///
+    /// Frames are separated by `;` (from the outermost to the innermost one), followed by the time
+    /// spent in the stack in microseconds. The output can be turned into a flame graph by
+    /// `flamegraph.pl` or `inferno-flamegraph`.
+    pub fn write_folded<W: io::Write>(&self, mut w: W) -> io::Result<()> {
+        let labels = self.frames.iter().map(Frame::label).collect::<Vec<_>>();
+        let mut lines = FxHashMap::<String, u128>::default();
+        for (stack, stats) in &self.stacks {
+            let micros = stats.time.as_micros();
+            if stack.is_empty() || micros == 0 {
+                continue;
+            }
+            let line = stack
+                .iter()
+                .map(|&id| labels[id].as_str())
+                .collect::<Vec<_>>()
+                .join(";");
+            *lines.entry(line).or_default() += micros;
+        }
+        let mut lines = lines.into_iter().collect::<Vec<_>>();
+        lines.sort();
+        for (line, micros) in lines {
+            writeln!(w, "{line} {micros}")?;
+        }
+        Ok(())
+    }
+
+    /// Returns the profile in the folded stacks format.
+    ///
+    /// See [`Profile::write_folded`] for details.
+    pub fn to_folded(&self) -> String {
+        let mut buf = Vec::new();
+        self.write_folded(&mut buf)
+            .expect("writing to a Vec cannot fail");
+        String::from_utf8(buf).expect("folded stacks must be valid UTF-8")
+    }
+
+    /// Returns the summary of the profile as a JSON object.
+    ///
+    /// The object contains `interval`, `samples`, `duration_us`, `callback_time_us` and
+    /// `functions`, the list of [`ProfileFunction`] objects ordered by self time.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            r#"{{"interval":{},"samples":{},"duration_us":{},"callback_time_us":{},"functions":["#,
+            self.interval,
+            self.samples(),
+            self.duration.as_micros(),
+            self.callback_time().as_micros(),
+        );
+        for (i, func) in self.functions().iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str(r#"{"name":"#);
+            write_json_string(&mut out, func.name.as_deref());
+            out.push_str(r#","source":"#);
+            write_json_string(&mut out, func.source.as_deref());
+            out.push_str(r#","line_defined":"#);
+            match func.line_defined {
+                Some(line) => out.push_str(&line.to_string()),
+                None => out.push_str("null"),
+            }
+            let _ = write!(
+                out,
+                r#","what":"{}","self_samples":{},"total_samples":{},"self_time_us":{},"total_time_us":{}}}"#,
+                func.what,
+                func.self_samples,
+                func.total_samples,
+                func.self_time.as_micros(),
+                func.total_time.as_micros(),
+            );
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+fn write_json_string(out: &mut String, s: Option<&str>) {
+    match s {
+        Some(s) => {
+            let _ = json::write_string(out, s);
+        }
+        None => out.push_str("null"),
+    }
+}
@@ -0,0 +1,188 @@
+use crate::error::Result;
+use crate::function::Function;
+use crate::lua::Lua;
+use crate::table::Table;
+
+// Builds the globals proxy of the sandbox mode on top of the original globals.
+//
+// Reads fall through to the globals (except the hidden ones), standard libraries are wrapped in
+// read-only proxies and writes stay in the returned table.
+const SANDBOX_ENV: &str = r#"
+local globals, is_lua_function = ...
+local type, error, select = type, error, select
+local next, pairs, ipairs, rawset = next, pairs, ipairs, rawset
+local setmetatable, getmetatable = setmetatable, getmetatable
+local getfenv, setfenv, collectgarbage = getfenv, setfenv, collectgarbage
+
+local hidden = {
+    debug = true, dofile = true, ffi = true, io = true, jit = true, load = true,
+    loadfile = true, loadstring = true, module = true, package = true, require = true,
+}
+local libs = { "bit", "bit32", "coroutine", "math", "os", "string", "table", "utf8" }
+local os_allowed = { "clock", "date", "difftime", "time" }
+
+local env = {}
+local base = { _G = env }
+
+-- Read-only proxy -> library
+local proxies = setmetatable({}, { __mode = "k" })
+local function read_only(name, lib)
+    local proxy = setmetatable({}, {
+        __index = lib,
+        __newindex = function()
+            error("attempt to modify a read-only library '" .. name .. "'", 2)
+        end,
+        __metatable = false,
+    })
+    proxies[proxy] = lib
+    return proxy
+end
+
+-- Raw writes would bypass `__newindex` of the read-only proxies
+local function check_writable(t, func)
+    if proxies[t] then
+        error("'" .. func .. "' cannot modify a read-only library", 3)
+    end
+end
+base.rawset = function(t, k, v)
+    check_writable(t, "rawset")
+    return rawset(t, k, v)
+end
+
+-- Functions of the `table` library that write to the table without metamethods
+local table_writers = { insert = 1, remove = 1, sort = 1, move = 5 }
+
+for _, name in ipairs(libs) do
+    local lib = globals[name]
+    if type(lib) == "table" then
+        if name == "os" then
+            local safe = {}
+            for _, func in ipairs(os_allowed) do
+                safe[func] = lib[func]
+            end
+            lib = safe
+        elseif name == "table" then
+            local safe = {}
+            for key, value in pairs(lib) do
+                safe[key] = value
+            end
+            for func, pos in pairs(table_writers) do
+                local orig = lib[func]
+                if orig then
+                    safe[func] = function(...)
+                        local t = select(pos, ...)
+                        if t == nil then
+                            -- `table.move` writes to its first argument by default
+                            t = ...
+                        end
+                        check_writable(t, "table." .. func)
+                        return orig(...)
+                    end
+                end
+            end
+            lib = safe
+        end
+        base[name] = read_only(name, lib)
+    end
+end
+
+-- Iteration sees the contents of the read-only libraries
+base.next = function(t, k)
+    return next(proxies[t] or t, k)
+end
+base.pairs = function(t)
+    if proxies[t] then
+        return base.next, t, nil
+    end
+    return pairs(t)
+end
+base.ipairs = function(t)
+    return ipairs(proxies[t] or t)
+end
+
+-- The string metatable would give access to the original `string` library
+local string_mt = read_only("string", { __index = base.string })
+base.getmetatable = function(value)
+    if type(value) == "string" then
+        return string_mt
+    end
+    return getmetatable(value)
+end
+
+if collectgarbage then
+    base.collectgarbage = function(opt)
+        if opt ~= "count" then
+            error("'collectgarbage' only supports the 'count' option in the sandbox", 2)
+        end
+        return collectgarbage("count")
+    end
+end
+
+if setfenv and getfenv then
+    -- Environments created by the sandbox
+    local owned = setmetatable({ [env] = true }, { __mode = "k" })
+    base.getfenv = function(f)
+        if f == nil then
+            f = 1
+        end
+        if type(f) == "number" then
+            if f < 1 then
+                return env
+            end
+            f = f + 1
+        end
+        local t = getfenv(f)
+        if owned[t] then
+            return t
+        end
+        return env
+    end
+    base.setfenv = function(f, t)
+        if type(t) ~= "table" then
+            error("bad argument #2 to 'setfenv' (table expected, got " .. type(t) .. ")", 2)
+        end
+        if type(f) == "number" then
+            if f < 1 then
+                error("'setfenv' cannot change the environment of the thread in the sandbox", 2)
+            end
+            f = f + 1
+        elseif type(f) ~= "function" then
+            error("bad argument #1 to 'setfenv' (number or function expected)", 2)
+        end
+        if (type(f) == "function" and not is_lua_function(f)) or not owned[getfenv(f)] then
+            error("'setfenv' cannot change the environment of a foreign function", 2)
+        end
+        owned[t] = true
+        local func = setfenv(f, t)
+        return func
+    end
+end
+
+setmetatable(base, {
+    __index = function(_, key)
+        if not hidden[key] then
+            return globals[key]
+        end
+    end,
+    __metatable = false,
+})
+return setmetatable(env, { __index = base, __metatable = false })
+"#;
+
+pub(crate) fn create_env<'lua>(lua: &'lua Lua, globals: Table<'lua>) -> Result<Table<'lua>> {
+    let is_lua_function = lua.create_function(|_, func: Function| Ok(func.info().what != "C"))?;
+    lua.load(SANDBOX_ENV)
+        .set_name("=__mlua_sandbox")
+        .set_environment(globals.clone())
+        .call((globals, is_lua_function))
+}
+
+// Environment of a sandboxed thread: writes stay local, reads go to the caller's globals
+pub(crate) fn create_thread_env<'lua>(lua: &'lua Lua, globals: Table<'lua>) -> Result<Table<'lua>> {
+    let env = lua.create_table()?;
+    let mt = lua.create_table_with_capacity(0, 2)?;
+    mt.raw_set("__index", globals)?;
+    mt.raw_set("__metatable", false)?;
+    env.set_metatable(Some(mt));
+    Ok(env)
+}
@@ -0,0 +1,462 @@
+use std::fmt;
+
+use crate::error::{Error, Result};
+use crate::function::Function;
+use crate::lua::Lua;
+use crate::table::Table;
+use crate::thread::{Thread, ThreadStatus};
+use crate::types::{Integer, RegistryKey};
+use crate::value::{IntoLuaMulti, MultiValue, Value};
+
+/// Identifier of a task in a [`Scheduler`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaskId(u64);
+
+impl fmt::Display for TaskId {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "task {}", self.0)
+    }
+}
+
+/// What a task in a [`Scheduler`] is waiting for.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum TaskState {
+    /// The task will be resumed on the next tick.
+    Ready,
+    /// The task is waiting for the given number of ticks.
+    Ticks(u64),
+    /// The task is waiting for an event fired by [`Scheduler::fire`].
+    Event(String),
+    /// The task is waiting for a predicate passed to `wait_until` to return a true value.
+    Until,
+}
+
+/// Information about a task in a [`Scheduler`].
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct TaskInfo {
+    /// Task identifier.
+    pub id: TaskId,
+    /// What the task is waiting for.
+    pub state: TaskState,
+}
+
+enum Wait {
+    // Arguments to resume the task with
+    Ready(Vec<RegistryKey>),
+    Ticks { remaining: u64, since: f64 },
+    Event(String),
+    Until(RegistryKey),
+}
+
+struct Task {
+    id: TaskId,
+    thread: RegistryKey,
+    wait: Wait,
+}
+
+// Loaded once per Lua instance and shared by all schedulers
+const SCHEDULER_LIB: &str = r##"
+local yield, select, type, error = ...
+local WAIT = {}
+-- Tasks spawned from Lua, by scheduler id
+local pending = {}
+-- Scheduler running the current task, or the first created one
+local current, default
+
+function wait(n)
+    n = n or 1
+    if type(n) ~= "number" or n < 0 then
+        error("bad argument #1 to 'wait' (non-negative number expected)", 2)
+    end
+    return yield(WAIT, "ticks", n)
+end
+
+function wait_event(name)
+    if type(name) ~= "string" then
+        error("bad argument #1 to 'wait_event' (string expected)", 2)
+    end
+    return yield(WAIT, "event", name)
+end
+
+function wait_until(f)
+    if type(f) ~= "function" then
+        error("bad argument #1 to 'wait_until' (function expected)", 2)
+    end
+    return yield(WAIT, "until", f)
+end
+
+function spawn(f, ...)
+    if type(f) ~= "function" then
+        error("bad argument #1 to 'spawn' (function expected)", 2)
+    end
+    local list = pending[current or default]
+    list[#list + 1] = {n = select("#", ...), f, ...}
+end
+
+local function register(id)
+    default = default or id
+    pending[id] = {}
+end
+
+local function drain(id)
+    local p = pending[id]
+    pending[id] = {}
+    return p
+end
+
+local function set_current(id)
+    local prev = current
+    current = id
+    return prev
+end
+
+return WAIT, register, drain, set_current
+"##;
+
+// Shared scheduler library, stored in the app data
+struct SchedulerLib {
+    marker: RegistryKey,
+    register: RegistryKey,
+    drain: RegistryKey,
+    set_current: RegistryKey,
+    next_id: u64,
+}
+
+/// A scheduler of Lua coroutines driven by game ticks.
+///
+/// Unlike [`AsyncThread`], the scheduler does not need an async runtime: the host calls
+/// [`Scheduler::tick`] once per frame (or turn) and every task that is due is resumed.
+///
+/// Creating a scheduler defines the following global functions for the tasks:
+/// - `wait([n])` suspends the task for `n` ticks (default 1) and returns the elapsed time
+/// - `wait_event(name)` suspends the task until the event is fired, and returns the event
+///   arguments
+/// - `wait_until(f)` suspends the task until `f()` returns a true value, and returns that value
+/// - `spawn(f, ...)` adds a new task
+///
+/// Several schedulers can be created for the same [`Lua`] instance, they share these functions.
+/// `spawn` adds the task to the scheduler running the calling task, or to the first created
+/// scheduler if called outside of a task.
+///
+/// A plain `coroutine.yield()` inside a task is equivalent to `wait(1)`.
+///
+/// Tasks are isolated: an error in one task stops only that task and is reported by
+/// [`Scheduler::tick`]. The scheduler keeps its state in the Lua registry, so it has no lifetime
+/// and can be stored alongside the [`Lua`] instance it was created for.
+///
+/// Requires the `coroutine` standard library.
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{Lua, Result, Scheduler};
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let mut scheduler = Scheduler::new(&lua)?;
+///
+/// let task = lua.load(r#"
+///     function()
+///         wait(2)
+///         done = true
+///     end
+/// "#).eval()?;
+/// scheduler.spawn(&lua, task, ())?;
+///
+/// for _ in 0..3 {
+///     scheduler.tick(&lua, 1.0 / 60.0)?;
+/// }
+/// assert!(lua.globals().get::<_, bool>("done")?);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`AsyncThread`]: crate::AsyncThread
+pub struct Scheduler {
+    id: u64,
+    marker: RegistryKey,
+    drain: RegistryKey,
+    set_current: RegistryKey,
+    tasks: Vec<Task>,
+    next_id: u64,
+    ticks: u64,
+    time: f64,
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Scheduler")
+            .field("tasks", &self.tasks.len())
+            .field("ticks", &self.ticks)
+            .field("time", &self.time)
+            .finish()
+    }
+}
+
+impl Scheduler {
+    /// Creates a new scheduler.
+    ///
+    /// The task functions are defined in the global environment when the first scheduler is
+    /// created for the `Lua` instance.
+    pub fn new(lua: &Lua) -> Result<Scheduler> {
+        if lua.app_data_ref::<SchedulerLib>().is_none() {
+            lua.set_app_data(Self::load_lib(lua)?);
+        }
+        let (id, marker, register, drain, set_current) = {
+            let mut lib = mlua_expect!(lua.app_data_mut::<SchedulerLib>(), "scheduler lib");
+            lib.next_id += 1;
+            (
+                lib.next_id - 1,
+                lua.registry_value::<Table>(&lib.marker)?,
+                lua.registry_value::<Function>(&lib.register)?,
+                lua.registry_value::<Function>(&lib.drain)?,
+                lua.registry_value::<Function>(&lib.set_current)?,
+            )
+        };
+        register.call::<_, ()>(id)?;
+
+        Ok(Scheduler {
+            id,
+            marker: lua.create_registry_value(marker)?,
+            drain: lua.create_registry_value(drain)?,
+            set_current: lua.create_registry_value(set_current)?,
+            tasks: Vec::new(),
+            next_id: 1,
+            ticks: 0,
+            time: 0.0,
+        })
+    }
+
+    fn load_lib(lua: &Lua) -> Result<SchedulerLib> {
+        let globals = lua.globals();
+        let yield_fn = match globals.get::<_, Option<Table>>("coroutine")? {
+            Some(coroutine) => coroutine.get::<_, Function>("yield")?,
+            None => return Err(Error::runtime("scheduler requires the coroutine library")),
+        };
+        let (marker, register, drain, set_current) = lua
+            .load(SCHEDULER_LIB)
+            .set_name("=__mlua_scheduler")
+            .call::<_, (Table, Function, Function, Function)>((
+                yield_fn,
+                globals.get::<_, Value>("select")?,
+                globals.get::<_, Value>("type")?,
+                globals.get::<_, Value>("error")?,
+            ))?;
+        Ok(SchedulerLib {
+            marker: lua.create_registry_value(marker)?,
+            register: lua.create_registry_value(register)?,
+            drain: lua.create_registry_value(drain)?,
+            set_current: lua.create_registry_value(set_current)?,
+            next_id: 1,
+        })
+    }
+
+    /// Adds a new task that calls `func` with the given arguments.
+    ///
+    /// The task starts running on the next tick.
+    pub fn spawn<'lua>(
+        &mut self,
+        lua: &'lua Lua,
+        func: Function<'lua>,
+        args: impl IntoLuaMulti<'lua>,
+    ) -> Result<TaskId> {
+        let args = args.into_lua_multi(lua)?;
+        let args = args
+            .into_iter()
+            .map(|v| lua.create_registry_value(v))
+            .collect::<Result<Vec<_>>>()?;
+        let thread = lua.create_thread(func)?;
+        let id = TaskId(self.next_id);
+        self.next_id += 1;
+        self.tasks.push(Task {
+            id,
+            thread: lua.create_registry_value(thread)?,
+            wait: Wait::Ready(args),
+        });
+        Ok(id)
+    }
+
+    /// Cancels a task.
+    ///
+    /// Returns `false` if the task does not exist (already finished or cancelled).
+    pub fn cancel(&mut self, id: TaskId) -> bool {
+        match self.tasks.iter().position(|t| t.id == id) {
+            Some(i) => {
+                self.tasks.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Fires an event, waking up all tasks waiting for it with `wait_event(name)`.
+    ///
+    /// The tasks receive `args` as the `wait_event` results and are resumed on the next tick.
+    /// Returns the number of woken up tasks.
+    pub fn fire<'lua>(
+        &mut self,
+        lua: &'lua Lua,
+        name: &str,
+        args: impl IntoLuaMulti<'lua>,
+    ) -> Result<usize> {
+        let args = args.into_lua_multi(lua)?;
+        let mut count = 0;
+        for task in &mut self.tasks {
+            if matches!(&task.wait, Wait::Event(event) if event == name) {
+                let args = args
+                    .iter()
+                    .map(|v| lua.create_registry_value(v.clone()))
+                    .collect::<Result<Vec<_>>>()?;
+                task.wait = Wait::Ready(args);
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Advances the scheduler by one tick, `dt` is the time elapsed since the previous tick.
+    ///
+    /// Resumes every task that is due, in the spawn order. Tasks spawned by Lua code using
+    /// `spawn` are added before resuming.
+    ///
+    /// Returns errors of the tasks that failed during this tick (these tasks are removed).
+    /// The outer `Result` is an error only if the scheduler itself failed.
+    pub fn tick(&mut self, lua: &Lua, dt: f64) -> Result<Vec<(TaskId, Error)>> {
+        self.ticks += 1;
+        self.time += dt;
+
+        // Collect tasks spawned from Lua
+        let drain: Function = lua.registry_value(&self.drain)?;
+        for item in drain.call::<_, Table>(self.id)?.sequence_values::<Table>() {
+            let item = item?;
+            let n: Integer = item.raw_get("n")?;
+            let args = (2..=n + 1)
+                .map(|i| item.raw_get::<_, Value>(i))
+                .collect::<Result<MultiValue>>()?;
+            self.spawn(lua, item.raw_get(1)?, args)?;
+        }
+
+        // Tasks spawned by the running tasks belong to this scheduler
+        let set_current: Function = lua.registry_value(&self.set_current)?;
+        let prev = set_current.call::<_, Value>(self.id)?;
+        let mut errors = Vec::new();
+        let mut i = 0;
+        while i < self.tasks.len() {
+            match self.run_task(lua, i) {
+                Ok(true) => i += 1,
+                Ok(false) => {
+                    self.tasks.remove(i);
+                }
+                Err(err) => {
+                    let task = self.tasks.remove(i);
+                    errors.push((task.id, err));
+                }
+            }
+        }
+        set_current.call::<_, ()>(prev)?;
+        Ok(errors)
+    }
+
+    /// Returns information about the tasks, in the spawn order.
+    pub fn tasks(&self) -> Vec<TaskInfo> {
+        self.tasks
+            .iter()
+            .map(|task| TaskInfo {
+                id: task.id,
+                state: match &task.wait {
+                    Wait::Ready(_) => TaskState::Ready,
+                    Wait::Ticks { remaining, .. } => TaskState::Ticks(*remaining),
+                    Wait::Event(name) => TaskState::Event(name.clone()),
+                    Wait::Until(_) => TaskState::Until,
+                },
+            })
+            .collect()
+    }
+
+    /// Returns the number of tasks.
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// Returns `true` if there are no tasks.
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Returns the number of ticks passed.
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Returns the total time passed, the sum of `dt` of all ticks.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    // Resumes the task if it is due. Returns `false` if the task has finished.
+    fn run_task(&mut self, lua: &Lua, i: usize) -> Result<bool> {
+        let args = match &mut self.tasks[i].wait {
+            Wait::Ready(args) => args
+                .iter()
+                .map(|key| lua.registry_value(key))
+                .collect::<Result<MultiValue>>()?,
+            Wait::Ticks { remaining, since } => {
+                *remaining = remaining.saturating_sub(1);
+                if *remaining > 0 {
+                    return Ok(true);
+                }
+                MultiValue::from_vec(vec![Value::Number(self.time - *since)])
+            }
+            Wait::Event(_) => return Ok(true),
+            Wait::Until(key) => {
+                let predicate: Function = lua.registry_value(key)?;
+                match predicate.call::<_, Value>(())? {
+                    Value::Nil | Value::Boolean(false) => return Ok(true),
+                    value => MultiValue::from_vec(vec![value]),
+                }
+            }
+        };
+
+        let thread: Thread = lua.registry_value(&self.tasks[i].thread)?;
+        let results = thread.resume::<_, MultiValue>(args)?;
+        if thread.status() != ThreadStatus::Resumable {
+            return Ok(false);
+        }
+
+        let marker: Table = lua.registry_value(&self.marker)?;
+        let mut results = results.into_iter();
+        let wait = match results.next() {
+            Some(Value::Table(t)) if t == marker => {
+                let kind = results.next();
+                let param = results.next().unwrap_or(Value::Nil);
+                match (kind, param) {
+                    (Some(Value::String(kind)), Value::Integer(n)) if kind == "ticks" => {
+                        self.wait_ticks(n as f64)
+                    }
+                    (Some(Value::String(kind)), Value::Number(n)) if kind == "ticks" => {
+                        self.wait_ticks(n)
+                    }
+                    (Some(Value::String(kind)), Value::String(name)) if kind == "event" => {
+                        Wait::Event(name.to_str()?.to_string())
+                    }
+                    (Some(Value::String(kind)), f @ Value::Function(_)) if kind == "until" => {
+                        Wait::Until(lua.create_registry_value(f)?)
+                    }
+                    _ => return Err(Error::runtime("invalid wait request")),
+                }
+            }
+            _ => self.wait_ticks(1.0),
+        };
+        self.tasks[i].wait = wait;
+        Ok(true)
+    }
+
+    fn wait_ticks(&self, n: f64) -> Wait {
+        // `wait(0)` behaves as `wait(1)`: a task is resumed at most once per tick
+        Wait::Ticks {
+            remaining: (n.ceil() as u64).max(1),
+            since: self.time,
+        }
+    }
+}
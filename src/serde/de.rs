@@ -2,22 +2,31 @@ use std::cell::RefCell;
 use std::os::raw::c_void;
 use std::rc::Rc;
 use std::result::Result as StdResult;
+use std::str;
 use std::string::String as StdString;
 
 use rustc_hash::FxHashSet;
-use serde::de::{self, IntoDeserializer};
+use serde::de::{self, DeserializeOwned, IntoDeserializer};
 
+use super::path::{PathSegment, PathTracker};
 use crate::error::{Error, Result};
+use crate::string::String;
 use crate::table::{Table, TablePairs, TableSequence};
 use crate::userdata::AnyUserData;
 use crate::value::Value;
 
+type Strings<'lua> = Rc<RefCell<Vec<String<'lua>>>>;
+
 /// A struct for deserializing Lua values into Rust values.
 #[derive(Debug)]
 pub struct Deserializer<'lua> {
     value: Value<'lua>,
     options: Options,
     visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+    strings: Option<Strings<'lua>>,
+    path: Rc<PathTracker>,
+    // The value is a table key
+    is_key: bool,
 }
 
 /// A struct with options to change default deserializer behavior.
@@ -47,6 +56,23 @@ pub struct Options {
     ///
     /// Default: **false**
     pub sort_keys: bool,
+
+    /// If true, [`LuaSerdeExt::from_value_with`] reports the errors of all invalid values
+    /// instead of stopping at the first one.
+    ///
+    /// Every error is prefixed with the path of the value, such as `units[3].name: ...`.
+    /// Has no effect when the [`Deserializer`] is used directly.
+    ///
+    /// The value is deserialized again after every error with the invalid values skipped, so
+    /// the cost grows with the number of errors. Collecting stops after 100 attempts.
+    ///
+    /// Default: **false**
+    ///
+    /// [`LuaSerdeExt::from_value_with`]: crate::LuaSerdeExt::from_value_with
+    pub collect_errors: bool,
+
+    // Number keys of tables are read as strings (used by the `json` module)
+    pub(crate) number_keys_as_strings: bool,
 }
 
 impl Default for Options {
@@ -62,6 +88,8 @@ impl Options {
             deny_unsupported_types: true,
             deny_recursive_tables: true,
             sort_keys: false,
+            collect_errors: false,
+            number_keys_as_strings: false,
         }
     }
 
@@ -91,6 +119,43 @@ impl Options {
         self.sort_keys = enabled;
         self
     }
+
+    /// Sets [`collect_errors`] option.
+    ///
+    /// [`collect_errors`]: #structfield.collect_errors
+    #[must_use]
+    pub const fn collect_errors(mut self, enabled: bool) -> Self {
+        self.collect_errors = enabled;
+        self
+    }
+}
+
+/// Keeps alive the Lua strings borrowed by values deserialized with
+/// [`LuaSerdeExt::from_value_borrowed`].
+///
+/// Strings are released when the arena is dropped.
+///
+/// [`LuaSerdeExt::from_value_borrowed`]: crate::LuaSerdeExt::from_value_borrowed
+#[derive(Debug, Default)]
+pub struct StringArena<'lua> {
+    strings: Strings<'lua>,
+}
+
+impl<'lua> StringArena<'lua> {
+    /// Creates a new empty arena.
+    pub fn new() -> Self {
+        StringArena::default()
+    }
+
+    /// Returns the number of strings kept alive by the arena.
+    pub fn len(&self) -> usize {
+        self.strings.borrow().len()
+    }
+
+    /// Returns `true` if the arena keeps no strings.
+    pub fn is_empty(&self) -> bool {
+        self.strings.borrow().is_empty()
+    }
 }
 
 impl<'lua> Deserializer<'lua> {
@@ -105,6 +170,21 @@ impl<'lua> Deserializer<'lua> {
             value,
             options,
             visited: Rc::new(RefCell::new(FxHashSet::default())),
+            strings: None,
+            path: Rc::new(PathTracker::default()),
+            is_key: false,
+        }
+    }
+
+    // Strings are borrowed from Lua and kept alive by the arena
+    pub(crate) fn new_borrowed(
+        value: Value<'lua>,
+        options: Options,
+        arena: &StringArena<'lua>,
+    ) -> Self {
+        Deserializer {
+            strings: Some(Rc::clone(&arena.strings)),
+            ..Self::new_with_options(value, options)
         }
     }
 
@@ -112,11 +192,65 @@ impl<'lua> Deserializer<'lua> {
         value: Value<'lua>,
         options: Options,
         visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+        strings: Option<Strings<'lua>>,
+        path: Rc<PathTracker>,
     ) -> Self {
         Deserializer {
             value,
             options,
             visited,
+            strings,
+            path,
+            is_key: false,
+        }
+    }
+
+    fn deserialize_sequence<'de, V>(self, visitor: V, fixed_len: bool) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value {
+            #[cfg(feature = "luau")]
+            Value::Vector(vec) => {
+                let mut deserializer = VecDeserializer {
+                    vec,
+                    next: 0,
+                    options: self.options,
+                    visited: self.visited,
+                    path: self.path,
+                };
+                visitor.visit_seq(&mut deserializer)
+            }
+            Value::Table(t) => {
+                let _guard = RecursionGuard::new(&t, &self.visited);
+
+                let len = t.raw_len();
+                let mut deserializer = SeqDeserializer {
+                    seq: t.sequence_values(),
+                    index: 0,
+                    fixed_len,
+                    options: self.options,
+                    visited: self.visited,
+                    strings: self.strings,
+                    path: self.path,
+                };
+                let seq = visitor.visit_seq(&mut deserializer)?;
+                if deserializer.seq.count() == 0 {
+                    Ok(seq)
+                } else {
+                    Err(de::Error::invalid_length(
+                        len,
+                        &"fewer elements in the table",
+                    ))
+                }
+            }
+            Value::UserData(ud) if ud.is_serializable() => serde_userdata(ud, |value| {
+                serde::Deserializer::deserialize_seq(value, visitor)
+            }),
+            value => Err(de::Error::invalid_type(
+                de::Unexpected::Other(value.type_name()),
+                &"table",
+            )),
         }
     }
 }
@@ -129,6 +263,12 @@ impl<'lua, 'de> serde::Deserializer<'de> for Deserializer<'lua> {
     where
         V: de::Visitor<'de>,
     {
+        #[cfg(feature = "lua51_civ6")]
+        if let Value::Other(ref v) = self.value {
+            if let Some(n) = v.as_u64()? {
+                return visitor.visit_u64(n);
+            }
+        }
         match self.value {
             Value::Nil => visitor.visit_unit(),
             Value::Boolean(b) => visitor.visit_bool(b),
@@ -138,9 +278,21 @@ impl<'lua, 'de> serde::Deserializer<'de> for Deserializer<'lua> {
             Value::Number(n) => visitor.visit_f64(n.into()),
             #[cfg(feature = "luau")]
             Value::Vector(_) => self.deserialize_seq(visitor),
-            Value::String(s) => match s.to_str() {
-                Ok(s) => visitor.visit_str(s),
-                Err(_) => visitor.visit_bytes(s.as_bytes()),
+            Value::String(s) => match self.strings {
+                Some(strings) => {
+                    // SAFETY: the string data is not moved by Lua and the arena keeps the string
+                    // alive while the borrowed values (limited by the arena lifetime) exist
+                    let bytes = unsafe { &*(s.as_bytes() as *const [u8]) };
+                    strings.borrow_mut().push(s);
+                    match str::from_utf8(bytes) {
+                        Ok(s) => visitor.visit_borrowed_str(s),
+                        Err(_) => visitor.visit_borrowed_bytes(bytes),
+                    }
+                }
+                None => match s.to_str() {
+                    Ok(s) => visitor.visit_str(s),
+                    Err(_) => visitor.visit_bytes(s.as_bytes()),
+                },
             },
             Value::Table(ref t) if t.raw_len() > 0 || t.is_array() => self.deserialize_seq(visitor),
             Value::Table(_) => self.deserialize_map(visitor),
@@ -156,11 +308,8 @@ impl<'lua, 'de> serde::Deserializer<'de> for Deserializer<'lua> {
                 let buf = std::slice::from_raw_parts(buf as *const u8, size);
                 visitor.visit_bytes(buf)
             },
-            Value::Function(_)
-            | Value::Thread(_)
-            | Value::UserData(_)
-            | Value::LightUserData(_)
-            | Value::Error(_) => {
+            // Functions, threads, userdata, errors and runtime specific values
+            _ => {
                 if self.options.deny_unsupported_types {
                     let msg = format!("unsupported value type `{}`", self.value.type_name());
                     Err(de::Error::custom(msg))
@@ -234,6 +383,8 @@ impl<'lua, 'de> serde::Deserializer<'de> for Deserializer<'lua> {
             value,
             options: self.options,
             visited: self.visited,
+            strings: self.strings,
+            path: self.path,
         })
     }
 
@@ -242,44 +393,7 @@ impl<'lua, 'de> serde::Deserializer<'de> for Deserializer<'lua> {
     where
         V: de::Visitor<'de>,
     {
-        match self.value {
-            #[cfg(feature = "luau")]
-            Value::Vector(vec) => {
-                let mut deserializer = VecDeserializer {
-                    vec,
-                    next: 0,
-                    options: self.options,
-                    visited: self.visited,
-                };
-                visitor.visit_seq(&mut deserializer)
-            }
-            Value::Table(t) => {
-                let _guard = RecursionGuard::new(&t, &self.visited);
-
-                let len = t.raw_len();
-                let mut deserializer = SeqDeserializer {
-                    seq: t.sequence_values(),
-                    options: self.options,
-                    visited: self.visited,
-                };
-                let seq = visitor.visit_seq(&mut deserializer)?;
-                if deserializer.seq.count() == 0 {
-                    Ok(seq)
-                } else {
-                    Err(de::Error::invalid_length(
-                        len,
-                        &"fewer elements in the table",
-                    ))
-                }
-            }
-            Value::UserData(ud) if ud.is_serializable() => {
-                serde_userdata(ud, |value| value.deserialize_seq(visitor))
-            }
-            value => Err(de::Error::invalid_type(
-                de::Unexpected::Other(value.type_name()),
-                &"table",
-            )),
-        }
+        self.deserialize_sequence(visitor, false)
     }
 
     #[inline]
@@ -287,7 +401,7 @@ impl<'lua, 'de> serde::Deserializer<'de> for Deserializer<'lua> {
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        self.deserialize_sequence(visitor, true)
     }
 
     #[inline]
@@ -300,7 +414,7 @@ impl<'lua, 'de> serde::Deserializer<'de> for Deserializer<'lua> {
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        self.deserialize_sequence(visitor, true)
     }
 
     #[inline]
@@ -317,6 +431,9 @@ impl<'lua, 'de> serde::Deserializer<'de> for Deserializer<'lua> {
                     value: None,
                     options: self.options,
                     visited: self.visited,
+                    strings: self.strings,
+                    path: self.path,
+                    segment: None,
                     processed: 0,
                 };
                 let map = visitor.visit_map(&mut deserializer)?;
@@ -388,16 +505,42 @@ impl<'lua, 'de> serde::Deserializer<'de> for Deserializer<'lua> {
         }
     }
 
+    #[inline]
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        // Number keys are read as strings, like the keys of JSON objects
+        let number_key = self.is_key && self.options.number_keys_as_strings;
+        match self.value {
+            Value::Integer(i) if number_key => visitor.visit_string(i.to_string()),
+            Value::Number(n) if number_key => visitor.visit_string(n.to_string()),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
     serde::forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char bytes
         byte_buf identifier ignored_any
     }
 }
 
 struct SeqDeserializer<'lua> {
     seq: TableSequence<'lua, Value<'lua>>,
+    index: usize,
+    // Skipping elements would shift the rest of a tuple
+    fixed_len: bool,
     options: Options,
     visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+    strings: Option<Strings<'lua>>,
+    path: Rc<PathTracker>,
 }
 
 impl<'lua, 'de> de::SeqAccess<'de> for SeqDeserializer<'lua> {
@@ -411,14 +554,24 @@ impl<'lua, 'de> de::SeqAccess<'de> for SeqDeserializer<'lua> {
             match self.seq.next() {
                 Some(value) => {
                     let value = value?;
-                    let skip = check_value_for_skip(&value, self.options, &self.visited)
-                        .map_err(|err| Error::DeserializeError(err.to_string()))?;
+                    self.index += 1;
+                    let segment = PathSegment::Index(self.index as i64);
+                    if !self.fixed_len && self.path.is_skipped(&segment) {
+                        continue;
+                    }
+                    let skip = self.path.enter(segment.clone(), || {
+                        check_value_for_skip(&value, self.options, &self.visited)
+                            .map_err(|err| Error::DeserializeError(err.to_string()))
+                    })?;
                     if skip {
                         continue;
                     }
                     let visited = Rc::clone(&self.visited);
-                    let deserializer = Deserializer::from_parts(value, self.options, visited);
-                    return seed.deserialize(deserializer).map(Some);
+                    let strings = self.strings.clone();
+                    let path = Rc::clone(&self.path);
+                    let deserializer =
+                        Deserializer::from_parts(value, self.options, visited, strings, path);
+                    return (self.path).enter(segment, || seed.deserialize(deserializer).map(Some));
                 }
                 None => return Ok(None),
             }
@@ -439,6 +592,7 @@ struct VecDeserializer {
     next: usize,
     options: Options,
     visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+    path: Rc<PathTracker>,
 }
 
 #[cfg(feature = "luau")]
@@ -453,9 +607,16 @@ impl<'de> de::SeqAccess<'de> for VecDeserializer {
             Some(&n) => {
                 self.next += 1;
                 let visited = Rc::clone(&self.visited);
-                let deserializer =
-                    Deserializer::from_parts(Value::Number(n as _), self.options, visited);
-                seed.deserialize(deserializer).map(Some)
+                let path = Rc::clone(&self.path);
+                let deserializer = Deserializer::from_parts(
+                    Value::Number(n as _),
+                    self.options,
+                    visited,
+                    None,
+                    path,
+                );
+                let segment = PathSegment::Index(self.next as i64);
+                (self.path).enter(segment, || seed.deserialize(deserializer).map(Some))
             }
             None => Ok(None),
         }
@@ -513,6 +674,10 @@ struct MapDeserializer<'lua> {
     value: Option<Value<'lua>>,
     options: Options,
     visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+    strings: Option<Strings<'lua>>,
+    path: Rc<PathTracker>,
+    // Path segment of the current value
+    segment: Option<PathSegment>,
     processed: usize,
 }
 
@@ -527,18 +692,32 @@ impl<'lua, 'de> de::MapAccess<'de> for MapDeserializer<'lua> {
             match self.pairs.next() {
                 Some(item) => {
                     let (key, value) = item?;
-                    let skip_key = check_value_for_skip(&key, self.options, &self.visited)
-                        .map_err(|err| Error::DeserializeError(err.to_string()))?;
-                    let skip_value = check_value_for_skip(&value, self.options, &self.visited)
-                        .map_err(|err| Error::DeserializeError(err.to_string()))?;
-                    if skip_key || skip_value {
+                    let segment = PathSegment::from_key(&key);
+                    if self.path.is_skipped(&segment) {
+                        continue;
+                    }
+                    let skip = self.path.enter(segment.clone(), || {
+                        let check = |value| {
+                            check_value_for_skip(value, self.options, &self.visited)
+                                .map_err(|err| Error::DeserializeError(err.to_string()))
+                        };
+                        let skip_key = check(&key)?;
+                        Ok(check(&value)? || skip_key)
+                    })?;
+                    if skip {
                         continue;
                     }
                     self.processed += 1;
                     self.value = Some(value);
+                    self.segment = Some(segment.clone());
                     let visited = Rc::clone(&self.visited);
-                    let key_de = Deserializer::from_parts(key, self.options, visited);
-                    return seed.deserialize(key_de).map(Some);
+                    let strings = self.strings.clone();
+                    let path = Rc::clone(&self.path);
+                    let key_de = Deserializer {
+                        is_key: true,
+                        ..Deserializer::from_parts(key, self.options, visited, strings, path)
+                    };
+                    return (self.path).enter(segment, || seed.deserialize(key_de).map(Some));
                 }
                 None => return Ok(None),
             }
@@ -552,7 +731,12 @@ impl<'lua, 'de> de::MapAccess<'de> for MapDeserializer<'lua> {
         match self.value.take() {
             Some(value) => {
                 let visited = Rc::clone(&self.visited);
-                seed.deserialize(Deserializer::from_parts(value, self.options, visited))
+                let strings = self.strings.clone();
+                let path = Rc::clone(&self.path);
+                let deserializer =
+                    Deserializer::from_parts(value, self.options, visited, strings, path);
+                let segment = mlua_expect!(self.segment.take(), "value without a key");
+                (self.path).enter(segment, || seed.deserialize(deserializer))
             }
             None => Err(de::Error::custom("value is missing")),
         }
@@ -571,6 +755,8 @@ struct EnumDeserializer<'lua> {
     value: Option<Value<'lua>>,
     options: Options,
     visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+    strings: Option<Strings<'lua>>,
+    path: Rc<PathTracker>,
 }
 
 impl<'lua, 'de> de::EnumAccess<'de> for EnumDeserializer<'lua> {
@@ -581,20 +767,36 @@ impl<'lua, 'de> de::EnumAccess<'de> for EnumDeserializer<'lua> {
     where
         T: de::DeserializeSeed<'de>,
     {
-        let variant = self.variant.into_deserializer();
         let variant_access = VariantDeserializer {
+            segment: PathSegment::Key(self.variant.clone()),
             value: self.value,
             options: self.options,
             visited: self.visited,
+            strings: self.strings,
+            path: self.path,
         };
+        let variant = self.variant.into_deserializer();
         seed.deserialize(variant).map(|v| (v, variant_access))
     }
 }
 
 struct VariantDeserializer<'lua> {
+    segment: PathSegment,
     value: Option<Value<'lua>>,
     options: Options,
     visited: Rc<RefCell<FxHashSet<*const c_void>>>,
+    strings: Option<Strings<'lua>>,
+    path: Rc<PathTracker>,
+}
+
+impl<'lua> VariantDeserializer<'lua> {
+    fn into_value_deserializer(self) -> Option<(PathSegment, Rc<PathTracker>, Deserializer<'lua>)> {
+        let value = self.value?;
+        let path = Rc::clone(&self.path);
+        let deserializer =
+            Deserializer::from_parts(value, self.options, self.visited, self.strings, self.path);
+        Some((self.segment, path, deserializer))
+    }
 }
 
 impl<'lua, 'de> de::VariantAccess<'de> for VariantDeserializer<'lua> {
@@ -614,9 +816,9 @@ impl<'lua, 'de> de::VariantAccess<'de> for VariantDeserializer<'lua> {
     where
         T: de::DeserializeSeed<'de>,
     {
-        match self.value {
-            Some(value) => {
-                seed.deserialize(Deserializer::from_parts(value, self.options, self.visited))
+        match self.into_value_deserializer() {
+            Some((segment, path, deserializer)) => {
+                path.enter(segment, || seed.deserialize(deserializer))
             }
             None => Err(de::Error::invalid_type(
                 de::Unexpected::UnitVariant,
@@ -629,11 +831,10 @@ impl<'lua, 'de> de::VariantAccess<'de> for VariantDeserializer<'lua> {
     where
         V: de::Visitor<'de>,
     {
-        match self.value {
-            Some(value) => serde::Deserializer::deserialize_seq(
-                Deserializer::from_parts(value, self.options, self.visited),
-                visitor,
-            ),
+        match self.into_value_deserializer() {
+            Some((segment, path, deserializer)) => {
+                path.enter(segment, || deserializer.deserialize_sequence(visitor, true))
+            }
             None => Err(de::Error::invalid_type(
                 de::Unexpected::UnitVariant,
                 &"tuple variant",
@@ -645,11 +846,10 @@ impl<'lua, 'de> de::VariantAccess<'de> for VariantDeserializer<'lua> {
     where
         V: de::Visitor<'de>,
     {
-        match self.value {
-            Some(value) => serde::Deserializer::deserialize_map(
-                Deserializer::from_parts(value, self.options, self.visited),
-                visitor,
-            ),
+        match self.into_value_deserializer() {
+            Some((segment, path, deserializer)) => path.enter(segment, || {
+                serde::Deserializer::deserialize_map(deserializer, visitor)
+            }),
             None => Err(de::Error::invalid_type(
                 de::Unexpected::UnitVariant,
                 &"struct variant",
@@ -658,6 +858,50 @@ impl<'lua, 'de> de::VariantAccess<'de> for VariantDeserializer<'lua> {
     }
 }
 
+// Limit of deserialization attempts when collecting errors
+const MAX_COLLECTING_PASSES: usize = 100;
+
+// Deserializes `value` again after every failure with the failed values skipped,
+// collecting the errors of all of them
+pub(crate) fn deserialize_collecting<T: DeserializeOwned>(
+    value: Value,
+    options: Options,
+) -> Result<T> {
+    let tracker = Rc::new(PathTracker::default());
+    let mut errors = Vec::new();
+    for pass in 0.. {
+        if pass == MAX_COLLECTING_PASSES {
+            errors.push("too many errors, the rest is not reported".to_string());
+            break;
+        }
+        let visited = Rc::new(RefCell::new(FxHashSet::default()));
+        let path = Rc::clone(&tracker);
+        let deserializer = Deserializer::from_parts(value.clone(), options, visited, None, path);
+        let msg = match T::deserialize(deserializer) {
+            Ok(value) if errors.is_empty() => return Ok(value),
+            Ok(_) => break,
+            Err(Error::DeserializeError(msg)) => msg,
+            Err(err) => return Err(err),
+        };
+
+        // Failures caused by skipping a value are not reported
+        let mut path = tracker.take_error_path().unwrap_or_default();
+        if !tracker.is_skipped_within(&path) {
+            errors.push(msg);
+        }
+        // The value is still read (e.g. a tuple element), so its parent is skipped instead
+        while !path.is_empty() && tracker.is_skipped_path(&path) {
+            path.pop();
+        }
+        if path.is_empty() {
+            break;
+        }
+        tracker.skip(path);
+    }
+
+    Err(Error::DeserializeError(errors.join("\n")))
+}
+
 // Adds `ptr` to the `visited` map and removes on drop
 // Used to track recursive tables but allow to traverse same tables multiple times
 struct RecursionGuard {
@@ -698,6 +942,8 @@ pub(crate) fn check_value_for_skip(
             }
         }
         Value::UserData(ud) if ud.is_serializable() => {}
+        #[cfg(feature = "lua51_civ6")]
+        Value::Other(v) if v.is_ui64() => {}
         Value::Function(_)
         | Value::Thread(_)
         | Value::UserData(_)
@@ -707,6 +953,8 @@ pub(crate) fn check_value_for_skip(
         {
             return Ok(true); // skip
         }
+        #[cfg(feature = "lua51_civ6")]
+        Value::Other(_) if !options.deny_unsupported_types => return Ok(true), // skip
         _ => {}
     }
     Ok(false) // do not skip
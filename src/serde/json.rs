@@ -0,0 +1,213 @@
+use std::fmt;
+
+use serde::de::{MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::de;
+use super::ser::Options as SerializeOptions;
+use super::LuaSerdeExt;
+use crate::error::{Error, Result};
+use crate::lua::Lua;
+use crate::string::String;
+use crate::table::Table;
+use crate::util::json::JsonValue;
+use crate::value::Value;
+
+pub(crate) fn install(lua: &Lua) -> Result<()> {
+    let json = lua.create_table()?;
+    json.set("null", lua.null())?;
+    json.set(
+        "encode",
+        lua.create_function(|lua, (value, opts): (Value, Option<Table>)| {
+            let data = encode(lua, value, EncodeOptions::from_table(opts)?)?;
+            lua.create_string(data)
+        })?,
+    )?;
+    json.set(
+        "decode",
+        lua.create_function(|lua, (data, opts): (String, Option<Table>)| {
+            decode(lua, data.as_bytes(), decode_options(opts)?)
+        })?,
+    )?;
+
+    // Also available via `require("json")`
+    let globals = lua.globals();
+    if let Some(package) = globals.get::<_, Option<Table>>("package")? {
+        if let Some(loaded) = package.get::<_, Option<Table>>("loaded")? {
+            loaded.set("json", json.clone())?;
+        }
+    }
+    globals.set("json", json)
+}
+
+struct EncodeOptions {
+    options: de::Options,
+    // Indentation of the pretty-printed output
+    indent: Option<std::string::String>,
+}
+
+impl EncodeOptions {
+    fn from_table(opts: Option<Table>) -> Result<Self> {
+        let mut options = de::Options::new();
+        // JSON object keys are strings
+        options.number_keys_as_strings = true;
+        let mut indent = None;
+        if let Some(opts) = opts {
+            if let Some(enabled) = opts.get("deny_unsupported_types")? {
+                options.deny_unsupported_types = enabled;
+            }
+            if let Some(enabled) = opts.get("deny_recursive_tables")? {
+                options.deny_recursive_tables = enabled;
+            }
+            if let Some(enabled) = opts.get("sort_keys")? {
+                options.sort_keys = enabled;
+            }
+            match opts.get::<_, Value>("pretty")? {
+                Value::Nil | Value::Boolean(false) => {}
+                Value::Boolean(true) => indent = Some("  ".to_string()),
+                Value::Integer(n) if n >= 0 => indent = Some(" ".repeat(n as usize)),
+                Value::String(s) => indent = Some(s.to_str()?.to_string()),
+                value => {
+                    let msg = format!("invalid `pretty` option of type {}", value.type_name());
+                    return Err(Error::SerializeError(msg));
+                }
+            }
+        }
+        Ok(EncodeOptions { options, indent })
+    }
+}
+
+fn decode_options(opts: Option<Table>) -> Result<SerializeOptions> {
+    let mut options = SerializeOptions::new();
+    if let Some(opts) = opts {
+        if let Some(enabled) = opts.get("set_array_metatable")? {
+            options.set_array_metatable = enabled;
+        }
+        if let Some(enabled) = opts.get("null")? {
+            options.serialize_unit_to_null = enabled;
+            options.serialize_none_to_null = enabled;
+        }
+    }
+    Ok(options)
+}
+
+fn encode(lua: &Lua, value: Value, options: EncodeOptions) -> Result<Vec<u8>> {
+    let json = lua.from_value_with::<JsonValue>(value, options.options)?;
+    let text = match options.indent {
+        Some(indent) => {
+            let mut text = std::string::String::new();
+            json.write_pretty(&mut text, &indent, 0);
+            text
+        }
+        None => json.to_string(),
+    };
+    Ok(text.into_bytes())
+}
+
+fn decode<'lua>(lua: &'lua Lua, data: &[u8], options: SerializeOptions) -> Result<Value<'lua>> {
+    let json = JsonValue::parse(data).map_err(|err| Error::DeserializeError(err.to_string()))?;
+    lua.to_value_with(&json, options)
+}
+
+impl Serialize for JsonValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            JsonValue::Null => serializer.serialize_unit(),
+            JsonValue::Bool(b) => serializer.serialize_bool(*b),
+            JsonValue::Integer(i) => serializer.serialize_i64(*i),
+            JsonValue::Number(n) => serializer.serialize_f64(*n),
+            JsonValue::String(s) => serializer.serialize_str(s),
+            JsonValue::Array(array) => {
+                let mut seq = serializer.serialize_seq(Some(array.len()))?;
+                for value in array {
+                    seq.serialize_element(value)?;
+                }
+                seq.end()
+            }
+            JsonValue::Object(object) => {
+                let mut map = serializer.serialize_map(Some(object.len()))?;
+                for (key, value) in object {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for JsonValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        deserializer.deserialize_any(JsonVisitor)
+    }
+}
+
+struct JsonVisitor;
+
+impl<'de> Visitor<'de> for JsonVisitor {
+    type Value = JsonValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any valid JSON value")
+    }
+
+    fn visit_unit<E>(self) -> std::result::Result<JsonValue, E> {
+        Ok(JsonValue::Null)
+    }
+
+    fn visit_none<E>(self) -> std::result::Result<JsonValue, E> {
+        Ok(JsonValue::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<JsonValue, D::Error> {
+        JsonValue::deserialize(deserializer)
+    }
+
+    fn visit_bool<E>(self, b: bool) -> std::result::Result<JsonValue, E> {
+        Ok(JsonValue::Bool(b))
+    }
+
+    fn visit_i64<E>(self, i: i64) -> std::result::Result<JsonValue, E> {
+        Ok(JsonValue::Integer(i))
+    }
+
+    fn visit_u64<E>(self, u: u64) -> std::result::Result<JsonValue, E> {
+        Ok(match i64::try_from(u) {
+            Ok(i) => JsonValue::Integer(i),
+            Err(_) => JsonValue::Number(u as f64),
+        })
+    }
+
+    fn visit_f64<E>(self, n: f64) -> std::result::Result<JsonValue, E> {
+        Ok(JsonValue::Number(n))
+    }
+
+    fn visit_str<E>(self, s: &str) -> std::result::Result<JsonValue, E> {
+        Ok(JsonValue::String(s.to_string()))
+    }
+
+    fn visit_string<E>(self, s: std::string::String) -> std::result::Result<JsonValue, E> {
+        Ok(JsonValue::String(s))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<JsonValue, A::Error> {
+        let mut array = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(value) = seq.next_element()? {
+            array.push(value);
+        }
+        Ok(JsonValue::Array(array))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<JsonValue, A::Error> {
+        let mut object = Vec::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some(entry) = map.next_entry()? {
+            object.push(entry);
+        }
+        // Lua tables have no order, so the keys are sorted to keep the output stable
+        object.sort_by(|(k1, _): &(std::string::String, _), (k2, _)| k1.cmp(k2));
+        Ok(JsonValue::Object(object))
+    }
+}
@@ -2,13 +2,16 @@
 
 use std::os::raw::c_void;
 
-use serde::{de::DeserializeOwned, ser::Serialize};
+use serde::{
+    de::{Deserialize, DeserializeOwned},
+    ser::Serialize,
+};
 
 use crate::error::Result;
 use crate::lua::Lua;
 use crate::private::Sealed;
 use crate::table::Table;
-use crate::util::check_stack;
+use crate::util::{check_stack, REGISTRY_KEYS};
 use crate::value::Value;
 
 /// Trait for serializing/deserializing Lua values using Serde.
@@ -191,6 +194,45 @@ pub trait LuaSerdeExt: Sealed {
     #[allow(clippy::wrong_self_convention)]
     fn from_value_with<T: DeserializeOwned>(&self, value: Value, options: de::Options)
         -> Result<T>;
+
+    /// Deserializes a [`Value`] into an object that borrows strings from Lua.
+    ///
+    /// Strings and byte strings (`&str`, `&[u8]`, `Cow<str>` with `#[serde(borrow)]`) are not
+    /// copied. The Lua strings are kept alive by the `arena`, which must outlive the result.
+    ///
+    /// Requires `feature = "serialize"`
+    ///
+    /// [`Value`]: crate::Value
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mlua::{Lua, Result, LuaSerdeExt, StringArena};
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize, Debug, PartialEq)]
+    /// struct User<'a> {
+    ///     name: &'a str,
+    ///     tags: Vec<&'a str>,
+    /// }
+    ///
+    /// fn main() -> Result<()> {
+    ///     let lua = Lua::new();
+    ///     let val = lua.load(r#"{name = "John Smith", tags = {"admin"}}"#).eval()?;
+    ///     let arena = StringArena::new();
+    ///     let u: User = lua.from_value_borrowed(val, &arena)?;
+    ///
+    ///     assert_eq!(u, User { name: "John Smith", tags: vec!["admin"] });
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[allow(clippy::wrong_self_convention)]
+    fn from_value_borrowed<'lua, 'a, T: Deserialize<'a>>(
+        &'lua self,
+        value: Value<'lua>,
+        arena: &'a de::StringArena<'lua>,
+    ) -> Result<T>;
 }
 
 impl LuaSerdeExt for Lua {
@@ -230,8 +272,39 @@ impl LuaSerdeExt for Lua {
     where
         T: DeserializeOwned,
     {
+        if options.collect_errors {
+            return de::deserialize_collecting(value, options);
+        }
         T::deserialize(de::Deserializer::new_with_options(value, options))
     }
+
+    fn from_value_borrowed<'lua, 'a, T>(
+        &'lua self,
+        value: Value<'lua>,
+        arena: &'a de::StringArena<'lua>,
+    ) -> Result<T>
+    where
+        T: Deserialize<'a>,
+    {
+        let options = de::Options::default();
+        T::deserialize(de::Deserializer::new_borrowed(value, options, arena))
+    }
+}
+
+// Overwrites the fields of `into` with the ones of `from`, merging nested maps
+pub(crate) fn merge_tables(into: &Table, from: Table) -> Result<()> {
+    for pair in from.pairs::<Value, Value>() {
+        let (key, value) = pair?;
+        match (into.raw_get::<_, Value>(key.clone())?, value) {
+            (Value::Table(dst), Value::Table(src))
+                if !dst.is_array() && !src.is_array() && src.raw_len() == 0 =>
+            {
+                merge_tables(&dst, src)?
+            }
+            (_, value) => into.raw_set(key, value)?,
+        }
+    }
+    Ok(())
 }
 
 // Uses 2 stack spaces and calls checkstack.
@@ -244,19 +317,20 @@ pub(crate) unsafe fn init_metatables(state: *mut ffi::lua_State) -> Result<()> {
         ffi::lua_pushboolean(state, 0);
         ffi::lua_rawset(state, -3);
 
-        let array_metatable_key = &ARRAY_METATABLE_REGISTRY_KEY as *const u8 as *const c_void;
+        let array_metatable_key = &REGISTRY_KEYS.array_mt as *const u8 as *const c_void;
         ffi::lua_rawsetp(state, ffi::LUA_REGISTRYINDEX, array_metatable_key);
     })
 }
 
 pub(crate) unsafe fn push_array_metatable(state: *mut ffi::lua_State) {
-    let array_metatable_key = &ARRAY_METATABLE_REGISTRY_KEY as *const u8 as *const c_void;
+    let array_metatable_key = &REGISTRY_KEYS.array_mt as *const u8 as *const c_void;
     ffi::lua_rawgetp(state, ffi::LUA_REGISTRYINDEX, array_metatable_key);
 }
 
-static ARRAY_METATABLE_REGISTRY_KEY: u8 = 0;
-
 pub mod de;
+#[cfg(feature = "json")]
+pub(crate) mod json;
+mod path;
 pub mod ser;
 
 #[doc(inline)]
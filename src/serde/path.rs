@@ -0,0 +1,131 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::string::String as StdString;
+
+use rustc_hash::FxHashSet;
+
+use crate::error::{Error, Result};
+use crate::value::Value;
+
+// A step from a table to one of its values
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum PathSegment {
+    Key(StdString),
+    Index(i64),
+    // Any other key, written in brackets as is
+    Other(StdString),
+}
+
+impl PathSegment {
+    pub(crate) fn from_key(key: &Value) -> Self {
+        match key {
+            Value::String(s) => PathSegment::Key(s.to_string_lossy().into_owned()),
+            #[allow(clippy::useless_conversion)]
+            Value::Integer(i) => PathSegment::Index((*i).into()),
+            Value::Number(n) => PathSegment::Other(n.to_string()),
+            Value::Boolean(b) => PathSegment::Other(b.to_string()),
+            key => PathSegment::Other(key.type_name().to_string()),
+        }
+    }
+}
+
+// Keys and indices leading to a value, written like `units[3].promotions.name`
+pub(crate) struct Path<'a>(pub(crate) &'a [PathSegment]);
+
+impl fmt::Display for Path<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) if is_identifier(key) => {
+                    if i > 0 {
+                        f.write_str(".")?;
+                    }
+                    f.write_str(key)?;
+                }
+                PathSegment::Key(key) => write!(f, "[{key:?}]")?,
+                PathSegment::Index(i) => write!(f, "[{i}]")?,
+                PathSegment::Other(key) => write!(f, "[{key}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Tracks the path of the value being (de)serialized, shared by the nested (de)serializers
+#[derive(Debug, Default)]
+pub(crate) struct PathTracker {
+    path: RefCell<Vec<PathSegment>>,
+    // Path of the value that failed, until the error is handled
+    error_path: RefCell<Option<Vec<PathSegment>>>,
+    // Values treated as missing by the deserializer
+    skipped: RefCell<FxHashSet<Vec<PathSegment>>>,
+}
+
+impl PathTracker {
+    // Runs `f` for the value at `segment`, prefixing the innermost error with its path
+    pub(crate) fn enter<T>(
+        &self,
+        segment: PathSegment,
+        f: impl FnOnce() -> Result<T>,
+    ) -> Result<T> {
+        self.path.borrow_mut().push(segment);
+        let res = match f() {
+            Ok(value) => {
+                // The error (if any) was handled by the visitor
+                *self.error_path.borrow_mut() = None;
+                Ok(value)
+            }
+            Err(err) if self.error_path.borrow().is_some() => Err(err),
+            Err(err) => {
+                let path = self.path.borrow().clone();
+                let err = match err {
+                    Error::SerializeError(msg) => {
+                        Error::SerializeError(format!("{}: {msg}", Path(&path)))
+                    }
+                    Error::DeserializeError(msg) => {
+                        Error::DeserializeError(format!("{}: {msg}", Path(&path)))
+                    }
+                    err => err,
+                };
+                *self.error_path.borrow_mut() = Some(path);
+                Err(err)
+            }
+        };
+        self.path.borrow_mut().pop();
+        res
+    }
+
+    pub(crate) fn take_error_path(&self) -> Option<Vec<PathSegment>> {
+        self.error_path.borrow_mut().take()
+    }
+
+    pub(crate) fn skip(&self, path: Vec<PathSegment>) {
+        self.skipped.borrow_mut().insert(path);
+    }
+
+    // Checks if the value at `segment` of the current table is skipped
+    pub(crate) fn is_skipped(&self, segment: &PathSegment) -> bool {
+        let skipped = self.skipped.borrow();
+        if skipped.is_empty() {
+            return false;
+        }
+        let mut path = self.path.borrow().clone();
+        path.push(segment.clone());
+        skipped.contains(&path)
+    }
+
+    // Checks if `path` or any of its descendants is skipped
+    pub(crate) fn is_skipped_within(&self, path: &[PathSegment]) -> bool {
+        (self.skipped.borrow().iter()).any(|skipped| skipped.starts_with(path))
+    }
+
+    pub(crate) fn is_skipped_path(&self, path: &[PathSegment]) -> bool {
+        self.skipped.borrow().contains(path)
+    }
+}
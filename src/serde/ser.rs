@@ -1,5 +1,8 @@
+use std::rc::Rc;
+
 use serde::{ser, Serialize};
 
+use super::path::{PathSegment, PathTracker};
 use super::LuaSerdeExt;
 use crate::error::{Error, Result};
 use crate::lua::Lua;
@@ -11,6 +14,7 @@ use crate::value::{IntoLua, Value};
 pub struct Serializer<'lua> {
     lua: &'lua Lua,
     options: Options,
+    path: Rc<PathTracker>,
 }
 
 /// A struct with options to change default serializer behavior.
@@ -117,10 +121,31 @@ impl<'lua> Serializer<'lua> {
 
     /// Creates a new Lua Serializer with custom options.
     pub fn new_with_options(lua: &'lua Lua, options: Options) -> Self {
-        Serializer { lua, options }
+        let path = Rc::new(PathTracker::default());
+        Serializer { lua, options, path }
+    }
+
+    fn nested(lua: &'lua Lua, options: Options, path: &Rc<PathTracker>) -> Self {
+        let path = Rc::clone(path);
+        Serializer { lua, options, path }
     }
 }
 
+// Serializes the value at `segment` of the current table
+fn to_value_at<'lua, T>(
+    lua: &'lua Lua,
+    value: &T,
+    options: Options,
+    path: &Rc<PathTracker>,
+    segment: PathSegment,
+) -> Result<Value<'lua>>
+where
+    T: Serialize + ?Sized,
+{
+    let serializer = Serializer::nested(lua, options, path);
+    path.enter(segment, || value.serialize(serializer))
+}
+
 macro_rules! lua_serialize_number {
     ($name:ident, $t:ty) => {
         #[inline]
@@ -243,8 +268,8 @@ impl<'lua> ser::Serializer for Serializer<'lua> {
         T: Serialize + ?Sized,
     {
         let table = self.lua.create_table()?;
-        let variant = self.lua.create_string(variant)?;
-        let value = self.lua.to_value_with(value, self.options)?;
+        let segment = PathSegment::Key(variant.to_owned());
+        let value = to_value_at(self.lua, value, self.options, &self.path, segment)?;
         table.raw_set(variant, value)?;
         Ok(Value::Table(table))
     }
@@ -255,7 +280,7 @@ impl<'lua> ser::Serializer for Serializer<'lua> {
         if self.options.set_array_metatable {
             table.set_metatable(Some(self.lua.array_metatable()));
         }
-        Ok(SerializeSeq::new(table, self.options))
+        Ok(SerializeSeq::new(table, self.options, self.path))
     }
 
     #[inline]
@@ -271,7 +296,7 @@ impl<'lua> ser::Serializer for Serializer<'lua> {
     ) -> Result<Self::SerializeTupleStruct> {
         #[cfg(feature = "luau")]
         if name == "Vector" && len == crate::types::Vector::SIZE {
-            return Ok(SerializeSeq::new_vector(self.lua, self.options));
+            return Ok(SerializeSeq::new_vector(self.lua, self.options, self.path));
         }
         _ = name;
         self.serialize_seq(Some(len))
@@ -289,6 +314,7 @@ impl<'lua> ser::Serializer for Serializer<'lua> {
             variant,
             table: self.lua.create_table()?,
             options: self.options,
+            path: self.path,
         })
     }
 
@@ -298,6 +324,7 @@ impl<'lua> ser::Serializer for Serializer<'lua> {
             key: None,
             table: self.lua.create_table_with_capacity(0, len.unwrap_or(0))?,
             options: self.options,
+            path: self.path,
         })
     }
 
@@ -311,6 +338,7 @@ impl<'lua> ser::Serializer for Serializer<'lua> {
                 lua: self.lua,
                 inner: None,
                 options: self.options,
+                path: self.path,
             });
         }
 
@@ -318,6 +346,7 @@ impl<'lua> ser::Serializer for Serializer<'lua> {
             lua: self.lua,
             inner: Some(Value::Table(self.lua.create_table_with_capacity(0, len)?)),
             options: self.options,
+            path: self.path,
         })
     }
 
@@ -333,6 +362,7 @@ impl<'lua> ser::Serializer for Serializer<'lua> {
             variant,
             table: self.lua.create_table_with_capacity(0, len)?,
             options: self.options,
+            path: self.path,
         })
     }
 }
@@ -345,10 +375,11 @@ pub struct SerializeSeq<'lua> {
     table: Option<Table<'lua>>,
     next: usize,
     options: Options,
+    path: Rc<PathTracker>,
 }
 
 impl<'lua> SerializeSeq<'lua> {
-    const fn new(table: Table<'lua>, options: Options) -> Self {
+    const fn new(table: Table<'lua>, options: Options, path: Rc<PathTracker>) -> Self {
         Self {
             lua: table.0.lua,
             #[cfg(feature = "luau")]
@@ -356,19 +387,29 @@ impl<'lua> SerializeSeq<'lua> {
             table: Some(table),
             next: 0,
             options,
+            path,
         }
     }
 
     #[cfg(feature = "luau")]
-    const fn new_vector(lua: &'lua Lua, options: Options) -> Self {
+    const fn new_vector(lua: &'lua Lua, options: Options, path: Rc<PathTracker>) -> Self {
         Self {
             lua,
             vector: Some(crate::types::Vector::zero()),
             table: None,
             next: 0,
             options,
+            path,
         }
     }
+
+    fn next_value<T>(&self, value: &T) -> Result<Value<'lua>>
+    where
+        T: Serialize + ?Sized,
+    {
+        let segment = PathSegment::Index(self.next as i64 + 1);
+        to_value_at(self.lua, value, self.options, &self.path, segment)
+    }
 }
 
 impl<'lua> ser::SerializeSeq for SerializeSeq<'lua> {
@@ -379,7 +420,7 @@ impl<'lua> ser::SerializeSeq for SerializeSeq<'lua> {
     where
         T: Serialize + ?Sized,
     {
-        let value = self.lua.to_value_with(value, self.options)?;
+        let value = self.next_value(value)?;
         let table = self.table.as_ref().unwrap();
         table.raw_seti(self.next + 1, value)?;
         self.next += 1;
@@ -417,7 +458,7 @@ impl<'lua> ser::SerializeTupleStruct for SerializeSeq<'lua> {
     {
         #[cfg(feature = "luau")]
         if let Some(vector) = self.vector.as_mut() {
-            let value = self.lua.to_value_with(value, self.options)?;
+            let value = self.next_value(value)?;
             let value = self.lua.unpack(value)?;
             vector.0[self.next] = value;
             self.next += 1;
@@ -440,6 +481,7 @@ pub struct SerializeTupleVariant<'lua> {
     variant: &'static str,
     table: Table<'lua>,
     options: Options,
+    path: Rc<PathTracker>,
 }
 
 impl<'lua> ser::SerializeTupleVariant for SerializeTupleVariant<'lua> {
@@ -451,7 +493,11 @@ impl<'lua> ser::SerializeTupleVariant for SerializeTupleVariant<'lua> {
         T: Serialize + ?Sized,
     {
         let lua = self.table.0.lua;
-        self.table.raw_push(lua.to_value_with(value, self.options)?)
+        let segment = PathSegment::Index(self.table.raw_len() as i64 + 1);
+        let value = (self.path).enter(PathSegment::Key(self.variant.to_owned()), || {
+            to_value_at(lua, value, self.options, &self.path, segment)
+        })?;
+        self.table.raw_push(value)
     }
 
     fn end(self) -> Result<Value<'lua>> {
@@ -467,6 +513,7 @@ pub struct SerializeMap<'lua> {
     table: Table<'lua>,
     key: Option<Value<'lua>>,
     options: Options,
+    path: Rc<PathTracker>,
 }
 
 impl<'lua> ser::SerializeMap for SerializeMap<'lua> {
@@ -478,7 +525,7 @@ impl<'lua> ser::SerializeMap for SerializeMap<'lua> {
         T: Serialize + ?Sized,
     {
         let lua = self.table.0.lua;
-        self.key = Some(lua.to_value_with(key, self.options)?);
+        self.key = Some(key.serialize(Serializer::nested(lua, self.options, &self.path))?);
         Ok(())
     }
 
@@ -491,7 +538,8 @@ impl<'lua> ser::SerializeMap for SerializeMap<'lua> {
             self.key.take(),
             "serialize_value called before serialize_key"
         );
-        let value = lua.to_value_with(value, self.options)?;
+        let segment = PathSegment::from_key(&key);
+        let value = to_value_at(lua, value, self.options, &self.path, segment)?;
         self.table.raw_set(key, value)
     }
 
@@ -505,6 +553,7 @@ pub struct SerializeStruct<'lua> {
     lua: &'lua Lua,
     inner: Option<Value<'lua>>,
     options: Options,
+    path: Rc<PathTracker>,
 }
 
 impl<'lua> ser::SerializeStruct for SerializeStruct<'lua> {
@@ -517,7 +566,9 @@ impl<'lua> ser::SerializeStruct for SerializeStruct<'lua> {
     {
         match self.inner {
             Some(Value::Table(ref table)) => {
-                table.raw_set(key, self.lua.to_value_with(value, self.options)?)?;
+                let segment = PathSegment::Key(key.to_owned());
+                let value = to_value_at(self.lua, value, self.options, &self.path, segment)?;
+                table.raw_set(key, value)?;
             }
             None if self.options.detect_serde_json_arbitrary_precision => {
                 // A special case for `serde_json::Number` with arbitrary precision.
@@ -555,6 +606,7 @@ pub struct SerializeStructVariant<'lua> {
     variant: &'static str,
     table: Table<'lua>,
     options: Options,
+    path: Rc<PathTracker>,
 }
 
 impl<'lua> ser::SerializeStructVariant for SerializeStructVariant<'lua> {
@@ -566,8 +618,11 @@ impl<'lua> ser::SerializeStructVariant for SerializeStructVariant<'lua> {
         T: Serialize + ?Sized,
     {
         let lua = self.table.0.lua;
-        self.table
-            .raw_set(key, lua.to_value_with(value, self.options)?)?;
+        let segment = PathSegment::Key(key.to_owned());
+        let value = (self.path).enter(PathSegment::Key(self.variant.to_owned()), || {
+            to_value_at(lua, value, self.options, &self.path, segment)
+        })?;
+        self.table.raw_set(key, value)?;
         Ok(())
     }
 
@@ -0,0 +1,148 @@
+use crate::error::{Error, Result};
+use crate::lua::Lua;
+use crate::multi::Variadic;
+use crate::string::String;
+use crate::userdata::{AnyUserData, MetaMethod, UserData, UserDataMethods};
+use crate::value::{FromLua, IntoLua, Value};
+
+/// Handle to a growable string buffer shared between Rust and Lua.
+///
+/// Created by [`Lua::create_string_builder`]. Chunks are accumulated in a single Rust buffer
+/// with amortized growth, and the result is created as one Lua string by [`build`].
+///
+/// The builder can be passed to Lua, where it has the following methods:
+/// - `builder:add(...)` appends strings and numbers and returns the builder
+/// - `builder:build()` or `tostring(builder)` returns the resulting string
+/// - `builder:clear()` removes all contents
+/// - `#builder` returns the current length in bytes
+///
+/// # Examples
+///
+/// ```
+/// # use mlua::{Lua, Result};
+/// # fn main() -> Result<()> {
+/// let lua = Lua::new();
+/// let builder = lua.create_string_builder()?;
+/// builder.push("hello")?;
+/// lua.load("local b = ...; b:add(', ', 'world'):add(' ', 1)")
+///     .call::<_, ()>(&builder)?;
+/// assert_eq!(builder.build()?, "hello, world 1");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`build`]: StringBuilder::build
+#[derive(Clone, Debug)]
+pub struct StringBuilder<'lua>(pub(crate) AnyUserData<'lua>);
+
+pub(crate) struct StringBuffer(Vec<u8>);
+
+impl<'lua> StringBuilder<'lua> {
+    pub(crate) fn new(lua: &'lua Lua, capacity: usize) -> Result<Self> {
+        let ud = lua.create_userdata(StringBuffer(Vec::with_capacity(capacity)))?;
+        Ok(StringBuilder(ud))
+    }
+
+    /// Appends a chunk of bytes.
+    pub fn push(&self, chunk: impl AsRef<[u8]>) -> Result<()> {
+        let mut buf = self.0.borrow_mut::<StringBuffer>()?;
+        buf.0.extend_from_slice(chunk.as_ref());
+        Ok(())
+    }
+
+    /// Appends a Lua value, which must be a string or a number (converted the same way as by
+    /// Lua concatenation).
+    pub fn push_value(&self, value: impl IntoLua<'lua>) -> Result<()> {
+        let lua = self.0 .0.lua;
+        let s = String::from_lua(value.into_lua(lua)?, lua)?;
+        self.push(s.as_bytes())
+    }
+
+    /// Reserves capacity for at least `additional` more bytes.
+    pub fn reserve(&self, additional: usize) -> Result<()> {
+        let mut buf = self.0.borrow_mut::<StringBuffer>()?;
+        buf.0.reserve(additional);
+        Ok(())
+    }
+
+    /// Returns the current length of the contents in bytes.
+    pub fn len(&self) -> Result<usize> {
+        Ok(self.0.borrow::<StringBuffer>()?.0.len())
+    }
+
+    /// Returns `true` if nothing has been appended yet (or after [`clear`]).
+    ///
+    /// [`clear`]: StringBuilder::clear
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Removes all contents, keeping the allocated capacity.
+    pub fn clear(&self) -> Result<()> {
+        self.0.borrow_mut::<StringBuffer>()?.0.clear();
+        Ok(())
+    }
+
+    /// Creates a Lua string from the current contents.
+    ///
+    /// The builder is left unchanged and can be used to build more strings.
+    pub fn build(&self) -> Result<String<'lua>> {
+        let lua = self.0 .0.lua;
+        let buf = self.0.borrow::<StringBuffer>()?;
+        lua.create_string(&buf.0)
+    }
+}
+
+impl UserData for StringBuffer {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_function(
+            "add",
+            |lua, (ud, chunks): (AnyUserData, Variadic<Value>)| {
+                {
+                    let mut buf = ud.borrow_mut::<StringBuffer>()?;
+                    for chunk in chunks {
+                        buf.0
+                            .extend_from_slice(String::from_lua(chunk, lua)?.as_bytes());
+                    }
+                }
+                Ok(ud)
+            },
+        );
+        methods.add_method("build", |lua, this, ()| lua.create_string(&this.0));
+        methods.add_method_mut("clear", |_, this, ()| {
+            this.0.clear();
+            Ok(())
+        });
+        methods.add_meta_method(MetaMethod::ToString, |lua, this, ()| {
+            lua.create_string(&this.0)
+        });
+        methods.add_meta_method(MetaMethod::Len, |_, this, ()| Ok(this.0.len()));
+    }
+}
+
+impl<'lua> IntoLua<'lua> for StringBuilder<'lua> {
+    #[inline]
+    fn into_lua(self, _: &'lua Lua) -> Result<Value<'lua>> {
+        Ok(Value::UserData(self.0))
+    }
+}
+
+impl<'lua> IntoLua<'lua> for &StringBuilder<'lua> {
+    #[inline]
+    fn into_lua(self, _: &'lua Lua) -> Result<Value<'lua>> {
+        Ok(Value::UserData(self.0.clone()))
+    }
+}
+
+impl<'lua> FromLua<'lua> for StringBuilder<'lua> {
+    fn from_lua(value: Value<'lua>, _: &'lua Lua) -> Result<StringBuilder<'lua>> {
+        match value {
+            Value::UserData(ud) if ud.is::<StringBuffer>() => Ok(StringBuilder(ud)),
+            _ => Err(Error::FromLuaConversionError {
+                from: value.type_name(),
+                to: "StringBuilder",
+                message: None,
+            }),
+        }
+    }
+}
@@ -9,10 +9,14 @@ use crate::value::{FromLuaMulti, IntoLuaMulti};
 
 #[cfg(not(feature = "luau"))]
 use crate::{
-    hook::{Debug, HookTriggers},
+    hook::{Debug, HookId, HookTriggers},
+    sandbox,
     types::MaybeSend,
 };
 
+#[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+use crate::function::Function;
+
 #[cfg(feature = "async")]
 use {
     crate::value::MultiValue,
@@ -176,6 +180,8 @@ impl<'lua> Thread<'lua> {
         }
 
         let mut nresults = 0;
+        #[cfg(not(feature = "luau"))]
+        lua.install_global_hooks(thread_state);
         let ret = ffi::lua_resume(thread_state, state, nargs, &mut nresults as *mut c_int);
         if ret != ffi::LUA_OK && ret != ffi::LUA_YIELD {
             if ret == ffi::LUA_ERRMEM {
@@ -224,6 +230,20 @@ impl<'lua> Thread<'lua> {
         }
     }
 
+    /// Adds a 'hook' function that runs along with the other hooks of the thread.
+    ///
+    /// This function is similar to [`Lua::add_hook()`] except that it adds the hook to the thread.
+    /// To remove the hook call [`Lua::remove_hook_by_id()`].
+    #[cfg(not(feature = "luau"))]
+    #[cfg_attr(docsrs, doc(cfg(not(feature = "luau"))))]
+    pub fn add_hook<F>(&self, triggers: HookTriggers, callback: F) -> HookId
+    where
+        F: Fn(&Lua, Debug) -> Result<()> + MaybeSend + 'static,
+    {
+        let lua = self.0.lua;
+        unsafe { lua.add_thread_hook(self.state(), triggers, callback) }
+    }
+
     /// Resets a thread
     ///
     /// In [Lua 5.4]: cleans its call stack and closes all pending to-be-closed variables.
@@ -335,10 +355,13 @@ impl<'lua> Thread<'lua> {
     /// Under the hood replaces the global environment table with a new table,
     /// that performs writes locally and proxies reads to caller's global environment.
     ///
-    /// This mode ideally should be used together with the global sandbox mode [`Lua::sandbox()`].
+    /// This mode ideally should be used together with the global sandbox mode [`Lua::sandbox()`],
+    /// then reads go to the sandbox environment instead.
     ///
-    /// Please note that Luau links environment table with chunk when loading it into Lua state.
-    /// Therefore you need to load chunks into a thread to link with the thread environment.
+    /// Please note that Luau (and Lua 5.1) links environment table with chunk when loading it
+    /// into Lua state. Therefore you need to load chunks into a thread to link with the thread
+    /// environment. Lua 5.2+ has no per-thread globals, so there the environment is set for the
+    /// function of a thread that has not been started yet.
     ///
     /// # Examples
     ///
@@ -352,7 +375,7 @@ impl<'lua> Thread<'lua> {
     ///     Ok(())
     /// })?)?;
     /// thread.sandbox()?;
-    /// thread.resume(())?;
+    /// thread.resume::<_, ()>(())?;
     ///
     /// // The global environment should be unchanged
     /// assert_eq!(lua.globals().get::<_, Option<u32>>("var")?, None);
@@ -360,19 +383,45 @@ impl<'lua> Thread<'lua> {
     /// # }
     /// ```
     ///
-    /// Requires `feature = "luau"`
-    #[cfg(any(feature = "luau", docsrs))]
-    #[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
-    #[doc(hidden)]
     pub fn sandbox(&self) -> Result<()> {
         let lua = self.0.lua;
-        let state = lua.state();
         let thread_state = self.state();
+        #[cfg(feature = "luau")]
         unsafe {
+            let state = lua.state();
             check_stack(thread_state, 3)?;
             check_stack(state, 3)?;
             protect_lua!(state, 0, 0, |_| ffi::luaL_sandboxthread(thread_state))
         }
+        #[cfg(not(feature = "luau"))]
+        unsafe {
+            let globals = match lua.sandbox_env()? {
+                Some(env) => env,
+                None => lua.globals(),
+            };
+            let env = sandbox::create_thread_env(lua, globals)?;
+            #[cfg(any(feature = "lua51", feature = "luajit"))]
+            {
+                check_stack(thread_state, 1)?;
+                ffi::lua_xpush(lua.ref_thread(), thread_state, env.0.index);
+                ffi::lua_replace(thread_state, ffi::LUA_GLOBALSINDEX);
+                Ok(())
+            }
+            #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+            {
+                let state = lua.state();
+                let _sg = StackGuard::new(state);
+                check_stack(state, 1)?;
+                if ffi::lua_status(thread_state) != ffi::LUA_OK
+                    || ffi::lua_type(thread_state, 1) != ffi::LUA_TFUNCTION
+                {
+                    return Err(Error::runtime("cannot sandbox a running thread"));
+                }
+                ffi::lua_xpush(thread_state, state, 1);
+                let func = Function(lua.pop_ref());
+                func.set_environment(env).map(|_| ())
+            }
+        }
     }
 
     /// Converts this thread to a generic C pointer.
@@ -25,6 +25,14 @@ use {crate::lua::LuaInner, std::marker::PhantomData};
 use serde::ser::{Serialize, SerializeTupleStruct, Serializer};
 
 /// Type of Lua integer numbers.
+///
+/// With `lua51_civ6` this is `i32`, matching the HavokScript runtime:
+/// - Rust integers outside of the 32-bit range are passed to Lua as [`Number`] if they are
+///   represented exactly, otherwise the conversion fails with an "out of range" error.
+/// - A Lua number is read as [`Value::Integer`] only if it is integral and fits into `i32`.
+/// - Converting a [`Number`] to a Rust integer type fails if it is out of range of that type.
+///
+/// [`Value::Integer`]: crate::Value::Integer
 pub type Integer = ffi::lua_Integer;
 /// Type of Lua floating point numbers.
 pub type Number = ffi::lua_Number;
@@ -62,9 +70,8 @@ pub(crate) type AsyncCallbackUpvalue = Upvalue<AsyncCallback<'static, 'static>>;
 #[cfg(feature = "async")]
 pub(crate) type AsyncPollUpvalue = Upvalue<LocalBoxFuture<'static, Result<c_int>>>;
 
-/// Type to set next Luau VM action after executing interrupt function.
-#[cfg(any(feature = "luau", doc))]
-#[cfg_attr(docsrs, doc(cfg(feature = "luau")))]
+/// Type to set next Lua VM action after executing interrupt function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum VmState {
     Continue,
     Yield,
@@ -76,10 +83,10 @@ pub(crate) type HookCallback = Arc<dyn Fn(&Lua, Debug) -> Result<()> + Send>;
 #[cfg(all(not(feature = "send"), not(feature = "luau")))]
 pub(crate) type HookCallback = Arc<dyn Fn(&Lua, Debug) -> Result<()>>;
 
-#[cfg(all(feature = "luau", feature = "send"))]
+#[cfg(feature = "send")]
 pub(crate) type InterruptCallback = Arc<dyn Fn(&Lua) -> Result<VmState> + Send>;
 
-#[cfg(all(feature = "luau", not(feature = "send")))]
+#[cfg(not(feature = "send"))]
 pub(crate) type InterruptCallback = Arc<dyn Fn(&Lua) -> Result<VmState>>;
 
 #[cfg(all(feature = "send", feature = "lua54"))]
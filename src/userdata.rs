@@ -13,6 +13,8 @@ use std::future::Future;
 
 #[cfg(feature = "serialize")]
 use {
+    crate::serde::{merge_tables, LuaSerdeExt},
+    serde::de::DeserializeOwned,
     serde::ser::{self, Serialize, Serializer},
     std::result::Result as StdResult,
 };
@@ -863,6 +865,84 @@ impl<'lua> AnyUserData<'lua> {
         }
     }
 
+    /// Replaces the value of this userdata with `T` deserialized from `value`.
+    ///
+    /// Requires `feature = "serialize"`
+    ///
+    /// # Errors
+    ///
+    /// Returns a `UserDataTypeMismatch` if the userdata is not of type `T` and a
+    /// `UserDataBorrowMutError` if it is borrowed. The value is kept if deserialization fails.
+    #[cfg(feature = "serialize")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "serialize")))]
+    pub fn set_from_value<T>(&self, value: Value<'lua>) -> Result<()>
+    where
+        T: DeserializeOwned + 'static,
+    {
+        if !self.is::<T>() {
+            return Err(Error::UserDataTypeMismatch);
+        }
+        let new_value = self.0.lua.from_value::<T>(value)?;
+        *self.borrow_mut::<T>()? = new_value;
+        Ok(())
+    }
+
+    /// Updates the fields of the `T` inside this userdata with the fields of the `value` table.
+    ///
+    /// The current value is serialized to a table, the fields present in `value` overwrite the
+    /// ones of this table (nested maps are merged the same way, sequences are replaced) and the
+    /// result is deserialized back to `T`. This allows scripts to update Rust-side state with
+    /// partial config tables. If either the value or `T` is not a map, the value is replaced.
+    ///
+    /// Requires `feature = "serialize"`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mlua::{Lua, Result, UserData};
+    /// # use serde::{Deserialize, Serialize};
+    /// # fn main() -> Result<()> {
+    /// #[derive(Serialize, Deserialize)]
+    /// struct Config {
+    ///     difficulty: u8,
+    ///     name: String,
+    /// }
+    /// impl UserData for Config {}
+    ///
+    /// let lua = Lua::new();
+    /// let config = lua.create_userdata(Config { difficulty: 1, name: "default".into() })?;
+    /// let update = lua.load("{ difficulty = 3 }").eval()?;
+    /// config.merge_from_value::<Config>(update)?;
+    ///
+    /// let config = config.borrow::<Config>()?;
+    /// assert_eq!((config.difficulty, config.name.as_str()), (3, "default"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Same as [`AnyUserData::set_from_value`].
+    #[cfg(feature = "serialize")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "serialize")))]
+    pub fn merge_from_value<T>(&self, value: Value<'lua>) -> Result<()>
+    where
+        T: Serialize + DeserializeOwned + 'static,
+    {
+        let lua = self.0.lua;
+        let value = match value {
+            Value::Table(update) => match lua.to_value(&*self.borrow::<T>()?)? {
+                Value::Table(current) => {
+                    merge_tables(&current, update)?;
+                    Value::Table(current)
+                }
+                _ => Value::Table(update),
+            },
+            value => value,
+        };
+        self.set_from_value::<T>(value)
+    }
+
     /// Sets an associated value to this `AnyUserData`.
     ///
     /// The value may be any Lua value whatsoever, and can be retrieved with [`user_value`].
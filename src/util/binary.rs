@@ -0,0 +1,115 @@
+// Building blocks of the binary formats of persisted and encoded values
+
+use crate::error::{Error, Result};
+
+// Nesting limit of tables, functions and threads
+pub(crate) const MAX_DEPTH: usize = 1000;
+
+// Header of a binary format: magic, version and one format-specific byte
+pub(crate) struct Format {
+    pub(crate) magic: &'static [u8; 4],
+    pub(crate) version: u8,
+    // Name of the data in error messages
+    pub(crate) name: &'static str,
+}
+
+impl Format {
+    pub(crate) fn corrupted(&self, reason: &str) -> Error {
+        Error::runtime(format!("invalid {}: {reason}", self.name))
+    }
+
+    pub(crate) fn write_header(&self, buf: &mut Vec<u8>, extra: u8) {
+        buf.extend_from_slice(self.magic);
+        buf.extend_from_slice(&[self.version, extra]);
+    }
+
+    // Returns the format-specific byte and the body following the header
+    pub(crate) fn read_header<'a>(&self, data: &'a [u8]) -> Result<(u8, &'a [u8])> {
+        let (version, extra, body) = match data {
+            [m0, m1, m2, m3, version, extra, body @ ..] if [*m0, *m1, *m2, *m3] == *self.magic => {
+                (*version, *extra, body)
+            }
+            _ => return Err(self.corrupted("bad header")),
+        };
+        if version != self.version {
+            let msg = format!("unsupported {} version {version}", self.name);
+            return Err(Error::runtime(msg));
+        }
+        Ok((extra, body))
+    }
+}
+
+pub(crate) fn write_varint(buf: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        buf.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    buf.push(n as u8);
+}
+
+pub(crate) struct Reader<'a> {
+    format: &'static Format,
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(format: &'static Format, data: &'a [u8]) -> Self {
+        Reader {
+            format,
+            data,
+            pos: 0,
+        }
+    }
+
+    pub(crate) fn corrupted(&self, reason: &str) -> Error {
+        self.format.corrupted(reason)
+    }
+
+    // Number of bytes left to read
+    pub(crate) fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    pub(crate) fn finish(&self) -> Result<()> {
+        if self.pos != self.data.len() {
+            return Err(self.corrupted("trailing data"));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_slice(1)?[0])
+    }
+
+    pub(crate) fn read_varint(&mut self) -> Result<u64> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.read_u8()?;
+            n |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(self.corrupted("bad varint"))
+    }
+
+    pub(crate) fn read_len(&mut self) -> Result<usize> {
+        let n = self.read_varint()?;
+        usize::try_from(n).map_err(|_| self.corrupted("bad length"))
+    }
+
+    // Reads a slice prefixed by its length
+    pub(crate) fn read_bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.read_len()?;
+        self.read_slice(len)
+    }
+
+    pub(crate) fn read_slice(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = (self.pos.checked_add(len)).filter(|&end| end <= self.data.len());
+        let end = end.ok_or_else(|| self.corrupted("unexpected end of data"))?;
+        let slice = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+}
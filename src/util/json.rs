@@ -0,0 +1,361 @@
+// A minimal JSON value shared by the debugger and the `json` module.
+//
+// `serde_json` is not used: its `PartialEq` impls between primitive types and its `Value` would
+// apply to every crate built together with it and break type inference there.
+
+use std::fmt::{self, Write};
+use std::str;
+
+// Nesting limit of the parsed documents
+const MAX_DEPTH: usize = 128;
+
+#[derive(Clone, Debug, Default)]
+pub(crate) enum JsonValue {
+    #[default]
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    // Keys are kept in the insertion order
+    Object(Vec<(String, JsonValue)>),
+}
+
+#[derive(Debug)]
+pub(crate) struct ParseError {
+    msg: &'static str,
+    line: usize,
+    column: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {} column {}",
+            self.msg, self.line, self.column
+        )
+    }
+}
+
+impl JsonValue {
+    pub(crate) fn parse(data: &[u8]) -> Result<JsonValue, ParseError> {
+        let mut parser = Parser { data, pos: 0 };
+        let value = parser.parse_value(0)?;
+        parser.skip_whitespace();
+        if parser.pos != data.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        Ok(value)
+    }
+
+    // Writes the value with one element per line, each level indented by `indent`
+    #[cfg(feature = "json")]
+    pub(crate) fn write_pretty(&self, out: &mut String, indent: &str, level: usize) {
+        let newline = |out: &mut String, level| {
+            out.push('\n');
+            for _ in 0..level {
+                out.push_str(indent);
+            }
+        };
+        match self {
+            JsonValue::Array(array) if !array.is_empty() => {
+                out.push('[');
+                for (i, value) in array.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    newline(out, level + 1);
+                    value.write_pretty(out, indent, level + 1);
+                }
+                newline(out, level);
+                out.push(']');
+            }
+            JsonValue::Object(object) if !object.is_empty() => {
+                out.push('{');
+                for (i, (key, value)) in object.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    newline(out, level + 1);
+                    let _ = write_string(out, key);
+                    out.push_str(": ");
+                    value.write_pretty(out, indent, level + 1);
+                }
+                newline(out, level);
+                out.push('}');
+            }
+            value => {
+                let _ = write!(out, "{value}");
+            }
+        }
+    }
+}
+
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonValue::Null => f.write_str("null"),
+            JsonValue::Bool(b) => write!(f, "{b}"),
+            JsonValue::Integer(i) => write!(f, "{i}"),
+            JsonValue::Number(n) if !n.is_finite() => f.write_str("null"),
+            JsonValue::Number(n) if n.fract() == 0.0 && n.abs() < 2f64.powi(53) => {
+                write!(f, "{}", *n as i64)
+            }
+            JsonValue::Number(n) => write!(f, "{n}"),
+            JsonValue::String(s) => write_string(f, s),
+            JsonValue::Array(array) => {
+                f.write_char('[')?;
+                for (i, value) in array.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                f.write_char(']')
+            }
+            JsonValue::Object(object) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in object.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+// Writes `s` as a quoted JSON string
+pub(crate) fn write_string(out: &mut impl Write, s: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    // Builds an error pointing at the current position
+    fn error(&self, msg: &'static str) -> ParseError {
+        let consumed = &self.data[..self.pos.min(self.data.len())];
+        let line_start = consumed
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        ParseError {
+            msg,
+            line: consumed.iter().filter(|&&b| b == b'\n').count() + 1,
+            column: self.pos - line_start + 1,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.data.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_whitespace();
+        if self.data.get(self.pos) == Some(&byte) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn eat_literal(&mut self, literal: &str) -> bool {
+        if self.data[self.pos..].starts_with(literal.as_bytes()) {
+            self.pos += literal.len();
+            return true;
+        }
+        false
+    }
+
+    fn parse_value(&mut self, depth: usize) -> Result<JsonValue, ParseError> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("recursion limit exceeded"));
+        }
+        self.skip_whitespace();
+        let byte = match self.data.get(self.pos) {
+            Some(&byte) => byte,
+            None => return Err(self.error("EOF while parsing a value")),
+        };
+        match byte {
+            b'n' if self.eat_literal("null") => Ok(JsonValue::Null),
+            b't' if self.eat_literal("true") => Ok(JsonValue::Bool(true)),
+            b'f' if self.eat_literal("false") => Ok(JsonValue::Bool(false)),
+            b'"' => self.parse_string().map(JsonValue::String),
+            b'[' => {
+                self.pos += 1;
+                let mut array = Vec::new();
+                if self.eat(b']') {
+                    return Ok(JsonValue::Array(array));
+                }
+                loop {
+                    array.push(self.parse_value(depth + 1)?);
+                    if self.eat(b']') {
+                        return Ok(JsonValue::Array(array));
+                    }
+                    if !self.eat(b',') {
+                        return Err(self.error("expected `,` or `]`"));
+                    }
+                }
+            }
+            b'{' => {
+                self.pos += 1;
+                let mut object = Vec::new();
+                if self.eat(b'}') {
+                    return Ok(JsonValue::Object(object));
+                }
+                loop {
+                    self.skip_whitespace();
+                    if self.data.get(self.pos) != Some(&b'"') {
+                        return Err(self.error("key must be a string"));
+                    }
+                    let key = self.parse_string()?;
+                    if !self.eat(b':') {
+                        return Err(self.error("expected `:`"));
+                    }
+                    object.push((key, self.parse_value(depth + 1)?));
+                    if self.eat(b'}') {
+                        return Ok(JsonValue::Object(object));
+                    }
+                    if !self.eat(b',') {
+                        return Err(self.error("expected `,` or `}`"));
+                    }
+                }
+            }
+            b'-' | b'0'..=b'9' => self.parse_number(),
+            _ => Err(self.error("expected value")),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue, ParseError> {
+        let start = self.pos;
+        while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.data.get(self.pos) {
+            self.pos += 1;
+        }
+        let number = str::from_utf8(&self.data[start..self.pos]).unwrap_or_default();
+        if let Ok(i) = number.parse() {
+            return Ok(JsonValue::Integer(i));
+        }
+        match number.parse() {
+            Ok(n) => Ok(JsonValue::Number(n)),
+            Err(_) => {
+                self.pos = start;
+                Err(self.error("invalid number"))
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, ParseError> {
+        // Skip the opening quote
+        self.pos += 1;
+        let mut buf = Vec::new();
+        loop {
+            let byte = match self.data.get(self.pos) {
+                Some(&byte) => byte,
+                None => return Err(self.error("EOF while parsing a string")),
+            };
+            match byte {
+                b'"' => {
+                    self.pos += 1;
+                    return String::from_utf8(buf).map_err(|_| self.error("invalid unicode"));
+                }
+                b'\\' => {
+                    let escape = self.data.get(self.pos + 1).copied();
+                    self.pos += 2;
+                    let c = match escape {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => self.parse_unicode_escape()?,
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    buf.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                byte if byte < 0x20 => {
+                    return Err(self.error("control character found while parsing a string"))
+                }
+                byte => {
+                    buf.push(byte);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    // Parses the code point after `\u`, including the low surrogate of a pair
+    fn parse_unicode_escape(&mut self) -> Result<char, ParseError> {
+        let high = self.parse_hex4()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).ok_or_else(|| self.error("invalid unicode code point"));
+        }
+        if !self.eat_literal("\\u") {
+            return Err(self.error("unexpected end of hex escape"));
+        }
+        let low = self.parse_hex4()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err(self.error("invalid unicode code point"));
+        }
+        Ok(char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)).unwrap())
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, ParseError> {
+        let hex = self.data.get(self.pos..self.pos + 4);
+        let code = hex.and_then(|hex| u32::from_str_radix(str::from_utf8(hex).ok()?, 16).ok());
+        let code = code.ok_or_else(|| self.error("invalid escape"))?;
+        self.pos += 4;
+        Ok(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JsonValue;
+
+    #[test]
+    fn tests() {
+        let text = r#" {"a": [1, -2.5, true, null], "b": {"c": "x\"\\\né😀"}} "#;
+        let value = JsonValue::parse(text.as_bytes()).unwrap();
+        assert_eq!(
+            value.to_string(),
+            r#"{"a":[1,-2.5,true,null],"b":{"c":"x\"\\\né😀"}}"#
+        );
+        assert!(matches!(
+            JsonValue::parse(b"[9007199254740993]").unwrap(),
+            JsonValue::Array(array) if matches!(array[..], [JsonValue::Integer(9007199254740993)])
+        ));
+
+        let err = |data: &[u8]| JsonValue::parse(data).unwrap_err().to_string();
+        assert_eq!(err(b"[1,]"), "expected value at line 1 column 4");
+        assert_eq!(err(b"{}\n x"), "trailing characters at line 2 column 2");
+        assert_eq!(err(b"{\"a\" 1}"), "expected `:` at line 1 column 6");
+        assert_eq!(err(b"\"\\u12\""), "invalid escape at line 1 column 4");
+        assert!(err(&[b'['; 1000]).starts_with("recursion limit exceeded"));
+    }
+}
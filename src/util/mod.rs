@@ -2,7 +2,7 @@ use std::any::{Any, TypeId};
 use std::borrow::Cow;
 use std::ffi::CStr;
 use std::fmt::Write;
-use std::mem::MaybeUninit;
+use std::mem::{self, MaybeUninit};
 use std::os::raw::{c_char, c_int, c_void};
 use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
 use std::sync::Arc;
@@ -11,8 +11,11 @@ use std::{ptr, slice, str};
 use once_cell::sync::Lazy;
 use rustc_hash::FxHashMap;
 
-use crate::error::{Error, Result};
+use crate::error::{Error, Result, StackFrame};
 use crate::memory::MemoryState;
+#[cfg(feature = "async")]
+use crate::types::AsyncCallbackUpvalue;
+use crate::types::CallbackUpvalue;
 
 pub(crate) use short_names::short_type_name;
 
@@ -213,22 +216,14 @@ pub unsafe fn pop_error(state: *mut ffi::lua_State, err_code: c_int) -> Error {
             ffi::lua_pop(state, 1);
 
             match err_code {
-                ffi::LUA_ERRRUN => Error::RuntimeError(err_string),
-                ffi::LUA_ERRSYNTAX => {
-                    Error::SyntaxError {
-                        // This seems terrible, but as far as I can tell, this is exactly what the
-                        // stock Lua REPL does.
-                        incomplete_input: err_string.ends_with("<eof>")
-                            || err_string.ends_with("'<eof>'"),
-                        message: err_string,
-                    }
-                }
+                ffi::LUA_ERRRUN => Error::runtime(err_string),
+                ffi::LUA_ERRSYNTAX => Error::syntax(err_string),
                 ffi::LUA_ERRERR => {
                     // This error is raised when the error handler raises an error too many times
                     // recursively, and continuing to trigger the error handler would cause a stack
                     // overflow. It is not very useful to differentiate between this and "ordinary"
                     // runtime errors, so we handle them the same way.
-                    Error::RuntimeError(err_string)
+                    Error::runtime(err_string)
                 }
                 ffi::LUA_ERRMEM => Error::MemoryError(err_string),
                 #[cfg(any(feature = "lua53", feature = "lua52"))]
@@ -419,7 +414,7 @@ unsafe extern "C-unwind" fn lua_istable_impl(state: *mut ffi::lua_State) -> c_in
 }
 
 unsafe fn init_userdata_metatable_index(state: *mut ffi::lua_State) -> Result<()> {
-    let index_key = &USERDATA_METATABLE_INDEX as *const u8 as *const _;
+    let index_key = &REGISTRY_KEYS.userdata_mt_index as *const u8 as *const _;
     if ffi::lua_rawgetp(state, ffi::LUA_REGISTRYINDEX, index_key) == ffi::LUA_TFUNCTION {
         return Ok(());
     }
@@ -490,7 +485,7 @@ unsafe fn init_userdata_metatable_index(state: *mut ffi::lua_State) -> Result<()
 }
 
 pub unsafe fn init_userdata_metatable_newindex(state: *mut ffi::lua_State) -> Result<()> {
-    let newindex_key = &USERDATA_METATABLE_NEWINDEX as *const u8 as *const _;
+    let newindex_key = &REGISTRY_KEYS.userdata_mt_newindex as *const u8 as *const _;
     if ffi::lua_rawgetp(state, ffi::LUA_REGISTRYINDEX, newindex_key) == ffi::LUA_TFUNCTION {
         return Ok(());
     }
@@ -704,11 +699,8 @@ pub unsafe extern "C-unwind" fn error_traceback(state: *mut ffi::lua_State) -> c
     }
 
     if get_gc_userdata::<WrappedFailure>(state, -1, ptr::null()).is_null() {
-        let s = ffi::luaL_tolstring(state, -1, ptr::null_mut());
-        if ffi::lua_checkstack(state, ffi::LUA_TRACEBACK_STACK) != 0 {
-            ffi::luaL_traceback(state, state, s, 0);
-            ffi::lua_remove(state, -2);
-        }
+        // Skip the error handler itself in the stack frames
+        push_traceback(state, state, 1);
     }
 
     1
@@ -720,12 +712,129 @@ pub unsafe fn error_traceback_thread(state: *mut ffi::lua_State, thread: *mut ff
     ffi::lua_xmove(thread, state, 1);
 
     if get_gc_userdata::<WrappedFailure>(state, -1, ptr::null()).is_null() {
-        let s = ffi::luaL_tolstring(state, -1, ptr::null_mut());
-        if ffi::lua_checkstack(state, ffi::LUA_TRACEBACK_STACK) != 0 {
-            ffi::luaL_traceback(state, thread, s, 0);
-            ffi::lua_remove(state, -2);
+        push_traceback(state, thread, 0);
+    }
+}
+
+// Pushes the string representation of the error value at the top of the stack followed by
+// the traceback of `thread`.
+// If error details are enabled, wraps it into `Error::WithDetails` with the stack frames of `thread`
+// starting from `level` and the value itself (unless it's a string).
+unsafe fn push_traceback(state: *mut ffi::lua_State, thread: *mut ffi::lua_State, level: c_int) {
+    let s = ffi::luaL_tolstring(state, -1, ptr::null_mut());
+    if ffi::lua_checkstack(state, ffi::LUA_TRACEBACK_STACK) != 0 {
+        ffi::luaL_traceback(state, thread, s, 0);
+        ffi::lua_remove(state, -2);
+    }
+
+    if !crate::lua::error_details(state) || ffi::lua_checkstack(state, 3) == 0 {
+        return;
+    }
+
+    // Allocate userdata and registry slot before touching any Rust values, Lua can raise
+    // a memory error here
+    let ud = WrappedFailure::new_userdata(state);
+    let value = if ffi::lua_type(state, -3) != ffi::LUA_TSTRING {
+        ffi::lua_pushvalue(state, -3);
+        crate::lua::ref_error_value(state)
+    } else {
+        None
+    };
+    let message = to_string(state, -2);
+    let traceback = stack_frames(thread, level);
+
+    let cause = Arc::new(Error::RuntimeError(message));
+    let value = value.map(Arc::new);
+    let error = Error::WithDetails {
+        traceback,
+        value,
+        cause,
+    };
+    ptr::write(ud, WrappedFailure::Error(error));
+    get_gc_metatable::<WrappedFailure>(state);
+    ffi::lua_setmetatable(state, -2);
+    ffi::lua_remove(state, -2);
+}
+
+// Captures the call stack of `state` starting from `level`.
+// Like `luaL_traceback`, the middle of a deep stack is omitted.
+// Uses 4 stack spaces (returns no frames if not available).
+pub(crate) unsafe fn stack_frames(state: *mut ffi::lua_State, level: c_int) -> Vec<StackFrame> {
+    const LEVELS1: c_int = 12; // size of the first part of the stack
+    const LEVELS2: c_int = 10; // size of the second part of the stack
+
+    if ffi::lua_checkstack(state, 4) == 0 {
+        return Vec::new();
+    }
+
+    let mut ar: ffi::lua_Debug = mem::zeroed();
+    let get_level = |level, ar: &mut ffi::lua_Debug| {
+        #[cfg(not(feature = "luau"))]
+        return ffi::lua_getstack(state, level, ar) != 0;
+        #[cfg(feature = "luau")]
+        return ffi::lua_getinfo(state, level, cstr!(""), ar) != 0;
+    };
+
+    let mut levels = level;
+    while get_level(levels, &mut ar) {
+        levels += 1;
+    }
+
+    let mut frames = Vec::new();
+    let mut level = level;
+    while get_level(level, &mut ar) {
+        if frames.len() == LEVELS1 as usize && levels - level > LEVELS2 {
+            level = levels - LEVELS2;
+            continue;
+        }
+
+        #[cfg(not(feature = "luau"))]
+        ffi::lua_getinfo(state, cstr!("Slnf"), &mut ar);
+        #[cfg(feature = "luau")]
+        ffi::lua_getinfo(state, level, cstr!("slnf"), &mut ar);
+
+        // Rust callbacks have the callback data as the first upvalue
+        let mut is_callback = false;
+        if ffi::lua_iscfunction(state, -1) != 0 && !ffi::lua_getupvalue(state, -1, 1).is_null() {
+            is_callback = !get_gc_userdata::<CallbackUpvalue>(state, -1, ptr::null()).is_null();
+            #[cfg(feature = "async")]
+            {
+                is_callback = is_callback
+                    || !get_gc_userdata::<AsyncCallbackUpvalue>(state, -1, ptr::null()).is_null();
+            }
+            ffi::lua_pop(state, 1);
         }
+        ffi::lua_pop(state, 1);
+
+        #[cfg(not(feature = "luau"))]
+        let short_src = ar.short_src.as_ptr();
+        #[cfg(feature = "luau")]
+        let short_src = ar.short_src;
+        frames.push(StackFrame {
+            level: level as usize,
+            source: ptr_to_lossy_str(ar.source).map(|s| s.into_owned()),
+            short_src: ptr_to_lossy_str(short_src).map(|s| s.into_owned()),
+            line: (ar.currentline > 0).then_some(ar.currentline as usize),
+            line_defined: linenumber_to_usize(ar.linedefined),
+            name: ptr_to_lossy_str(ar.name).map(|s| s.into_owned()),
+            #[cfg(not(feature = "luau"))]
+            name_what: match ptr_to_lossy_str(ar.namewhat) {
+                Some(s) if s.is_empty() => None,
+                s => s.map(|s| s.into_owned()),
+            },
+            #[cfg(feature = "luau")]
+            name_what: None,
+            what: match ptr_to_str(ar.what) {
+                Some("Lua") => "Lua",
+                Some("C") => "C",
+                Some("tail") => "tail",
+                _ => "main",
+            },
+            is_callback,
+        });
+        level += 1;
     }
+    frames
 }
 
 // A variant of `pcall` that does not allow Lua to catch Rust panics from `callback_error`.
@@ -876,7 +985,8 @@ pub unsafe fn init_error_registry(state: *mut ffi::lua_State) -> Result<()> {
 
             let err_buf = match get_gc_userdata::<WrappedFailure>(state, -1, ptr::null()).as_ref() {
                 Some(WrappedFailure::Error(error)) => {
-                    let err_buf_key = &ERROR_PRINT_BUFFER_KEY as *const u8 as *const c_void;
+                    let err_buf_key =
+                        &REGISTRY_KEYS.error_print_buffer as *const u8 as *const c_void;
                     ffi::lua_rawgetp(state, ffi::LUA_REGISTRYINDEX, err_buf_key);
                     let err_buf = ffi::lua_touserdata(state, -1) as *mut String;
                     ffi::lua_pop(state, 2);
@@ -889,7 +999,8 @@ pub unsafe fn init_error_registry(state: *mut ffi::lua_State) -> Result<()> {
                     Ok(err_buf)
                 }
                 Some(WrappedFailure::Panic(Some(ref panic))) => {
-                    let err_buf_key = &ERROR_PRINT_BUFFER_KEY as *const u8 as *const c_void;
+                    let err_buf_key =
+                        &REGISTRY_KEYS.error_print_buffer as *const u8 as *const c_void;
                     ffi::lua_rawgetp(state, ffi::LUA_REGISTRYINDEX, err_buf_key);
                     let err_buf = ffi::lua_touserdata(state, -1) as *mut String;
                     (*err_buf).clear();
@@ -985,7 +1096,7 @@ pub unsafe fn init_error_registry(state: *mut ffi::lua_State) -> Result<()> {
     ffi::lua_pop(state, 1);
 
     protect_lua!(state, 1, 0, fn(state) {
-        let destructed_mt_key = &DESTRUCTED_USERDATA_METATABLE as *const u8 as *const c_void;
+        let destructed_mt_key = &REGISTRY_KEYS.destructed_userdata_mt as *const u8 as *const c_void;
         ffi::lua_rawsetp(state, ffi::LUA_REGISTRYINDEX, destructed_mt_key);
     })?;
 
@@ -993,7 +1104,7 @@ pub unsafe fn init_error_registry(state: *mut ffi::lua_State) -> Result<()> {
     init_gc_metatable::<String>(state, None)?;
     push_gc_userdata(state, String::new(), true)?;
     protect_lua!(state, 1, 0, fn(state) {
-        let err_buf_key = &ERROR_PRINT_BUFFER_KEY as *const u8 as *const c_void;
+        let err_buf_key = &REGISTRY_KEYS.error_print_buffer as *const u8 as *const c_void;
         ffi::lua_rawsetp(state, ffi::LUA_REGISTRYINDEX, err_buf_key);
     })?;
 
@@ -1066,7 +1177,7 @@ pub(crate) unsafe fn to_string(state: *mut ffi::lua_State, index: c_int) -> Stri
 }
 
 pub(crate) unsafe fn get_destructed_userdata_metatable(state: *mut ffi::lua_State) {
-    let key = &DESTRUCTED_USERDATA_METATABLE as *const u8 as *const c_void;
+    let key = &REGISTRY_KEYS.destructed_userdata_mt as *const u8 as *const c_void;
     ffi::lua_rawgetp(state, ffi::LUA_REGISTRYINDEX, key);
 }
 
@@ -1091,9 +1202,33 @@ pub(crate) fn linenumber_to_usize(n: c_int) -> Option<usize> {
     }
 }
 
-static DESTRUCTED_USERDATA_METATABLE: u8 = 0;
-static ERROR_PRINT_BUFFER_KEY: u8 = 0;
-static USERDATA_METATABLE_INDEX: u8 = 0;
-static USERDATA_METATABLE_NEWINDEX: u8 = 0;
+// Keys of the values that mlua stores in the registry.
+//
+// Every copy of mlua (e.g. separately built modules attached to the same Lua state) has its own
+// keys and never sees values stored by the others. The keys point into an allocation that is never
+// freed, so they stay unique even if a module is unloaded and another one is loaded at the same
+// address.
+#[derive(Default)]
+pub(crate) struct RegistryKeys {
+    pub(crate) extra: u8,
+    destructed_userdata_mt: u8,
+    error_print_buffer: u8,
+    userdata_mt_index: u8,
+    userdata_mt_newindex: u8,
+    #[cfg(not(feature = "luau"))]
+    pub(crate) hook_threads: u8,
+    #[cfg(feature = "serialize")]
+    pub(crate) array_mt: u8,
+}
+
+pub(crate) static REGISTRY_KEYS: Lazy<&'static RegistryKeys> =
+    Lazy::new(|| Box::leak(Box::default()));
 
+pub(crate) mod binary;
+// The JSON values are used only by the debugger and the `json` module
+#[cfg_attr(
+    not(any(all(feature = "debugger", not(feature = "luau")), feature = "json")),
+    allow(dead_code)
+)]
+pub(crate) mod json;
 mod short_names;
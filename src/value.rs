@@ -24,7 +24,7 @@ use crate::lua::Lua;
 use crate::string::String;
 use crate::table::Table;
 use crate::thread::Thread;
-use crate::types::{Integer, LightUserData, Number, SubtypeId};
+use crate::types::{Integer, LightUserData, LuaRef, Number, SubtypeId};
 use crate::userdata::AnyUserData;
 use crate::util::{check_stack, StackGuard};
 
@@ -42,6 +42,7 @@ pub enum Value<'lua> {
     /// An integer number.
     ///
     /// Any Lua number convertible to a `Integer` will be represented as this variant.
+    /// See [`Integer`] for the range of `lua51_civ6` integers.
     Integer(Integer),
     /// A floating point number.
     Number(Number),
@@ -64,6 +65,11 @@ pub enum Value<'lua> {
     UserData(AnyUserData<'lua>),
     /// `Error` is a special builtin userdata type. When received from Lua it is implicitly cloned.
     Error(Error),
+    /// Reference to a value of a type not otherwise known to mlua
+    /// (eg. a HavokScript `ui64` or struct).
+    #[cfg(any(feature = "lua51_civ6", doc))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "lua51_civ6")))]
+    Other(OtherValue<'lua>),
 }
 
 pub use self::Value::Nil;
@@ -94,6 +100,8 @@ impl<'lua> Value<'lua> {
             #[cfg(feature = "luajit")]
             Value::UserData(AnyUserData(_, SubtypeId::CData)) => "cdata",
             Value::Error(_) => "error",
+            #[cfg(feature = "lua51_civ6")]
+            Value::Other(ref v) => v.type_name(),
         }
     }
 
@@ -131,6 +139,8 @@ impl<'lua> Value<'lua> {
             | Value::Function(Function(r))
             | Value::Thread(Thread(r, ..))
             | Value::UserData(AnyUserData(r, ..)) => r.to_pointer(),
+            #[cfg(feature = "lua51_civ6")]
+            Value::Other(OtherValue(r, _)) => r.to_pointer(),
             _ => ptr::null(),
         }
     }
@@ -139,31 +149,34 @@ impl<'lua> Value<'lua> {
     ///
     /// If the value has a metatable with a `__tostring` method, then it will be called to get the result.
     pub fn to_string(&self) -> Result<StdString> {
-        match self {
-            Value::Nil => Ok("nil".to_string()),
-            Value::Boolean(b) => Ok(b.to_string()),
-            Value::LightUserData(ud) if ud.0.is_null() => Ok("null".to_string()),
-            Value::LightUserData(ud) => Ok(format!("lightuserdata: {:p}", ud.0)),
-            Value::Integer(i) => Ok(i.to_string()),
-            Value::Number(n) => Ok(n.to_string()),
+        let r = match self {
+            Value::Nil => return Ok("nil".to_string()),
+            Value::Boolean(b) => return Ok(b.to_string()),
+            Value::LightUserData(ud) if ud.0.is_null() => return Ok("null".to_string()),
+            Value::LightUserData(ud) => return Ok(format!("lightuserdata: {:p}", ud.0)),
+            Value::Integer(i) => return Ok(i.to_string()),
+            Value::Number(n) => return Ok(n.to_string()),
             #[cfg(feature = "luau")]
-            Value::Vector(v) => Ok(v.to_string()),
-            Value::String(s) => Ok(s.to_str()?.to_string()),
+            Value::Vector(v) => return Ok(v.to_string()),
+            Value::String(s) => return Ok(s.to_str()?.to_string()),
             Value::Table(Table(r))
             | Value::Function(Function(r))
             | Value::Thread(Thread(r, ..))
-            | Value::UserData(AnyUserData(r, ..)) => unsafe {
-                let state = r.lua.state();
-                let _guard = StackGuard::new(state);
-                check_stack(state, 3)?;
-
-                r.lua.push_ref(r);
-                protect_lua!(state, 1, 1, fn(state) {
-                    ffi::luaL_tolstring(state, -1, ptr::null_mut());
-                })?;
-                Ok(String(r.lua.pop_ref()).to_str()?.to_string())
-            },
-            Value::Error(err) => Ok(err.to_string()),
+            | Value::UserData(AnyUserData(r, ..)) => r,
+            #[cfg(feature = "lua51_civ6")]
+            Value::Other(OtherValue(r, _)) => r,
+            Value::Error(err) => return Ok(err.to_string()),
+        };
+        unsafe {
+            let state = r.lua.state();
+            let _guard = StackGuard::new(state);
+            check_stack(state, 3)?;
+
+            r.lua.push_ref(r);
+            protect_lua!(state, 1, 1, fn(state) {
+                ffi::luaL_tolstring(state, -1, ptr::null_mut());
+            })?;
+            Ok(String(r.lua.pop_ref()).to_str()?.to_string())
         }
     }
 
@@ -257,10 +270,15 @@ impl<'lua> Value<'lua> {
 
     /// Cast the value to `u64`.
     ///
-    /// If the value is a Lua [`Integer`], try to convert it to `u64` or return `None` otherwise.
+    /// If the value is a Lua [`Integer`] or a HavokScript `ui64`, try to convert it to `u64`
+    /// or return `None` otherwise.
     #[inline]
     pub fn as_u64(&self) -> Option<u64> {
-        self.as_integer().and_then(|i| u64::try_from(i).ok())
+        match self {
+            #[cfg(feature = "lua51_civ6")]
+            Value::Other(v) => v.as_u64().ok().flatten(),
+            _ => self.as_integer().and_then(|i| u64::try_from(i).ok()),
+        }
     }
 
     /// Cast the value to `isize`.
@@ -435,6 +453,27 @@ impl<'lua> Value<'lua> {
             .unwrap_or_default()
     }
 
+    /// Returns `true` if the value is an [`OtherValue`].
+    #[cfg(any(feature = "lua51_civ6", doc))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "lua51_civ6")))]
+    #[inline]
+    pub fn is_other(&self) -> bool {
+        self.as_other().is_some()
+    }
+
+    /// Cast the value to [`OtherValue`].
+    ///
+    /// If the value is an [`OtherValue`], returns it or `None` otherwise.
+    #[cfg(any(feature = "lua51_civ6", doc))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "lua51_civ6")))]
+    #[inline]
+    pub fn as_other(&self) -> Option<&OtherValue> {
+        match self {
+            Value::Other(v) => Some(v),
+            _ => None,
+        }
+    }
+
     /// Wrap reference to this Value into [`SerializableValue`].
     ///
     /// This allows customizing serialization behavior using serde.
@@ -519,6 +558,11 @@ impl<'lua> Value<'lua> {
             }
             Value::Error(e) if recursive => write!(fmt, "{e:?}"),
             Value::Error(_) => write!(fmt, "error"),
+            #[cfg(feature = "lua51_civ6")]
+            Value::Other(v) => match v.as_u64().ok().flatten() {
+                Some(n) => write!(fmt, "{}: {n}", v.type_name()),
+                None => write!(fmt, "{}: {:?}", v.type_name(), v.to_pointer()),
+            },
         }
     }
 }
@@ -542,6 +586,8 @@ impl fmt::Debug for Value<'_> {
             Value::Thread(t) => write!(fmt, "{t:?}"),
             Value::UserData(ud) => write!(fmt, "{ud:?}"),
             Value::Error(e) => write!(fmt, "Error({e:?})"),
+            #[cfg(feature = "lua51_civ6")]
+            Value::Other(v) => write!(fmt, "{v:?}"),
         }
     }
 }
@@ -563,6 +609,8 @@ impl<'lua> PartialEq for Value<'lua> {
             (Value::Function(a), Value::Function(b)) => a == b,
             (Value::Thread(a), Value::Thread(b)) => a == b,
             (Value::UserData(a), Value::UserData(b)) => a == b,
+            #[cfg(feature = "lua51_civ6")]
+            (Value::Other(a), Value::Other(b)) => a == b,
             _ => false,
         }
     }
@@ -575,6 +623,95 @@ impl<'lua> AsRef<Value<'lua>> for Value<'lua> {
     }
 }
 
+/// Reference to a Lua value of a type that has no dedicated [`Value`] variant.
+///
+/// Such values come from runtime specific extensions, for example HavokScript `ui64` and struct
+/// values. mlua cannot inspect them (apart from `ui64`), but keeps a reference so they can be
+/// stored and passed back to Lua unchanged.
+#[cfg(any(feature = "lua51_civ6", doc))]
+#[cfg_attr(docsrs, doc(cfg(feature = "lua51_civ6")))]
+#[derive(Clone)]
+pub struct OtherValue<'lua>(pub(crate) LuaRef<'lua>, pub(crate) c_int);
+
+#[cfg(any(feature = "lua51_civ6", doc))]
+impl<'lua> OtherValue<'lua> {
+    /// Returns the type tag of the value, as reported by `lua_type`.
+    #[inline]
+    pub const fn type_tag(&self) -> c_int {
+        self.1
+    }
+
+    /// Returns type name of the value.
+    pub const fn type_name(&self) -> &'static str {
+        match self.1 {
+            #[cfg(feature = "lua51_civ6")]
+            ffi::LUA_TUI64 => "ui64",
+            #[cfg(feature = "lua51_civ6")]
+            ffi::LUA_TSTRUCT => "struct",
+            _ => "other",
+        }
+    }
+
+    /// Returns `true` if the value is a HavokScript `ui64`.
+    #[inline]
+    pub fn is_ui64(&self) -> bool {
+        #[cfg(feature = "lua51_civ6")]
+        return self.1 == ffi::LUA_TUI64;
+        #[cfg(not(feature = "lua51_civ6"))]
+        return false;
+    }
+
+    /// Returns the value of a HavokScript `ui64`.
+    ///
+    /// Returns `None` if the value is not a `ui64`, or [`Error::Ui64NotAvailable`] if the runtime
+    /// does not support converting it (or the `civ6-ui64` feature is disabled).
+    ///
+    /// [`Error::Ui64NotAvailable`]: crate::Error::Ui64NotAvailable
+    pub fn as_u64(&self) -> Result<Option<u64>> {
+        if !self.is_ui64() {
+            return Ok(None);
+        }
+        #[cfg(feature = "civ6-dynamic")]
+        if !ffi::dynamic::lua_ui64_supported() {
+            return Err(Error::Ui64NotAvailable);
+        }
+        #[cfg(any(feature = "civ6-ui64", feature = "civ6-dynamic"))]
+        return Ok(Some(unsafe {
+            ffi::lua_toui64(self.0.lua.ref_thread(), self.0.index)
+        }));
+        #[cfg(not(any(feature = "civ6-ui64", feature = "civ6-dynamic")))]
+        Err(Error::Ui64NotAvailable)
+    }
+
+    /// Converts the value to a generic C pointer.
+    ///
+    /// Different objects will give different pointers, values without identity give NULL.
+    #[inline]
+    pub fn to_pointer(&self) -> *const c_void {
+        self.0.to_pointer()
+    }
+}
+
+#[cfg(any(feature = "lua51_civ6", doc))]
+impl fmt::Debug for OtherValue<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.as_u64() {
+            Ok(Some(n)) => write!(fmt, "Other({}: {n})", self.type_name()),
+            _ => write!(fmt, "Other({}: {:?})", self.type_name(), self.0),
+        }
+    }
+}
+
+#[cfg(any(feature = "lua51_civ6", doc))]
+impl<'lua> PartialEq for OtherValue<'lua> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.as_u64(), other.as_u64()) {
+            (Ok(Some(a)), Ok(Some(b))) => a == b,
+            _ => self.1 == other.1 && self.0 == other.0,
+        }
+    }
+}
+
 /// A wrapped [`Value`] with customized serialization behavior.
 #[cfg(feature = "serialize")]
 #[cfg_attr(docsrs, doc(cfg(feature = "serialize")))]
@@ -654,6 +791,12 @@ impl<'a, 'lua> Serialize for SerializableValue<'a, 'lua> {
     where
         S: Serializer,
     {
+        #[cfg(feature = "lua51_civ6")]
+        if let Value::Other(v) = self.value {
+            if let Some(n) = v.as_u64().map_err(ser::Error::custom)? {
+                return serializer.serialize_u64(n);
+            }
+        }
         match self.value {
             Value::Nil => serializer.serialize_unit(),
             Value::Boolean(b) => serializer.serialize_bool(*b),
@@ -671,11 +814,8 @@ impl<'a, 'lua> Serialize for SerializableValue<'a, 'lua> {
             Value::UserData(ud) if ud.is_serializable() || self.options.deny_unsupported_types => {
                 ud.serialize(serializer)
             }
-            Value::Function(_)
-            | Value::Thread(_)
-            | Value::UserData(_)
-            | Value::LightUserData(_)
-            | Value::Error(_) => {
+            // Functions, threads, userdata, errors and runtime specific values
+            _ => {
                 if self.options.deny_unsupported_types {
                     let msg = format!("cannot serialize <{}>", self.value.type_name());
                     Err(ser::Error::custom(msg))
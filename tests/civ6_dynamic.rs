@@ -0,0 +1,52 @@
+#![cfg(all(feature = "civ6-dynamic", feature = "civ6-standin"))]
+
+use mlua::{ffi, Error, Lua, Result};
+
+#[test]
+fn test_load_runtime() -> Result<()> {
+    match unsafe { Lua::load_runtime(ffi::dynamic::STANDIN_LIBRARY) } {
+        Ok(()) => {}
+        // Loaded on first use by another test
+        Err(Error::ExternalError(err))
+            if matches!(
+                err.downcast_ref(),
+                Some(ffi::dynamic::LoadError::AlreadyLoaded)
+            ) => {}
+        Err(err) => panic!("cannot load the stand-in runtime: {err}"),
+    }
+    assert!(ffi::dynamic::is_loaded());
+    assert!(ffi::dynamic::fallbacks().is_empty());
+
+    let lua = Lua::new();
+    assert_eq!(lua.load("return 1 + 2").eval::<i64>()?, 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_missing_symbols() {
+    // Any shared library without the HavokScript exports will do
+    let library = if cfg!(target_os = "macos") {
+        "/usr/lib/libSystem.B.dylib"
+    } else {
+        "libm.so.6"
+    };
+
+    match unsafe { Lua::load_runtime(library) } {
+        Err(Error::MissingSymbols(symbols)) => {
+            assert!(symbols.contains(&"?lua_gettop@@YAHPEAUlua_State@@@Z".to_string()));
+            assert!(symbols.contains(&"?luaL_error@@YAHPEAUlua_State@@PEBDZZ".to_string()));
+            // Non-essential symbols use fallbacks
+            assert!(
+                !symbols.contains(&"?lua_dump@@YAHPEAUlua_State@@P6AH0PEBX_KPEAX@Z3@Z".to_string())
+            );
+            assert!(!symbols.contains(&"?luaopen_io@@YAHPEAUlua_State@@@Z".to_string()));
+
+            let err = Error::MissingSymbols(symbols);
+            assert!(err
+                .to_string()
+                .starts_with("HavokScript runtime is missing symbols: "));
+        }
+        r => panic!("expected MissingSymbols error, got {r:?}"),
+    }
+}
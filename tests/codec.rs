@@ -0,0 +1,100 @@
+use mlua::{EncodeOptions, Lua, Result, Table, Value};
+
+#[test]
+fn test_encode_value() -> Result<()> {
+    let lua = Lua::new();
+    let value: Table = lua
+        .load(
+            r#"
+            local shared = { name = "shared" }
+            local t = { 1, -2, 3.5, "\0\255", [-7] = 0.25, a = shared, b = shared, [true] = false }
+            t.self = t
+            shared.parent = t
+            return t
+        "#,
+        )
+        .eval()?;
+    let data = lua.encode_value(value, EncodeOptions::new())?;
+
+    let lua2 = Lua::new();
+    let t: Table = lua2.decode_value(&data)?;
+    assert_eq!(t.raw_len(), 4);
+    assert_eq!(t.get::<_, Value>(1)?, Value::Integer(1));
+    assert_eq!(t.get::<_, Value>(2)?, Value::Integer(-2));
+    assert_eq!(t.get::<_, Value>(3)?, Value::Number(3.5));
+    assert_eq!(t.get::<_, mlua::String>(4)?, b"\0\xff".as_ref());
+    assert_eq!(t.get::<_, f64>(-7)?, 0.25);
+    assert!(!t.get::<_, bool>(true)?);
+
+    // Shared tables and cycles
+    let (a, b): (Table, Table) = (t.get("a")?, t.get("b")?);
+    assert_eq!(a, b);
+    assert_eq!(a.get::<_, Table>("parent")?, t);
+    assert_eq!(t.get::<_, Table>("self")?, t);
+
+    // Repeated strings are written once
+    let strings = lua.create_sequence_from(vec!["repeated string"; 100])?;
+    let data = lua.encode_value(strings, EncodeOptions::new())?;
+    assert!(data.len() < 250);
+    let strings: Vec<String> = lua.decode_value(&data)?;
+    assert_eq!(strings, vec!["repeated string"; 100]);
+
+    // Primitives
+    let data = lua.encode_value(i32::MIN, EncodeOptions::new())?;
+    assert_eq!(lua.decode_value::<i32>(&data)?, i32::MIN);
+    let data = lua.encode_value(Value::Nil, EncodeOptions::new())?;
+    assert_eq!(lua.decode_value::<Value>(&data)?, Value::Nil);
+
+    Ok(())
+}
+
+#[test]
+fn test_encode_value_errors() -> Result<()> {
+    let lua = Lua::new();
+
+    let t = lua.create_table()?;
+    t.set("f", lua.create_function(|_, ()| Ok(()))?)?;
+    let err = lua.encode_value(t, EncodeOptions::new()).unwrap_err();
+    assert!(err.to_string().contains("cannot encode function"), "{err}");
+
+    let data = lua.encode_value(lua.create_sequence_from([1, 2])?, EncodeOptions::new())?;
+    assert!(lua.decode_value::<Value>(b"garbage").is_err());
+    assert!(lua.decode_value::<Value>(&data[..data.len() - 1]).is_err());
+    let mut trailing = data.clone();
+    trailing.push(0);
+    let err = lua.decode_value::<Value>(&trailing).unwrap_err();
+    assert!(err.to_string().contains("trailing data"), "{err}");
+    // Huge table length
+    let mut bad = data[..6].to_vec();
+    bad.extend_from_slice(&[7, 0xff, 0xff, 0xff, 0xff, 0x0f]);
+    assert!(lua.decode_value::<Value>(&bad).is_err());
+
+    Ok(())
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn test_encode_value_compressed() -> Result<()> {
+    let lua = Lua::new();
+    let value = lua.create_sequence_from((0..1000).map(|i| i % 10))?;
+    let plain = lua.encode_value(value.clone(), EncodeOptions::new())?;
+    let compressed = lua.encode_value(value, EncodeOptions::new().compress(true))?;
+    assert!(compressed.len() < plain.len() / 4);
+
+    let values: Vec<i32> = lua.decode_value(&compressed)?;
+    assert_eq!(values, (0..1000).map(|i| i % 10).collect::<Vec<_>>());
+
+    // Data compressed with the best ratio
+    let s = lua.create_string(vec![b'a'; 1 << 20])?;
+    let compressed = lua.encode_value(Value::String(s), EncodeOptions::new().compress(true))?;
+    assert_eq!(lua.decode_value::<String>(&compressed)?.len(), 1 << 20);
+
+    // The declared size is checked before allocating the output
+    let mut bad = compressed[..6].to_vec();
+    bad.extend_from_slice(&u32::MAX.to_le_bytes());
+    bad.extend_from_slice(&[0; 16]);
+    let err = lua.decode_value::<Value>(&bad).unwrap_err();
+    assert!(err.to_string().contains("bad decompressed size"));
+
+    Ok(())
+}
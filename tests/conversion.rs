@@ -471,3 +471,67 @@ fn test_option_into_from_lua() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(feature = "lua51_civ6")]
+#[test]
+fn test_civ6_integer_boundaries() -> Result<()> {
+    let lua = Lua::new();
+
+    // The 32-bit range is represented as `Integer`
+    for i in [i32::MIN, -1, 0, 1, i32::MAX] {
+        assert_eq!(i.into_lua(&lua)?, Value::Integer(i));
+        assert_eq!(
+            lua.load(format!("return {i}")).eval::<Value>()?,
+            Value::Integer(i)
+        );
+    }
+    assert_eq!((i32::MAX as i64).into_lua(&lua)?, Value::Integer(i32::MAX));
+
+    // Anything outside falls back to `Number`
+    let max_plus_one = i32::MAX as i64 + 1;
+    let min_minus_one = i32::MIN as i64 - 1;
+    assert_eq!(max_plus_one.into_lua(&lua)?, Value::Number(2147483648.0));
+    assert_eq!(min_minus_one.into_lua(&lua)?, Value::Number(-2147483649.0));
+    assert_eq!(u32::MAX.into_lua(&lua)?, Value::Number(u32::MAX as f64));
+    assert_eq!(
+        lua.load("return 2^31").eval::<Value>()?,
+        Value::Number(2147483648.0)
+    );
+
+    // ... and converts back to wide integer types losslessly (up to 2^53)
+    let f = lua.create_function(|_, i: i64| Ok(i))?;
+    assert_eq!(f.call::<_, i64>(max_plus_one)?, max_plus_one);
+    assert_eq!(f.call::<_, i64>(min_minus_one)?, min_minus_one);
+    assert_eq!(f.call::<_, i64>(1i64 << 53)?, 1i64 << 53);
+    assert_eq!(lua.load("return 2^40").eval::<u64>()?, 1u64 << 40);
+
+    // Integers that cannot be represented exactly are not truncated
+    let inexact = (1i64 << 53) + 1;
+    assert!(matches!(
+        inexact.into_lua(&lua),
+        Err(Error::ToLuaConversionError { .. })
+    ));
+    assert!(f.call::<_, i64>(inexact).is_err());
+    assert!(u64::MAX.into_lua(&lua).is_err());
+    assert!(i128::MAX.into_lua(&lua).is_err());
+
+    // Narrow integer types check for overflow
+    assert_eq!(lua.load("return 2^31 - 1").eval::<i32>()?, i32::MAX);
+    assert_eq!(lua.load("return -2^31").eval::<i32>()?, i32::MIN);
+    assert!(lua.load("return 2^31").eval::<i32>().is_err());
+    assert!(lua.load("return -2^31 - 1").eval::<i32>().is_err());
+    assert_eq!(lua.load("return 2^32 - 1").eval::<u32>()?, u32::MAX);
+    assert!(lua.load("return 2^32").eval::<u32>().is_err());
+    assert!(lua.load("return -1").eval::<u32>().is_err());
+
+    // Fractional numbers are not integers
+    assert_eq!(lua.load("return 0.5").eval::<Value>()?, Value::Number(0.5));
+    assert_eq!(lua.load("return 1.0").eval::<Value>()?, Value::Integer(1));
+
+    // Arithmetic past the 32-bit range stays a `Number`
+    let v = lua.load("return 2147483647 + 1").eval::<Value>()?;
+    assert_eq!(v, Value::Number(2147483648.0));
+    assert_eq!(v.as_integer(), None);
+
+    Ok(())
+}
@@ -0,0 +1,109 @@
+#![cfg(not(feature = "luau"))]
+
+use mlua::{Function, Lua, Result, Scheduler, Thread};
+
+#[test]
+fn test_line_coverage() -> Result<()> {
+    let lua = Lua::new();
+    assert!(lua.stop_coverage().is_none());
+
+    lua.start_coverage(|chunk| chunk.starts_with("scripts/"));
+    lua.load(
+        r#"local function add(a, b)
+    return a + b
+end
+local function unused()
+    local x = 1
+    return x
+end
+local sum = 0
+for i = 1, 3 do
+    sum = add(sum, i)
+end
+return sum
+"#,
+    )
+    .set_name("@scripts/main.lua")
+    .exec()?;
+    lua.load("local x = 1\nreturn x")
+        .set_name("=other")
+        .exec()?;
+    let coverage = lua.stop_coverage().unwrap();
+
+    assert_eq!(coverage.chunks().collect::<Vec<_>>(), ["scripts/main.lua"]);
+    assert_eq!(coverage.hits("scripts/main.lua", 2), Some(3));
+    assert_eq!(coverage.hits("scripts/main.lua", 8), Some(1));
+    assert_eq!(coverage.hits("scripts/main.lua", 10), Some(3));
+    assert!(coverage.hits("scripts/main.lua", 12).unwrap() >= 1);
+    // `unused` was never called, so its lines are not known
+    assert_eq!(coverage.hits("scripts/main.lua", 5), None);
+    assert_eq!(coverage.hits("other", 1), None);
+
+    let lcov = coverage.to_lcov();
+    assert!(lcov.starts_with("TN:\nSF:scripts/main.lua\n"));
+    assert!(lcov.contains("DA:2,3\n"));
+    assert!(lcov.ends_with("end_of_record\n"));
+
+    // Results of several runs can be merged
+    lua.start_coverage(|_| true);
+    lua.load("local t = {}\nif #t > 0 then\n    t = nil\nend\n")
+        .set_name("@scripts/branch.lua")
+        .exec()?;
+    let mut total = lua.stop_coverage().unwrap();
+    assert_eq!(total.hits("scripts/branch.lua", 3), Some(0));
+    assert!(total.to_lcov().contains("DA:3,0\n"));
+
+    total.merge(&coverage);
+    total.merge(&coverage);
+    assert_eq!(total.hits("scripts/main.lua", 2), Some(6));
+    assert_eq!(total.chunks().count(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_line_coverage_coroutines() -> Result<()> {
+    let lua = Lua::new();
+
+    // Created before starting the coverage and resumed from Rust
+    let thread: Thread = lua
+        .load("coroutine.create(function()\n    local x = 1\n    coroutine.yield(x)\n    return x + 1\nend)")
+        .set_name("@scripts/thread.lua")
+        .eval()?;
+    let mut scheduler = Scheduler::new(&lua)?;
+
+    lua.start_coverage(|chunk| chunk.starts_with("scripts/"));
+    lua.load(
+        r#"local co = coroutine.wrap(function(n)
+    for i = 1, n do
+        coroutine.yield(i)
+    end
+end)
+co(3)
+co()
+co()
+"#,
+    )
+    .set_name("@scripts/wrap.lua")
+    .exec()?;
+    thread.resume::<_, ()>(())?;
+    thread.resume::<_, ()>(())?;
+
+    let task: Function = lua
+        .load("function()\n    wait()\n    done = true\nend")
+        .set_name("@scripts/task.lua")
+        .eval()?;
+    scheduler.spawn(&lua, task, ())?;
+    for _ in 0..3 {
+        scheduler.tick(&lua, 1.0 / 60.0)?;
+    }
+    assert!(lua.globals().get::<_, bool>("done")?);
+    let coverage = lua.stop_coverage().unwrap();
+
+    assert_eq!(coverage.hits("scripts/wrap.lua", 3), Some(3));
+    assert_eq!(coverage.hits("scripts/thread.lua", 2), Some(1));
+    assert_eq!(coverage.hits("scripts/thread.lua", 4), Some(1));
+    assert_eq!(coverage.hits("scripts/task.lua", 3), Some(1));
+
+    Ok(())
+}
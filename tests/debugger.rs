@@ -0,0 +1,344 @@
+#![cfg(all(feature = "debugger", not(feature = "luau")))]
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use mlua::debugger::Debugger;
+use mlua::{Function, Lua, Result, Scheduler};
+use serde_json::{json, Value as JsonValue};
+
+// A minimal scripted DAP client
+struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    seq: i64,
+    events: VecDeque<JsonValue>,
+}
+
+impl Client {
+    fn connect(addr: SocketAddr) -> Client {
+        let stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        Client {
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            writer: stream,
+            seq: 0,
+            events: VecDeque::new(),
+        }
+    }
+
+    fn read(&mut self) -> JsonValue {
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Content-Length:") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; length];
+        self.reader.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn request(&mut self, command: &str, arguments: JsonValue) -> JsonValue {
+        self.seq += 1;
+        let message = json!({
+            "seq": self.seq,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        })
+        .to_string();
+        write!(
+            self.writer,
+            "Content-Length: {}\r\n\r\n{message}",
+            message.len()
+        )
+        .unwrap();
+
+        loop {
+            let message = self.read();
+            if message["type"] == "response" && message["request_seq"] == self.seq {
+                assert_eq!(message["command"], command);
+                return message;
+            }
+            self.events.push_back(message);
+        }
+    }
+
+    fn wait_event(&mut self, event: &str) -> JsonValue {
+        loop {
+            let message = match self.events.pop_front() {
+                Some(message) => message,
+                None => self.read(),
+            };
+            if message["type"] == "event" && message["event"] == event {
+                return message["body"].clone();
+            }
+        }
+    }
+
+    // Returns (name, line, frame id) of the top frame
+    fn top_frame(&mut self) -> (String, i64, i64) {
+        let response = self.request("stackTrace", json!({"threadId": 1}));
+        let frame = &response["body"]["stackFrames"][0];
+        (
+            frame["name"].as_str().unwrap().to_string(),
+            frame["line"].as_i64().unwrap(),
+            frame["id"].as_i64().unwrap(),
+        )
+    }
+
+    fn variables(&mut self, reference: &JsonValue) -> Vec<(String, String, JsonValue)> {
+        let response = self.request("variables", json!({ "variablesReference": reference }));
+        let vars = response["body"]["variables"].as_array().unwrap();
+        vars.iter()
+            .map(|var| {
+                (
+                    var["name"].as_str().unwrap().to_string(),
+                    var["value"].as_str().unwrap().to_string(),
+                    var["variablesReference"].clone(),
+                )
+            })
+            .collect()
+    }
+}
+
+const SCRIPT: &str = r#"local function add(a, b)
+    local sum = a + b
+    return sum
+end
+local x = 10
+local t = {name = "civ", n = 6}
+local y = add(x, 5)
+result = y * 2
+"#;
+
+#[test]
+fn test_debugger_session() -> Result<()> {
+    let lua = Lua::new();
+    let debugger = Debugger::listen("127.0.0.1:0")?;
+    debugger.attach(&lua);
+    let addr = debugger.local_addr();
+
+    let client = thread::spawn(move || {
+        let mut client = Client::connect(addr);
+        let response = client.request("initialize", json!({"adapterID": "mlua"}));
+        assert_eq!(response["success"], true);
+        client.wait_event("initialized");
+        let response = client.request(
+            "setBreakpoints",
+            json!({
+                "source": {"path": "C:\\Mods\\Test\\Scripts\\Main.lua"},
+                "breakpoints": [{"line": 7}],
+            }),
+        );
+        assert_eq!(response["body"]["breakpoints"][0]["verified"], true);
+        client.request("configurationDone", json!({}));
+
+        // Breakpoint
+        let stopped = client.wait_event("stopped");
+        assert_eq!(stopped["reason"], "breakpoint");
+        let (name, line, frame_id) = client.top_frame();
+        assert_eq!((name.as_str(), line), ("main chunk", 7));
+
+        let response = client.request("scopes", json!({ "frameId": frame_id }));
+        let locals_ref = response["body"]["scopes"][0]["variablesReference"].clone();
+        let locals = client.variables(&locals_ref);
+        let names = locals.iter().map(|v| v.0.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["add", "x", "t"]);
+        assert_eq!(locals[1].1, "10");
+        let fields = client.variables(&locals[2].2);
+        assert_eq!(fields[0], ("n".into(), "6".into(), json!(0)));
+        assert_eq!(fields[1], ("name".into(), "\"civ\"".into(), json!(0)));
+
+        let response = client.request(
+            "evaluate",
+            json!({"expression": "x + t.n", "frameId": frame_id}),
+        );
+        assert_eq!(response["body"]["result"], "16");
+        let response = client.request("evaluate", json!({"expression": "error('oops')"}));
+        assert_eq!(response["success"], false);
+
+        // Assignments change the paused frame
+        let response = client.request(
+            "evaluate",
+            json!({"expression": "x = x * 2", "frameId": frame_id}),
+        );
+        assert_eq!(response["body"]["result"], "");
+
+        // Step into `add`
+        client.request("stepIn", json!({"threadId": 1}));
+        assert_eq!(client.wait_event("stopped")["reason"], "step");
+        let (name, line, frame_id) = client.top_frame();
+        assert_eq!((name.as_str(), line), ("add", 2));
+        let response = client.request(
+            "evaluate",
+            json!({"expression": "a * b", "frameId": frame_id}),
+        );
+        assert_eq!(response["body"]["result"], "100");
+
+        // Step out back to the main chunk
+        client.request("stepOut", json!({"threadId": 1}));
+        client.wait_event("stopped");
+        let (name, line, _) = client.top_frame();
+        assert_eq!(name, "main chunk");
+        assert!(line >= 7);
+
+        // Step over to the last line
+        if line == 7 {
+            client.request("next", json!({"threadId": 1}));
+            client.wait_event("stopped");
+            assert_eq!(client.top_frame().1, 8);
+        }
+
+        client.request("continue", json!({"threadId": 1}));
+        client.request("disconnect", json!({}));
+    });
+
+    assert!(debugger.wait_for_client(Some(Duration::from_secs(10))));
+    lua.load(SCRIPT).set_name("@scripts/main.lua").exec()?;
+    client.join().unwrap();
+    assert_eq!(lua.globals().get::<_, i64>("result")?, 50);
+
+    Ok(())
+}
+
+#[test]
+fn test_debugger_pause() -> Result<()> {
+    let lua = Lua::new();
+    let debugger = Debugger::listen("127.0.0.1:0")?;
+    debugger.attach(&lua);
+    let addr = debugger.local_addr();
+
+    // Without a client the hook does nothing
+    lua.load("local n = 0 for i = 1, 100 do n = n + i end")
+        .exec()?;
+    assert!(!debugger.wait_for_client(Some(Duration::from_millis(10))));
+
+    let client = thread::spawn(move || {
+        let mut client = Client::connect(addr);
+        client.request("initialize", json!({}));
+        client.request("configurationDone", json!({}));
+        client.request("pause", json!({"threadId": 1}));
+        assert_eq!(client.wait_event("stopped")["reason"], "pause");
+        let response = client.request("evaluate", json!({"expression": "stop"}));
+        assert_eq!(response["body"]["result"], "false");
+        client.request("evaluate", json!({"expression": "stop = true"}));
+        // Dropping the connection resumes the execution
+    });
+
+    assert!(debugger.wait_for_client(Some(Duration::from_secs(10))));
+    lua.load("stop = false while not stop do end").exec()?;
+    client.join().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn test_debugger_step_over_coroutine() -> Result<()> {
+    let lua = Lua::new();
+    let debugger = Debugger::listen("127.0.0.1:0")?;
+    debugger.attach(&lua);
+    let addr = debugger.local_addr();
+
+    // The coroutine stays suspended, so its calls never return
+    let co = lua.create_thread(
+        lua.load("coroutine.yield()\nreturn 1")
+            .set_name("@scripts/co.lua")
+            .into_function()?,
+    )?;
+    lua.globals().set("co", co)?;
+
+    let client = thread::spawn(move || {
+        let mut client = Client::connect(addr);
+        client.request("initialize", json!({}));
+        client.request(
+            "setBreakpoints",
+            json!({"source": {"path": "scripts/main.lua"}, "breakpoints": [{"line": 1}]}),
+        );
+        client.request("configurationDone", json!({}));
+        assert_eq!(client.wait_event("stopped")["reason"], "breakpoint");
+
+        client.request("next", json!({"threadId": 1}));
+        assert_eq!(client.wait_event("stopped")["reason"], "step");
+        let (name, line, _) = client.top_frame();
+        assert_eq!((name.as_str(), line), ("main chunk", 2));
+
+        client.request("continue", json!({"threadId": 1}));
+        client.request("disconnect", json!({}));
+    });
+
+    assert!(debugger.wait_for_client(Some(Duration::from_secs(10))));
+    lua.load("coroutine.resume(co)\nresult = 1")
+        .set_name("@scripts/main.lua")
+        .exec()?;
+    client.join().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn test_debugger_coroutines() -> Result<()> {
+    let lua = Lua::new();
+    let debugger = Debugger::listen("127.0.0.1:0")?;
+    debugger.attach(&lua);
+    let addr = debugger.local_addr();
+    let mut scheduler = Scheduler::new(&lua)?;
+
+    let client = thread::spawn(move || {
+        let mut client = Client::connect(addr);
+        client.request("initialize", json!({}));
+        client.request(
+            "setBreakpoints",
+            json!({"source": {"path": "scripts/co.lua"}, "breakpoints": [{"line": 2}]}),
+        );
+        client.request(
+            "setBreakpoints",
+            json!({"source": {"path": "scripts/task.lua"}, "breakpoints": [{"line": 3}]}),
+        );
+        client.request("configurationDone", json!({}));
+
+        // Coroutine created by Lua code
+        assert_eq!(client.wait_event("stopped")["reason"], "breakpoint");
+        assert_eq!(client.top_frame().1, 2);
+        // Stepping over stays in the coroutine
+        client.request("next", json!({"threadId": 1}));
+        assert_eq!(client.wait_event("stopped")["reason"], "step");
+        assert_eq!(client.top_frame().1, 3);
+        client.request("continue", json!({"threadId": 1}));
+
+        // Scheduler task
+        assert_eq!(client.wait_event("stopped")["reason"], "breakpoint");
+        assert_eq!(client.top_frame().1, 3);
+        client.request("continue", json!({"threadId": 1}));
+        client.request("disconnect", json!({}));
+    });
+
+    assert!(debugger.wait_for_client(Some(Duration::from_secs(10))));
+    lua.load("coroutine.wrap(function()\n    local x = 1\n    result = x + 1\nend)()")
+        .set_name("@scripts/co.lua")
+        .exec()?;
+    let task: Function = lua
+        .load("function()\n    wait()\n    done = true\nend")
+        .set_name("@scripts/task.lua")
+        .eval()?;
+    scheduler.spawn(&lua, task, ())?;
+    for _ in 0..3 {
+        scheduler.tick(&lua, 1.0 / 60.0)?;
+    }
+    client.join().unwrap();
+    assert!(lua.globals().get::<_, bool>("done")?);
+
+    Ok(())
+}
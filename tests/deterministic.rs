@@ -0,0 +1,191 @@
+use mlua::{DeterministicOptions, Lua, LuaOptions, Result, StdLib, Table};
+
+#[test]
+fn test_deterministic_random() -> Result<()> {
+    let sequence = |seed| -> Result<Vec<f64>> {
+        let lua = Lua::new();
+        lua.enable_deterministic_mode(DeterministicOptions::new().seed(seed))?;
+        lua.load("local t = {} for i = 1, 10 do t[i] = math.random() end return t")
+            .eval()
+    };
+    assert_eq!(sequence(42)?, sequence(42)?);
+    assert_ne!(sequence(42)?, sequence(43)?);
+    assert!(sequence(1)?.iter().all(|&r| (0.0..1.0).contains(&r)));
+
+    let lua = Lua::new();
+    lua.enable_deterministic_mode(DeterministicOptions::new())?;
+    lua.load(
+        r#"
+        for i = 1, 1000 do
+            local r = math.random(6)
+            assert(r >= 1 and r <= 6 and r % 1 == 0)
+            r = math.random(-3, 3)
+            assert(r >= -3 and r <= 3)
+        end
+        assert(math.random(5, 5) == 5)
+        assert(not pcall(math.random, 2, 1))
+
+        -- Float bounds are floored
+        for i = 1, 100 do
+            local r = math.random(2.5)
+            assert(r >= 1 and r <= 2)
+            r = math.random(1, 7 / 2)
+            assert(r >= 1 and r <= 3)
+        end
+        assert(math.random(4.9, 4.1) == 4)
+        assert(not pcall(math.random, 0.5))
+    "#,
+    )
+    .exec()?;
+
+    // Reseeding restarts the sequence
+    lua.load("math.randomseed(7); a = math.random(1000000)")
+        .exec()?;
+    lua.set_deterministic_seed(7)?;
+    let b: i64 = lua.load("math.random(1000000)").eval()?;
+    assert_eq!(lua.globals().get::<_, i64>("a")?, b);
+
+    Ok(())
+}
+
+#[test]
+fn test_deterministic_time() -> Result<()> {
+    let lua = Lua::new();
+
+    // Not enabled yet
+    assert!(lua.set_deterministic_time(0, 0.0).is_err());
+    assert!(lua.set_deterministic_seed(0).is_err());
+
+    lua.enable_deterministic_mode(DeterministicOptions::new().time(1000).clock(1.5))?;
+    assert_eq!(lua.load("os.time()").eval::<i64>()?, 1000);
+    assert_eq!(lua.load("os.clock()").eval::<f64>()?, 1.5);
+
+    lua.set_deterministic_time(2000, 2.5)?;
+    assert_eq!(lua.load("os.time()").eval::<i64>()?, 2000);
+    assert_eq!(lua.load("os.clock()").eval::<f64>()?, 2.5);
+
+    // Date tables are interpreted in UTC
+    let time: i64 = lua
+        .load("os.time({year = 2000, month = 1, day = 1, hour = 0})")
+        .eval()?;
+    assert_eq!(time, 946684800);
+    let time: i64 = lua
+        .load("os.time({year = 2024, month = 2, day = 29, hour = 13, min = 30, sec = 15})")
+        .eval()?;
+    assert_eq!(time, 1709213415);
+
+    Ok(())
+}
+
+#[test]
+fn test_deterministic_pairs() -> Result<()> {
+    let lua = Lua::new();
+    lua.enable_deterministic_mode(DeterministicOptions::new())?;
+
+    let keys: String = lua
+        .load(
+            r#"
+        local t = {c = 1, a = 2, b = 3, [2] = 4, [1] = 5, [true] = 6}
+        local keys = {}
+        for k, v in opairs(t) do
+            assert(t[k] == v)
+            keys[#keys + 1] = tostring(k)
+        end
+        return table.concat(keys, ",")
+    "#,
+        )
+        .eval()?;
+    assert_eq!(keys, "true,1,2,a,b,c");
+
+    // `pairs` is untouched by default
+    let pairs_eq: bool = lua.load("pairs == opairs").eval()?;
+    assert!(!pairs_eq);
+
+    lua.enable_deterministic_mode(DeterministicOptions::new().ordered_pairs(true))?;
+    let keys: String = lua
+        .load(
+            r#"
+        local keys = {}
+        for k in pairs({z = 1, y = 2, x = 3}) do
+            keys[#keys + 1] = k
+        end
+        return table.concat(keys)
+    "#,
+        )
+        .eval()?;
+    assert_eq!(keys, "xyz");
+
+    // Keys ordered by address are rejected
+    for key in ["{}", "print", "coroutine.create(function() end)"] {
+        let err = lua
+            .load(format!("for _ in pairs({{[{key}] = 1}}) do end"))
+            .exec()
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("cannot iterate in order over a table with a key of type"),
+            "{err}"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_deterministic_load_std_lib() -> Result<()> {
+    let lua = Lua::new_with(StdLib::NONE, LuaOptions::default())?;
+    lua.enable_deterministic_mode(DeterministicOptions::new().time(123))?;
+    lua.load_from_std_lib(StdLib::OS)?;
+    assert_eq!(lua.load("os.time()").eval::<i64>()?, 123);
+
+    Ok(())
+}
+
+#[test]
+fn test_checksum() -> Result<()> {
+    let make = |extra: Option<i64>| -> Result<(Lua, u64)> {
+        let lua = Lua::new();
+        let t: Table = lua
+            .load(
+                r#"
+            local t = {1, 2.5, "three", nested = {a = true, b = false}, [10] = 4.0}
+            t.self = t
+            t.shared1 = t.nested
+            return t
+        "#,
+            )
+            .eval()?;
+        if let Some(extra) = extra {
+            t.set("extra", extra)?;
+        }
+        let sum = lua.checksum(&[t])?;
+        Ok((lua, sum))
+    };
+
+    let (_lua1, sum1) = make(None)?;
+    let (_lua2, sum2) = make(None)?;
+    assert_eq!(sum1, sum2);
+
+    let (_lua3, sum3) = make(Some(1))?;
+    assert_ne!(sum1, sum3);
+
+    // Insertion order does not matter
+    let lua = Lua::new();
+    let t1 = lua
+        .load("local t = {} t.x = 1 t.y = 2 t.z = 3 return t")
+        .eval()?;
+    let t2 = lua
+        .load("local t = {} t.z = 3 t.y = 2 t.x = 1 return t")
+        .eval()?;
+    assert_eq!(lua.checksum(&[t1])?, lua.checksum(&[t2])?);
+
+    // Keys without a portable order are rejected
+    let t = lua.load("return { nested = { [{}] = 1 } }").eval()?;
+    let err = lua.checksum(&[t]).unwrap_err().to_string();
+    assert!(
+        err.contains("cannot checksum a table with a key of type table"),
+        "{err}"
+    );
+
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use std::io;
+use std::sync::Arc;
 
-use mlua::{Error, ErrorContext, Lua, Result};
+use mlua::{Error, ErrorContext, Function, Lua, Result, Table, Value};
 
 #[test]
 fn test_error_context() -> Result<()> {
@@ -50,3 +51,282 @@ fn test_error_context() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_error_traceback() -> Result<()> {
+    let lua = Lua::new();
+    lua.set_error_details(true);
+
+    let rust_fail = lua.create_function(|_, ()| Err::<(), _>(Error::runtime("rust failure")))?;
+    lua.globals().set("rust_fail", rust_fail)?;
+
+    let chunk = lua
+        .load(
+            r#"
+            local function inner()
+                error("lua failure")
+            end
+            function outer()
+                inner()
+            end
+            function call_rust()
+                rust_fail()
+            end
+        "#,
+        )
+        .set_name("@mods/ui.lua");
+    chunk.exec()?;
+
+    // Lua errors
+    let err = lua.load("outer()").exec().unwrap_err();
+    let Error::WithDetails {
+        traceback, cause, ..
+    } = &err
+    else {
+        panic!("expected WithDetails, got {err:?}");
+    };
+    assert!(matches!(**cause, Error::RuntimeError(ref msg) if msg.contains("lua failure")));
+    let top = traceback.iter().find(|frame| frame.what == "Lua").unwrap();
+    assert_eq!(top.source.as_deref(), Some("@mods/ui.lua"));
+    assert_eq!(top.line, Some(3));
+    assert_eq!(top.line_defined, Some(2));
+    assert_eq!(top.name.as_deref(), Some("inner"));
+    assert_eq!(top.name_what.as_deref(), Some("upvalue"));
+    assert!(!top.is_callback);
+    let outer = &traceback[traceback.iter().position(|f| f == top).unwrap() + 1];
+    assert_eq!(outer.name.as_deref(), Some("outer"));
+    assert_eq!(outer.line, Some(6));
+    assert_eq!(err.traceback(), Some(traceback.as_slice()));
+    let callback_err = Error::CallbackError {
+        traceback: String::new(),
+        cause: Arc::new(err.clone()),
+    };
+    assert_eq!(callback_err.traceback(), Some(traceback.as_slice()));
+
+    // The classic text is preserved
+    let text = err.to_string();
+    assert!(text.contains("\nstack traceback:\n\t"));
+    assert!(text.contains("\n\tmods/ui.lua:3: in function 'inner'"));
+    assert!(text.contains("\n\tmods/ui.lua:6: in function 'outer'"));
+
+    // Rust callback errors
+    let err = lua.load("call_rust()").exec().unwrap_err();
+    let traceback = err.traceback().unwrap();
+    let Error::WithDetails { cause, .. } = &err else {
+        panic!("expected WithDetails, got {err:?}");
+    };
+    let Error::CallbackError { cause, .. } = &**cause else {
+        panic!("expected CallbackError, got {cause:?}");
+    };
+    assert!(matches!(**cause, Error::RuntimeError(ref msg) if msg == "rust failure"));
+    assert!(traceback[0].is_callback);
+    assert_eq!(traceback[0].what, "C");
+    assert_eq!(traceback[1].name.as_deref(), Some("call_rust"));
+    assert_eq!(traceback[1].line, Some(9));
+    assert!(err
+        .to_string()
+        .contains("\n\tmods/ui.lua:9: in function 'call_rust'"));
+
+    // Errors without a Lua stack have no frames
+    assert_eq!(Error::runtime("manual").traceback(), None);
+
+    // Details are not attached by default
+    lua.set_error_details(false);
+    let err = lua.load("outer()").exec().unwrap_err();
+    assert!(matches!(err, Error::RuntimeError(_)));
+    assert!(err.traceback().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_error_traceback_deep_stack() -> Result<()> {
+    let lua = Lua::new();
+    lua.set_error_details(true);
+
+    let err = lua
+        .load("local function f(n) if n == 0 then error('deep') end f(n - 1) end f(50)")
+        .exec()
+        .unwrap_err();
+    let traceback = err.traceback().unwrap();
+    // The middle of the stack is omitted
+    assert_eq!(traceback.len(), 22);
+    assert!(traceback[12].level > traceback[11].level + 1);
+    assert!(err.to_string().contains("\n\t...\n\t"));
+
+    Ok(())
+}
+
+#[test]
+fn test_error_value() -> Result<()> {
+    let lua = Lua::new();
+    lua.set_error_details(true);
+
+    // Tables passed to `error` are preserved
+    let err = lua
+        .load("error({code = 42, msg = 'bad move'})")
+        .exec()
+        .unwrap_err();
+    let Error::WithDetails {
+        value: Some(key), ..
+    } = &err
+    else {
+        panic!("expected WithDetails with a value, got {err:?}");
+    };
+    let value = lua.registry_value::<Table>(key)?;
+    assert_eq!(value.get::<_, i32>("code")?, 42);
+    assert_eq!(value.get::<_, String>("msg")?, "bad move");
+    assert_eq!(err.value(), Some(&**key));
+    assert!(err.context("ctx").value().is_some());
+
+    // Strings are kept only as the message
+    let err = lua.load("error('plain')").exec().unwrap_err();
+    assert!(err.value().is_none());
+
+    // Other values too
+    let err = lua.load("error(false)").exec().unwrap_err();
+    assert!(!lua.registry_value::<bool>(err.value().unwrap())?);
+
+    // Rethrowing from a Rust callback restores the original object
+    let raise: Function = lua.load("return function(t) error(t) end").eval()?;
+    let rethrow = lua.create_function(move |lua, t: Table| {
+        let raise = lua.globals().get::<_, Function>("raise")?;
+        raise.call::<_, ()>(t).context("rethrow")
+    })?;
+    lua.globals().set("raise", raise)?;
+    lua.globals().set("rethrow", rethrow)?;
+    let same = lua
+        .load(
+            r#"
+            local t = {}
+            local ok, err = pcall(rethrow, t)
+            return not ok and rawequal(err, t)
+        "#,
+        )
+        .eval::<bool>()?;
+    assert!(same);
+
+    // ... also through nested callbacks
+    let call = lua.create_function(|_, (f, t): (Function, Table)| f.call::<_, ()>(t))?;
+    lua.globals().set("call", call)?;
+    let same = lua
+        .load("local t = {} local ok, err = pcall(call, rethrow, t) return rawequal(err, t)")
+        .eval::<bool>()?;
+    assert!(same);
+
+    // Errors from other Lua states are not restored
+    let other = Lua::new();
+    let foreign = other.load("error({})").exec().unwrap_err();
+    let raise_foreign = lua.create_function(move |_, ()| Err::<(), _>(foreign.clone()))?;
+    let err = lua
+        .load("local ok, err = pcall(...) return err")
+        .call::<_, Value>(raise_foreign)?;
+    let Value::Error(Error::WithDetails { cause, .. }) = err else {
+        panic!("expected WithDetails, got {err:?}");
+    };
+    assert!(matches!(*cause, Error::CallbackError { .. }));
+
+    Ok(())
+}
+
+#[test]
+fn test_error_value_thread() -> Result<()> {
+    let lua = Lua::new();
+    lua.set_error_details(true);
+
+    let thread = lua.create_thread(
+        lua.load("error({reason = 'yield failed'})")
+            .into_function()?,
+    )?;
+    let err = thread.resume::<_, ()>(()).unwrap_err();
+    let value = lua.registry_value::<Table>(err.value().unwrap())?;
+    assert_eq!(value.get::<_, String>("reason")?, "yield failed");
+
+    Ok(())
+}
+
+#[test]
+fn test_syntax_error_position() -> Result<()> {
+    let lua = Lua::new();
+
+    let err = lua
+        .load("local x = 1\nlocal y = = 2\n")
+        .set_name("@mods/ui.lua")
+        .exec()
+        .unwrap_err();
+    let Error::SyntaxError {
+        chunk_name,
+        line,
+        column,
+        token,
+        incomplete_input,
+        ..
+    } = &err
+    else {
+        panic!("expected SyntaxError, got {err:?}");
+    };
+    assert_eq!(chunk_name.as_deref(), Some("@mods/ui.lua"));
+    assert_eq!(*line, Some(2));
+    assert_eq!(token.as_deref(), Some("="));
+    // `=` occurs twice in the line
+    assert_eq!(*column, None);
+    assert!(!incomplete_input);
+
+    let err = lua
+        .load("print('a')\n\nlocal t = { 1 2 }")
+        .set_name("=long chunk name that would be shortened by Lua")
+        .exec()
+        .unwrap_err();
+    let Error::SyntaxError {
+        chunk_name,
+        line,
+        column,
+        token,
+        ..
+    } = &err
+    else {
+        panic!("expected SyntaxError, got {err:?}");
+    };
+    assert_eq!(
+        chunk_name.as_deref(),
+        Some("=long chunk name that would be shortened by Lua")
+    );
+    assert_eq!(*line, Some(3));
+    assert_eq!(token.as_deref(), Some("2"));
+    assert_eq!(*column, Some(15));
+
+    let err = lua
+        .load("function f()\n  return 1\n")
+        .set_name("@mods/unfinished.lua")
+        .exec()
+        .unwrap_err();
+    let Error::SyntaxError {
+        line,
+        column,
+        token,
+        incomplete_input,
+        ..
+    } = &err
+    else {
+        panic!("expected SyntaxError, got {err:?}");
+    };
+    assert_eq!(*line, Some(3));
+    assert_eq!(token.as_deref(), Some("<eof>"));
+    assert_eq!(*column, None);
+    assert!(incomplete_input);
+
+    // Chunk names looking like a position
+    for name in ["a:1: b", "=a:1: b", "@a:1: b"] {
+        let err = lua
+            .load("local x = 1\nlocal y = = 2\n")
+            .set_name(name)
+            .exec()
+            .unwrap_err();
+        let Error::SyntaxError { line, .. } = &err else {
+            panic!("expected SyntaxError, got {err:?}");
+        };
+        assert_eq!(*line, Some(2), "chunk name {name:?}");
+    }
+
+    Ok(())
+}
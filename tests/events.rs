@@ -0,0 +1,142 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+
+use mlua::{Error, EventBus, Function, Lua, Result, MAX_EVENT_ERRORS};
+
+#[test]
+fn test_events_emulated() -> Result<()> {
+    let lua = Lua::new();
+    let bus = EventBus::new(&lua)?;
+    assert!(bus.is_emulated());
+
+    let sum = Arc::new(AtomicI64::new(0));
+    let sum2 = sum.clone();
+    let event = bus.lua_events("Moved")?;
+    assert_eq!(event.name(), "LuaEvents.Moved");
+    let listener = event.add(move |_, (x, y): (i64, i64)| {
+        sum2.fetch_add(x + y, Ordering::Relaxed);
+        Ok(())
+    })?;
+
+    // Lua listeners and firing from Lua
+    lua.load(
+        r#"
+        calls = 0
+        LuaEvents.Moved.Add(function(x, y) calls = calls + 1 end)
+        LuaEvents.Moved(1, 2)
+    "#,
+    )
+    .exec()?;
+    assert_eq!(sum.load(Ordering::Relaxed), 3);
+    assert_eq!(lua.globals().get::<_, i64>("calls")?, 1);
+
+    // Firing from Rust
+    assert!(event.fire((10, 20))?.is_empty());
+    assert_eq!(sum.load(Ordering::Relaxed), 33);
+    assert_eq!(lua.globals().get::<_, i64>("calls")?, 2);
+
+    event.remove(&listener)?;
+    event.fire((10, 20))?;
+    assert_eq!(sum.load(Ordering::Relaxed), 33);
+    assert_eq!(lua.globals().get::<_, i64>("calls")?, 3);
+
+    // `Events` works the same
+    let func: Function = lua.load("function(n) turn = n end").eval()?;
+    bus.events("TurnBegin")?.add_function(&func)?;
+    bus.events("TurnBegin")?.fire(5)?;
+    assert_eq!(lua.globals().get::<_, i64>("turn")?, 5);
+
+    Ok(())
+}
+
+#[test]
+fn test_events_errors() -> Result<()> {
+    let lua = Lua::new();
+    let bus = EventBus::new(&lua)?;
+
+    let event = bus.lua_events("Test")?;
+    event.add(|_, ()| Err(Error::runtime("rust listener failed")))?;
+    event.add(|_, _: String| Ok(()))?;
+    lua.load(
+        r#"
+        LuaEvents.Test.Add(function() error("lua listener failed") end)
+        LuaEvents.Test.Add(function() reached = true end)
+    "#,
+    )
+    .exec()?;
+
+    let errors = event.fire(())?;
+    assert_eq!(errors.len(), 3);
+    let messages = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+    assert!(messages[0].contains("error in a listener of event 'LuaEvents.Test'"));
+    assert!(messages[0].contains("rust listener failed"));
+    assert!(messages[1].contains("error converting Lua nil to String"));
+    assert!(messages[2].contains("lua listener failed"));
+    assert!(lua.globals().get::<_, bool>("reached")?);
+
+    // Errors of the dispatches started by Lua are kept until taken
+    lua.load("LuaEvents.Test()").exec()?;
+    assert_eq!(bus.take_errors().len(), 3);
+    assert!(bus.take_errors().is_empty());
+
+    // Untaken errors are capped, the rest are counted
+    lua.load("for i = 1, 100 do LuaEvents.Test() end").exec()?;
+    assert_eq!(event.fire(())?.len(), 3);
+    assert_eq!(bus.take_errors().len(), MAX_EVENT_ERRORS);
+    assert_eq!(bus.dropped_errors(), 300 - MAX_EVENT_ERRORS);
+    assert_eq!(bus.dropped_errors(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_events_attached() -> Result<()> {
+    let lua = Lua::new();
+
+    // Stand-in for the game tables, which do not isolate listeners
+    lua.load(
+        r#"
+        local function new_group()
+            return setmetatable({}, {__index = function(t, name)
+                local listeners = {}
+                local event = setmetatable({
+                    Add = function(f) listeners[f] = true end,
+                    Remove = function(f) listeners[f] = nil end,
+                }, {__call = function(_, ...)
+                    for f in pairs(listeners) do f(...) end
+                end})
+                rawset(t, name, event)
+                return event
+            end})
+        end
+        Events = new_group()
+        LuaEvents = new_group()
+    "#,
+    )
+    .exec()?;
+
+    let bus = EventBus::new(&lua)?;
+    assert!(!bus.is_emulated());
+
+    let count = Arc::new(AtomicI64::new(0));
+    let count2 = count.clone();
+    bus.events("CityAdded")?.add(move |_, id: i64| {
+        count2.fetch_add(1, Ordering::Relaxed);
+        if id < 0 {
+            return Err(Error::runtime("invalid city"));
+        }
+        Ok(())
+    })?;
+
+    lua.load("Events.CityAdded(1)").exec()?;
+    assert_eq!(count.load(Ordering::Relaxed), 1);
+
+    // Rust listener errors do not reach the game dispatcher
+    lua.load("Events.CityAdded(-1)").exec()?;
+    assert_eq!(count.load(Ordering::Relaxed), 2);
+    assert_eq!(bus.take_errors().len(), 1);
+
+    assert_eq!(bus.events("CityAdded")?.fire(-2)?.len(), 1);
+
+    Ok(())
+}
@@ -1,4 +1,6 @@
-use mlua::{Function, Lua, Result, String, Table};
+use std::time::{Duration, Instant};
+
+use mlua::{Budget, Error, Function, Lua, Result, String, Table};
 
 #[test]
 fn test_function() -> Result<()> {
@@ -174,6 +176,43 @@ fn test_function_environment() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_function_upvalues() -> Result<()> {
+    let lua = Lua::new();
+
+    let func: Function = lua
+        .load(
+            r#"
+        local x, y = 1, "two"
+        return function()
+            return x, y
+        end
+    "#,
+        )
+        .eval()?;
+
+    let upvalues = func.upvalues()?;
+    assert_eq!(upvalues.len(), 2);
+    assert_eq!(upvalues[0].0, "x");
+    assert_eq!(upvalues[0].1.as_i64(), Some(1));
+    assert_eq!(upvalues[1].0, "y");
+    assert_eq!(upvalues[1].1.as_str(), Some("two"));
+
+    func.set_upvalue(1, 10)?;
+    assert_eq!(func.call::<_, (i64, String)>(())?.0, 10);
+    assert!(func.set_upvalue(3, 0).is_err());
+    assert!(func.set_upvalue(0, 0).is_err());
+
+    // Rust functions have no upvalues visible to Lua
+    let rust_func = lua.create_function(|_, ()| Ok(()))?;
+    assert!(rust_func
+        .upvalues()?
+        .iter()
+        .all(|(name, _)| name.is_empty()));
+
+    Ok(())
+}
+
 #[test]
 fn test_function_info() -> Result<()> {
     let lua = Lua::new();
@@ -314,6 +353,81 @@ fn test_owned_function() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_function_call_with_budget() -> Result<()> {
+    let lua = Lua::new();
+
+    let sum: Function = lua
+        .load("function(n) local s = 0 for i = 1, n do s = s + i end return s end")
+        .eval()?;
+    let budget = Budget::new().instructions(100_000);
+    assert_eq!(sum.call_with_budget::<_, i64>(100, budget)?, 5050);
+    let result = sum.call_with_budget::<_, i64>(1_000_000, budget);
+    assert!(matches!(result, Err(Error::BudgetExceeded)));
+
+    // Catching the error in Lua does not escape the budget
+    let runaway: Function = lua
+        .load("function() while true do pcall(function() while true do end end) end end")
+        .eval()?;
+    let result = runaway.call_with_budget::<_, ()>((), budget);
+    assert!(matches!(result, Err(Error::BudgetExceeded)));
+
+    let start = Instant::now();
+    let budget = Budget::new().time(Duration::from_millis(50));
+    let result = runaway.call_with_budget::<_, ()>((), budget);
+    assert!(matches!(result, Err(Error::BudgetExceeded)));
+    assert!(start.elapsed() >= Duration::from_millis(50));
+
+    // Nested budgets are limited by the enclosing one
+    lua.globals().set("runaway", runaway)?;
+    let nested = lua.create_function(|lua, ()| {
+        let runaway: Function = lua.globals().get("runaway")?;
+        let result = runaway.call_with_budget::<_, ()>((), Budget::new().instructions(u64::MAX));
+        assert!(matches!(result, Err(Error::BudgetExceeded)));
+        Ok(())
+    })?;
+    let result = nested.call_with_budget::<_, ()>((), Budget::new().instructions(50_000));
+    assert!(matches!(result, Err(Error::BudgetExceeded)));
+
+    // Budgets do not stay installed
+    lua.load("for i = 1, 1000000 do end").exec()?;
+
+    Ok(())
+}
+
+#[cfg(not(feature = "luau"))]
+#[test]
+fn test_function_call_with_budget_and_hook() -> Result<()> {
+    use std::sync::atomic::{AtomicI64, Ordering};
+    use std::sync::Arc;
+
+    use mlua::HookTriggers;
+
+    let lua = Lua::new();
+    let counts = Arc::new(AtomicI64::new(0));
+    let counts2 = counts.clone();
+    lua.set_hook(
+        HookTriggers::new().every_nth_instruction(300),
+        move |_, _| {
+            counts2.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        },
+    );
+
+    let runaway: Function = lua.load("function() while true do end end").eval()?;
+    let budget = Budget::new().instructions(30_000);
+    let result = runaway.call_with_budget::<_, ()>((), budget);
+    assert!(matches!(result, Err(Error::BudgetExceeded)));
+    // The user hook keeps its own interval
+    let count = counts.load(Ordering::Relaxed);
+    assert!((95..=105).contains(&count), "{count}");
+
+    lua.load("for i = 1, 3000 do end").exec()?;
+    assert!(counts.load(Ordering::Relaxed) > count);
+
+    Ok(())
+}
+
 #[cfg(all(feature = "unstable", not(feature = "send")))]
 #[test]
 fn test_owned_function_drop() -> Result<()> {
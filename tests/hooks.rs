@@ -4,7 +4,7 @@ use std::ops::Deref;
 use std::sync::atomic::{AtomicI64, Ordering};
 use std::sync::{Arc, Mutex};
 
-use mlua::{DebugEvent, Error, HookTriggers, Lua, Result, Value};
+use mlua::{DebugEvent, Error, HookTriggers, Lua, Result, Value, VmState};
 
 #[test]
 fn test_hook_triggers() {
@@ -263,3 +263,209 @@ fn test_hook_threads() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_interrupt_with_hook() -> Result<()> {
+    let lua = Lua::new();
+
+    let lines = Arc::new(AtomicI64::new(0));
+    let hook_lines = lines.clone();
+    lua.set_hook(HookTriggers::EVERY_LINE, move |_, _| {
+        hook_lines.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    });
+
+    let interrupts = Arc::new(AtomicI64::new(0));
+    let interrupts2 = interrupts.clone();
+    lua.set_interrupt(move |_| {
+        if interrupts2.fetch_add(1, Ordering::Relaxed) >= 10 {
+            return Err(Error::runtime("interrupted"));
+        }
+        Ok(VmState::Continue)
+    });
+
+    let err = lua
+        .load("local n = 0\nwhile true do\n    n = n + 1\nend")
+        .exec()
+        .unwrap_err();
+    assert!(err.to_string().contains("interrupted"));
+    assert_eq!(interrupts.load(Ordering::Relaxed), 11);
+    assert!(lines.load(Ordering::Relaxed) > 100);
+
+    // Removing the hook keeps the interrupt and vice versa
+    lua.remove_hook();
+    let lines_before = lines.load(Ordering::Relaxed);
+    assert!(lua.load("while true do end").exec().is_err());
+    assert_eq!(lines.load(Ordering::Relaxed), lines_before);
+
+    lua.remove_interrupt();
+    let hook_lines = lines.clone();
+    lua.set_hook(HookTriggers::EVERY_LINE, move |_, _| {
+        hook_lines.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    });
+    lua.load("local x = 1\nlocal y = 2").exec()?;
+    assert_eq!(lines.load(Ordering::Relaxed), lines_before + 2);
+    assert_eq!(interrupts.load(Ordering::Relaxed), 12);
+
+    Ok(())
+}
+
+#[test]
+fn test_multiple_hooks() -> Result<()> {
+    let lua = Lua::new();
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events2 = events.clone();
+    let lines = lua.add_hook(HookTriggers::EVERY_LINE, move |_, debug| {
+        events2
+            .lock()
+            .unwrap()
+            .push(format!("line {}", debug.curr_line()));
+        Ok(())
+    });
+    let events2 = events.clone();
+    let calls = lua.add_hook(HookTriggers::ON_CALLS, move |_, debug| {
+        let name = debug.names().name.unwrap_or_default().into_owned();
+        events2.lock().unwrap().push(format!("call {name}"));
+        Ok(())
+    });
+    assert_ne!(lines, calls);
+    // `set_hook` keeps working along with the added hooks
+    let events2 = events.clone();
+    lua.set_hook(HookTriggers::EVERY_LINE, move |_, debug| {
+        events2
+            .lock()
+            .unwrap()
+            .push(format!("set {}", debug.curr_line()));
+        Ok(())
+    });
+
+    lua.load("local x = 1\nlocal y = tostring(x)").exec()?;
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            "call ",
+            "line 1",
+            "set 1",
+            "line 2",
+            "set 2",
+            "call tostring"
+        ]
+    );
+
+    events.lock().unwrap().clear();
+    lua.remove_hook();
+    assert!(lua.remove_hook_by_id(calls));
+    assert!(!lua.remove_hook_by_id(calls));
+    lua.load("local x = 1\nlocal y = tostring(x)").exec()?;
+    assert_eq!(*events.lock().unwrap(), ["line 1", "line 2"]);
+
+    assert!(lua.remove_hook_by_id(lines));
+    events.lock().unwrap().clear();
+    lua.load("local x = 1").exec()?;
+    assert!(events.lock().unwrap().is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_multiple_count_hooks() -> Result<()> {
+    let lua = Lua::new();
+
+    let counters = [300, 700].map(|n| {
+        let counter = Arc::new(AtomicI64::new(0));
+        let counter2 = counter.clone();
+        let triggers = HookTriggers::new().every_nth_instruction(n);
+        let id = lua.add_hook(triggers, move |_, debug| {
+            assert_eq!(debug.event(), DebugEvent::Count);
+            counter2.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        });
+        (id, counter)
+    });
+
+    let func = lua
+        .load("local n = 0\nfor i = 1, 10000 do n = n + i end")
+        .into_function()?;
+    func.call(())?;
+    let first = [0, 1].map(|i| counters[i].1.load(Ordering::Relaxed));
+    // Each hook is called at its own interval
+    assert!(first[0] > 0 && first[1] > 0);
+    let ratio = first[0] as f64 / first[1] as f64;
+    assert!((2.0..2.7).contains(&ratio), "ratio {ratio}");
+
+    // The interval is recomputed when a hook is removed
+    lua.remove_hook_by_id(counters[0].0);
+    func.call(())?;
+    assert_eq!(counters[0].1.load(Ordering::Relaxed), first[0]);
+    assert_eq!(counters[1].1.load(Ordering::Relaxed), first[1] * 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_thread_add_hook() -> Result<()> {
+    let lua = Lua::new();
+
+    let func = lua
+        .load("local x = 1\nx = coroutine.yield(x)\nreturn x")
+        .into_function()?;
+    let co = lua.create_thread(func)?;
+
+    let lines = Arc::new(Mutex::new(Vec::new()));
+    let lines2 = lines.clone();
+    let id = co.add_hook(HookTriggers::EVERY_LINE, move |_, debug| {
+        lines2.lock().unwrap().push(debug.curr_line());
+        Ok(())
+    });
+    // Hooks of the main thread are not called for the coroutine
+    let main_lines = Arc::new(AtomicI64::new(0));
+    let main_lines2 = main_lines.clone();
+    lua.add_hook(HookTriggers::EVERY_LINE, move |_, _| {
+        main_lines2.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    });
+
+    co.resume::<_, i64>(())?;
+    assert!(lua.remove_hook_by_id(id));
+    co.resume::<_, i64>(2)?;
+    assert_eq!(*lines.lock().unwrap(), [1, 2]);
+    assert_eq!(main_lines.load(Ordering::Relaxed), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_hooks_of_collected_threads() -> Result<()> {
+    let lua = Lua::new();
+
+    // Coroutines inherit the hook of the main thread, so `hook_proc` runs for all of them
+    lua.add_hook(HookTriggers::ON_CALLS, |_, _| Ok(()));
+
+    let current = Arc::new(AtomicI64::new(0));
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    for i in 0..100 {
+        current.store(i, Ordering::Relaxed);
+        let co = lua.create_thread(lua.load("local x = 1\nreturn x").into_function()?)?;
+        let (current, calls) = (current.clone(), calls.clone());
+        co.add_hook(HookTriggers::EVERY_LINE, move |_, _| {
+            calls
+                .lock()
+                .unwrap()
+                .push((i, current.load(Ordering::Relaxed)));
+            Ok(())
+        });
+        co.resume::<_, i64>(())?;
+        drop(co);
+        lua.gc_collect()?;
+        lua.gc_collect()?;
+    }
+
+    // Hooks are never called for a new coroutine allocated at the address of a collected one
+    let calls = calls.lock().unwrap();
+    assert_eq!(calls.len(), 200);
+    assert!(calls.iter().all(|(i, current)| i == current));
+
+    Ok(())
+}
@@ -0,0 +1,111 @@
+#![cfg(feature = "json")]
+
+use mlua::{Lua, Result};
+
+#[test]
+fn test_json_encode() -> Result<()> {
+    let lua = Lua::new();
+    lua.load_json_module()?;
+
+    let encode = |code: &str| lua.load(code).eval::<String>();
+    assert_eq!(
+        encode("json.encode({ 1, 2.5, 'x', true })")?,
+        r#"[1,2.5,"x",true]"#
+    );
+    assert_eq!(encode("json.encode({ a = json.null })")?, r#"{"a":null}"#);
+    assert_eq!(encode("json.encode({})")?, "{}");
+    assert_eq!(encode("json.encode('a\"b')")?, r#""a\"b""#);
+    assert_eq!(
+        encode("json.encode({ b = 1, a = { [10] = 'x' } }, { sort_keys = true })")?,
+        r#"{"a":{"10":"x"},"b":1}"#
+    );
+    assert_eq!(
+        encode("json.encode({ a = { 1 } }, { pretty = true })")?,
+        "{\n  \"a\": [\n    1\n  ]\n}"
+    );
+    assert_eq!(
+        encode("json.encode({ 1 }, { pretty = '\\t' })")?,
+        "[\n\t1\n]"
+    );
+    assert_eq!(
+        encode("json.encode({ f = print, n = 1 }, { deny_unsupported_types = false })")?,
+        r#"{"n":1}"#
+    );
+
+    // Like serde_json, NaN and infinities are encoded as `null`
+    assert_eq!(
+        encode("json.encode({ ['a b'] = { 0/0, 1/0 }, [1.5] = 1 })")?,
+        r#"{"1.5":1,"a b":[null,null]}"#
+    );
+
+    // Shared tables are fine
+    assert_eq!(
+        encode("local t = { 1 } return json.encode({ t, t })")?,
+        "[[1],[1]]"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_json_encode_errors() -> Result<()> {
+    let lua = Lua::new();
+    lua.load_json_module()?;
+
+    let error = |code: &str| lua.load(code).exec().unwrap_err().to_string();
+    let err = error("json.encode({ units = { {}, {}, { promotions = { name = print } } } })");
+    assert!(
+        err.contains("units[3].promotions.name: unsupported value type `function`"),
+        "{err}"
+    );
+    let err = error("local t = {} t.self = t json.encode(t)");
+    assert!(err.contains("self: recursive table detected"), "{err}");
+    let err = error("json.encode({ [true] = 1 })");
+    assert!(
+        err.contains("[true]: invalid type: boolean `true`"),
+        "{err}"
+    );
+
+    // Errors can be caught in Lua
+    let ok = lua
+        .load("return pcall(json.encode, { print })")
+        .eval::<bool>()?;
+    assert!(!ok);
+
+    Ok(())
+}
+
+#[test]
+fn test_json_decode() -> Result<()> {
+    let lua = Lua::new();
+    lua.load_json_module()?;
+
+    lua.load(
+        r#"
+        local v = json.decode('{"a": [1, 2.5, "x"], "b": null, "c": {"d": false}, "e": []}')
+        assert(v.a[1] == 1 and v.a[2] == 2.5 and v.a[3] == "x")
+        assert(v.b == json.null)
+        assert(v.c.d == false)
+        assert(#v.e == 0)
+
+        -- Empty arrays survive a round trip
+        assert(json.encode(v.e) == "[]")
+        assert(json.encode(json.decode('{"x":[]}')) == '{"x":[]}')
+
+        local v = json.decode('[null, {}]', { null = false, set_array_metatable = false })
+        assert(v[1] == nil and getmetatable(v) == nil)
+        assert(json.decode("12345678901") == 12345678901)
+        assert(require("json") == json)
+    "#,
+    )
+    .exec()?;
+
+    let err = lua
+        .load(r#"json.decode('{"a": }')"#)
+        .exec()
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("line 1 column 7"), "{err}");
+
+    Ok(())
+}
@@ -1,3 +1,5 @@
+use std::os::raw::c_void;
+use std::ptr;
 use std::sync::Arc;
 
 use mlua::{Error, GCMode, Lua, Result, UserData};
@@ -126,3 +128,95 @@ fn test_gc_error() {
         Ok(()) => panic!("__gc error did not result in error"),
     }
 }
+
+#[cfg(not(feature = "luau"))]
+#[test]
+fn test_wrap_allocator() -> Result<()> {
+    use mlua::ffi;
+
+    let state = unsafe { ffi::luaL_newstate() };
+    unsafe { ffi::luaL_openlibs(state) };
+    let lua = unsafe { Lua::init_from_ptr(state) };
+    assert!(matches!(
+        lua.set_memory_limit(0),
+        Err(Error::MemoryLimitNotAvailable)
+    ));
+
+    unsafe { lua.wrap_allocator()? };
+    // Wrapping again is a no-op
+    unsafe { lua.wrap_allocator()? };
+    assert_eq!(lua.used_memory(), 0);
+
+    let f = lua
+        .load("local t = {}; for i = 1,10000 do t[i] = i end")
+        .into_function()?;
+    f.call::<_, ()>(())?;
+    assert!(lua.used_memory() > 0);
+
+    // Collect the garbage first, so the limit cannot be met by freeing it
+    lua.gc_collect()?;
+    lua.set_memory_limit(lua.used_memory() + 10000)?;
+    match f.call::<_, ()>(()) {
+        Err(Error::MemoryError(_)) => {}
+        something_else => panic!("did not trigger memory error: {:?}", something_else),
+    };
+
+    // Lua functions called by the host are accounted too
+    lua.set_memory_limit(0)?;
+    lua.globals().set("f", f)?;
+    lua.gc_collect()?;
+    lua.set_memory_limit(lua.used_memory() + 10000)?;
+    unsafe {
+        ffi::lua_getglobal(state, b"f\0".as_ptr() as _);
+        assert_eq!(ffi::lua_pcall(state, 0, 0, 0), ffi::LUA_ERRMEM);
+        ffi::lua_pop(state, 1);
+    }
+
+    // Callbacks called by the host are accounted
+    lua.set_memory_limit(0)?;
+    lua.gc_collect()?;
+    lua.set_memory_limit(lua.used_memory() + 10000)?;
+    let alloc = lua.create_function(|lua, ()| lua.create_sequence_from(0..10000).map(|_| ()))?;
+    lua.globals().set("alloc", alloc)?;
+    unsafe {
+        ffi::lua_getglobal(state, b"alloc\0".as_ptr() as _);
+        assert_ne!(ffi::lua_pcall(state, 0, 0, 0), ffi::LUA_OK);
+        ffi::lua_pop(state, 1);
+    }
+    lua.set_memory_limit(0)?;
+
+    // Another allocator installed on top prevents restoring
+    unsafe {
+        let mut ud = ptr::null_mut();
+        let chained_alloc = ffi::lua_getallocf(state, &mut ud);
+        let host_alloc = Box::into_raw(Box::new((chained_alloc, ud)));
+        ffi::lua_setallocf(state, forward_alloc, host_alloc as *mut c_void);
+        assert!(lua.restore_allocator().is_err());
+        assert!(lua.set_memory_limit(0).is_ok());
+
+        ffi::lua_setallocf(state, chained_alloc, ud);
+        drop(Box::from_raw(host_alloc));
+    }
+
+    unsafe { lua.restore_allocator()? };
+    assert!(matches!(
+        lua.set_memory_limit(0),
+        Err(Error::MemoryLimitNotAvailable)
+    ));
+    lua.load("local t = {}; for i = 1,10000 do t[i] = i end")
+        .exec()?;
+
+    Ok(())
+}
+
+#[cfg(not(feature = "luau"))]
+unsafe extern "C-unwind" fn forward_alloc(
+    ud: *mut c_void,
+    ptr: *mut c_void,
+    osize: usize,
+    nsize: usize,
+) -> *mut c_void {
+    let (alloc, alloc_ud) = *(ud as *mut (mlua::ffi::lua_Alloc, *mut c_void));
+    alloc(alloc_ud, ptr, osize, nsize)
+}
+
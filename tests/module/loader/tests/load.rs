@@ -28,6 +28,37 @@ fn test_module_multi() -> Result<()> {
     .exec()
 }
 
+#[test]
+fn test_module_isolation() -> Result<()> {
+    let lua = make_lua()?;
+    lua.load(
+        r#"
+        local mod = require("test_module")
+        local mod2 = require("test_module.second")
+        local other = require("test_module_other")
+
+        -- Separately built modules do not share their state
+        assert(mod.app_data() == "test_module")
+        assert(other.app_data() == "other")
+
+        -- Plain Lua values (including functions) can be exchanged
+        assert(other.sum({1, 2, 3}) == 6)
+        assert(other.call(mod.check_userdata, mod2.userdata) == 123)
+
+        -- Userdata types of one module are unknown to another
+        assert(not pcall(mod.check_userdata, other.userdata))
+        assert(not pcall(other.check_userdata, mod2.userdata))
+        assert(other.check_userdata(other.userdata) == 321)
+
+        -- Errors pass through other modules
+        local ok, err = pcall(other.call, mod.fail)
+        assert(not ok)
+        assert(string.find(tostring(err), "module failure"))
+    "#,
+    )
+    .exec()
+}
+
 #[test]
 fn test_module_error() -> Result<()> {
     let lua = make_lua()?;
@@ -41,6 +72,53 @@ fn test_module_error() -> Result<()> {
     .exec()
 }
 
+#[cfg(not(feature = "luau"))]
+#[test]
+fn test_module_wrap_allocator() -> Result<()> {
+    let lua = make_lua()?;
+    lua.load(
+        r#"
+        local mod = require("test_module")
+        local other = require("test_module_other")
+        collectgarbage("stop")
+
+        -- The allocator of the host is not mistaken for a tracked one
+        mod.wrap_allocator()
+        other.wrap_allocator()
+
+        -- Each module accounts all allocations of the state
+        mod.alloc(100000)
+        assert(mod.used_memory() >= 100000)
+        assert(other.used_memory() >= 100000)
+
+        -- including the ones made by plain Lua functions
+        local function grow()
+            local t = {}
+            for i = 1, 100000 do t[i] = i end
+        end
+        mod.set_memory_limit(mod.used_memory() + 1024)
+        assert(not pcall(grow))
+        collectgarbage()
+        assert(mod.set_memory_limit(0) > 0)
+
+        -- and has its own limit
+        mod.set_memory_limit(mod.used_memory() + 1024 * 1024)
+        other.set_memory_limit(other.used_memory() + 1024)
+        assert(not pcall(mod.alloc, 100000))
+        assert(other.set_memory_limit(0) > 0)
+        assert(pcall(mod.alloc, 200000))
+        assert(mod.set_memory_limit(0) > 0)
+
+        -- Allocators are restored in reverse order
+        assert(not pcall(mod.restore_allocator))
+        other.restore_allocator()
+        mod.restore_allocator()
+        collectgarbage("restart")
+    "#,
+    )
+    .exec()
+}
+
 #[cfg(any(
     feature = "lua54",
     feature = "lua53",
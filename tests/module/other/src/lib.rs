@@ -0,0 +1,56 @@
+use mlua::prelude::*;
+
+// Another module built separately from `test_module` and loaded into the same Lua state
+
+#[derive(Clone, Copy)]
+struct OtherUserData(i32);
+
+impl LuaUserData for OtherUserData {}
+
+#[mlua::lua_module]
+fn test_module_other(lua: &Lua) -> LuaResult<LuaTable> {
+    lua.set_app_data("other");
+
+    let exports = lua.create_table()?;
+    exports.set(
+        "app_data",
+        lua.create_function(|lua, ()| Ok(lua.app_data_ref::<&str>().map(|s| *s)))?,
+    )?;
+    exports.set(
+        "sum",
+        lua.create_function(|_, t: LuaTable| t.sequence_values::<i64>().sum::<LuaResult<i64>>())?,
+    )?;
+    exports.set(
+        "call",
+        lua.create_function(|_, (f, v): (LuaFunction, LuaValue)| f.call::<_, LuaValue>(v))?,
+    )?;
+    exports.set(
+        "used_memory",
+        lua.create_function(|lua, ()| Ok(lua.used_memory()))?,
+    )?;
+    exports.set(
+        "alloc",
+        lua.create_function(|lua, n: usize| lua.create_string(vec![b'y'; n]))?,
+    )?;
+    #[cfg(not(feature = "luau"))]
+    {
+        exports.set(
+            "wrap_allocator",
+            lua.create_function(|lua, ()| unsafe { lua.wrap_allocator() })?,
+        )?;
+        exports.set(
+            "restore_allocator",
+            lua.create_function(|lua, ()| unsafe { lua.restore_allocator() })?,
+        )?;
+        exports.set(
+            "set_memory_limit",
+            lua.create_function(|lua, limit: usize| lua.set_memory_limit(limit))?,
+        )?;
+    }
+    exports.set("userdata", OtherUserData(321))?;
+    exports.set(
+        "check_userdata",
+        lua.create_function(|_, ud: LuaAnyUserData| Ok(ud.borrow::<OtherUserData>()?.0))?,
+    )?;
+    Ok(exports)
+}
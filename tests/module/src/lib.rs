@@ -8,16 +8,48 @@ fn used_memory(lua: &Lua, _: ()) -> LuaResult<usize> {
     Ok(lua.used_memory())
 }
 
+fn alloc(lua: &Lua, n: usize) -> LuaResult<LuaString> {
+    lua.create_string(vec![b'x'; n])
+}
+
 fn check_userdata(_: &Lua, ud: LuaAnyUserData) -> LuaResult<i32> {
     Ok(ud.borrow::<MyUserData>()?.0)
 }
 
+fn app_data(lua: &Lua, _: ()) -> LuaResult<Option<&'static str>> {
+    Ok(lua.app_data_ref::<&str>().map(|s| *s))
+}
+
+fn fail(_: &Lua, _: ()) -> LuaResult<()> {
+    Err("module failure".into_lua_err())
+}
+
 #[mlua::lua_module]
 fn test_module(lua: &Lua) -> LuaResult<LuaTable> {
+    lua.set_app_data("test_module");
+
     let exports = lua.create_table()?;
     exports.set("sum", lua.create_function(sum)?)?;
     exports.set("used_memory", lua.create_function(used_memory)?)?;
     exports.set("check_userdata", lua.create_function(check_userdata)?)?;
+    exports.set("app_data", lua.create_function(app_data)?)?;
+    exports.set("fail", lua.create_function(fail)?)?;
+    exports.set("alloc", lua.create_function(alloc)?)?;
+    #[cfg(not(feature = "luau"))]
+    {
+        exports.set(
+            "wrap_allocator",
+            lua.create_function(|lua, ()| unsafe { lua.wrap_allocator() })?,
+        )?;
+        exports.set(
+            "restore_allocator",
+            lua.create_function(|lua, ()| unsafe { lua.restore_allocator() })?,
+        )?;
+        exports.set(
+            "set_memory_limit",
+            lua.create_function(|lua, limit: usize| lua.set_memory_limit(limit))?,
+        )?;
+    }
     Ok(exports)
 }
 
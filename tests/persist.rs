@@ -0,0 +1,185 @@
+#![cfg(not(feature = "luau"))]
+
+use mlua::{Function, Lua, Result, Table, Thread, ThreadStatus, Value};
+
+fn permanents(lua: &Lua) -> Result<Table> {
+    let permanents = lua.create_table()?;
+    permanents.set(lua.globals(), "_G")?;
+    Ok(permanents)
+}
+
+#[test]
+fn test_persist_tables() -> Result<()> {
+    let lua = Lua::new();
+    let value: Table = lua
+        .load(
+            r#"
+            local shared = { 1, 2, 3 }
+            local t = { a = shared, b = shared, [1.5] = true, [10] = "ten", s = "\0bin" }
+            t.self = t
+            setmetatable(t, { __index = function(_, k) return k .. "!" end })
+            return t
+        "#,
+        )
+        .eval()?;
+    let data = lua.persist(value, &permanents(&lua)?)?;
+
+    let lua2 = Lua::new();
+    let t: Table = lua2.unpersist(&data, &permanents(&lua2)?)?;
+    let (a, b): (Table, Table) = (t.get("a")?, t.get("b")?);
+    assert_eq!(a, b);
+    assert_eq!(a.raw_len(), 3);
+    assert_eq!(t.get::<_, Table>("self")?, t);
+    assert!(t.get::<_, bool>(1.5)?);
+    assert_eq!(t.get::<_, String>(10)?, "ten");
+    assert_eq!(t.get::<_, mlua::String>("s")?, b"\0bin".as_ref());
+    assert_eq!(t.get::<_, String>("missing")?, "missing!");
+
+    Ok(())
+}
+
+#[test]
+fn test_persist_closures() -> Result<()> {
+    let lua = Lua::new();
+    lua.globals().set("base", 100)?;
+    let value: Table = lua
+        .load(
+            r#"
+            local n = 0
+            local obj = {}
+            function obj.inc() n = n + 1 return base + n end
+            function obj.get() return n end
+            obj.inc()
+            return obj
+        "#,
+        )
+        .eval()?;
+
+    // Upvalues cannot be joined on Lua 5.1
+    #[cfg(any(feature = "lua51", feature = "luajit"))]
+    {
+        let err = lua.persist(value, &permanents(&lua)?).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("cannot persist closures sharing upvalue 'n'"),
+            "{err}"
+        );
+        let value: Table = lua
+            .load("local n = 1 return { inc = function() n = n + 1 return base + n end }")
+            .eval()?;
+        let data = lua.persist(value, &permanents(&lua)?)?;
+        let lua2 = Lua::new();
+        lua2.globals().set("base", 200)?;
+        let obj: Table = lua2.unpersist(&data, &permanents(&lua2)?)?;
+        assert_eq!(obj.get::<_, Function>("inc")?.call::<_, i32>(())?, 202);
+    }
+
+    #[cfg(any(feature = "lua54", feature = "lua53", feature = "lua52"))]
+    {
+        let data = lua.persist(value, &permanents(&lua)?)?;
+
+        let lua2 = Lua::new();
+        lua2.globals().set("base", 200)?;
+        let obj: Table = lua2.unpersist(&data, &permanents(&lua2)?)?;
+        let inc: Function = obj.get("inc")?;
+        let get: Function = obj.get("get")?;
+        // Globals are taken from the permanent `_G`
+        assert_eq!(inc.call::<_, i32>(())?, 202);
+        // Upvalues stay shared
+        assert_eq!(get.call::<_, i32>(())?, 2);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_persist_permanents() -> Result<()> {
+    let lua = Lua::new();
+    let print: Function = lua.globals().get("print")?;
+    let ud = lua.create_any_userdata(123i32)?;
+    let value = lua.create_table()?;
+    value.set("print", print)?;
+    value.set("ud", ud.clone())?;
+
+    // C functions and userdata cannot be persisted
+    let err = lua
+        .persist(value.clone(), &lua.create_table()?)
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("cannot persist C function"),
+        "{err}"
+    );
+    let permanents = lua.create_table()?;
+    permanents.set(lua.globals().get::<_, Function>("print")?, "print")?;
+    let err = lua.persist(value.clone(), &permanents).unwrap_err();
+    assert!(err.to_string().contains("cannot persist userdata"), "{err}");
+
+    permanents.set(ud, 1)?;
+    let data = lua.persist(value, &permanents)?;
+
+    // Unknown permanent
+    let lua2 = Lua::new();
+    let permanents2 = lua2.create_table()?;
+    permanents2.set(lua2.globals().get::<_, Function>("print")?, "print")?;
+    let err = lua2.unpersist::<Table>(&data, &permanents2).unwrap_err();
+    assert!(err.to_string().contains("permanent '1'"), "{err}");
+
+    let ud2 = lua2.create_any_userdata(456i32)?;
+    permanents2.set(ud2, 1)?;
+    let t: Table = lua2.unpersist(&data, &permanents2)?;
+    assert_eq!(t.get::<_, Function>("print")?, lua2.globals().get("print")?);
+    assert_eq!(*t.get::<_, mlua::AnyUserData>("ud")?.borrow::<i32>()?, 456);
+
+    Ok(())
+}
+
+#[test]
+fn test_persist_threads() -> Result<()> {
+    let lua = Lua::new();
+    let func: Function = lua
+        .load("return function(a) local b = coroutine.yield(a + 1) return b * 2 end")
+        .eval()?;
+    let new = lua.create_thread(func.clone())?;
+    let dead = lua.create_thread(func.clone())?;
+    dead.resume::<_, ()>(1)?;
+    dead.resume::<_, ()>(2)?;
+    assert_eq!(dead.status(), ThreadStatus::Unresumable);
+
+    let data = lua.persist(vec![new, dead], &permanents(&lua)?)?;
+    let lua2 = Lua::new();
+    let threads: Vec<Thread> = lua2.unpersist(&data, &permanents(&lua2)?)?;
+    let (new, dead) = (&threads[0], &threads[1]);
+    assert_eq!(new.resume::<_, i32>(1)?, 2);
+    assert_eq!(new.resume::<_, i32>(5)?, 10);
+    assert_eq!(dead.status(), ThreadStatus::Unresumable);
+
+    // Suspended threads cannot be persisted
+    let suspended = lua.create_thread(func)?;
+    suspended.resume::<_, i32>(1)?;
+    let err = lua.persist(suspended, &permanents(&lua)?).unwrap_err();
+    assert!(err.to_string().contains("suspended thread"), "{err}");
+
+    Ok(())
+}
+
+#[test]
+fn test_persist_invalid_data() -> Result<()> {
+    let lua = Lua::new();
+    let permanents = lua.create_table()?;
+    let data = lua.persist(lua.create_sequence_from([1, 2, 3])?, &permanents)?;
+
+    assert!(lua.unpersist::<Value>(b"garbage", &permanents).is_err());
+    assert!(lua
+        .unpersist::<Value>(&data[..data.len() - 1], &permanents)
+        .is_err());
+    let mut trailing = data.clone();
+    trailing.push(0);
+    let err = lua.unpersist::<Value>(&trailing, &permanents).unwrap_err();
+    assert!(err.to_string().contains("trailing data"), "{err}");
+    let mut version = data.clone();
+    version[4] = 99;
+    let err = lua.unpersist::<Value>(&version, &permanents).unwrap_err();
+    assert!(err.to_string().contains("version 99"), "{err}");
+
+    Ok(())
+}
@@ -0,0 +1,135 @@
+#![cfg(not(feature = "luau"))]
+
+use std::thread;
+use std::time::Duration;
+
+use mlua::{Lua, Result};
+
+#[test]
+fn test_profiler() -> Result<()> {
+    let lua = Lua::new();
+    assert!(lua.stop_profiler().is_none());
+
+    let sleep = lua.create_function(|_, ms: u64| {
+        thread::sleep(Duration::from_millis(ms));
+        Ok(())
+    })?;
+    lua.globals().set("sleep", sleep)?;
+
+    lua.start_profiler(100);
+    lua.load(
+        r#"
+        local function busy(n)
+            local x = 0
+            for i = 1, n do x = x + i % 7 end
+            return x
+        end
+        local function light()
+            local x = busy(1000)
+            return x
+        end
+        busy(200000)
+        light()
+        sleep(30)
+    "#,
+    )
+    .set_name("@profiled.lua")
+    .exec()?;
+    let profile = lua.stop_profiler().unwrap();
+    assert!(lua.stop_profiler().is_none());
+
+    assert_eq!(profile.interval(), 100);
+    assert!(profile.samples() > 100);
+    assert!(profile.callback_time() >= Duration::from_millis(30));
+    assert!(profile.duration() >= profile.callback_time());
+
+    let functions = profile.functions();
+    let find = |name: &str| {
+        (functions.iter())
+            .find(|f| f.name.as_deref() == Some(name))
+            .unwrap_or_else(|| panic!("function '{name}' not found"))
+    };
+    let busy = find("busy");
+    assert_eq!(busy.what, "Lua");
+    assert_eq!(busy.source.as_deref(), Some("profiled.lua"));
+    assert_eq!(busy.line_defined, Some(2));
+    assert!(busy.self_samples > 100);
+    let light = find("light");
+    assert!(light.total_samples < busy.total_samples);
+    let sleep = find("sleep");
+    assert_eq!(sleep.what, "C");
+    assert_eq!(sleep.self_samples, 0);
+    assert!(sleep.self_time >= Duration::from_millis(30));
+
+    let folded = profile.to_folded();
+    let lines = folded.lines().collect::<Vec<_>>();
+    assert!(lines
+        .iter()
+        .any(|l| l.starts_with("main chunk (profiled.lua);busy (profiled.lua:2) ")));
+    assert!(lines
+        .iter()
+        .any(|l| l.starts_with("main chunk (profiled.lua);sleep [C] ")));
+    for line in lines {
+        let (_, weight) = line.rsplit_once(' ').unwrap();
+        assert!(weight.parse::<u64>().unwrap() > 0);
+    }
+
+    let json = profile.to_json();
+    assert!(json.starts_with(r#"{"interval":100,"samples":"#));
+    assert!(json.contains(r#"{"name":"sleep","source":"[C]","line_defined":null,"what":"C","#));
+    assert!(json.contains(r#""name":"busy","source":"profiled.lua","line_defined":2,"what":"Lua""#));
+
+    // The hook is removed and callbacks are not measured anymore
+    lua.load("sleep(1)").exec()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_profiler_with_coverage() -> Result<()> {
+    let lua = Lua::new();
+
+    lua.start_coverage(|_| true);
+    lua.start_profiler(10);
+    lua.load("local n = 0\nfor i = 1, 10000 do\n    n = n + i\nend")
+        .set_name("@both.lua")
+        .exec()?;
+    let profile = lua.stop_profiler().unwrap();
+    let coverage = lua.stop_coverage().unwrap();
+
+    assert!(profile.samples() > 100);
+    assert_eq!(coverage.hits("both.lua", 3), Some(10000));
+
+    Ok(())
+}
+
+#[test]
+fn test_profiler_coroutines() -> Result<()> {
+    let lua = Lua::new();
+
+    lua.start_profiler(10);
+    lua.load(
+        r#"
+        local function busy(n)
+            local x = 0
+            for i = 1, n do x = x + i % 7 end
+            return x
+        end
+        local co = coroutine.wrap(function()
+            busy(100000)
+        end)
+        co()
+    "#,
+    )
+    .set_name("@coroutine.lua")
+    .exec()?;
+    let profile = lua.stop_profiler().unwrap();
+
+    let busy = (profile.functions().iter())
+        .find(|f| f.name.as_deref() == Some("busy"))
+        .map(|f| f.self_samples)
+        .unwrap_or(0);
+    assert!(busy > 100);
+
+    Ok(())
+}
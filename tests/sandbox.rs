@@ -0,0 +1,255 @@
+#![cfg(not(feature = "luau"))]
+
+use mlua::{Function, Lua, Result, Table};
+
+#[test]
+fn test_sandbox() -> Result<()> {
+    let lua = Lua::new();
+    lua.globals().set("host", 1)?;
+    let host_chunk = lua.load("host_global = 1").into_function()?;
+
+    lua.sandbox(true)?;
+    lua.load("global = 123").exec()?;
+    let n: i32 = lua.load("return global").eval()?;
+    assert_eq!(n, 123);
+    // The real globals are untouched
+    assert_eq!(lua.globals().get::<_, Option<i32>>("global")?, None);
+    // Original globals are readable
+    assert_eq!(lua.load("return host").eval::<i32>()?, 1);
+    lua.load("host = 2").exec()?;
+    assert_eq!(lua.load("return host").eval::<i32>()?, 2);
+    assert_eq!(lua.globals().get::<_, i32>("host")?, 1);
+
+    // Chunks loaded outside the sandbox keep using the globals
+    host_chunk.call::<_, ()>(())?;
+    assert_eq!(lua.globals().get::<_, i32>("host_global")?, 1);
+    // and so do chunks with an explicit environment
+    lua.load("explicit = true")
+        .set_environment(lua.globals())
+        .exec()?;
+    assert!(lua.globals().get::<_, bool>("explicit")?);
+
+    // Coroutines created in the sandbox share its environment
+    let f: Function = lua.load("return function() return global end").eval()?;
+    let co = lua.create_thread(f.clone())?;
+    assert_eq!(co.resume::<_, Option<i32>>(())?, Some(123));
+
+    // Sandboxed threads read the sandbox environment too
+    let co = lua.create_thread(f)?;
+    co.sandbox()?;
+    assert_eq!(co.resume::<_, Option<i32>>(())?, Some(123));
+
+    // Enabling the sandbox again keeps the changes
+    lua.sandbox(true)?;
+    assert_eq!(lua.load("return global").eval::<Option<i32>>()?, Some(123));
+
+    // Resetting discards the changes, also for the chunks loaded before
+    let get_global: Function = lua.load("return function() return global end").eval()?;
+    lua.reset_sandbox()?;
+    assert_eq!(get_global.call::<_, Option<i32>>(())?, None);
+    assert_eq!(lua.load("return host").eval::<i32>()?, 1);
+
+    lua.load("global = 456").exec()?;
+    lua.sandbox(false)?;
+
+    // Changes made in the sandbox are not visible outside
+    assert_eq!(lua.load("return global").eval::<Option<i32>>()?, None);
+    assert_eq!(get_global.call::<_, Option<i32>>(())?, Some(456));
+    assert_eq!(lua.globals().get::<_, i32>("host")?, 1);
+    assert!(lua.globals().get::<_, Function>("dofile").is_ok());
+
+    // Each sandbox starts with an empty layer
+    lua.sandbox(true)?;
+    assert_eq!(lua.load("return global").eval::<Option<i32>>()?, None);
+    lua.sandbox(false)?;
+    // Resetting without the sandbox does nothing
+    lua.reset_sandbox()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_sandbox_attached_state() -> Result<()> {
+    use mlua::ffi;
+
+    // The host (e.g. the game) loads its scripts directly into the state
+    let state = unsafe { ffi::luaL_newstate() };
+    unsafe { ffi::luaL_openlibs(state) };
+    let lua = unsafe { Lua::init_from_ptr(state) };
+    lua.sandbox(true)?;
+    lua.load("modded = true").exec()?;
+
+    unsafe {
+        let chunk = b"host_script = modded == nil and dofile ~= nil\0";
+        assert_eq!(
+            ffi::luaL_loadstring(state, chunk.as_ptr() as _),
+            ffi::LUA_OK
+        );
+        assert_eq!(ffi::lua_pcall(state, 0, 0, 0), ffi::LUA_OK);
+    }
+    assert!(lua.globals().get::<_, bool>("host_script")?);
+    assert_eq!(lua.globals().get::<_, Option<bool>>("modded")?, None);
+    lua.sandbox(false)?;
+
+    drop(lua);
+    unsafe { ffi::lua_close(state) };
+    Ok(())
+}
+
+#[test]
+fn test_sandbox_libraries() -> Result<()> {
+    let lua = Lua::new();
+    lua.sandbox(true)?;
+
+    // Libraries are read-only
+    let err = lua.load("string.upper = nil").exec().unwrap_err();
+    assert!(err.to_string().contains("read-only library 'string'"));
+    let err = lua
+        .load("getmetatable('').__index.upper = nil")
+        .exec()
+        .unwrap_err();
+    assert!(err.to_string().contains("read-only library 'string'"));
+    assert!(lua.load("setmetatable(table, nil)").exec().is_err());
+    assert_eq!(lua.load("return ('abc'):upper()").eval::<String>()?, "ABC");
+
+    // but can be iterated
+    let n = lua
+        .load("local n = 0 for _ in pairs(math) do n = n + 1 end return n")
+        .eval::<i32>()?;
+    assert!(n > 10);
+
+    // Dangerous functions are hidden
+    for name in [
+        "loadstring",
+        "load",
+        "dofile",
+        "loadfile",
+        "require",
+        "debug",
+        "io",
+        "package",
+    ] {
+        let value = lua
+            .load(format!("return {name}"))
+            .eval::<Option<mlua::Value>>()?;
+        assert!(value.is_none(), "{name} is not hidden");
+    }
+    assert_eq!(
+        lua.load("return os.execute").eval::<Option<Function>>()?,
+        None
+    );
+    assert!(lua.load("return os.time()").eval::<i64>()? > 0);
+    assert!(lua.load("return collectgarbage('count')").eval::<f64>()? > 0.0);
+    assert!(lua.load("collectgarbage('stop')").exec().is_err());
+
+    // Replacing a library in the sandbox does not affect the host
+    lua.load("string = { upper = 1 }").exec()?;
+    assert_eq!(lua.load("return string.upper").eval::<i32>()?, 1);
+    lua.globals()
+        .get::<_, Table>("string")?
+        .get::<_, Function>("upper")?;
+
+    Ok(())
+}
+
+#[test]
+fn test_sandbox_raw_writes() -> Result<()> {
+    let lua = Lua::new();
+    lua.sandbox(true)?;
+
+    // Raw writes cannot bypass the read-only libraries
+    for code in [
+        "rawset(math, 'floor', function() return 42 end)",
+        "rawset(getmetatable(''), '__index', {})",
+        "table.insert(math, 1)",
+        "table.insert(string, 1, 1)",
+        "table.sort(math)",
+    ] {
+        let err = lua.load(code).exec().unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("cannot modify a read-only library"),
+            "{code}: {err}"
+        );
+    }
+    // but still work on other tables
+    let n: i32 = lua
+        .load("local t = {} rawset(t, 'x', 1) table.insert(t, 2) return t.x + t[1]")
+        .eval()?;
+    assert_eq!(n, 3);
+
+    // The libraries are intact after a reset
+    lua.reset_sandbox()?;
+    assert_eq!(lua.load("return math.floor(1.5)").eval::<i32>()?, 1);
+    assert_eq!(lua.load("return ('a'):upper()").eval::<String>()?, "A");
+
+    Ok(())
+}
+
+#[cfg(any(feature = "lua51", feature = "luajit"))]
+#[test]
+fn test_sandbox_setfenv() -> Result<()> {
+    let lua = Lua::new();
+    lua.sandbox(true)?;
+
+    // Functions defined in the sandbox can be moved to other environments
+    let value = lua
+        .load(
+            r#"
+            local function f() return x end
+            setfenv(f, { x = 1 })
+            local n = f()
+            setfenv(f, { x = 2 })
+            return n + f()
+        "#,
+        )
+        .eval::<i32>()?;
+    assert_eq!(value, 3);
+
+    // but foreign functions cannot
+    let err = lua.load("setfenv(print, {})").exec().unwrap_err();
+    assert!(err.to_string().contains("foreign function"), "{err}");
+    assert!(lua.load("setfenv(0, {})").exec().is_err());
+
+    // `getfenv` never returns the original globals
+    let env = lua
+        .load("return getfenv(print) == _G and getfenv(0) == _G")
+        .eval::<bool>()?;
+    assert!(env);
+
+    // Level-based `setfenv` changes the caller's environment
+    let value = lua
+        .load(
+            r#"
+            local function f()
+                setfenv(1, { y = 5 })
+                return y
+            end
+            return f()
+        "#,
+        )
+        .eval::<i32>()?;
+    assert_eq!(value, 5);
+
+    Ok(())
+}
+
+#[test]
+fn test_sandbox_threads() -> Result<()> {
+    let lua = Lua::new();
+
+    let f = lua.create_function(|lua, v: mlua::Value| lua.globals().set("global", v))?;
+
+    let co = lua.create_thread(f.clone())?;
+    co.resume(321)?;
+    // The main state should see the `global` variable (as the thread is not sandboxed)
+    assert_eq!(lua.globals().get::<_, Option<i32>>("global")?, Some(321));
+
+    let co = lua.create_thread(f)?;
+    co.sandbox()?;
+    co.resume(123)?;
+    // The main state should see the previous `global` value (as the thread is sandboxed)
+    assert_eq!(lua.globals().get::<_, Option<i32>>("global")?, Some(321));
+
+    Ok(())
+}
@@ -0,0 +1,195 @@
+use mlua::{Error, Function, Lua, Result, Scheduler, TaskState};
+
+#[test]
+fn test_scheduler_wait() -> Result<()> {
+    let lua = Lua::new();
+    let mut scheduler = Scheduler::new(&lua)?;
+
+    let func: Function = lua
+        .load(
+            r#"
+        function(log, name)
+            log[#log + 1] = name .. ":start"
+            local elapsed = wait(2)
+            log[#log + 1] = name .. ":" .. elapsed
+            coroutine.yield()
+            log[#log + 1] = name .. ":end"
+        end
+    "#,
+        )
+        .eval()?;
+    let log = lua.create_table()?;
+    let id = scheduler.spawn(&lua, func, (log.clone(), "a"))?;
+    assert_eq!(scheduler.tasks()[0].id, id);
+    assert_eq!(scheduler.tasks()[0].state, TaskState::Ready);
+
+    let log_str = || -> Result<String> {
+        let parts = log.clone().sequence_values::<String>();
+        Ok(parts.collect::<Result<Vec<_>>>()?.join(","))
+    };
+
+    assert!(scheduler.tick(&lua, 0.5)?.is_empty());
+    assert_eq!(log_str()?, "a:start");
+    assert_eq!(scheduler.tasks()[0].state, TaskState::Ticks(2));
+
+    scheduler.tick(&lua, 0.5)?;
+    assert_eq!(log_str()?, "a:start");
+    scheduler.tick(&lua, 0.5)?;
+    assert_eq!(log_str()?, "a:start,a:1");
+    scheduler.tick(&lua, 0.5)?;
+    assert_eq!(log_str()?, "a:start,a:1,a:end");
+    assert!(scheduler.is_empty());
+    assert_eq!(scheduler.ticks(), 4);
+    assert_eq!(scheduler.time(), 2.0);
+
+    Ok(())
+}
+
+#[test]
+fn test_scheduler_events() -> Result<()> {
+    let lua = Lua::new();
+    let mut scheduler = Scheduler::new(&lua)?;
+
+    let func: Function = lua
+        .load(
+            r#"
+        function()
+            local a, b = wait_event("TurnBegin")
+            result = a + b
+        end
+    "#,
+        )
+        .eval()?;
+    scheduler.spawn(&lua, func.clone(), ())?;
+    scheduler.spawn(&lua, func, ())?;
+    scheduler.tick(&lua, 0.0)?;
+    assert_eq!(
+        scheduler.tasks()[1].state,
+        TaskState::Event("TurnBegin".into())
+    );
+
+    assert_eq!(scheduler.fire(&lua, "TurnEnd", ())?, 0);
+    assert_eq!(scheduler.fire(&lua, "TurnBegin", (1, 2))?, 2);
+    assert_eq!(lua.globals().get::<_, Option<i64>>("result")?, None);
+    scheduler.tick(&lua, 0.0)?;
+    assert_eq!(lua.globals().get::<_, i64>("result")?, 3);
+    assert!(scheduler.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_scheduler_wait_until_and_spawn() -> Result<()> {
+    let lua = Lua::new();
+    let mut scheduler = Scheduler::new(&lua)?;
+
+    lua.load(
+        r#"
+        flag = false
+        spawn(function(x)
+            got = wait_until(function() return flag and x end)
+        end, 42)
+    "#,
+    )
+    .exec()?;
+    assert!(scheduler.is_empty());
+
+    scheduler.tick(&lua, 0.0)?;
+    scheduler.tick(&lua, 0.0)?;
+    assert_eq!(scheduler.tasks()[0].state, TaskState::Until);
+
+    lua.globals().set("flag", true)?;
+    scheduler.tick(&lua, 0.0)?;
+    assert_eq!(lua.globals().get::<_, i64>("got")?, 42);
+    assert!(scheduler.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_scheduler_errors_and_cancel() -> Result<()> {
+    let lua = Lua::new();
+    let mut scheduler = Scheduler::new(&lua)?;
+
+    let failing: Function = lua.load("function() wait(1) error('boom') end").eval()?;
+    let counter: Function = lua
+        .load("function() count = 0 while true do count = count + 1 wait() end end")
+        .eval()?;
+    let bad_id = scheduler.spawn(&lua, failing, ())?;
+    let counter_id = scheduler.spawn(&lua, counter, ())?;
+
+    assert!(scheduler.tick(&lua, 0.0)?.is_empty());
+    let errors = scheduler.tick(&lua, 0.0)?;
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, bad_id);
+    match &errors[0].1 {
+        Error::RuntimeError(msg) => assert!(msg.contains("boom")),
+        err => panic!("expected RuntimeError, got {err:?}"),
+    }
+
+    // Other tasks are not affected
+    assert_eq!(lua.globals().get::<_, i64>("count")?, 2);
+    assert_eq!(scheduler.len(), 1);
+
+    assert!(scheduler.cancel(counter_id));
+    assert!(!scheduler.cancel(counter_id));
+    scheduler.tick(&lua, 0.0)?;
+    assert_eq!(lua.globals().get::<_, i64>("count")?, 2);
+
+    // Invalid arguments are reported as the task errors
+    let invalid: Function = lua.load("function() wait_event(1) end").eval()?;
+    scheduler.spawn(&lua, invalid, ())?;
+    assert_eq!(scheduler.tick(&lua, 0.0)?.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_two_schedulers() -> Result<()> {
+    let lua = Lua::new();
+    let mut first = Scheduler::new(&lua)?;
+    let mut second = Scheduler::new(&lua)?;
+
+    let func: Function = lua
+        .load(
+            r#"
+        function(name)
+            local elapsed = wait(2)
+            spawn(function() log[#log + 1] = name .. ":child" end)
+            log[#log + 1] = name .. ":" .. elapsed
+        end
+    "#,
+        )
+        .eval()?;
+    lua.globals().set("log", lua.create_table()?)?;
+    first.spawn(&lua, func.clone(), "a")?;
+    second.spawn(&lua, func, "b")?;
+
+    // Both schedulers recognize the `wait` requests of their tasks
+    first.tick(&lua, 1.0)?;
+    second.tick(&lua, 0.5)?;
+    assert_eq!(first.tasks()[0].state, TaskState::Ticks(2));
+    assert_eq!(second.tasks()[0].state, TaskState::Ticks(2));
+    for _ in 0..2 {
+        assert!(first.tick(&lua, 1.0)?.is_empty());
+        assert!(second.tick(&lua, 0.5)?.is_empty());
+    }
+
+    // Tasks spawned from a task belong to its scheduler
+    assert!(first.is_empty() && second.is_empty());
+    first.tick(&lua, 1.0)?;
+    let log = lua.globals().get::<_, Vec<String>>("log")?;
+    assert_eq!(log, ["a:2", "b:1", "a:child"]);
+    second.tick(&lua, 0.5)?;
+
+    // Outside of a task, `spawn` uses the first scheduler
+    lua.load("spawn(function() log[#log + 1] = 'top' end)")
+        .exec()?;
+    second.tick(&lua, 0.5)?;
+    assert!(first.tick(&lua, 1.0)?.is_empty());
+
+    let log = lua.globals().get::<_, Vec<String>>("log")?;
+    assert_eq!(log, ["a:2", "b:1", "a:child", "b:child", "top"]);
+
+    Ok(())
+}
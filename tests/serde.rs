@@ -5,7 +5,7 @@ use std::error::Error as StdError;
 
 use mlua::{
     DeserializeOptions, Error, ExternalResult, Lua, LuaSerdeExt, Result as LuaResult,
-    SerializeOptions, UserData, Value,
+    SerializeOptions, StringArena, UserData, Value,
 };
 use serde::{Deserialize, Serialize};
 
@@ -206,6 +206,26 @@ fn test_serialize_vector() -> Result<(), Box<dyn StdError>> {
     Ok(())
 }
 
+#[cfg(feature = "civ6-standin")]
+#[test]
+fn test_serialize_ui64() -> Result<(), Box<dyn StdError>> {
+    let lua = Lua::new();
+
+    let globals = lua.globals();
+    globals.set("ui64", lua.create_ui64(u64::MAX)?)?;
+
+    let val = lua.load("{_ui64 = ui64}").eval::<Value>()?;
+    let json = serde_json::json!({
+        "_ui64": u64::MAX,
+    });
+    assert_eq!(serde_json::to_value(&val)?, json);
+
+    let expected_json = lua.from_value::<serde_json::Value>(val)?;
+    assert_eq!(expected_json, json);
+
+    Ok(())
+}
+
 #[test]
 fn test_serialize_sorted() -> LuaResult<()> {
     let lua = Lua::new();
@@ -713,6 +733,24 @@ fn test_from_value_sorted() -> Result<(), Box<dyn StdError>> {
     Ok(())
 }
 
+#[test]
+fn test_from_value_number_keys() -> Result<(), Box<dyn StdError>> {
+    let lua = Lua::new();
+
+    // Number keys are not read as strings (only the `json` module does it)
+    let value = lua.load("{ [10] = 1, a = 3 }").eval()?;
+    assert!(lua.from_value::<HashMap<String, i32>>(value).is_err());
+
+    let value: Value = lua.load("{ [10] = 1 }").eval()?;
+    let map: HashMap<i64, i32> = lua.from_value(value.clone())?;
+    assert_eq!(map.get(&10), Some(&1));
+    assert!(lua.from_value::<serde_json::Value>(value).is_err());
+
+    assert!(lua.from_value::<String>(Value::Integer(10)).is_err());
+
+    Ok(())
+}
+
 #[test]
 fn test_arbitrary_precision() {
     let lua = Lua::new();
@@ -770,3 +808,234 @@ fn test_buffer_from_value() {
         .unwrap();
     assert_eq!(val, serde_value::Value::Bytes(vec![1, 2, 3, 4]));
 }
+
+#[test]
+fn test_from_value_borrowed() -> Result<(), Box<dyn StdError>> {
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Config<'a> {
+        name: &'a str,
+        data: &'a [u8],
+        tags: Vec<&'a str>,
+        #[serde(borrow)]
+        aliases: HashMap<&'a str, &'a str>,
+    }
+
+    let lua = Lua::new();
+    let value = lua
+        .load(r#"{ name = "civ", data = "\0\255", tags = { "a", "b" }, aliases = { x = "y" } }"#)
+        .eval()?;
+    let arena = StringArena::new();
+    let config: Config = lua.from_value_borrowed(value, &arena)?;
+    assert_eq!(config.name, "civ");
+    assert_eq!(config.data, b"\0\xff");
+    assert_eq!(config.tags, vec!["a", "b"]);
+    assert_eq!(config.aliases["x"], "y");
+    assert!(!arena.is_empty());
+
+    // Strings outlive the Lua values they were borrowed from
+    lua.gc_collect()?;
+    lua.load("for i = 1, 1000 do local _ = 'garbage' .. i end")
+        .exec()?;
+    lua.gc_collect()?;
+    assert_eq!(config.name, "civ");
+
+    // Owned values work as well
+    let value = lua.load(r#"{ 1, 2, 3 }"#).eval()?;
+    let v: Vec<i32> = lua.from_value_borrowed(value, &arena)?;
+    assert_eq!(v, vec![1, 2, 3]);
+
+    Ok(())
+}
+
+#[test]
+fn test_userdata_from_value() -> Result<(), Box<dyn StdError>> {
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Graphics {
+        quality: String,
+        fps: u32,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Config {
+        difficulty: u8,
+        mods: Vec<String>,
+        graphics: Graphics,
+    }
+
+    impl UserData for Config {}
+
+    let lua = Lua::new();
+    let ud = lua.create_userdata(Config {
+        difficulty: 1,
+        mods: vec!["a".into(), "b".into()],
+        graphics: Graphics {
+            quality: "high".into(),
+            fps: 60,
+        },
+    })?;
+
+    // Fields are merged, nested maps too, sequences are replaced
+    let update = lua
+        .load(r#"{ difficulty = 2, mods = { "c" }, graphics = { fps = 30 } }"#)
+        .eval()?;
+    ud.merge_from_value::<Config>(update)?;
+    assert_eq!(
+        *ud.borrow::<Config>()?,
+        Config {
+            difficulty: 2,
+            mods: vec!["c".into()],
+            graphics: Graphics {
+                quality: "high".into(),
+                fps: 30
+            },
+        }
+    );
+
+    // Invalid update keeps the value
+    let update = lua.load(r#"{ difficulty = "hard" }"#).eval()?;
+    assert!(ud.merge_from_value::<Config>(update).is_err());
+    assert_eq!(ud.borrow::<Config>()?.difficulty, 2);
+
+    // Replacing requires all fields
+    let update = lua.load(r#"{ difficulty = 5 }"#).eval()?;
+    assert!(ud.set_from_value::<Config>(update).is_err());
+    let update = lua
+        .load(r#"{ difficulty = 5, mods = {}, graphics = { quality = "low", fps = 24 } }"#)
+        .eval()?;
+    ud.set_from_value::<Config>(update)?;
+    assert_eq!(ud.borrow::<Config>()?.graphics.quality, "low");
+
+    // Type mismatch
+    let update = lua.load(r#"{ quality = "low", fps = 1 }"#).eval()?;
+    match ud.set_from_value::<Graphics>(update) {
+        Err(Error::UserDataTypeMismatch) => {}
+        r => panic!("expected UserDataTypeMismatch, got {r:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_from_value_error_path() -> Result<(), Box<dyn StdError>> {
+    #[derive(Deserialize, Debug)]
+    #[allow(unused)]
+    struct Promotion {
+        name: String,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(unused)]
+    struct Unit {
+        name: String,
+        promotions: Vec<Promotion>,
+        #[serde(default)]
+        tags: HashMap<String, (u8, bool)>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(unused)]
+    struct Config {
+        units: Vec<Unit>,
+    }
+
+    let lua = Lua::new();
+    let value = lua
+        .load(
+            r#"{ units = {
+                { name = "scout", promotions = {} },
+                { name = "warrior", promotions = { { name = "ambush" }, { name = 1 } } },
+                { name = true, promotions = {}, tags = { ["a b"] = { 1, "x" } } },
+            } }"#,
+        )
+        .eval::<Value>()?;
+
+    let err = lua.from_value::<Config>(value.clone()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "deserialize error: units[2].promotions[2].name: invalid type: integer `1`, expected a string"
+    );
+
+    // All errors at once
+    let options = DeserializeOptions::new().collect_errors(true);
+    let err = lua.from_value_with::<Config>(value, options).unwrap_err();
+    let msg = err.to_string();
+    let lines = msg.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3, "{msg}");
+    assert!(lines.contains(&"units[3].name: invalid type: boolean `true`, expected a string"));
+    assert!(
+        lines.contains(&r#"units[3].tags["a b"][2]: invalid type: string "x", expected a boolean"#)
+    );
+    assert!(msg.contains("units[2].promotions[2].name: invalid type"));
+
+    // Collecting is limited
+    let value = lua
+        .load("local t = {} for i = 1, 200 do t[i] = -i end return t")
+        .eval()?;
+    let err = lua.from_value_with::<Vec<u8>>(value, options).unwrap_err();
+    let msg = err.to_string();
+    assert_eq!(msg.lines().count(), 101);
+    assert!(msg.ends_with("too many errors, the rest is not reported"));
+
+    // Unknown fields and missing values
+    #[derive(Deserialize, Debug)]
+    #[serde(deny_unknown_fields)]
+    #[allow(unused)]
+    struct Strict {
+        a: u8,
+        b: u8,
+    }
+    let value = lua.load("{ { a = 1, c = 2 }, { a = -1, b = 2 } }").eval()?;
+    let err = lua
+        .from_value_with::<Vec<Strict>>(value, options)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "deserialize error: [1].c: unknown field `c`, expected `a` or `b`\n\
+         [2].a: invalid value: integer `-1`, expected u8"
+    );
+
+    // Valid values are deserialized as usual
+    let value = lua.load("{ { a = 1, b = 2 } }").eval()?;
+    let strict = lua.from_value_with::<Vec<Strict>>(value, options)?;
+    assert_eq!(strict.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_to_value_error_path() -> Result<(), Box<dyn StdError>> {
+    struct Invalid;
+
+    impl Serialize for Invalid {
+        fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("invalid value"))
+        }
+    }
+
+    #[derive(Serialize)]
+    enum Kind {
+        Named { values: Vec<Invalid> },
+    }
+
+    #[derive(Serialize)]
+    struct State {
+        kinds: HashMap<i32, Kind>,
+    }
+
+    let lua = Lua::new();
+    let state = State {
+        kinds: HashMap::from([(
+            5,
+            Kind::Named {
+                values: vec![Invalid],
+            },
+        )]),
+    };
+    let err = lua.to_value(&state).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "serialize error: kinds[5].Named.values[1]: invalid value"
+    );
+
+    Ok(())
+}
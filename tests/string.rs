@@ -1,7 +1,7 @@
 use std::borrow::Cow;
 use std::collections::HashSet;
 
-use mlua::{Lua, Result, String};
+use mlua::{Error, Lua, Result, String, StringBuilder};
 
 #[test]
 fn test_string_compare() {
@@ -112,6 +112,90 @@ fn test_string_pointer() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_string_builder() -> Result<()> {
+    let lua = Lua::new();
+
+    let builder = lua.create_string_builder()?;
+    assert!(builder.is_empty()?);
+    builder.push("abc")?;
+    builder.push(b"\0def")?;
+    builder.push_value(12)?;
+    assert!(builder.push_value(true).is_err());
+    assert_eq!(builder.len()?, 9);
+
+    lua.globals().set("builder", &builder)?;
+    lua.load(
+        r#"
+        assert(#builder == 9)
+        for i = 1, 1000 do
+            builder:add(" ", i):add(";")
+        end
+        assert(not pcall(builder.add, builder, {}))
+    "#,
+    )
+    .exec()?;
+
+    let s = builder.build()?;
+    assert!(s.as_bytes().starts_with(b"abc\0def12 1; 2;"));
+    assert!(s.as_bytes().ends_with(b" 1000;"));
+    assert_eq!(lua.load("tostring(builder)").eval::<String>()?, s);
+
+    // Builders can be received from Lua
+    let builder2: StringBuilder = lua.load("builder").eval()?;
+    builder2.clear()?;
+    assert_eq!(lua.load("#builder").eval::<usize>()?, 0);
+    assert_eq!(builder.build()?, "");
+    match lua.load("{}").eval::<StringBuilder>() {
+        Err(Error::FromLuaConversionError {
+            to: "StringBuilder",
+            ..
+        }) => {}
+        r => panic!("expected FromLuaConversionError, got {r:?}"),
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "lua51_civ6")]
+#[test]
+fn test_civ6_buffer() -> Result<()> {
+    use std::mem::MaybeUninit;
+
+    use mlua::ffi;
+
+    unsafe extern "C-unwind" fn build(state: *mut ffi::lua_State) -> std::os::raw::c_int {
+        let mut b = MaybeUninit::<ffi::luaL_Buffer>::uninit();
+        let b = b.as_mut_ptr();
+        ffi::luaL_buffinit(state, b);
+        ffi::luaL_addchar(b, b'<' as _);
+        for _ in 0..100 {
+            ffi::luaL_addstring(b, b"0123456789\0".as_ptr() as _);
+        }
+        ffi::lua_pushvalue(state, 1);
+        ffi::luaL_addvalue(b);
+        ffi::luaL_addlstring(b, b">".as_ptr() as _, 1);
+        ffi::luaL_pushresult(b);
+        1
+    }
+
+    let lua = Lua::new();
+    let f = unsafe { lua.create_c_function(build)? };
+
+    let s: String = f.call("x".repeat(ffi::LUAL_BUFFERSIZE * 3))?;
+    let expected = format!(
+        "<{}{}>",
+        "0123456789".repeat(100),
+        "x".repeat(ffi::LUAL_BUFFERSIZE * 3)
+    );
+    assert_eq!(s, expected.as_str());
+
+    let s: String = f.call("")?;
+    assert_eq!(s.as_bytes().len(), 1002);
+
+    Ok(())
+}
+
 #[cfg(all(feature = "unstable", not(feature = "send")))]
 #[test]
 fn test_owned_string() -> Result<()> {
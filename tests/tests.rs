@@ -1234,6 +1234,44 @@ fn test_inspect_stack() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_inspect_locals() -> Result<()> {
+    let lua = Lua::new();
+
+    let locals = lua.create_function(|lua, ()| {
+        let debug = lua.inspect_stack(1).unwrap(); // caller
+        let locals = debug.locals()?;
+        let mut names = Vec::new();
+        for (i, (name, value)) in locals.into_iter().enumerate() {
+            if name == "counter" {
+                debug.set_local(i + 1, value.as_i64().unwrap_or_default() + 100)?;
+            }
+            if !name.starts_with('(') {
+                names.push(format!("{name}={}", value.to_string()?));
+            }
+        }
+        assert!(debug.set_local(100, 1).is_err());
+        Ok(names.join(","))
+    })?;
+    lua.globals().set("locals", locals)?;
+
+    lua.load(
+        r#"
+        local function foo(a, b)
+            local counter = 1
+            local s = "str"
+            local dump = locals()
+            assert(dump == "a=1,b=true,counter=1,s=str", dump)
+            assert(counter == 101)
+        end
+        foo(1, true)
+    "#,
+    )
+    .exec()?;
+
+    Ok(())
+}
+
 #[test]
 fn test_multi_states() -> Result<()> {
     let lua = Lua::new();
@@ -229,3 +229,44 @@ fn test_value_conversions() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(feature = "civ6-standin")]
+#[test]
+fn test_other_value() -> Result<()> {
+    let lua = Lua::new();
+
+    let n = u64::MAX - 1;
+    let v = lua.create_ui64(n)?;
+    let other = v.as_other().expect("ui64 must be an `OtherValue`");
+    assert_eq!(other.type_name(), "ui64");
+    assert_eq!(other.type_tag(), mlua::ffi::LUA_TUI64);
+    assert_eq!(v.type_name(), "ui64");
+    assert_eq!(other.as_u64()?, Some(n));
+    assert_eq!(v.as_u64(), Some(n));
+    assert_eq!(lua.unpack::<u64>(v.clone())?, n);
+    assert!(lua.unpack::<i64>(v.clone()).is_err());
+    assert_eq!(format!("{v:?}"), format!("Other(ui64: {n})"));
+
+    // Round trip through a Rust callback
+    let identity = lua.create_function(|_, v: Value| Ok(v))?;
+    let ret: Value = identity.call(v.clone())?;
+    assert_eq!(ret, v);
+
+    // ..., a table and `MultiValue`
+    let t = lua.create_table()?;
+    t.set("ui64", v.clone())?;
+    assert_eq!(t.get::<_, Value>("ui64")?, v);
+    let f = lua
+        .load("return function(...) return select('#', ...), ... end")
+        .eval::<mlua::Function>()?;
+    let (count, ret): (i32, MultiValue) = f.call((v.clone(), v.clone()))?;
+    assert_eq!(count, 2);
+    assert_eq!(ret.into_vec(), vec![v.clone(), v.clone()]);
+
+    // The value is untouched by the trip through Rust
+    lua.globals().set("ui64", v)?;
+    let s: StdString = lua.load("return tostring(ui64)").eval()?;
+    assert_eq!(s, n.to_string());
+
+    Ok(())
+}